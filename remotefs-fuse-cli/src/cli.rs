@@ -1,10 +1,12 @@
 #[cfg(feature = "aws-s3")]
 mod aws_s3;
+mod backend;
 #[cfg(feature = "ftp")]
 mod ftp;
 #[cfg(feature = "kube")]
 mod kube;
 mod memory;
+mod overlay;
 #[cfg(feature = "smb")]
 mod smb;
 #[cfg(feature = "ssh")]
@@ -12,24 +14,32 @@ mod ssh;
 #[cfg(feature = "webdav")]
 mod webdav;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use argh::FromArgs;
+use remotefs::RemoteFs;
 use remotefs_fuse::MountOption;
 
 #[cfg(feature = "aws-s3")]
 use self::aws_s3::AwsS3Args;
+#[cfg(feature = "aws-s3")]
+pub use self::aws_s3::AwsS3Check;
+use self::backend::BackendArgs;
 #[cfg(feature = "ftp")]
 use self::ftp::FtpArgs;
 #[cfg(feature = "kube")]
-use self::kube::KubeArgs;
+use self::kube::{KubeArgs, KubeFs};
 use self::memory::MemoryArgs;
+use self::overlay::OverlayArgs;
 #[cfg(feature = "smb")]
 use self::smb::SmbArgs;
 #[cfg(feature = "ssh")]
+pub use self::ssh::HostKeyCheck;
+#[cfg(feature = "ssh")]
 use self::ssh::{ScpArgs, SftpArgs};
 #[cfg(feature = "webdav")]
 use self::webdav::WebdavArgs;
+use crate::registry::Registry;
 use crate::remotefs_wrapper::RemoteFsWrapper;
 
 /// RemoteFS FUSE CLI
@@ -58,6 +68,80 @@ pub struct CliArgs {
     #[argh(option, from_str_fn(from_octal))]
     #[cfg(unix)]
     pub default_mode: Option<u32>,
+    /// allow all users to access the mounted filesystem.
+    ///
+    /// requires `user_allow_other` to be set in `/etc/fuse.conf`. It already grants access to root,
+    /// so it can't be used together with `--allow-root`.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub allow_other: bool,
+    /// allow root to access the mounted filesystem, in addition to the user who mounted it.
+    ///
+    /// can't be used together with `--allow-other`.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub allow_root: bool,
+    /// let the kernel check permissions using the file modes instead of the driver.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub default_permissions: bool,
+    /// mount the filesystem as read-only
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub ro: bool,
+    /// don't allow execution of binaries on the mounted filesystem
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub no_exec: bool,
+    /// perform I/O asynchronously
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub no_sync: bool,
+    /// allow mounting over a directory which is not empty.
+    ///
+    /// the files in the directory are hidden until the filesystem is unmounted.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub nonempty: bool,
+    /// fail if the mount point doesn't exist, instead of creating it.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub no_create_mountpoint: bool,
+    /// create the missing parent directories of the mount point too.
+    ///
+    /// without it, the mount point is created only if its parent exists.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub create_parents: bool,
+    /// directory where the temporary files are created, instead of the system temporary directory.
+    ///
+    /// the directory must be writable.
+    #[cfg(unix)]
+    #[argh(option)]
+    pub temp_dir: Option<PathBuf>,
+    /// fail if mounting the filesystem and connecting to the remote take longer than this many seconds.
+    ///
+    /// the mount point is removed if it was created for the mount.
+    #[cfg(unix)]
+    #[argh(option)]
+    pub mount_timeout: Option<u64>,
+    /// mount the remote again if the FUSE session dies, such as when the connection to the kernel is aborted.
+    ///
+    /// without it, the process exits; in both cases the dead mount is unmounted.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub auto_remount: bool,
+    /// mount the filesystem, run a POSIX conformance smoke test against it and exit.
+    ///
+    /// a pass/fail report of the supported operations is printed; the exit status is 0 if all of them pass.
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub selftest: bool,
+    /// check the connection to the remote and exit, without mounting the filesystem.
+    ///
+    /// the remote root is listed; the exit status is 0 on success.
+    #[argh(switch)]
+    pub check: bool,
     /// mount options
     ///
     /// Mount options are specific to the underlying filesystem and are passed as key=value pairs.
@@ -102,16 +186,80 @@ impl CliArgs {
     }
 }
 
-#[derive(FromArgs, Debug)]
+impl CliArgs {
+    /// Assemble the [`MountOption`]s from the CLI arguments
+    pub fn mount_options(&self) -> anyhow::Result<Vec<MountOption>> {
+        let mut options = Vec::new();
+
+        #[cfg(unix)]
+        {
+            if self.allow_other && self.allow_root {
+                anyhow::bail!("--allow-other and --allow-root are mutually exclusive: --allow-other already grants access to root");
+            }
+            if self.allow_other {
+                options.push(MountOption::AllowOther);
+            }
+            if self.allow_root {
+                options.push(MountOption::AllowRoot);
+            }
+            if self.default_permissions {
+                options.push(MountOption::DefaultPermissions);
+            }
+            options.push(if self.ro {
+                MountOption::RO
+            } else {
+                MountOption::RW
+            });
+            options.push(if self.no_exec {
+                MountOption::NoExec
+            } else {
+                MountOption::Exec
+            });
+            options.push(if self.no_sync {
+                MountOption::Async
+            } else {
+                MountOption::Sync
+            });
+            if self.nonempty {
+                options.push(MountOption::NonEmpty);
+            }
+            if let Some(temp_dir) = &self.temp_dir {
+                options.push(MountOption::TempDir(temp_dir.clone()));
+            }
+            options.push(MountOption::FSName(self.volume.clone()));
+
+            if let Some(uid) = self.uid {
+                log::info!("Default uid: {uid}");
+                options.push(MountOption::Uid(uid));
+            }
+            if let Some(gid) = self.gid {
+                log::info!("Default gid: {gid}");
+                options.push(MountOption::Gid(gid));
+            }
+            if let Some(default_mode) = self.default_mode {
+                log::info!("Default mode: {default_mode:o}");
+                options.push(MountOption::DefaultMode(default_mode));
+            }
+        }
+
+        options.extend(self.option.clone());
+
+        Ok(options)
+    }
+}
+
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand)]
 pub enum RemoteArgs {
     #[cfg(feature = "aws-s3")]
     AwsS3(AwsS3Args),
+    Backend(BackendArgs),
     #[cfg(feature = "ftp")]
     Ftp(FtpArgs),
     #[cfg(feature = "kube")]
     Kube(KubeArgs),
     Memory(MemoryArgs),
+    Overlay(OverlayArgs),
     #[cfg(feature = "ssh")]
     Scp(ScpArgs),
     #[cfg(feature = "ssh")]
@@ -123,24 +271,174 @@ pub enum RemoteArgs {
 }
 
 impl CliArgs {
-    /// Create a RemoteFs instance from the CLI arguments
-    pub fn remote(self) -> RemoteFsWrapper {
-        match self.remote {
+    /// Create a RemoteFs instance from the CLI arguments, with the backends of `registry`.
+    ///
+    /// The arguments resolved on the remote, such as the Kube container, are updated, so that the connector
+    /// uses them as well.
+    pub fn remote(&mut self, registry: &Registry) -> anyhow::Result<RemoteFsWrapper> {
+        self.remote.create(registry)
+    }
+
+    /// Get a function creating new connections to the remote, used by the mount workers.
+    ///
+    /// Returns [`None`] for the memory filesystem, since each instance is a different filesystem, and for the
+    /// backends selected by name, including the layers of an overlay, whose creation may fail.
+    #[cfg(unix)]
+    pub fn connector(&self) -> Option<impl Fn() -> RemoteFsWrapper + Send + Sync + 'static> {
+        if matches!(
+            self.remote,
+            RemoteArgs::Memory(_) | RemoteArgs::Backend(_) | RemoteArgs::Overlay(_)
+        ) {
+            return None;
+        }
+        let remote = self.remote.clone();
+
+        Some(move || remote.clone().build())
+    }
+}
+
+/// Connect to `remote` and list its root, to check that the remote is reachable with the given credentials.
+///
+/// Returns the amount of entries in the root.
+pub fn check_connection<T: RemoteFs>(remote: &mut T) -> anyhow::Result<usize> {
+    remote
+        .connect()
+        .map_err(|err| anyhow::anyhow!("connection check failed: could not connect: {err}"))?;
+    let result = remote.list_dir(Path::new("/"));
+    if let Err(err) = remote.disconnect() {
+        log::warn!("failed to disconnect from the remote: {err}");
+    }
+
+    result
+        .map(|entries| entries.len())
+        .map_err(|err| anyhow::anyhow!("connection check failed: could not list /: {err}"))
+}
+
+/// Create the mount point at `path` if it doesn't exist, unless `create` is unset, which fails instead.
+///
+/// The missing parent directories are created only if `parents` is set.
+///
+/// Returns whether the mount point was created.
+#[cfg(unix)]
+pub fn create_mount_point(path: &Path, create: bool, parents: bool) -> anyhow::Result<bool> {
+    if path.exists() {
+        return Ok(false);
+    }
+    if !create {
+        anyhow::bail!(
+            "mount point {} does not exist; create it or omit --no-create-mountpoint",
+            path.display()
+        );
+    }
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    if !parents && parent.is_some_and(|parent| !parent.exists()) {
+        anyhow::bail!(
+            "the parent of the mount point {} does not exist; use --create-parents to create it",
+            path.display()
+        );
+    }
+
+    log::info!("creating mount point at {}", path.display());
+    if parents {
+        std::fs::create_dir_all(path)?;
+    } else {
+        std::fs::create_dir(path)?;
+    }
+
+    Ok(true)
+}
+
+impl RemoteArgs {
+    /// Create a RemoteFs instance from the remote arguments, with the backends of `registry`.
+    ///
+    /// The arguments resolved on the remote, such as the Kube container, are updated.
+    pub fn create(&mut self, registry: &Registry) -> anyhow::Result<RemoteFsWrapper> {
+        self.validate()?;
+        #[cfg(feature = "kube")]
+        if let RemoteArgs::Kube(args) = self {
+            args.resolve_container()?;
+        }
+        let seed = match self {
+            RemoteArgs::Backend(args) => {
+                let remote = registry.build(args.name(), &args.args())?;
+                return Ok(RemoteFsWrapper::Custom(remote));
+            }
+            RemoteArgs::Overlay(args) => {
+                return Ok(RemoteFsWrapper::Custom(args.create(registry)?))
+            }
+            RemoteArgs::Memory(args) => args.seed().map(PathBuf::from),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        };
+
+        let mut remote = self.clone().build();
+        if let (Some(seed), RemoteFsWrapper::Memory(fs)) = (seed, &mut remote) {
+            log::info!("seeding memory filesystem from {}", seed.display());
+            memory::seed(fs, &seed)?;
+        }
+
+        Ok(remote)
+    }
+
+    /// Check the remote arguments which can't be checked by the parser
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            #[cfg(feature = "ssh")]
+            RemoteArgs::Scp(args) => args.validate(),
+            #[cfg(feature = "ssh")]
+            RemoteArgs::Sftp(args) => args.validate(),
             #[cfg(feature = "aws-s3")]
-            RemoteArgs::AwsS3(args) => RemoteFsWrapper::Aws(remotefs_aws_s3::AwsS3Fs::from(args)),
+            RemoteArgs::AwsS3(args) => args.validate(),
+            #[cfg(feature = "kube")]
+            RemoteArgs::Kube(args) => args.validate(),
+            #[cfg(feature = "ftp")]
+            RemoteArgs::Ftp(args) => args.validate(),
+            RemoteArgs::Memory(args) => args.validate(),
+            #[cfg(feature = "smb")]
+            RemoteArgs::Smb(args) => args.validate(),
+            #[cfg(feature = "webdav")]
+            RemoteArgs::Webdav(args) => args.validate(),
+            #[allow(unreachable_patterns)]
+            _ => Ok(()),
+        }
+    }
+
+    /// Create a RemoteFs instance from the remote arguments of a built-in backend.
+    ///
+    /// The backends selected by name are created by the registry in [`RemoteArgs::create`].
+    fn build(self) -> RemoteFsWrapper {
+        match self {
+            #[cfg(feature = "aws-s3")]
+            RemoteArgs::AwsS3(args) => {
+                let check = args.connection_check();
+                RemoteFsWrapper::Aws(remotefs_aws_s3::AwsS3Fs::from(args), check)
+            }
+            RemoteArgs::Backend(args) => {
+                unreachable!("backend {} must be created by the registry", args.name())
+            }
+            RemoteArgs::Overlay(_) => unreachable!("overlay must be created by the registry"),
             #[cfg(feature = "ftp")]
             RemoteArgs::Ftp(args) => RemoteFsWrapper::Ftp(remotefs_ftp::FtpFs::from(args)),
             #[cfg(feature = "kube")]
-            RemoteArgs::Kube(args) => {
-                RemoteFsWrapper::Kube(remotefs_kube::KubeMultiPodFs::from(args))
-            }
+            RemoteArgs::Kube(args) => match KubeFs::from(args) {
+                KubeFs::MultiPod(fs) => RemoteFsWrapper::Kube(fs),
+                KubeFs::Container(fs) => RemoteFsWrapper::KubeContainer(fs),
+            },
             RemoteArgs::Memory(args) => {
                 RemoteFsWrapper::Memory(remotefs_memory::MemoryFs::from(args))
             }
             #[cfg(feature = "ssh")]
-            RemoteArgs::Scp(args) => RemoteFsWrapper::Scp(remotefs_ssh::ScpFs::from(args)),
+            RemoteArgs::Scp(args) => {
+                let check = args.host_key_check();
+                RemoteFsWrapper::Scp(remotefs_ssh::ScpFs::from(args), check)
+            }
             #[cfg(feature = "ssh")]
-            RemoteArgs::Sftp(args) => RemoteFsWrapper::Sftp(remotefs_ssh::SftpFs::from(args)),
+            RemoteArgs::Sftp(args) => {
+                let check = args.host_key_check();
+                RemoteFsWrapper::Sftp(remotefs_ssh::SftpFs::from(args), check)
+            }
             #[cfg(feature = "smb")]
             RemoteArgs::Smb(args) => RemoteFsWrapper::Smb(remotefs_smb::SmbFs::from(args)),
             #[cfg(feature = "webdav")]
@@ -150,3 +448,43 @@ impl CliArgs {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use remotefs::fs::UnixPex;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_check_connection() {
+        let mut fs = MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        )));
+        assert_eq!(check_connection(&mut fs).unwrap(), 0);
+        assert!(!fs.is_connected());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_create_mount_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("existing");
+        std::fs::create_dir(&existing).unwrap();
+        assert!(!create_mount_point(&existing, false, false).unwrap());
+
+        let missing = dir.path().join("missing");
+        assert!(create_mount_point(&missing, false, false).is_err());
+        assert!(!missing.exists());
+        assert!(create_mount_point(&missing, true, false).unwrap());
+        assert!(missing.is_dir());
+
+        let nested = dir.path().join("a/b");
+        assert!(create_mount_point(&nested, true, false).is_err());
+        assert!(!dir.path().join("a").exists());
+        assert!(create_mount_point(&nested, true, true).unwrap());
+        assert!(nested.is_dir());
+    }
+}
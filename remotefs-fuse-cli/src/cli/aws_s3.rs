@@ -1,7 +1,13 @@
+use std::path::Path;
+
 use argh::FromArgs;
+use remotefs::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
 use remotefs_aws_s3::AwsS3Fs;
 
-#[derive(FromArgs, Debug)]
+/// Environment variables from which the credentials are loaded when they are not passed as arguments
+const CREDENTIALS_ENV: &[&str] = &["AWS_ACCESS_KEY_ID", "AWS_PROFILE"];
+
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "aws-s3")]
 /// Mount an AWS S3 bucket
 pub struct AwsS3Args {
@@ -11,7 +17,7 @@ pub struct AwsS3Args {
     /// the region of the bucket
     #[argh(option)]
     region: Option<String>,
-    /// custom endpoint
+    /// custom endpoint, for S3-compatible stores such as MinIO, Ceph or R2
     #[argh(option)]
     endpoint: Option<String>,
     /// aws profile
@@ -26,14 +32,72 @@ pub struct AwsS3Args {
     /// security token
     #[argh(option)]
     security_token: Option<String>,
-    /// new path style
+    /// use path-style addressing (`<endpoint>/<bucket>`), required by most self-hosted gateways
+    #[argh(switch)]
+    path_style: bool,
+    /// same as --path-style
     #[argh(switch)]
     new_path_style: bool,
 }
 
+impl AwsS3Args {
+    /// Check that the credentials are provided, either explicitly, with a profile or from the environment
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match (&self.access_key, &self.secret_access_key) {
+            (Some(_), None) => anyhow::bail!("--access-key requires --secret-access-key"),
+            (None, Some(_)) => anyhow::bail!("--secret-access-key requires --access-key"),
+            (Some(_), Some(_)) => return Ok(()),
+            (None, None) => {}
+        }
+        if self.profile.is_some()
+            || CREDENTIALS_ENV
+                .iter()
+                .any(|var| std::env::var_os(var).is_some())
+        {
+            return Ok(());
+        }
+
+        anyhow::bail!(
+            "no credentials provided: pass --access-key and --secret-access-key, or --profile"
+        )
+    }
+
+    /// Get the check of the connection to the bucket performed at mount
+    pub fn connection_check(&self) -> AwsS3Check {
+        AwsS3Check {
+            bucket: self.bucket.clone(),
+            endpoint: self.endpoint.clone(),
+        }
+    }
+}
+
+/// Check that the bucket is reachable once connected, since connecting doesn't contact the server
+#[derive(Debug, Clone)]
+pub struct AwsS3Check {
+    bucket: String,
+    endpoint: Option<String>,
+}
+
+impl AwsS3Check {
+    /// List the root of the bucket on `fs`, reporting the bucket and endpoint on failure
+    pub fn verify(&self, fs: &mut AwsS3Fs) -> RemoteResult<()> {
+        fs.list_dir(Path::new("/")).map(|_| ()).map_err(|err| {
+            RemoteError::new_ex(
+                RemoteErrorType::ConnectionError,
+                format!(
+                    "could not access bucket {} at {}: {err}",
+                    self.bucket,
+                    self.endpoint.as_deref().unwrap_or("the AWS endpoint")
+                ),
+            )
+        })
+    }
+}
+
 impl From<AwsS3Args> for AwsS3Fs {
     fn from(args: AwsS3Args) -> Self {
-        let mut fs = AwsS3Fs::new(args.bucket).new_path_style(args.new_path_style);
+        let mut fs =
+            AwsS3Fs::new(args.bucket).new_path_style(args.path_style || args.new_path_style);
         if let Some(region) = args.region {
             fs = fs.region(region);
         }
@@ -56,3 +120,33 @@ impl From<AwsS3Args> for AwsS3Fs {
         fs
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn args(access_key: Option<&str>, secret: Option<&str>, profile: Option<&str>) -> AwsS3Args {
+        AwsS3Args {
+            bucket: "bucket".to_string(),
+            region: None,
+            endpoint: Some("http://localhost:9000".to_string()),
+            profile: profile.map(str::to_string),
+            access_key: access_key.map(str::to_string),
+            secret_access_key: secret.map(str::to_string),
+            security_token: None,
+            path_style: true,
+            new_path_style: false,
+        }
+    }
+
+    #[test]
+    fn test_should_validate_s3_credentials() {
+        assert!(args(Some("key"), Some("secret"), None).validate().is_ok());
+        assert!(args(None, None, Some("minio")).validate().is_ok());
+        assert!(args(Some("key"), None, None).validate().is_err());
+        assert!(args(None, Some("secret"), Some("minio"))
+            .validate()
+            .is_err());
+    }
+}
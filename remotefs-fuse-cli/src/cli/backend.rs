@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+use argh::FromArgs;
+
+#[derive(FromArgs, Debug, Clone)]
+#[argh(subcommand, name = "backend")]
+/// Mount a backend by name, such as one registered by a program embedding the CLI
+pub struct BackendArgs {
+    /// name of the backend
+    #[argh(option)]
+    name: String,
+    /// argument passed to the backend as key=value; can be repeated
+    #[argh(option, from_str_fn(parse_arg))]
+    arg: Vec<(String, String)>,
+}
+
+impl BackendArgs {
+    /// Name of the backend
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Arguments of the backend, by key
+    pub fn args(&self) -> BTreeMap<String, String> {
+        self.arg.iter().cloned().collect()
+    }
+}
+
+/// Parse a `key=value` argument; a key without value has an empty value
+pub fn parse_arg(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s.split_once('=').unwrap_or((s, ""));
+    if key.is_empty() {
+        return Err(format!("Invalid backend argument: {s}; expected key=value"));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_backend_args() {
+        assert_eq!(
+            parse_arg("host=example.com").unwrap(),
+            ("host".to_string(), "example.com".to_string())
+        );
+        assert_eq!(
+            parse_arg("secure").unwrap(),
+            ("secure".to_string(), String::new())
+        );
+        assert!(parse_arg("=value").is_err());
+    }
+}
@@ -1,7 +1,9 @@
+use std::str::FromStr;
+
 use argh::FromArgs;
 use remotefs_ftp::FtpFs;
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "ftp")]
 /// Mount an FTP server filesystem
 pub struct FtpArgs {
@@ -17,12 +19,57 @@ pub struct FtpArgs {
     /// FTP server password
     #[argh(option)]
     password: Option<String>,
-    /// use FTPS (FTP over TLS)
+    /// use FTPS (FTP over TLS); same as `--ftps explicit`
     #[argh(switch)]
     secure: bool,
+    /// use FTPS (FTP over TLS): explicit, negotiating TLS with `AUTH TLS` after connecting, or implicit
+    #[argh(option)]
+    ftps: Option<FtpsMode>,
     /// active mode; default passive
     #[argh(switch)]
     active: bool,
+    /// passive mode, which works behind most NATs; this is the default
+    #[argh(switch)]
+    passive: bool,
+}
+
+/// FTPS mode selected with `--ftps`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FtpsMode {
+    /// TLS is negotiated with `AUTH TLS` on the plain connection
+    Explicit,
+    /// TLS is established right away, usually on port 990
+    Implicit,
+}
+
+impl FromStr for FtpsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "explicit" => Ok(FtpsMode::Explicit),
+            "implicit" => Ok(FtpsMode::Implicit),
+            _ => Err(format!(
+                "Invalid FTPS mode: {s}; expected explicit or implicit"
+            )),
+        }
+    }
+}
+
+impl FtpArgs {
+    /// Check that the transfer mode and the FTPS options are coherent
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.active && self.passive {
+            anyhow::bail!("--active and --passive can't be used together");
+        }
+        if self.ftps == Some(FtpsMode::Implicit) {
+            anyhow::bail!(
+                "implicit FTPS is not supported by the ftp backend; use --ftps explicit with a server supporting AUTH TLS"
+            );
+        }
+
+        Ok(())
+    }
 }
 
 impl From<FtpArgs> for FtpFs {
@@ -39,10 +86,48 @@ impl From<FtpArgs> for FtpFs {
             ftp.passive_mode()
         };
 
-        if args.secure {
+        if args.secure || args.ftps == Some(FtpsMode::Explicit) {
             ftp.secure()
         } else {
             ftp
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn args() -> FtpArgs {
+        FtpArgs {
+            hostname: "localhost".to_string(),
+            port: 21,
+            username: "anonymous".to_string(),
+            password: None,
+            secure: false,
+            ftps: None,
+            active: false,
+            passive: false,
+        }
+    }
+
+    #[test]
+    fn test_should_validate_ftp_args() {
+        assert!(args().validate().is_ok());
+
+        let mut conflicting = args();
+        conflicting.active = true;
+        conflicting.passive = true;
+        assert!(conflicting.validate().is_err());
+
+        let mut explicit = args();
+        explicit.ftps = Some(FtpsMode::from_str("explicit").unwrap());
+        assert!(explicit.validate().is_ok());
+
+        let mut implicit = args();
+        implicit.ftps = Some(FtpsMode::from_str("implicit").unwrap());
+        assert!(implicit.validate().is_err());
+        assert!(FtpsMode::from_str("tls").is_err());
+    }
+}
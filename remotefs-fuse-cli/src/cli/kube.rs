@@ -1,9 +1,12 @@
 use std::sync::Arc;
 
+use anyhow::Context as _;
 use argh::FromArgs;
-use remotefs_kube::{Config, KubeMultiPodFs};
+use k8s_openapi::api::core::v1::Pod;
+use remotefs_kube::{Config, KubeContainerFs, KubeMultiPodFs};
+use tokio::runtime::Runtime;
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "kube")]
 /// Mount a Kube multipod filesystem
 pub struct KubeArgs {
@@ -13,21 +16,136 @@ pub struct KubeArgs {
     /// kubernetes cluster URL
     #[argh(option)]
     cluster_url: String,
+    /// pod to mount; if not set, all the pods of the namespace are mounted, each under `/<pod>/<container>`
+    #[argh(option)]
+    pod: Option<String>,
+    /// container of the pod to mount; if not set, the first container of the pod is mounted
+    #[argh(option)]
+    container: Option<String>,
+}
+
+/// Kube filesystem built from the [`KubeArgs`]
+pub enum KubeFs {
+    /// All the pods of the namespace
+    MultiPod(KubeMultiPodFs),
+    /// A single container of a pod
+    Container(KubeContainerFs),
+}
+
+impl KubeArgs {
+    /// Check that a container is only selected along with a pod
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.container.is_some() && self.pod.is_none() {
+            anyhow::bail!("--container requires --pod");
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the container to mount, if a pod is selected, checking that it exists in the pod.
+    ///
+    /// If no container is given, the first container of the pod is selected.
+    pub fn resolve_container(&mut self) -> anyhow::Result<()> {
+        let Some(pod_name) = self.pod.as_deref() else {
+            return Ok(());
+        };
+
+        let config = self.config()?;
+        let containers = runtime()?.block_on(async {
+            let client = kube::Client::try_from(config)?;
+            let pods: kube::Api<Pod> = kube::Api::namespaced(client, &self.namespace);
+            let pod = pods.get(pod_name).await?;
+
+            Ok::<_, kube::Error>(
+                pod.spec
+                    .map(|spec| spec.containers)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|container| container.name)
+                    .collect::<Vec<_>>(),
+            )
+        });
+        let containers = containers
+            .with_context(|| format!("failed to get pod {pod_name} in {}", self.namespace))?;
+
+        self.container = Some(select_container(
+            pod_name,
+            &containers,
+            self.container.as_deref(),
+        )?);
+
+        Ok(())
+    }
+
+    fn config(&self) -> anyhow::Result<Config> {
+        let cluster_url = self.cluster_url.parse().context("invalid cluster URL")?;
+        let mut config = Config::new(cluster_url);
+        config.default_namespace = self.namespace.clone();
+
+        Ok(config)
+    }
+}
+
+/// Select the container named `container` among the `containers` of the pod, or the first one if not set
+fn select_container(
+    pod: &str,
+    containers: &[String],
+    container: Option<&str>,
+) -> anyhow::Result<String> {
+    match container {
+        Some(name) if containers.iter().any(|c| c == name) => Ok(name.to_string()),
+        Some(name) => anyhow::bail!(
+            "container {name} doesn't exist in pod {pod}; available containers: {}",
+            containers.join(", ")
+        ),
+        None => containers
+            .first()
+            .cloned()
+            .with_context(|| format!("pod {pod} has no containers")),
+    }
+}
+
+fn runtime() -> anyhow::Result<Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .context("Unable to create tokio runtime")
 }
 
-impl From<KubeArgs> for KubeMultiPodFs {
+impl From<KubeArgs> for KubeFs {
     fn from(args: KubeArgs) -> Self {
-        let mut config = Config::new(args.cluster_url.parse().expect("Invalid cluster URL"));
-        config.default_namespace = args.namespace;
-
-        let rt = Arc::new(
-            tokio::runtime::Builder::new_current_thread()
-                .worker_threads(1)
-                .enable_all()
-                .build()
-                .expect("Unable to create tokio runtime"),
-        );
+        let config = args.config().expect("Invalid cluster URL");
+        let rt = Arc::new(runtime().expect("Unable to create tokio runtime"));
 
-        KubeMultiPodFs::new(&rt).config(config)
+        match args.pod {
+            Some(pod) => {
+                // the container is resolved before building the filesystem
+                let container = args.container.unwrap_or_default();
+                KubeFs::Container(KubeContainerFs::new(pod, container, &rt).config(config))
+            }
+            None => KubeFs::MultiPod(KubeMultiPodFs::new(&rt).config(config)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_select_container() {
+        let containers = vec!["app".to_string(), "sidecar".to_string()];
+        assert_eq!(
+            select_container("pod", &containers, None).unwrap(),
+            "app".to_string()
+        );
+        assert_eq!(
+            select_container("pod", &containers, Some("sidecar")).unwrap(),
+            "sidecar".to_string()
+        );
+        assert!(select_container("pod", &containers, Some("db")).is_err());
+        assert!(select_container("pod", &[], None).is_err());
     }
 }
@@ -1,13 +1,42 @@
-use std::path::PathBuf;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 
+use anyhow::Context as _;
 use argh::FromArgs;
-use remotefs::fs::UnixPex;
+use remotefs::fs::{Metadata, UnixPex};
+use remotefs::RemoteFs;
 use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "memory")]
 /// Mount a Virtual Memory filesystem
-pub struct MemoryArgs {}
+pub struct MemoryArgs {
+    /// local directory whose tree is copied into the filesystem at mount time
+    #[argh(option)]
+    seed: Option<PathBuf>,
+}
+
+impl MemoryArgs {
+    /// Check that the seed directory, if any, exists and is a directory
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let Some(seed) = self.seed.as_deref() else {
+            return Ok(());
+        };
+        if !seed.exists() {
+            anyhow::bail!("seed directory {} does not exist", seed.display());
+        }
+        if !seed.is_dir() {
+            anyhow::bail!("seed path {} is not a directory", seed.display());
+        }
+
+        Ok(())
+    }
+
+    /// Local directory to copy into the filesystem
+    pub fn seed(&self) -> Option<&Path> {
+        self.seed.as_deref()
+    }
+}
 
 impl From<MemoryArgs> for MemoryFs {
     fn from(_: MemoryArgs) -> Self {
@@ -49,3 +78,128 @@ impl From<MemoryArgs> for MemoryFs {
             })
     }
 }
+
+/// Recursively copy the local directory `seed` into the root of `fs`, preserving modes and mtimes.
+pub fn seed(fs: &mut MemoryFs, seed: &Path) -> anyhow::Result<()> {
+    fs.connect()
+        .context("failed to connect to memory filesystem")?;
+    let result = copy_dir(fs, seed, Path::new("/"));
+    fs.disconnect()
+        .context("failed to disconnect from memory filesystem")?;
+
+    result
+}
+
+/// Copy the entries of the local directory `local` into the directory `remote` of `fs`
+fn copy_dir(fs: &mut MemoryFs, local: &Path, remote: &Path) -> anyhow::Result<()> {
+    let entries =
+        std::fs::read_dir(local).with_context(|| format!("failed to read {}", local.display()))?;
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", local.display()))?;
+        let local_path = entry.path();
+        let remote_path = remote.join(entry.file_name());
+        let metadata = std::fs::symlink_metadata(&local_path)
+            .with_context(|| format!("failed to stat {}", local_path.display()))?;
+        let mode = local_mode(&metadata);
+
+        if metadata.is_dir() {
+            fs.create_dir(&remote_path, mode)
+                .with_context(|| format!("failed to create directory {}", remote_path.display()))?;
+            copy_dir(fs, &local_path, &remote_path)?;
+        } else if metadata.is_file() {
+            let reader = File::open(&local_path)
+                .with_context(|| format!("failed to open {}", local_path.display()))?;
+            fs.create_file(
+                &remote_path,
+                &Metadata::default().mode(mode).size(metadata.len()),
+                Box::new(reader),
+            )
+            .with_context(|| format!("failed to create file {}", remote_path.display()))?;
+        } else {
+            log::warn!("skipping {}: not a file or directory", local_path.display());
+            continue;
+        }
+
+        // set the mtime once the content is written, so it's not updated by the copy
+        if let Ok(mtime) = metadata.modified() {
+            let mut attrs = fs
+                .stat(&remote_path)
+                .with_context(|| format!("failed to stat {}", remote_path.display()))?
+                .metadata;
+            attrs.modified = Some(mtime);
+            fs.setstat(&remote_path, attrs)
+                .with_context(|| format!("failed to set mtime of {}", remote_path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn local_mode(metadata: &std::fs::Metadata) -> UnixPex {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    UnixPex::from(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(windows)]
+fn local_mode(metadata: &std::fs::Metadata) -> UnixPex {
+    match (metadata.is_dir(), metadata.permissions().readonly()) {
+        (true, _) => UnixPex::from(0o755),
+        (false, true) => UnixPex::from(0o444),
+        (false, false) => UnixPex::from(0o644),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Read as _;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn test_should_reject_invalid_seed() {
+        let args = |seed: &Path| MemoryArgs {
+            seed: Some(seed.to_path_buf()),
+        };
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        assert!(MemoryArgs { seed: None }.validate().is_ok());
+        assert!(args(file.path().parent().unwrap()).validate().is_ok());
+        assert!(args(file.path()).validate().is_err());
+        assert!(args(Path::new("/does/not/exist")).validate().is_err());
+    }
+
+    #[test]
+    fn test_should_seed_memory_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/hello.txt"), b"hello").unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(dir.path().join("sub/hello.txt"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let mut fs = MemoryFs::from(MemoryArgs { seed: None });
+        seed(&mut fs, dir.path()).unwrap();
+        fs.connect().unwrap();
+
+        assert!(fs.stat(Path::new("/sub")).unwrap().is_dir());
+        let file = fs.stat(Path::new("/sub/hello.txt")).unwrap();
+        assert_eq!(file.metadata.modified, Some(mtime));
+        assert_eq!(file.metadata.size, 5);
+
+        let mut content = String::new();
+        fs.open(Path::new("/sub/hello.txt"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "hello");
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use argh::FromArgs;
+use remotefs_fuse::OverlayFs;
+
+use super::backend::parse_arg;
+use crate::registry::{DynRemoteFs, Registry};
+
+#[derive(FromArgs, Debug, Clone)]
+#[argh(subcommand, name = "overlay")]
+/// Mount two backends as a single filesystem: changes land on the upper one, and the files missing from it are read
+/// from the lower one, which is never changed
+pub struct OverlayArgs {
+    /// name of the writable upper backend, such as `sftp` or a registered one
+    #[argh(option)]
+    upper: String,
+    /// argument passed to the upper backend as key=value; can be repeated
+    #[argh(option, from_str_fn(parse_arg))]
+    upper_arg: Vec<(String, String)>,
+    /// name of the read-only lower backend
+    #[argh(option)]
+    lower: String,
+    /// argument passed to the lower backend as key=value; can be repeated
+    #[argh(option, from_str_fn(parse_arg))]
+    lower_arg: Vec<(String, String)>,
+}
+
+impl OverlayArgs {
+    /// Create the overlay of the upper backend over the lower one, with the backends of `registry`
+    pub fn create(&self, registry: &Registry) -> anyhow::Result<DynRemoteFs> {
+        let upper = registry
+            .build(&self.upper, &to_map(&self.upper_arg))
+            .map_err(|err| anyhow::anyhow!("failed to create the upper backend: {err}"))?;
+        let lower = registry
+            .build(&self.lower, &to_map(&self.lower_arg))
+            .map_err(|err| anyhow::anyhow!("failed to create the lower backend: {err}"))?;
+
+        Ok(Box::new(OverlayFs::new(upper, lower)))
+    }
+}
+
+fn to_map(args: &[(String, String)]) -> BTreeMap<String, String> {
+    args.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_should_create_overlay() {
+        let args = OverlayArgs {
+            upper: "memory".to_string(),
+            upper_arg: vec![],
+            lower: "memory".to_string(),
+            lower_arg: vec![],
+        };
+        let mut remote = args
+            .create(&Registry::new())
+            .expect("failed to create overlay");
+        assert!(remote.connect().is_ok());
+        assert!(remote.list_dir(Path::new("/")).unwrap().is_empty());
+
+        let args = OverlayArgs {
+            lower: "overlay".to_string(),
+            ..args
+        };
+        assert!(args.create(&Registry::new()).is_err());
+    }
+}
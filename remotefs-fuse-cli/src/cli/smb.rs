@@ -1,18 +1,21 @@
+#[cfg(unix)]
+use std::str::FromStr;
+
 use argh::FromArgs;
 use remotefs_smb::{SmbCredentials, SmbFs};
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "smb")]
 /// Mount a SMB share filesystem
 pub struct SmbArgs {
-    /// hostname of the SCP server
+    /// hostname of the SMB server
     #[argh(option)]
     address: String,
-    /// port of the SCP server
+    /// port of the SMB server
     #[cfg(unix)]
     #[argh(option, default = "139")]
     port: u16,
-    /// username to authenticate with
+    /// username to authenticate with; if omitted, the share is accessed as guest
     #[argh(option)]
     username: Option<String>,
     /// password to authenticate with
@@ -25,6 +28,81 @@ pub struct SmbArgs {
     #[cfg(unix)]
     #[argh(option)]
     workgroup: Option<String>,
+    /// domain to authenticate with; same as `--workgroup`
+    #[cfg(unix)]
+    #[argh(option)]
+    domain: Option<String>,
+    /// SMB protocol version to use: nt1, smb2 or smb3
+    #[cfg(unix)]
+    #[argh(option)]
+    smb_version: Option<SmbVersion>,
+}
+
+/// SMB protocol version selected with `--smb-version`
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmbVersion {
+    Nt1,
+    Smb2,
+    Smb3,
+}
+
+#[cfg(unix)]
+impl SmbVersion {
+    /// Name of the protocol in the Samba configuration
+    fn protocol(self) -> &'static str {
+        match self {
+            SmbVersion::Nt1 => "NT1",
+            SmbVersion::Smb2 => "SMB2",
+            SmbVersion::Smb3 => "SMB3",
+        }
+    }
+}
+
+#[cfg(unix)]
+impl FromStr for SmbVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nt1" | "smb1" => Ok(SmbVersion::Nt1),
+            "smb2" => Ok(SmbVersion::Smb2),
+            "smb3" => Ok(SmbVersion::Smb3),
+            _ => Err(format!(
+                "Invalid SMB version: {s}; expected nt1, smb2 or smb3"
+            )),
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" {
+    /// Set the range of protocol versions negotiated by libsmbclient
+    fn smbc_setOptionProtocols(
+        ctx: *mut std::ffi::c_void,
+        min_proto: *const std::ffi::c_char,
+        max_proto: *const std::ffi::c_char,
+    ) -> std::ffi::c_int;
+}
+
+impl SmbArgs {
+    /// Check that a share is provided and that the credentials are coherent
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.share.trim_matches('/').is_empty() {
+            anyhow::bail!("a share must be provided with --share");
+        }
+        if self.username.is_none() && self.password.is_some() {
+            anyhow::bail!("--password requires --username");
+        }
+        #[cfg(unix)]
+        if self.workgroup.is_some() && self.domain.is_some() {
+            anyhow::bail!(
+                "--workgroup and --domain are the same setting; only one can be provided"
+            );
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(unix)]
@@ -34,31 +112,40 @@ impl From<SmbArgs> for SmbFs {
             .server(format!("smb://{}:{}", args.address, args.port))
             .share(args.share);
 
-        if let Some(username) = args.username {
-            credentials = credentials.username(username);
+        match args.username {
+            Some(username) => credentials = credentials.username(username),
+            None => log::info!("no username provided; accessing the SMB share as guest"),
         }
         if let Some(password) = args.password {
             credentials = credentials.password(password);
         }
-        if let Some(workgroup) = args.workgroup {
+        if let Some(workgroup) = args.workgroup.or(args.domain) {
             credentials = credentials.workgroup(workgroup);
         }
 
-        #[cfg(unix)]
-        {
-            SmbFs::try_new(
-                credentials,
-                remotefs_smb::SmbOptions::default()
-                    .one_share_per_server(true)
-                    .case_sensitive(false),
-            )
-            .expect("Failed to create SMB client")
-        }
+        let fs = SmbFs::try_new(
+            credentials,
+            remotefs_smb::SmbOptions::default()
+                .one_share_per_server(true)
+                .case_sensitive(false)
+                .no_auto_anonymous_login(false),
+        )
+        .expect("Failed to create SMB client");
 
-        #[cfg(windows)]
-        {
-            SmbFs::new(credentials)
+        if let Some(version) = args.smb_version {
+            let ctx = fs.client().ctx().expect("Failed to get SMB context");
+            let protocol =
+                std::ffi::CString::new(version.protocol()).expect("protocol name contains nul");
+            // SAFETY: the context is initialized by `SmbFs::try_new` and the protocol name outlives the call
+            let ok = unsafe {
+                smbc_setOptionProtocols(ctx.cast(), protocol.as_ptr(), protocol.as_ptr())
+            };
+            if ok == 0 {
+                log::error!("failed to set SMB protocol version to {version:?}");
+            }
         }
+
+        fs
     }
 }
 
@@ -77,3 +164,52 @@ impl From<SmbArgs> for SmbFs {
         SmbFs::new(credentials)
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn args(share: &str, username: Option<&str>, password: Option<&str>) -> SmbArgs {
+        SmbArgs {
+            address: "localhost".to_string(),
+            #[cfg(unix)]
+            port: 139,
+            username: username.map(str::to_string),
+            password: password.map(str::to_string),
+            share: share.to_string(),
+            #[cfg(unix)]
+            workgroup: None,
+            #[cfg(unix)]
+            domain: None,
+            #[cfg(unix)]
+            smb_version: None,
+        }
+    }
+
+    #[test]
+    fn test_should_validate_smb_args() {
+        assert!(args("temp", Some("user"), Some("secret"))
+            .validate()
+            .is_ok());
+        assert!(args("temp", None, None).validate().is_ok());
+        assert!(args("/", None, None).validate().is_err());
+        assert!(args("temp", None, Some("secret")).validate().is_err());
+
+        #[cfg(unix)]
+        {
+            let mut args = args("temp", Some("user"), None);
+            args.workgroup = Some("WORKGROUP".to_string());
+            args.domain = Some("CORP".to_string());
+            assert!(args.validate().is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_parse_smb_version() {
+        assert_eq!(SmbVersion::from_str("SMB3").unwrap(), SmbVersion::Smb3);
+        assert_eq!(SmbVersion::from_str("smb1").unwrap(), SmbVersion::Nt1);
+        assert!(SmbVersion::from_str("smb4").is_err());
+    }
+}
@@ -1,7 +1,12 @@
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
 use argh::FromArgs;
-use remotefs_ssh::{ScpFs, SftpFs, SshOpts};
+use remotefs::{RemoteError, RemoteErrorType, RemoteResult};
+use remotefs_ssh::{ScpFs, SftpFs, SshAgentIdentity, SshKeyStorage, SshOpts};
+use ssh2::{CheckResult, KnownHostFileKind, KnownHosts, Session};
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "scp")]
 /// Mount a SCP server filesystem
 pub struct ScpArgs {
@@ -16,21 +21,64 @@ pub struct ScpArgs {
     username: String,
     /// password to authenticate with
     #[argh(option)]
-    password: String,
+    password: Option<String>,
+    /// private key to authenticate with
+    #[argh(option)]
+    identity_file: Option<PathBuf>,
+    /// passphrase to decrypt the private key
+    #[argh(option)]
+    passphrase: Option<String>,
+    /// authenticate with the ssh agent at `SSH_AUTH_SOCK`
+    #[argh(switch)]
+    use_agent: bool,
+    /// known hosts file used to verify the host key (default: `~/.ssh/known_hosts`)
+    #[argh(option)]
+    known_hosts: Option<PathBuf>,
+    /// add the host key to the known hosts file if the host is unknown
+    #[argh(switch)]
+    accept_new: bool,
+    /// don't verify the host key
+    #[argh(switch)]
+    no_strict_host_key_checking: bool,
+}
+
+impl ScpArgs {
+    /// Check the authentication and host key verification arguments
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.auth().validate()?;
+        validate_host_key_policy(self.accept_new, self.no_strict_host_key_checking)
+    }
+
+    /// Get the host key verification to perform before connecting
+    pub fn host_key_check(&self) -> HostKeyCheck {
+        HostKeyCheck::new(
+            &self.hostname,
+            self.port,
+            self.known_hosts.as_deref(),
+            HostKeyPolicy::from_flags(self.accept_new, self.no_strict_host_key_checking),
+        )
+    }
+
+    fn auth(&self) -> SshAuth<'_> {
+        SshAuth {
+            password: self.password.as_deref(),
+            identity_file: self.identity_file.as_deref(),
+            passphrase: self.passphrase.as_deref(),
+            use_agent: self.use_agent,
+        }
+    }
 }
 
 impl From<ScpArgs> for ScpFs {
     fn from(args: ScpArgs) -> Self {
-        ScpFs::new(
-            SshOpts::new(args.hostname)
-                .port(args.port)
-                .username(args.username)
-                .password(args.password),
-        )
+        let opts = SshOpts::new(&args.hostname)
+            .port(args.port)
+            .username(&args.username);
+        ScpFs::new(args.auth().apply(opts))
     }
 }
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "sftp")]
 /// Mount a SFTP server filesystem
 pub struct SftpArgs {
@@ -45,16 +93,421 @@ pub struct SftpArgs {
     username: String,
     /// password to authenticate with
     #[argh(option)]
-    password: String,
+    password: Option<String>,
+    /// private key to authenticate with
+    #[argh(option)]
+    identity_file: Option<PathBuf>,
+    /// passphrase to decrypt the private key
+    #[argh(option)]
+    passphrase: Option<String>,
+    /// authenticate with the ssh agent at `SSH_AUTH_SOCK`
+    #[argh(switch)]
+    use_agent: bool,
+    /// known hosts file used to verify the host key (default: `~/.ssh/known_hosts`)
+    #[argh(option)]
+    known_hosts: Option<PathBuf>,
+    /// add the host key to the known hosts file if the host is unknown
+    #[argh(switch)]
+    accept_new: bool,
+    /// don't verify the host key
+    #[argh(switch)]
+    no_strict_host_key_checking: bool,
+}
+
+impl SftpArgs {
+    /// Check the authentication and host key verification arguments
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.auth().validate()?;
+        validate_host_key_policy(self.accept_new, self.no_strict_host_key_checking)
+    }
+
+    /// Get the host key verification to perform before connecting
+    pub fn host_key_check(&self) -> HostKeyCheck {
+        HostKeyCheck::new(
+            &self.hostname,
+            self.port,
+            self.known_hosts.as_deref(),
+            HostKeyPolicy::from_flags(self.accept_new, self.no_strict_host_key_checking),
+        )
+    }
+
+    fn auth(&self) -> SshAuth<'_> {
+        SshAuth {
+            password: self.password.as_deref(),
+            identity_file: self.identity_file.as_deref(),
+            passphrase: self.passphrase.as_deref(),
+            use_agent: self.use_agent,
+        }
+    }
 }
 
 impl From<SftpArgs> for SftpFs {
     fn from(args: SftpArgs) -> Self {
-        SftpFs::new(
-            SshOpts::new(args.hostname)
-                .port(args.port)
-                .username(args.username)
-                .password(args.password),
-        )
+        let opts = SshOpts::new(&args.hostname)
+            .port(args.port)
+            .username(&args.username);
+        SftpFs::new(args.auth().apply(opts))
+    }
+}
+
+/// Authentication arguments shared by the SSH backends
+struct SshAuth<'a> {
+    password: Option<&'a str>,
+    identity_file: Option<&'a Path>,
+    passphrase: Option<&'a str>,
+    use_agent: bool,
+}
+
+impl SshAuth<'_> {
+    /// Check that the arguments describe a single way to authenticate
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.password.is_some() && self.identity_file.is_some() {
+            anyhow::bail!("--password and --identity-file can't be used together; use --passphrase to decrypt the key");
+        }
+        if self.passphrase.is_some() && self.identity_file.is_none() {
+            anyhow::bail!("--passphrase requires --identity-file");
+        }
+        if let Some(identity_file) = self.identity_file {
+            let identity_file = expand_tilde(identity_file);
+            if !identity_file.is_file() {
+                anyhow::bail!("identity file {} doesn't exist", identity_file.display());
+            }
+        }
+        if self.use_agent && std::env::var_os("SSH_AUTH_SOCK").is_none() {
+            anyhow::bail!("--use-agent requires SSH_AUTH_SOCK to be set");
+        }
+        if self.password.is_none() && self.identity_file.is_none() && !self.use_agent {
+            anyhow::bail!("one of --password, --identity-file or --use-agent is required");
+        }
+
+        Ok(())
+    }
+
+    /// Apply the authentication arguments to `opts`.
+    ///
+    /// If the agent is enabled, it is tried first; the key or the password are used if it fails.
+    fn apply(&self, mut opts: SshOpts) -> SshOpts {
+        if self.use_agent {
+            opts = opts.ssh_agent_identity(Some(SshAgentIdentity::All));
+        }
+        if let Some(identity_file) = self.identity_file {
+            opts = opts.key_storage(Box::new(IdentityFile(expand_tilde(identity_file))));
+        }
+        // the password is also used to decrypt the key
+        if let Some(secret) = self.passphrase.or(self.password) {
+            opts = opts.password(secret);
+        }
+
+        opts
+    }
+}
+
+/// Check that the host key verification flags are not conflicting
+fn validate_host_key_policy(
+    accept_new: bool,
+    no_strict_host_key_checking: bool,
+) -> anyhow::Result<()> {
+    if accept_new && no_strict_host_key_checking {
+        anyhow::bail!("--accept-new and --no-strict-host-key-checking can't be used together");
+    }
+
+    Ok(())
+}
+
+/// How the host key is verified against the known hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKeyPolicy {
+    /// Reject hosts which are unknown or whose key doesn't match
+    Strict,
+    /// Add unknown hosts to the known hosts; reject hosts whose key doesn't match
+    AcceptNew,
+    /// Don't verify the host key
+    Disabled,
+}
+
+impl HostKeyPolicy {
+    fn from_flags(accept_new: bool, no_strict_host_key_checking: bool) -> Self {
+        if no_strict_host_key_checking {
+            Self::Disabled
+        } else if accept_new {
+            Self::AcceptNew
+        } else {
+            Self::Strict
+        }
+    }
+}
+
+/// Verification of the host key of a SSH server against a known hosts file.
+///
+/// The SSH backends don't verify the host key, so it is verified with a separate handshake each time
+/// they connect.
+#[derive(Debug, Clone)]
+pub struct HostKeyCheck {
+    hostname: String,
+    port: u16,
+    known_hosts: PathBuf,
+    policy: HostKeyPolicy,
+}
+
+impl HostKeyCheck {
+    fn new(hostname: &str, port: u16, known_hosts: Option<&Path>, policy: HostKeyPolicy) -> Self {
+        let known_hosts = known_hosts
+            .map(expand_tilde)
+            .unwrap_or_else(|| expand_tilde(Path::new("~/.ssh/known_hosts")));
+
+        Self {
+            hostname: hostname.to_string(),
+            port,
+            known_hosts,
+            policy,
+        }
+    }
+
+    /// Connect to the server and verify its host key
+    pub fn verify(&self) -> RemoteResult<()> {
+        if self.policy == HostKeyPolicy::Disabled {
+            log::warn!("host key verification for {} is disabled", self.hostname);
+            return Ok(());
+        }
+
+        let stream = TcpStream::connect((self.hostname.as_str(), self.port))
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::ConnectionError, err))?;
+        let mut session = Session::new()
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::ConnectionError, err))?;
+        session.set_tcp_stream(stream);
+        session
+            .handshake()
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::ProtocolError, err))?;
+        let Some((key, key_type)) = session.host_key() else {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::ProtocolError,
+                "the server didn't provide a host key",
+            ));
+        };
+
+        let mut known_hosts = session
+            .known_hosts()
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::ProtocolError, err))?;
+        self.verify_key(&mut known_hosts, key, key_type.into())
+    }
+
+    /// Verify `key` against `known_hosts`, loaded from the known hosts file
+    fn verify_key(
+        &self,
+        known_hosts: &mut KnownHosts,
+        key: &[u8],
+        format: ssh2::KnownHostKeyFormat,
+    ) -> RemoteResult<()> {
+        if self.known_hosts.exists() {
+            known_hosts
+                .read_file(&self.known_hosts, KnownHostFileKind::OpenSSH)
+                .map_err(|err| {
+                    RemoteError::new_ex(
+                        RemoteErrorType::IoError,
+                        format!("failed to read {}: {err}", self.known_hosts.display()),
+                    )
+                })?;
+        }
+
+        match known_hosts.check_port(&self.hostname, self.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(RemoteError::new_ex(
+                RemoteErrorType::AuthenticationFailed,
+                format!(
+                    "the host key of {} doesn't match the one in {}",
+                    self.hostname,
+                    self.known_hosts.display()
+                ),
+            )),
+            CheckResult::NotFound if self.policy == HostKeyPolicy::AcceptNew => {
+                log::info!(
+                    "adding host key of {} to {}",
+                    self.hostname,
+                    self.known_hosts.display()
+                );
+                known_hosts
+                    .add(
+                        &self.host_entry(),
+                        key,
+                        "added by remotefs-fuse-cli",
+                        format,
+                    )
+                    .and_then(|_| {
+                        known_hosts.write_file(&self.known_hosts, KnownHostFileKind::OpenSSH)
+                    })
+                    .map_err(|err| {
+                        RemoteError::new_ex(
+                            RemoteErrorType::IoError,
+                            format!("failed to write {}: {err}", self.known_hosts.display()),
+                        )
+                    })
+            }
+            CheckResult::NotFound => Err(RemoteError::new_ex(
+                RemoteErrorType::AuthenticationFailed,
+                format!(
+                    "{} is not a known host; add it to {} or use --accept-new",
+                    self.hostname,
+                    self.known_hosts.display()
+                ),
+            )),
+            CheckResult::Failure => Err(RemoteError::new_ex(
+                RemoteErrorType::ProtocolError,
+                format!("failed to verify the host key of {}", self.hostname),
+            )),
+        }
+    }
+
+    /// Name of the host in the known hosts file
+    fn host_entry(&self) -> String {
+        if self.port == 22 {
+            self.hostname.clone()
+        } else {
+            format!("[{}]:{}", self.hostname, self.port)
+        }
+    }
+}
+
+/// Key storage which resolves the same identity file for every host
+struct IdentityFile(PathBuf);
+
+impl SshKeyStorage for IdentityFile {
+    fn resolve(&self, _host: &str, _username: &str) -> Option<PathBuf> {
+        Some(self.0.clone())
+    }
+}
+
+/// Replace a leading `~` in `path` with the home directory
+fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn auth<'a>(
+        password: Option<&'a str>,
+        identity_file: Option<&'a Path>,
+        passphrase: Option<&'a str>,
+    ) -> SshAuth<'a> {
+        SshAuth {
+            password,
+            identity_file,
+            passphrase,
+            use_agent: false,
+        }
+    }
+
+    #[test]
+    fn test_should_validate_ssh_auth() {
+        let key = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"));
+
+        assert!(auth(Some("secret"), None, None).validate().is_ok());
+        assert!(auth(None, Some(key), Some("phrase")).validate().is_ok());
+        assert!(auth(Some("secret"), Some(key), None).validate().is_err());
+        assert!(auth(None, None, Some("phrase")).validate().is_err());
+        assert!(auth(None, Some(Path::new("/does/not/exist")), None)
+            .validate()
+            .is_err());
+        assert!(auth(None, None, None).validate().is_err());
+    }
+
+    #[test]
+    fn test_should_expand_tilde() {
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+        assert_eq!(
+            expand_tilde(Path::new("~/.ssh/id_rsa")),
+            home.join(".ssh/id_rsa")
+        );
+        assert_eq!(
+            expand_tilde(Path::new("/etc/key")),
+            PathBuf::from("/etc/key")
+        );
+        assert_eq!(
+            expand_tilde(Path::new("~user/key")),
+            PathBuf::from("~user/key")
+        );
+    }
+
+    fn known_hosts() -> KnownHosts {
+        Session::new().unwrap().known_hosts().unwrap()
+    }
+
+    #[test]
+    fn test_should_reject_unknown_host_by_default() {
+        let dir =
+            std::env::temp_dir().join(format!("remotefs-fuse-known-hosts-{}", std::process::id()));
+        let known_hosts_path = dir.join("known_hosts");
+        let check = HostKeyCheck::new(
+            "example.com",
+            2222,
+            Some(&known_hosts_path),
+            HostKeyPolicy::Strict,
+        );
+
+        assert!(check
+            .verify_key(&mut known_hosts(), b"key", ssh2::KnownHostKeyFormat::SshRsa)
+            .is_err());
+    }
+
+    #[test]
+    fn test_should_accept_new_host() {
+        let dir =
+            std::env::temp_dir().join(format!("remotefs-fuse-accept-new-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let known_hosts_path = dir.join("known_hosts");
+        let check = HostKeyCheck::new(
+            "example.com",
+            2222,
+            Some(&known_hosts_path),
+            HostKeyPolicy::AcceptNew,
+        );
+        assert!(check
+            .verify_key(&mut known_hosts(), b"key", ssh2::KnownHostKeyFormat::SshRsa)
+            .is_ok());
+        assert!(std::fs::read_to_string(&known_hosts_path)
+            .unwrap()
+            .starts_with("[example.com]:2222"));
+
+        // now the host is known with strict checking, and a different key is rejected
+        let check = HostKeyCheck::new(
+            "example.com",
+            2222,
+            Some(&known_hosts_path),
+            HostKeyPolicy::Strict,
+        );
+        assert!(check
+            .verify_key(&mut known_hosts(), b"key", ssh2::KnownHostKeyFormat::SshRsa)
+            .is_ok());
+        assert!(check
+            .verify_key(
+                &mut known_hosts(),
+                b"other",
+                ssh2::KnownHostKeyFormat::SshRsa
+            )
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_should_get_host_key_policy() {
+        assert_eq!(
+            HostKeyPolicy::from_flags(false, false),
+            HostKeyPolicy::Strict
+        );
+        assert_eq!(
+            HostKeyPolicy::from_flags(true, false),
+            HostKeyPolicy::AcceptNew
+        );
+        assert_eq!(
+            HostKeyPolicy::from_flags(false, true),
+            HostKeyPolicy::Disabled
+        );
+        assert!(validate_host_key_policy(true, true).is_err());
     }
 }
@@ -1,7 +1,9 @@
+use std::str::FromStr;
+
 use argh::FromArgs;
 use remotefs_webdav::WebDAVFs;
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, Debug, Clone)]
 #[argh(subcommand, name = "webdav")]
 /// Mount a WebDAV server filesystem
 pub struct WebdavArgs {
@@ -14,10 +16,69 @@ pub struct WebdavArgs {
     /// webDAV password
     #[argh(option)]
     password: String,
+    /// authentication scheme: basic (default) or digest
+    #[argh(option, default = "WebdavAuth::Basic")]
+    auth: WebdavAuth,
+}
+
+/// Authentication scheme selected with `--auth`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebdavAuth {
+    Basic,
+    Digest,
+}
+
+impl FromStr for WebdavAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "basic" => Ok(WebdavAuth::Basic),
+            "digest" => Ok(WebdavAuth::Digest),
+            _ => Err(format!(
+                "Invalid authentication scheme: {s}; expected basic or digest"
+            )),
+        }
+    }
+}
+
+impl WebdavArgs {
+    /// Check that the authentication scheme is supported by the WebDAV client
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.auth == WebdavAuth::Digest {
+            anyhow::bail!(
+                "digest authentication is not supported by the webdav client; use --auth basic"
+            );
+        }
+
+        Ok(())
+    }
 }
 
 impl From<WebdavArgs> for WebDAVFs {
     fn from(args: WebdavArgs) -> Self {
-        WebDAVFs::new(&args.url, &args.username, &args.password)
+        WebDAVFs::new(&args.username, &args.password, &args.url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn args(auth: &str) -> WebdavArgs {
+        WebdavArgs {
+            url: "http://localhost:3080".to_string(),
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+            auth: WebdavAuth::from_str(auth).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_should_validate_webdav_auth() {
+        assert!(args("basic").validate().is_ok());
+        assert!(args("digest").validate().is_err());
+        assert!(WebdavAuth::from_str("ntlm").is_err());
     }
 }
@@ -0,0 +1,190 @@
+//! # remotefs-fuse-cli
+//!
+//! The CLI tool mounting a remote filesystem with **remotefs-fuse**.
+//!
+//! Programs embedding the CLI can mount their own [`remotefs::RemoteFs`] implementations, by registering a
+//! [`RemoteFactory`] for them and running the CLI with [`run`]:
+//!
+//! ```rust,no_run,ignore
+//! fn main() -> anyhow::Result<()> {
+//!     let mut registry = remotefs_fuse_cli::Registry::new();
+//!     registry.register(MyRemoteFactory);
+//!     remotefs_fuse_cli::run(registry)
+//! }
+//! ```
+//!
+//! The backend is then mounted with `backend --name <name> --arg key=value`.
+
+mod cli;
+mod registry;
+mod remotefs_wrapper;
+#[cfg(unix)]
+mod selftest;
+#[cfg(unix)]
+mod watchdog;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use remotefs_fuse::{Mount, MountError};
+
+pub use self::registry::{DynRemoteFs, Registry, RemoteFactory};
+
+/// How long the in-flight operations are waited for when unmounting on SIGINT
+const UNMOUNT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait before mounting again after the FUSE session died, with `--auto-remount`
+#[cfg(unix)]
+const REMOUNT_DELAY: Duration = Duration::from_secs(1);
+
+/// Run the CLI with the arguments of the process, mounting the remote until the filesystem is unmounted.
+///
+/// `registry` provides the backends which can be mounted with the `backend` subcommand, in addition to the built-in
+/// ones.
+pub fn run(registry: Registry) -> anyhow::Result<()> {
+    let mut args = argh::from_env::<cli::CliArgs>();
+    args.init_logger()?;
+    let mount_path = args.to.clone();
+
+    // make options
+    let options = args.mount_options()?;
+
+    if args.check {
+        let mut remote = args.remote(&registry)?;
+        let entries = cli::check_connection(&mut remote)?;
+        println!("connection check succeeded: {entries} entries in /");
+        return Ok(());
+    }
+
+    log::info!("Mounting remote fs at {}", mount_path.display());
+
+    // create the mount point if it does not exist
+    #[cfg(unix)]
+    let created =
+        cli::create_mount_point(&mount_path, !args.no_create_mountpoint, args.create_parents)?;
+
+    // mounting over a populated directory hides its files, so it must be asked for explicitly
+    #[cfg(unix)]
+    if mount_path.is_dir()
+        && std::fs::read_dir(&mount_path)?.next().is_some()
+        && !options.contains(&remotefs_fuse::MountOption::NonEmpty)
+    {
+        anyhow::bail!(
+            "mount point {} is not empty; use --nonempty to mount over it and hide its files until unmounted",
+            mount_path.display()
+        );
+    }
+
+    // Mount the remote file system
+    let remote = args.remote(&registry)?;
+    #[cfg(unix)]
+    if args.selftest {
+        let mount = Mount::spawn(remote, &mount_path, &options).map_err(mount_failed)?;
+        let results = selftest::run(&mount_path);
+        mount.umount()?;
+        if !selftest::report(&results?) {
+            anyhow::bail!("some self-test checks failed");
+        }
+        return Ok(());
+    }
+    #[cfg(unix)]
+    let watchdog = args
+        .mount_timeout
+        .map(|secs| {
+            watchdog::MountWatchdog::start(mount_path.clone(), Duration::from_secs(secs), created)
+        })
+        .transpose()?;
+    #[cfg(unix)]
+    let mut mount = mount_remote(&args, remote, &mount_path, &options)?;
+    #[cfg(windows)]
+    let mut mount = Mount::mount_blocking(remote, &mount_path, &options).map_err(mount_failed)?;
+    let umount = Arc::new(Mutex::new(mount.unmounter()));
+    #[cfg(unix)]
+    if let Some(watchdog) = watchdog {
+        watchdog.mounted(mount.unmounter(), &mount_path)?;
+    }
+
+    // setup signal handler
+    let stopping = Arc::new(AtomicBool::new(false));
+    {
+        let umount = umount.clone();
+        let stopping = stopping.clone();
+        ctrlc::set_handler(move || {
+            log::info!("Received SIGINT, unmounting filesystem");
+            stopping.store(true, Ordering::SeqCst);
+            umount
+                .lock()
+                .expect("unmount handle poisoned")
+                .unmount_graceful(UNMOUNT_TIMEOUT)
+                .expect("Failed to unmount");
+        })?;
+    }
+
+    log::info!("Running filesystem event loop");
+    #[allow(unused_mut)]
+    let mut result = mount.run();
+    // the session died rather than being unmounted: mount the remote again
+    #[cfg(unix)]
+    while args.auto_remount && result.is_err() && !stopping.load(Ordering::SeqCst) {
+        drop(mount);
+        log::warn!(
+            "FUSE session at {} died; remounting in {}s",
+            mount_path.display(),
+            REMOUNT_DELAY.as_secs()
+        );
+        std::thread::sleep(REMOUNT_DELAY);
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let remote = args.remote(&registry)?;
+        mount = mount_remote(&args, remote, &mount_path, &options)?;
+        *umount.lock().expect("unmount handle poisoned") = mount.unmounter();
+        log::info!("Remounted remote fs at {}", mount_path.display());
+        result = mount.run();
+    }
+    result?;
+
+    Ok(())
+}
+
+/// Mount `remote` at `mount_path`, with the connector of the backend if it supports workers
+#[cfg(unix)]
+fn mount_remote(
+    args: &cli::CliArgs,
+    remote: remotefs_wrapper::RemoteFsWrapper,
+    mount_path: &std::path::Path,
+    options: &[remotefs_fuse::MountOption],
+) -> anyhow::Result<Mount<remotefs_wrapper::RemoteFsWrapper>> {
+    let mount = match args.connector() {
+        Some(connect) => Mount::mount_with_connector(remote, connect, mount_path, options),
+        None => Mount::mount_blocking(remote, mount_path, options),
+    };
+
+    mount.map_err(mount_failed)
+}
+
+/// Report `err` with what to do about it
+fn mount_failed(err: MountError) -> anyhow::Error {
+    let hint = match &err {
+        MountError::PermissionDenied(_) if cfg!(target_os = "linux") => {
+            "failed to mount; add your user to the fuse group, or enable user_allow_other in /etc/fuse.conf to use allow_other"
+        }
+        MountError::PermissionDenied(_) => "failed to mount; check that your user is allowed to use FUSE",
+        MountError::MissingHelper(_) if cfg!(target_os = "macos") => {
+            "failed to mount; install macFUSE from https://osxfuse.github.io/"
+        }
+        MountError::MissingHelper(_) if cfg!(windows) => {
+            "failed to mount; install the Dokan driver from https://github.com/dokan-dev/dokany/releases"
+        }
+        MountError::MissingHelper(_) => {
+            "failed to mount; install fuse3 (which provides fusermount3) and load the fuse kernel module"
+        }
+        MountError::Busy(_) => {
+            "failed to mount; the mount point is busy, unmount it first with `fusermount -u`"
+        }
+        MountError::OptionRejected(_) => "failed to mount; check the mount point and the mount options",
+        _ => "failed to mount",
+    };
+
+    anyhow::Error::new(err).context(hint)
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use argh::FromArgs as _;
+use remotefs::RemoteFs;
+
+use crate::cli::RemoteArgs;
+
+/// A remote created by a [`RemoteFactory`]
+pub type DynRemoteFs = Box<dyn RemoteFs + Send + Sync>;
+
+/// A backend which can be mounted by the CLI once added to a [`Registry`].
+///
+/// The backend is selected with `backend --name <name>`, and created from the `--arg key=value` pairs of the
+/// subcommand.
+pub trait RemoteFactory: Send + Sync {
+    /// Name of the backend
+    fn name(&self) -> &str;
+
+    /// Create the remote from the `key=value` arguments
+    fn create(&self, args: &BTreeMap<String, String>) -> anyhow::Result<DynRemoteFs>;
+}
+
+/// The backends which can be mounted with `backend --name`, consulted before the built-in ones.
+///
+/// To mount a custom [`RemoteFs`] implementation with the CLI, register its factory and run the CLI with
+/// [`crate::run`] from your own binary.
+#[derive(Default)]
+pub struct Registry {
+    factories: Vec<Box<dyn RemoteFactory>>,
+}
+
+impl Registry {
+    /// Create a registry with only the built-in backends
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `factory` to the registry; it replaces any backend with the same name, including a built-in one.
+    pub fn register(&mut self, factory: impl RemoteFactory + 'static) -> &mut Self {
+        self.factories
+            .retain(|registered| registered.name() != factory.name());
+        self.factories.push(Box::new(factory));
+        self
+    }
+
+    /// Create the remote of the backend `name` from the `key=value` arguments in `args`.
+    ///
+    /// The built-in backends are the subcommands of the CLI, such as `sftp` or `aws-s3`: each argument is passed as
+    /// an option of the subcommand, so `key=value` becomes `--key value` and a key with an empty value becomes the
+    /// `--key` switch.
+    pub fn build(
+        &self,
+        name: &str,
+        args: &BTreeMap<String, String>,
+    ) -> anyhow::Result<DynRemoteFs> {
+        if let Some(factory) = self.factories.iter().find(|factory| factory.name() == name) {
+            log::debug!("creating remote with the registered backend {name}");
+            return factory.create(args);
+        }
+
+        // a backend can't refer to other backends by name
+        let builtin = <RemoteArgs as argh::SubCommands>::COMMANDS
+            .iter()
+            .any(|command| command.name == name && !matches!(name, "backend" | "overlay"));
+        if !builtin {
+            anyhow::bail!("unknown backend {name}");
+        }
+        let options = args
+            .iter()
+            .flat_map(|(key, value)| {
+                let flag = format!("--{key}");
+                match value.is_empty() {
+                    true => vec![flag],
+                    false => vec![flag, value.clone()],
+                }
+            })
+            .collect::<Vec<_>>();
+        let options = options.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut remote =
+            RemoteArgs::from_args(&[env!("CARGO_PKG_NAME"), name], &options).map_err(|exit| {
+                anyhow::anyhow!("invalid arguments for backend {name}: {}", exit.output)
+            })?;
+
+        Ok(Box::new(remote.create(self)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use remotefs::fs::UnixPex;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    struct TestFactory;
+
+    impl RemoteFactory for TestFactory {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn create(&self, args: &BTreeMap<String, String>) -> anyhow::Result<DynRemoteFs> {
+            anyhow::ensure!(args.get("token").is_some(), "token is required");
+            Ok(Box::new(MemoryFs::new(Tree::new(node!(
+                "/".into(),
+                Inode::dir(0, 0, UnixPex::from(0o755))
+            )))))
+        }
+    }
+
+    #[test]
+    fn test_should_build_registered_backend() {
+        let mut registry = Registry::new();
+        registry.register(TestFactory);
+
+        let mut args = BTreeMap::new();
+        assert!(registry.build("test", &args).is_err());
+        args.insert("token".to_string(), "secret".to_string());
+        let mut remote = registry
+            .build("test", &args)
+            .expect("failed to build remote");
+        assert!(remote.connect().is_ok());
+    }
+
+    #[test]
+    fn test_should_build_builtin_backend() {
+        let registry = Registry::new();
+        let mut remote = registry
+            .build("memory", &BTreeMap::new())
+            .expect("failed to build remote");
+        assert!(remote.connect().is_ok());
+
+        let args = BTreeMap::from([("seed".to_string(), "/does/not/exist".to_string())]);
+        assert!(registry.build("memory", &args).is_err());
+    }
+
+    #[test]
+    fn test_should_reject_unknown_backend() {
+        let registry = Registry::new();
+        let err = registry
+            .build("gopher", &BTreeMap::new())
+            .err()
+            .expect("unknown backend should fail");
+        assert_eq!(err.to_string(), "unknown backend gopher");
+        assert!(registry.build("backend", &BTreeMap::new()).is_err());
+    }
+}
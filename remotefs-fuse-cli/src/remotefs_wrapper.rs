@@ -3,20 +3,29 @@ use std::path::PathBuf;
 use remotefs::fs::UnixPex;
 use remotefs::{RemoteFs, RemoteResult};
 
+#[cfg(feature = "aws-s3")]
+use crate::cli::AwsS3Check;
+#[cfg(feature = "ssh")]
+use crate::cli::HostKeyCheck;
+
 /// Wrapper around the different [`RemoteFs`] implementations
 #[allow(clippy::large_enum_variant)]
 pub enum RemoteFsWrapper {
     #[cfg(feature = "aws-s3")]
-    Aws(remotefs_aws_s3::AwsS3Fs),
+    Aws(remotefs_aws_s3::AwsS3Fs, AwsS3Check),
+    /// A backend created by a [`crate::RemoteFactory`]
+    Custom(crate::registry::DynRemoteFs),
     #[cfg(feature = "ftp")]
     Ftp(remotefs_ftp::FtpFs),
     #[cfg(feature = "kube")]
     Kube(remotefs_kube::KubeMultiPodFs),
+    #[cfg(feature = "kube")]
+    KubeContainer(remotefs_kube::KubeContainerFs),
     Memory(remotefs_memory::MemoryFs),
     #[cfg(feature = "ssh")]
-    Scp(remotefs_ssh::ScpFs),
+    Scp(remotefs_ssh::ScpFs, HostKeyCheck),
     #[cfg(feature = "ssh")]
-    Sftp(remotefs_ssh::SftpFs),
+    Sftp(remotefs_ssh::SftpFs, HostKeyCheck),
     #[cfg(feature = "smb")]
     Smb(remotefs_smb::SmbFs),
     #[cfg(feature = "webdav")]
@@ -31,16 +40,19 @@ impl RemoteFsWrapper {
     {
         match self {
             #[cfg(feature = "aws-s3")]
-            RemoteFsWrapper::Aws(fs) => f(fs),
+            RemoteFsWrapper::Aws(fs, _) => f(fs),
+            RemoteFsWrapper::Custom(fs) => f(fs.as_mut()),
             #[cfg(feature = "ftp")]
             RemoteFsWrapper::Ftp(fs) => f(fs),
             #[cfg(feature = "kube")]
             RemoteFsWrapper::Kube(fs) => f(fs),
+            #[cfg(feature = "kube")]
+            RemoteFsWrapper::KubeContainer(fs) => f(fs),
             RemoteFsWrapper::Memory(fs) => f(fs),
             #[cfg(feature = "ssh")]
-            RemoteFsWrapper::Scp(fs) => f(fs),
+            RemoteFsWrapper::Scp(fs, _) => f(fs),
             #[cfg(feature = "ssh")]
-            RemoteFsWrapper::Sftp(fs) => f(fs),
+            RemoteFsWrapper::Sftp(fs, _) => f(fs),
             #[cfg(feature = "smb")]
             RemoteFsWrapper::Smb(fs) => f(fs),
             #[cfg(feature = "webdav")]
@@ -76,7 +88,17 @@ impl RemoteFs for RemoteFsWrapper {
     }
 
     fn connect(&mut self) -> RemoteResult<remotefs::fs::Welcome> {
-        self.on_remote(|fs| fs.connect())
+        #[cfg(feature = "ssh")]
+        if let RemoteFsWrapper::Scp(_, check) | RemoteFsWrapper::Sftp(_, check) = self {
+            check.verify()?;
+        }
+        let welcome = self.on_remote(|fs| fs.connect())?;
+        #[cfg(feature = "aws-s3")]
+        if let RemoteFsWrapper::Aws(fs, check) = self {
+            check.verify(fs)?;
+        }
+
+        Ok(welcome)
     }
 
     fn copy(&mut self, src: &std::path::Path, dest: &std::path::Path) -> RemoteResult<()> {
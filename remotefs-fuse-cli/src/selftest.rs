@@ -0,0 +1,224 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, Write as _};
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+
+/// A POSIX behavior checked by the self-test, in its own directory
+struct Check {
+    name: &'static str,
+    run: fn(&Path) -> std::io::Result<()>,
+}
+
+/// The checks run by the self-test, in order
+const CHECKS: &[Check] = &[
+    Check {
+        name: "mkdir",
+        run: check_mkdir,
+    },
+    Check {
+        name: "create",
+        run: check_create,
+    },
+    Check {
+        name: "write/read",
+        run: check_write_read,
+    },
+    Check {
+        name: "append",
+        run: check_append,
+    },
+    Check {
+        name: "truncate",
+        run: check_truncate,
+    },
+    Check {
+        name: "rename",
+        run: check_rename,
+    },
+    Check {
+        name: "symlink",
+        run: check_symlink,
+    },
+    Check {
+        name: "chmod",
+        run: check_chmod,
+    },
+    Check {
+        name: "stat",
+        run: check_stat,
+    },
+    Check {
+        name: "unlink",
+        run: check_unlink,
+    },
+    Check {
+        name: "rmdir",
+        run: check_rmdir,
+    },
+];
+
+/// Run the checks against the filesystem mounted at `mount_path`, in a scratch directory removed afterwards.
+///
+/// Returns the result of each check, by name.
+pub fn run(mount_path: &Path) -> std::io::Result<Vec<(&'static str, std::io::Result<()>)>> {
+    let scratch = mount_path.join(format!(".remotefs-fuse-selftest-{}", std::process::id()));
+    fs::create_dir(&scratch)?;
+
+    let results = CHECKS
+        .iter()
+        .enumerate()
+        .map(|(index, check)| {
+            let dir = scratch.join(index.to_string());
+            let result = fs::create_dir(&dir).and_then(|_| (check.run)(&dir));
+            (check.name, result)
+        })
+        .collect();
+
+    if let Err(err) = fs::remove_dir_all(&scratch) {
+        log::warn!(
+            "failed to remove the self-test directory {}: {err}",
+            scratch.display()
+        );
+    }
+
+    Ok(results)
+}
+
+/// Print the pass/fail matrix of `results`, returning whether all the checks passed
+pub fn report(results: &[(&'static str, std::io::Result<()>)]) -> bool {
+    for (name, result) in results {
+        match result {
+            Ok(()) => println!("{name:<12} pass"),
+            Err(err) => println!("{name:<12} FAIL: {err}"),
+        }
+    }
+    let passed = results.iter().filter(|(_, result)| result.is_ok()).count();
+    println!("{passed}/{} checks passed", results.len());
+
+    passed == results.len()
+}
+
+/// Fail with `what` unless `condition` holds
+fn ensure(condition: bool, what: &str) -> std::io::Result<()> {
+    if condition {
+        Ok(())
+    } else {
+        Err(Error::other(what.to_string()))
+    }
+}
+
+fn check_mkdir(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("dir");
+    fs::create_dir(&path)?;
+    ensure(
+        fs::metadata(&path)?.is_dir(),
+        "created directory is not a directory",
+    )
+}
+
+fn check_create(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::File::create(&path)?;
+    ensure(fs::metadata(&path)?.is_file(), "created file is not a file")
+}
+
+fn check_write_read(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::write(&path, b"hello")?;
+    ensure(
+        fs::read(&path)? == b"hello",
+        "read content differs from the written one",
+    )
+}
+
+fn check_append(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::write(&path, b"hello")?;
+    OpenOptions::new()
+        .append(true)
+        .open(&path)?
+        .write_all(b" world")?;
+    ensure(
+        fs::read(&path)? == b"hello world",
+        "appended content is not at the end of the file",
+    )
+}
+
+fn check_truncate(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::write(&path, b"hello world")?;
+    OpenOptions::new().write(true).open(&path)?.set_len(5)?;
+    ensure(fs::read(&path)? == b"hello", "file was not truncated")
+}
+
+fn check_rename(dir: &Path) -> std::io::Result<()> {
+    let (from, to) = (dir.join("from"), dir.join("to"));
+    fs::write(&from, b"hello")?;
+    fs::rename(&from, &to)?;
+    ensure(!from.exists(), "source still exists after rename")?;
+    ensure(fs::read(&to)? == b"hello", "renamed file content differs")
+}
+
+fn check_symlink(dir: &Path) -> std::io::Result<()> {
+    let (target, link) = (dir.join("target"), dir.join("link"));
+    fs::write(&target, b"hello")?;
+    std::os::unix::fs::symlink("target", &link)?;
+    ensure(
+        fs::read_link(&link)? == Path::new("target"),
+        "link target differs",
+    )?;
+    ensure(
+        fs::read(&link)? == b"hello",
+        "content read through the link differs",
+    )
+}
+
+fn check_chmod(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::write(&path, b"hello")?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    ensure(
+        fs::metadata(&path)?.permissions().mode() & 0o777 == 0o600,
+        "mode was not changed",
+    )
+}
+
+fn check_stat(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::write(&path, b"hello")?;
+    let metadata = fs::metadata(&path)?;
+    ensure(metadata.is_file(), "file is not a file")?;
+    ensure(metadata.len() == 5, "size differs from the written one")
+}
+
+fn check_unlink(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("file");
+    fs::write(&path, b"hello")?;
+    fs::remove_file(&path)?;
+    ensure(!path.exists(), "file still exists after unlink")
+}
+
+fn check_rmdir(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join("dir");
+    fs::create_dir(&path)?;
+    fs::remove_dir(&path)?;
+    ensure(!path.exists(), "directory still exists after rmdir")
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_pass_selftest_on_local_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        let results = run(dir.path()).expect("failed to run self-test");
+        assert_eq!(results.len(), CHECKS.len());
+        for (name, result) in &results {
+            assert!(result.is_ok(), "{name} failed: {result:?}");
+        }
+        assert!(report(&results));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
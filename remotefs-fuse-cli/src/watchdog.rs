@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use remotefs_fuse::Unmount;
+
+/// Progress of the mount, reported to the [`MountWatchdog`]
+enum Event {
+    /// The kernel mount completed
+    Mounted(Unmount),
+    /// The driver replied to the first request, so it has connected to the remote
+    Ready,
+}
+
+/// Exits the process if the filesystem isn't mounted and connected to the remote within `--mount-timeout`.
+///
+/// On timeout the filesystem is unmounted if the kernel mount completed, and the mount point is removed if it was
+/// created for the mount.
+pub struct MountWatchdog {
+    sender: Sender<Event>,
+}
+
+impl MountWatchdog {
+    /// Start watching the mount at `mount_path`; `created` tells whether the mount point was created for the mount.
+    pub fn start(mount_path: PathBuf, timeout: Duration, created: bool) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("remotefs-fuse-mount-watchdog".to_string())
+            .spawn(move || {
+                let deadline = Instant::now() + timeout;
+                let mut unmount = None;
+                loop {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match receiver.recv_timeout(remaining) {
+                        Ok(Event::Mounted(handle)) => unmount = Some(handle),
+                        Ok(Event::Ready) | Err(RecvTimeoutError::Disconnected) => return,
+                        Err(RecvTimeoutError::Timeout) => {
+                            fail(&mount_path, timeout, created, unmount)
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Self { sender })
+    }
+
+    /// Report the kernel mount completed.
+    ///
+    /// The watchdog stops once the driver has replied to a stat of `mount_path`, which the kernel holds until the
+    /// driver is initialized.
+    pub fn mounted(self, unmount: Unmount, mount_path: &Path) -> anyhow::Result<()> {
+        let _ = self.sender.send(Event::Mounted(unmount));
+        let mount_path = mount_path.to_path_buf();
+        std::thread::Builder::new()
+            .name("remotefs-fuse-mount-probe".to_string())
+            .spawn(move || {
+                if let Err(err) = std::fs::metadata(&mount_path) {
+                    log::debug!("failed to stat mount point: {err}");
+                }
+                let _ = self.sender.send(Event::Ready);
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Give up on the mount at `mount_path`, cleaning up and exiting the process
+fn fail(mount_path: &Path, timeout: Duration, created: bool, unmount: Option<Unmount>) -> ! {
+    log::error!(
+        "mounting {} did not complete within {}s; check that FUSE is installed and the fuse kernel module is loaded, and that the remote is reachable",
+        mount_path.display(),
+        timeout.as_secs()
+    );
+    if let Some(mut unmount) = unmount {
+        if let Err(err) = unmount.unmount() {
+            log::error!("failed to unmount {}: {err}", mount_path.display());
+        }
+    }
+    if created {
+        if let Err(err) = std::fs::remove_dir(mount_path) {
+            log::error!(
+                "failed to remove mount point {}: {err}",
+                mount_path.display()
+            );
+        }
+    }
+
+    std::process::exit(1);
+}
@@ -7,6 +7,8 @@ mod windows;
 
 use remotefs::RemoteFs;
 
+#[cfg(unix)]
+pub(crate) use self::unix::{Drain, PendingRemote};
 use crate::MountOption;
 
 /// Remote Filesystem Driver
@@ -22,6 +24,80 @@ pub struct Driver<T: RemoteFs> {
     /// File handle database
     #[cfg(unix)]
     file_handlers: unix::FileHandlersDb,
+    /// Inode change times tracked by the mount, since remote filesystems don't provide them
+    #[cfg(unix)]
+    ctimes: std::collections::HashMap<u64, std::time::SystemTime>,
+    /// Directories with the setgid bit set through the mount, since remote filesystems don't store it
+    #[cfg(unix)]
+    setgid_dirs: std::collections::HashSet<u64>,
+    /// Operation metrics; set only if [`MountOption::MetricsAddr`] is provided
+    #[cfg(unix)]
+    metrics: Option<std::sync::Arc<unix::Metrics>>,
+    /// Trace of the last operations; set only if [`MountOption::OpTrace`] is provided
+    #[cfg(unix)]
+    op_trace: Option<std::sync::Arc<unix::OpTrace>>,
+    /// Liveness of the mount; set only if [`MountOption::HealthAddr`] is provided
+    #[cfg(unix)]
+    health: Option<std::sync::Arc<unix::Health>>,
+    /// Read cache; set only if [`MountOption::ReadCacheSize`] is provided
+    #[cfg(unix)]
+    page_cache: Option<unix::PageCache>,
+    /// Symlink targets cache; set only if [`MountOption::CacheSymlinks`] is provided
+    #[cfg(unix)]
+    symlink_cache: Option<unix::SymlinkCache>,
+    /// Paces the reads from the remote; set only if [`MountOption::ReadRateLimit`] is provided
+    #[cfg(unix)]
+    read_limit: Option<std::sync::Arc<unix::RateLimit>>,
+    /// Paces the uploads to the remote; set only if [`MountOption::WriteRateLimit`] is provided
+    #[cfg(unix)]
+    write_limit: Option<std::sync::Arc<unix::RateLimit>>,
+    /// Content of the files being written, uploaded to the remote on flush
+    #[cfg(unix)]
+    write_buffers: std::collections::HashMap<u64, unix::WriteBuffer>,
+    /// Directory listings used by [`MountOption::CaseInsensitive`] lookups, with the time they were taken
+    #[cfg(unix)]
+    case_insensitive_listings: std::collections::HashMap<
+        std::path::PathBuf,
+        (std::time::Instant, Vec<std::path::PathBuf>),
+    >,
+    /// Paths which were missing on the remote, with the time they were looked up, for [`MountOption::NegativeTimeout`]
+    #[cfg(unix)]
+    missing_paths: std::collections::HashMap<std::path::PathBuf, std::time::Instant>,
+    /// Maps the owners of the files between the remote and the local host; set only if [`MountOption::IdMap`] is provided
+    #[cfg(unix)]
+    id_map: Option<unix::IdMap>,
+    /// Watches the open files and the opened directories for external changes; set only if
+    /// [`MountOption::RevalidateInterval`] or [`MountOption::WatchInterval`] is provided
+    #[cfg(unix)]
+    monitor: Option<unix::Monitor>,
+    /// Remote stream kept open between sequential reads of the same file
+    #[cfg(unix)]
+    read_stream: Option<unix::OpenStream>,
+    /// Records the operations changing the filesystem; set only if [`MountOption::AuditLog`] is provided
+    #[cfg(unix)]
+    audit_log: Option<unix::AuditLog>,
+    /// Switches the mount to read-only on repeated write failures; set only if [`MountOption::WriteFailureThreshold`] is provided
+    #[cfg(unix)]
+    write_breaker: Option<unix::WriteBreaker>,
+    /// Workers serving the reads; set only if [`MountOption::Workers`] is provided along with a connector
+    #[cfg(unix)]
+    workers: Option<unix::WorkerPool<T>>,
+    /// Set once the remote streams are found not to support seeking, to stop splitting the reads with
+    /// [`MountOption::ParallelReadStreams`]
+    #[cfg(unix)]
+    streams_unsupported: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    /// Set once the remote is found not to support appending, to stop trying with [`MountOption::AppendWrites`]
+    #[cfg(unix)]
+    append_unsupported: bool,
+    /// Operations changing the filesystem, waited for by [`crate::Unmount::unmount_graceful`]
+    #[cfg(unix)]
+    pub(crate) drain: std::sync::Arc<unix::Drain>,
+    /// Remote replacing [`Driver::remote`] before the next operation, set by [`crate::Refresher`]
+    #[cfg(unix)]
+    pub(crate) pending_remote: std::sync::Arc<unix::PendingRemote<T>>,
+    /// Notifier of the session, used to invalidate the kernel caches; set once mounted
+    #[cfg(unix)]
+    pub(crate) notifier: std::sync::Arc<std::sync::OnceLock<fuser::Notifier>>,
     /// Mount options
     pub(crate) options: Vec<MountOption>,
     #[cfg(unix)]
@@ -49,11 +125,183 @@ where
     /// * `remote` - The instance which implements the [`RemoteFs`] trait.
     /// * `options` - The mount options.
     pub fn new(remote: T, options: Vec<MountOption>) -> Self {
+        #[cfg(unix)]
+        let metrics = options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::MetricsAddr(_)))
+            .then(Default::default);
+        #[cfg(unix)]
+        let health = options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::HealthAddr(_)))
+            .then(Default::default);
+        #[cfg(unix)]
+        let database = match options.iter().find_map(|opt| match opt {
+            MountOption::InodeCacheCap(capacity) => Some(*capacity),
+            _ => None,
+        }) {
+            Some(capacity) => unix::InodeDb::load().with_capacity(capacity),
+            None => unix::InodeDb::load(),
+        };
+        #[cfg(unix)]
+        let op_trace = options.iter().find_map(|opt| match opt {
+            MountOption::OpTrace(size) => Some(std::sync::Arc::new(unix::OpTrace::new(*size))),
+            _ => None,
+        });
+        #[cfg(unix)]
+        let page_cache = options.iter().find_map(|opt| match opt {
+            MountOption::ReadCacheSize(size) => Some(unix::PageCache::new(*size)),
+            _ => None,
+        });
+        #[cfg(unix)]
+        let symlink_cache = options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::CacheSymlinks))
+            .then(|| {
+                let size = options
+                    .iter()
+                    .find_map(|opt| match opt {
+                        MountOption::ReadCacheSize(size) => Some(*size),
+                        _ => None,
+                    })
+                    .unwrap_or(unix::DEFAULT_SYMLINK_CACHE_SIZE);
+                unix::SymlinkCache::new(size)
+            });
+        #[cfg(unix)]
+        let read_limit = options.iter().find_map(|opt| match opt {
+            MountOption::ReadRateLimit(rate) => {
+                Some(std::sync::Arc::new(unix::RateLimit::new(*rate)))
+            }
+            _ => None,
+        });
+        #[cfg(unix)]
+        let write_limit = options.iter().find_map(|opt| match opt {
+            MountOption::WriteRateLimit(rate) => {
+                Some(std::sync::Arc::new(unix::RateLimit::new(*rate)))
+            }
+            _ => None,
+        });
+        #[cfg(unix)]
+        let id_map = options.iter().find_map(|opt| match opt {
+            MountOption::IdMap {
+                uid_map,
+                gid_map,
+                default_uid,
+                default_gid,
+            } => Some(unix::IdMap::new(
+                uid_map.clone(),
+                gid_map.clone(),
+                *default_uid,
+                *default_gid,
+            )),
+            _ => None,
+        });
+        #[cfg(unix)]
+        let revalidator = options.iter().find_map(|opt| match opt {
+            MountOption::RevalidateInterval(interval) => Some(unix::Revalidator::new(*interval)),
+            _ => None,
+        });
+        #[cfg(unix)]
+        let audit_log = options.iter().find_map(|opt| match opt {
+            MountOption::AuditLog(path) => {
+                let reads = options
+                    .iter()
+                    .any(|opt| matches!(opt, MountOption::AuditReads));
+                match unix::AuditLog::open(path, reads) {
+                    Ok(log) => Some(log),
+                    Err(err) => {
+                        error!("Failed to open audit log at {}: {err}", path.display());
+                        None
+                    }
+                }
+            }
+            _ => None,
+        });
+        #[cfg(unix)]
+        let dir_watcher = options.iter().find_map(|opt| match opt {
+            MountOption::WatchInterval(interval) => {
+                let depth = options
+                    .iter()
+                    .find_map(|opt| match opt {
+                        MountOption::WatchDepth(depth) => Some(*depth),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                Some(unix::DirWatcher::new(*interval, depth))
+            }
+            _ => None,
+        });
+        #[cfg(unix)]
+        let monitor = (revalidator.is_some() || dir_watcher.is_some()).then(|| {
+            let exclude = options
+                .iter()
+                .filter_map(|opt| match opt {
+                    MountOption::Exclude(patterns) => Some(patterns.clone()),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            unix::Monitor::new(revalidator, dir_watcher, exclude)
+        });
+        #[cfg(unix)]
+        let write_breaker = options.iter().find_map(|opt| match opt {
+            MountOption::WriteFailureThreshold(threshold) => {
+                Some(unix::WriteBreaker::new(*threshold))
+            }
+            _ => None,
+        });
+
         Self {
             #[cfg(unix)]
-            database: unix::InodeDb::load(),
+            database,
             #[cfg(unix)]
             file_handlers: unix::FileHandlersDb::default(),
+            #[cfg(unix)]
+            ctimes: std::collections::HashMap::new(),
+            #[cfg(unix)]
+            setgid_dirs: std::collections::HashSet::new(),
+            #[cfg(unix)]
+            metrics,
+            #[cfg(unix)]
+            op_trace,
+            #[cfg(unix)]
+            health,
+            #[cfg(unix)]
+            page_cache,
+            #[cfg(unix)]
+            symlink_cache,
+            #[cfg(unix)]
+            read_limit,
+            #[cfg(unix)]
+            write_limit,
+            #[cfg(unix)]
+            write_buffers: std::collections::HashMap::new(),
+            #[cfg(unix)]
+            case_insensitive_listings: std::collections::HashMap::new(),
+            #[cfg(unix)]
+            missing_paths: std::collections::HashMap::new(),
+            #[cfg(unix)]
+            id_map,
+            #[cfg(unix)]
+            monitor,
+            #[cfg(unix)]
+            read_stream: None,
+            #[cfg(unix)]
+            audit_log,
+            #[cfg(unix)]
+            write_breaker,
+            #[cfg(unix)]
+            workers: None,
+            #[cfg(unix)]
+            streams_unsupported: Default::default(),
+            #[cfg(unix)]
+            append_unsupported: false,
+            #[cfg(unix)]
+            drain: Default::default(),
+            #[cfg(unix)]
+            pending_remote: Default::default(),
+            #[cfg(unix)]
+            notifier: Default::default(),
             options,
             #[cfg(unix)]
             remote,
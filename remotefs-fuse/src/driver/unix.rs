@@ -1,39 +1,146 @@
+mod audit;
+mod drain;
 mod file_handle;
+mod health;
+mod id_map;
 mod inode;
+mod metrics;
+mod monitor;
+mod page_cache;
+mod parallel_read;
+mod pool;
+mod read_stream;
+mod refresh;
+mod retry;
+mod revalidate;
+mod symlink_cache;
 #[cfg(test)]
 mod test;
+mod throttle;
+mod trace;
+mod watcher;
+mod workers;
+mod write_breaker;
+mod write_buffer;
 
 use std::ffi::OsStr;
 use std::fs;
 use std::hash::{Hash as _, Hasher as _};
-use std::io::{Cursor, Read as _, Seek as _};
+use std::io::{Cursor, Read, Seek as _, Write};
 use std::os::unix::ffi::OsStrExt;
-use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use fuser::{
-    FileAttr, FileType, Filesystem, KernelConfig, ReplyAttr, ReplyCreate, ReplyData,
-    ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr,
-    Request, TimeOrNow,
+    fuse_forget_one, FileAttr, FileType, Filesystem, KernelConfig, PollHandle, ReplyAttr,
+    ReplyBmap, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen,
+    ReplyPoll, ReplyStatfs, ReplyWrite, ReplyXattr, Request, TimeOrNow,
 };
 use inode::{Inode, ROOT_INODE};
 use libc::{c_int, mode_t};
 use nix::fcntl::OFlag;
 use nix::sys::stat::SFlag;
 use nix::unistd::AccessFlags;
-use remotefs::fs::UnixPex;
+use remotefs::fs::{ReadStream, UnixPex};
 use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
 
-pub use self::file_handle::FileHandlersDb;
+pub use self::audit::AuditLog;
+use self::audit::Audited;
+pub use self::drain::Drain;
+use self::drain::InFlight;
+pub use self::file_handle::{DirEntry, FileHandlersDb};
+pub use self::health::Health;
+pub use self::id_map::IdMap;
 pub use self::inode::InodeDb;
+pub use self::metrics::Metrics;
+use self::metrics::{Endpoints, OpTimer};
+pub use self::monitor::Monitor;
+use self::monitor::Stale;
+pub use self::page_cache::PageCache;
+use self::page_cache::PAGE_SIZE;
+use self::parallel_read::ParallelRead;
+use self::pool::RemotePool;
+pub use self::read_stream::OpenStream;
+pub use self::refresh::PendingRemote;
+use self::retry::{Retrying, DEFAULT_IO_RETRIES};
+use self::revalidate::FileVersion;
+pub use self::revalidate::Revalidator;
+pub use self::symlink_cache::{SymlinkCache, DEFAULT_SYMLINK_CACHE_SIZE};
+pub use self::throttle::RateLimit;
+use self::throttle::Throttled;
+pub use self::trace::OpTrace;
+use self::watcher::Change;
+pub use self::watcher::DirWatcher;
+pub use self::workers::WorkerPool;
+pub use self::write_breaker::WriteBreaker;
+pub use self::write_buffer::WriteBuffer;
 use super::Driver;
 use crate::MountOption;
 
 const BLOCK_SIZE: usize = 512;
+
+/// The setgid bit of a file mode; `mode_t` is narrower than `u32` on some platforms
+#[allow(clippy::unnecessary_cast)]
+const S_ISGID: u32 = libc::S_ISGID as u32;
+/// Default size of the kernel readahead, larger than the kernel default since remote latency dominates
+const DEFAULT_READ_AHEAD: u32 = 512 * 1024;
+/// Default maximum of pending background requests, larger than the kernel default since remote latency dominates
+const DEFAULT_MAX_BACKGROUND: u16 = 64;
 const FMODE_EXEC: c_int = 0x20;
 const ROOT_UID: u32 = 0;
+const ROOT_GID: u32 = 0;
+/// How long a directory listing is reused for [`MountOption::CaseInsensitive`] lookups
+const CASE_INSENSITIVE_LISTING_TTL: Duration = Duration::from_secs(1);
+/// Default time the kernel caches the attributes of a file, if [`MountOption::AttrTimeout`] is not set
+const DEFAULT_ATTR_TIMEOUT: Duration = Duration::from_secs(1);
+/// Default time the kernel caches a directory entry, if [`MountOption::EntryTimeout`] is not set
+const DEFAULT_ENTRY_TIMEOUT: Duration = Duration::from_secs(1);
+/// Default time a missing path is remembered, if [`MountOption::NegativeTimeout`] is not set
+const DEFAULT_NEGATIVE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Amount of bytes written since the last upload which triggers an upload, with [`MountOption::WriteCoalesceWindow`]
+const WRITE_COALESCE_SIZE: u64 = 8 * 1024 * 1024;
+/// Default maximum length of a file name, if [`MountOption::MaxNameLen`] is not set
+const DEFAULT_MAX_NAME_LEN: u32 = 255;
+/// Default capacity of the filesystem reported by `statfs`, if [`MountOption::Capacity`] is not set
+const DEFAULT_CAPACITY: u64 = 1 << 50;
+/// Amount of free inodes reported by `statfs`, since remote filesystems don't limit the amount of files
+const FREE_FILES: u64 = u32::MAX as u64;
+
+/// Get the total and the free blocks of a filesystem with `capacity` bytes, `used` of which are in use.
+///
+/// If more than `capacity` bytes are used, the filesystem is reported as full.
+fn block_usage(capacity: u64, used: u64) -> (u64, u64) {
+    let used = used.div_ceil(BLOCK_SIZE as u64);
+    let total = (capacity / BLOCK_SIZE as u64).max(used);
+
+    (total, total - used)
+}
+
+/// Reply to `getxattr` or `listxattr` with `value`: its size if `size` is 0, or `ERANGE` if it doesn't fit in `size`
+fn reply_xattr(reply: ReplyXattr, size: u32, value: &[u8]) {
+    if size == 0 {
+        reply.size(value.len() as u32);
+    } else if value.len() > size as usize {
+        reply.error(libc::ERANGE);
+    } else {
+        reply.data(value);
+    }
+}
+
+/// Whether the file at `path` in the mount matches any of `patterns`, either by its name or by its path
+fn matches_patterns(patterns: &[glob::Pattern], path: &Path) -> bool {
+    let name = path.file_name().and_then(OsStr::to_str);
+    patterns.iter().any(|pattern| {
+        name.map(|name| pattern.matches(name)).unwrap_or_default() || pattern.matches_path(path)
+    })
+}
 
 /// Convert a [`remotefs::fs::FileType`] to a [`FileType`] from [`fuser`]
+///
+/// [`remotefs::fs::FileType`] only has directories, regular files and symlinks: the backends already report the
+/// special files, such as sockets and devices, as one of these, and [`UnixPex`] carries no file type bits, so the
+/// mapping is total and there is nothing left for the driver to filter.
 fn convert_remote_filetype(filetype: remotefs::fs::FileType) -> FileType {
     match filetype {
         remotefs::fs::FileType::Directory => FileType::Directory,
@@ -42,19 +149,363 @@ fn convert_remote_filetype(filetype: remotefs::fs::FileType) -> FileType {
     }
 }
 
+/// Discard `n` bytes from `reader`, reading at most `chunk_size` bytes at a time.
+///
+/// Stops early if the end of the stream is reached, so that a read past the end returns no data instead of failing.
+fn skip_bytes(reader: &mut impl Read, n: u64, chunk_size: usize) -> std::io::Result<()> {
+    let mut chunk = vec![0; chunk_size.min(n as usize)];
+    let mut remaining = n;
+    while remaining > 0 {
+        let len = chunk.len().min(remaining as usize);
+        match reader.read(&mut chunk[..len]) {
+            Ok(0) => break,
+            Ok(read) => remaining -= read as u64,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Move `reader` forward to `offset`, seeking if the stream supports it, or discarding the bytes before `offset`
+/// otherwise, retrying the interrupted reads up to `retries` times.
+fn seek_stream(
+    reader: &mut ReadStream,
+    offset: u64,
+    chunk_size: usize,
+    retries: usize,
+) -> std::io::Result<()> {
+    if reader.seekable() {
+        reader.seek(std::io::SeekFrom::Start(offset)).map(|_| ())
+    } else {
+        skip_bytes(&mut Retrying::new(reader, retries), offset, chunk_size)
+    }
+}
+
+/// Read data from the file at `path` on `remote`, at most `chunk_size` bytes at a time.
+///
+/// The file is read from a stream, falling back to a temporary file in `temp_dir` if streams are not supported.
+/// Interrupted reads are retried up to `retries` times.
+fn read_remote<T: RemoteFs>(
+    remote: &mut T,
+    path: &Path,
+    buffer: &mut [u8],
+    offset: u64,
+    chunk_size: usize,
+    retries: usize,
+    temp_dir: Option<&Path>,
+) -> RemoteResult<usize> {
+    let reader = match remote.open(path) {
+        Ok(reader) => reader,
+        Err(RemoteError {
+            kind: RemoteErrorType::UnsupportedFeature,
+            ..
+        }) => return read_tempfile(remote, path, buffer, offset, retries, temp_dir),
+        Err(err) => return Err(err),
+    };
+
+    read_from_stream(remote, reader, path, buffer, offset, chunk_size, retries)
+}
+
+/// Read data from `reader`, the stream opened on the file at `path`, at most `chunk_size` bytes at a time.
+///
+/// Interrupted reads are retried up to `retries` times.
+fn read_from_stream<T: RemoteFs>(
+    remote: &mut T,
+    mut reader: ReadStream,
+    path: &Path,
+    buffer: &mut [u8],
+    offset: u64,
+    chunk_size: usize,
+    retries: usize,
+) -> RemoteResult<usize> {
+    debug!("Reading file from stream: {:?} at {offset}", path);
+    let bytes_read = seek_stream(&mut reader, offset, chunk_size, retries)
+        .and_then(|_| read_chunked(&mut Retrying::new(&mut reader, retries), buffer, chunk_size))
+        .map_err(|err| {
+            remotefs::RemoteError::new_ex(remotefs::RemoteErrorType::IoError, err.to_string())
+        })?;
+    debug!("Read {bytes_read} bytes from stream; closing stream");
+    remote.on_read(reader)?;
+
+    Ok(bytes_read)
+}
+
+/// Create a temporary file in `temp_dir`, or in the system temporary directory if not set.
+fn create_tempfile(temp_dir: Option<&Path>) -> std::io::Result<tempfile::NamedTempFile> {
+    match temp_dir {
+        Some(dir) => tempfile::Builder::new().tempfile_in(dir),
+        None => tempfile::NamedTempFile::new(),
+    }
+}
+
+/// Read data from a file using a temporary file created in `temp_dir`.
+///
+/// Returns the amount of bytes read, which is less than the size of `buffer` if the end of the file is reached.
+fn read_tempfile<T: RemoteFs>(
+    remote: &mut T,
+    path: &Path,
+    buffer: &mut [u8],
+    offset: u64,
+    retries: usize,
+    temp_dir: Option<&Path>,
+) -> RemoteResult<usize> {
+    let tempfile = match create_tempfile(temp_dir) {
+        Ok(tempfile) => tempfile,
+        Err(err) => {
+            error!("Failed to create temporary file: {err}");
+            return Err(remotefs::RemoteError::new(
+                remotefs::RemoteErrorType::IoError,
+            ));
+        }
+    };
+    let Ok(writer) = fs::OpenOptions::new().write(true).open(tempfile.path()) else {
+        error!("Failed to open temporary file");
+        return Err(remotefs::RemoteError::new(
+            remotefs::RemoteErrorType::IoError,
+        ));
+    };
+
+    // transfer to tempfile
+    remote.open_file(path, Box::new(Retrying::new(writer, retries)))?;
+
+    let Ok(mut reader) = fs::File::open(tempfile.path()) else {
+        error!("Failed to open temporary file");
+        return Err(remotefs::RemoteError::new(
+            remotefs::RemoteErrorType::IoError,
+        ));
+    };
+
+    // skip to offset
+    if offset > 0 {
+        if let Err(err) = reader.seek(std::io::SeekFrom::Start(offset)) {
+            error!("Failed to seek file: {err}");
+            return Err(remotefs::RemoteError::new(
+                remotefs::RemoteErrorType::IoError,
+            ));
+        }
+    }
+
+    // read file
+    let mut reader = Retrying::new(reader, retries);
+    let bytes_read = read_chunked(&mut reader, buffer, buffer.len().max(1)).map_err(|err| {
+        remotefs::RemoteError::new_ex(remotefs::RemoteErrorType::IoError, err.to_string())
+    })?;
+
+    if let Err(err) = tempfile.close() {
+        error!("Failed to close temporary file: {err}");
+    }
+
+    Ok(bytes_read)
+}
+
+/// Set `requested` as `name` in the kernel configuration using `set`.
+///
+/// If the kernel doesn't permit the requested value, the nearest permitted value is set instead and a warning is logged.
+/// Returns the value which has been set.
+fn negotiate_kernel_value<V, F>(name: &str, requested: V, mut set: F) -> V
+where
+    V: Copy + std::fmt::Display,
+    F: FnMut(V) -> Result<V, V>,
+{
+    match set(requested) {
+        Ok(_) => requested,
+        Err(nearest) => {
+            warn!("{name} {requested} is not permitted by the kernel; using {nearest} instead");
+            if let Err(err) = set(nearest) {
+                error!("Failed to set {name} to {nearest}: nearest permitted value is {err}");
+            }
+            nearest
+        }
+    }
+}
+
+/// Fill `buffer` from `reader`, reading at most `chunk_size` bytes at a time, until the buffer is full or EOF is reached.
+///
+/// Returns the amount of bytes read.
+fn read_chunked(
+    reader: &mut impl Read,
+    buffer: &mut [u8],
+    chunk_size: usize,
+) -> std::io::Result<usize> {
+    let mut bytes_read = 0;
+    while bytes_read < buffer.len() {
+        let end = buffer.len().min(bytes_read + chunk_size);
+        match reader.read(&mut buffer[bytes_read..end]) {
+            Ok(0) => break,
+            Ok(read) => bytes_read += read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(bytes_read)
+}
+
+/// Copy the whole content of `reader` to `writer`, at most `chunk_size` bytes at a time.
+fn copy_chunked(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    chunk_size: usize,
+) -> std::io::Result<()> {
+    let mut chunk = vec![0; chunk_size.max(1)];
+    loop {
+        let read = read_chunked(reader, &mut chunk, chunk_size)?;
+        if read == 0 {
+            return Ok(());
+        }
+        writer.write_all(&chunk[..read])?;
+    }
+}
+
+/// A [`RemoteError`] along with the entry the operation failed on, to be logged.
+///
+/// It shows the [`RemoteErrorType`] of the error next to its message, which carries the details reported by the
+/// backend, so that the failures can be told apart in the logs.
+struct ErrorContext<'a> {
+    err: &'a RemoteError,
+    path: Option<&'a Path>,
+    inode: Option<Inode>,
+}
+
+impl<'a> ErrorContext<'a> {
+    fn new(err: &'a RemoteError) -> Self {
+        Self {
+            err,
+            path: None,
+            inode: None,
+        }
+    }
+
+    /// Set the path of the entry the operation failed on
+    fn path(mut self, path: &'a Path) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Set the inode of the entry the operation failed on
+    fn inode(mut self, inode: Option<Inode>) -> Self {
+        self.inode = inode;
+        self
+    }
+}
+
+impl std::fmt::Display for ErrorContext<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [kind: {:?}", self.err, self.err.kind)?;
+        if let Some(path) = self.path {
+            write!(f, ", path: {}", path.display())?;
+        }
+        if let Some(inode) = self.inode {
+            write!(f, ", inode: {inode}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Convert a [`RemoteError`] to the errno to reply with
+fn remote_err_to_errno(err: &RemoteError) -> c_int {
+    match err.kind {
+        RemoteErrorType::NoSuchFileOrDirectory => libc::ENOENT,
+        RemoteErrorType::DirectoryAlreadyExists => libc::EEXIST,
+        RemoteErrorType::FileCreateDenied | RemoteErrorType::CouldNotOpenFile => libc::EACCES,
+        _ => libc::EIO,
+    }
+}
+
+/// Messages reported by the remotes when they are out of space or over quota, in lowercase.
+///
+/// [`RemoteErrorType`] has no variant for this, so the backends report it as a generic error with the message
+/// from the server; FTP replies with `552`, WebDAV with `507 Insufficient Storage`.
+const OUT_OF_SPACE_MESSAGES: &[&str] = &[
+    "no space",
+    "not enough space",
+    "disk full",
+    "quota",
+    "insufficient storage",
+    "exceeded storage allocation",
+    "storage full",
+    "os error 28",
+    "os error 122",
+];
+
+/// Convert a [`RemoteError`] raised while writing file content to the errno to reply with.
+///
+/// Same as [`remote_err_to_errno`], but errors telling that the remote is out of space are reported as `ENOSPC`,
+/// so that applications stop writing instead of retrying.
+fn write_err_to_errno(err: &RemoteError) -> c_int {
+    let out_of_space = err
+        .msg
+        .as_deref()
+        .map(str::to_lowercase)
+        .is_some_and(|msg| OUT_OF_SPACE_MESSAGES.iter().any(|m| msg.contains(m)));
+    if out_of_space {
+        return libc::ENOSPC;
+    }
+
+    remote_err_to_errno(err)
+}
+
+/// Normalize `path` relative to the root of the mount, resolving its `.` and `..` components lexically and
+/// collapsing repeated separators, so that the same entry always gets the same inode.
+///
+/// Returns `None` if the path escapes the root with `..`.
+fn normalize_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+
+    Some(normalized)
+}
+
+/// Get the name of a directory entry from the last component of its raw path, ignoring trailing separators.
+///
+/// Returns `None` if the component is empty or is `.` or `..`.
+fn entry_name_from_bytes(path: &Path) -> Option<&OsStr> {
+    let mut bytes = path.as_os_str().as_bytes();
+    while let Some(stripped) = bytes.strip_suffix(b"/") {
+        bytes = stripped;
+    }
+    let name = bytes.rsplit(|b| *b == b'/').next()?;
+
+    match name {
+        b"" | b"." | b".." => None,
+        name => Some(OsStr::from_bytes(name)),
+    }
+}
+
 /// Convert a [`File`] from [`remotefs`] to a [`FileAttr`] from [`fuser`]
 fn convert_file<T>(value: &File) -> FileAttr
 where
     T: RemoteFs,
 {
+    // if the remote doesn't provide a time, fallback to the other times before the epoch
+    let mtime = value
+        .metadata()
+        .modified
+        .or(value.metadata().created)
+        .or(value.metadata().accessed)
+        .unwrap_or(UNIX_EPOCH);
+
     FileAttr {
         ino: Driver::<T>::inode(value.path()),
         size: value.metadata().size,
         blocks: value.metadata().size.div_ceil(BLOCK_SIZE as u64),
-        atime: value.metadata().accessed.unwrap_or(UNIX_EPOCH),
-        mtime: value.metadata().modified.unwrap_or(UNIX_EPOCH),
-        ctime: value.metadata().created.unwrap_or(UNIX_EPOCH),
-        crtime: UNIX_EPOCH,
+        atime: value.metadata().accessed.unwrap_or(mtime),
+        mtime,
+        // the remote doesn't track the inode change time, so it's the last modification time unless changed by the mount
+        ctime: mtime,
+        crtime: value.metadata().created.unwrap_or(mtime),
         kind: convert_remote_filetype(value.metadata().file_type),
         perm: value
             .metadata()
@@ -70,11 +521,55 @@ where
     }
 }
 
-/// Convert a [`TimeOrNow`] to a [`SystemTime`]
-fn time_or_now(t: TimeOrNow) -> SystemTime {
-    match t {
+/// Truncate `time` to a multiple of `granularity` since the epoch.
+///
+/// Times before the epoch, and a zero granularity, leave `time` untouched.
+fn truncate_time(time: SystemTime, granularity: Duration) -> SystemTime {
+    let (Ok(since_epoch), granularity @ 1..) =
+        (time.duration_since(UNIX_EPOCH), granularity.as_nanos())
+    else {
+        return time;
+    };
+    let nanos = since_epoch.as_nanos() / granularity * granularity;
+
+    UNIX_EPOCH
+        + Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+}
+
+/// Apply the access and modification times set with `utimens` to `metadata`.
+///
+/// A time which is [`None`] (`UTIME_OMIT`) is left untouched, while [`TimeOrNow::Now`] (`UTIME_NOW`) is the same
+/// instant for both times. When only one time is set the other is pinned to its current value in `current`, since
+/// the remotes which don't report a time derive it from the others, so it would change along with them.
+fn set_times(
+    metadata: &mut remotefs::fs::Metadata,
+    current: &FileAttr,
+    atime: Option<TimeOrNow>,
+    mtime: Option<TimeOrNow>,
+) {
+    let now = SystemTime::now();
+    let resolve = |t| match t {
         TimeOrNow::SpecificTime(t) => t,
-        TimeOrNow::Now => SystemTime::now(),
+        TimeOrNow::Now => now,
+    };
+
+    match (atime, mtime) {
+        (None, None) => {}
+        (Some(atime), None) => {
+            metadata.accessed = Some(resolve(atime));
+            metadata.modified.get_or_insert(current.mtime);
+        }
+        (None, Some(mtime)) => {
+            metadata.modified = Some(resolve(mtime));
+            metadata.accessed.get_or_insert(current.atime);
+        }
+        (Some(atime), Some(mtime)) => {
+            metadata.accessed = Some(resolve(atime));
+            metadata.modified = Some(resolve(mtime));
+        }
     }
 }
 
@@ -111,20 +606,278 @@ where
     /// Get the inode for a path.
     ///
     /// If the inode is not in the database, it will be fetched from the remote filesystem.
+    /// The paths found missing are remembered for [`MountOption::NegativeTimeout`].
     fn get_inode_from_path(&mut self, path: &Path) -> RemoteResult<(File, FileAttr)> {
-        let (file, attrs) = self.remote.stat(path).map(|file| {
-            let attrs = convert_file::<T>(&file);
+        let Some(path) = normalize_path(path) else {
+            error!("{} escapes the root of the mount", path.display());
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::CouldNotOpenFile,
+                "path escapes the root of the mount",
+            ));
+        };
+        let path = path.as_path();
+        self.trace_path(path);
+        if self.is_missing(path) {
+            debug!("{} is known to be missing", path.display());
+            return Err(remotefs::RemoteError::new(
+                remotefs::RemoteErrorType::NoSuchFileOrDirectory,
+            ));
+        }
+
+        let stat = match self.remote.stat(&self.remote_path(path)) {
+            // some remotes can't stat their root, which must not make the whole mount unusable
+            Err(err) if path == Path::new("/") && self.single_file().is_none() => {
+                debug!("Failed to stat root directory, using default attributes: {err}");
+                Ok(self.root_file())
+            }
+            stat => stat,
+        };
+        let stat = match stat {
+            Ok(file) if self.follow_symlinks() && file.metadata().symlink.is_some() => {
+                Ok(self.dereference(path, file))
+            }
+            stat => stat,
+        };
+        if matches!(&stat, Err(err) if err.kind == remotefs::RemoteErrorType::NoSuchFileOrDirectory)
+        {
+            self.remember_missing(path);
+        }
+        let (file, attrs) = stat.map(|mut file| {
+            file.path = path.to_path_buf();
+            // the size of a file being written is the size of the pending content
+            if let Some(buffer) = self.write_buffers.get(&Self::inode(path)) {
+                file.metadata.size = buffer.size();
+            }
+            let attrs = self.file_attr(&file);
             (file, attrs)
         })?;
 
         // Save the inode to the database
-        if !self.database.has(attrs.ino) {
-            self.database.put(attrs.ino, path.to_path_buf());
-        }
+        self.remember_inode(attrs.ino, path);
 
         Ok((file, attrs))
     }
 
+    /// Save `inode` for `path` to the database as recently resolved, evicting the least recently resolved inodes
+    /// which aren't open if over [`MountOption::InodeCacheCap`].
+    fn remember_inode(&mut self, inode: Inode, path: &Path) {
+        if self.database.has(inode) {
+            if self.database.get(inode) != Some(path) {
+                warn!(
+                    "{} collides on inode {inode} with {:?}",
+                    path.display(),
+                    self.database.paths_for(inode)
+                );
+            }
+            self.database.touch(inode);
+            return;
+        }
+        self.database.put(inode, path.to_path_buf());
+        // the inode being resolved is kept, even if all the others are open
+        let file_handlers = &self.file_handlers;
+        self.database
+            .evict(|evicted| evicted == inode || file_handlers.is_open(evicted));
+    }
+
+    /// Update the database once the entry `src_inode` has been moved to `dest`, resolved to `dest_inode`.
+    ///
+    /// The source path is forgotten, since nothing is there anymore, but `src_inode` resolves to `dest` until the kernel
+    /// forgets it, as the files opened before the move keep using it.
+    fn moved_inode(&mut self, src_inode: Inode, dest_inode: Inode, dest: PathBuf) {
+        self.database.moved(src_inode, dest_inode, dest);
+        self.touch_ctime(dest_inode);
+    }
+
+    /// Whether `path` was found missing on the remote within [`MountOption::NegativeTimeout`].
+    fn is_missing(&self, path: &Path) -> bool {
+        let timeout = self.negative_timeout();
+        self.missing_paths
+            .get(path)
+            .is_some_and(|looked_up_at| looked_up_at.elapsed() < timeout)
+    }
+
+    /// Remember that `path` is missing on the remote, dropping the entries which have expired.
+    fn remember_missing(&mut self, path: &Path) {
+        let timeout = self.negative_timeout();
+        if timeout.is_zero() {
+            return;
+        }
+        self.missing_paths
+            .retain(|_, looked_up_at| looked_up_at.elapsed() < timeout);
+        self.missing_paths
+            .insert(path.to_path_buf(), Instant::now());
+    }
+
+    /// Forget that `path` is missing, once something is created there.
+    fn forget_missing(&mut self, path: &Path) {
+        self.missing_paths.remove(path);
+    }
+
+    /// Forget about `inodes`, which the kernel doesn't reference anymore.
+    fn forget_inodes(&mut self, inodes: impl IntoIterator<Item = Inode>) {
+        for inode in inodes {
+            if let Some(monitor) = self.monitor.as_ref() {
+                monitor.unwatch_dir(inode);
+            }
+            self.ctimes.remove(&inode);
+            self.setgid_dirs.remove(&inode);
+            self.database.forget(inode);
+        }
+    }
+
+    /// Present the symbolic link `link` at `path` as its target, for [`MountOption::FollowSymlinks`].
+    ///
+    /// If the link can't be resolved, it is returned as is.
+    fn dereference(&mut self, path: &Path, link: File) -> File {
+        match self.resolve_symlink(path) {
+            Ok(target) => File {
+                path: link.path,
+                metadata: target.metadata,
+            },
+            Err(errno) => {
+                debug!(
+                    "Could not follow symlink {}: {errno}; presenting it as a link",
+                    path.display()
+                );
+                link
+            }
+        }
+    }
+
+    /// Root directory with the attributes from the mount options, used if the remote can't stat it.
+    fn root_file(&self) -> File {
+        File {
+            path: PathBuf::from("/"),
+            metadata: remotefs::fs::Metadata {
+                file_type: remotefs::fs::FileType::Directory,
+                mode: Some(UnixPex::from(self.default_mode())),
+                uid: Some(
+                    self.remote_uid(self.uid().unwrap_or_else(|| nix::unistd::getuid().as_raw())),
+                ),
+                gid: Some(
+                    self.remote_gid(self.gid().unwrap_or_else(|| nix::unistd::getgid().as_raw())),
+                ),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Convert a [`File`] to a [`FileAttr`], applying the attributes tracked by the mount.
+    fn file_attr(&self, file: &File) -> FileAttr {
+        let mut attrs = convert_file::<T>(file);
+        if let Some(ctime) = self.ctimes.get(&attrs.ino) {
+            attrs.ctime = *ctime;
+        }
+        attrs.uid = self.local_uid(attrs.uid);
+        attrs.gid = self.local_gid(attrs.gid);
+        attrs.perm = self.mask_mode(u32::from(attrs.perm)) as u16;
+        if self.setgid_dirs.contains(&attrs.ino) {
+            attrs.perm |= S_ISGID as u16;
+        }
+        if let Some(granularity) = self.time_granularity() {
+            for time in [
+                &mut attrs.atime,
+                &mut attrs.mtime,
+                &mut attrs.ctime,
+                &mut attrs.crtime,
+            ] {
+                *time = truncate_time(*time, granularity);
+            }
+        }
+
+        attrs
+    }
+
+    /// Truncate the times set on `metadata` to [`MountOption::TimeGranularity`], as the remote stores them.
+    fn truncate_times(&self, metadata: &mut remotefs::fs::Metadata) {
+        let Some(granularity) = self.time_granularity() else {
+            return;
+        };
+        for time in [
+            &mut metadata.accessed,
+            &mut metadata.modified,
+            &mut metadata.created,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *time = truncate_time(*time, granularity);
+        }
+    }
+
+    /// Get the remote group of the entries created in `parent`, if they inherit it from the directory.
+    ///
+    /// Like on POSIX, the entries created in a directory with the setgid bit, or in any directory with
+    /// [`MountOption::GidFromParent`], belong to the group of the directory.
+    fn inherited_gid(&mut self, parent: Inode) -> Option<u32> {
+        if !self.setgid_dirs.contains(&parent) && !self.gid_from_parent() {
+            return None;
+        }
+
+        match self.get_inode(parent) {
+            Ok((dir, _)) => dir.metadata().gid,
+            Err(err) => {
+                error!(
+                    "Failed to get the group of the parent: {}",
+                    self.err_context(&err, parent)
+                );
+                None
+            }
+        }
+    }
+
+    /// Create the directory at `path` in `parent` on the remote, with `mode`.
+    ///
+    /// The directory inherits the group of `parent` per [`Self::inherited_gid`], and its setgid bit.
+    fn make_dir(&mut self, parent: Inode, path: &Path, mode: UnixPex) -> Result<(), c_int> {
+        let remote_path = self.remote_path(path);
+        let result = self.remote.create_dir(&remote_path, mode);
+        self.record_write(&result);
+        if let Err(err) = result {
+            return Err(self.remote_failed("create directory", path, &err));
+        }
+
+        if let Some(gid) = self.inherited_gid(parent) {
+            debug!("{} inherits group {gid}", path.display());
+            let result = self.remote.stat(&remote_path).and_then(|dir| {
+                let metadata = dir.metadata.gid(gid);
+                self.remote.setstat(&remote_path, metadata)
+            });
+            self.record_write(&result);
+            if let Err(err) = result {
+                error!(
+                    "Failed to set the group: {}",
+                    self.path_err_context(&err, path)
+                );
+                return Err(libc::EIO);
+            }
+        }
+        if self.setgid_dirs.contains(&parent) {
+            self.setgid_dirs.insert(Self::inode(path));
+        }
+
+        Ok(())
+    }
+
+    /// Set or clear the setgid bit of the directory `inode`, tracked by the mount.
+    fn set_setgid(&mut self, inode: Inode, setgid: bool) {
+        if setgid {
+            self.setgid_dirs.insert(inode);
+        } else {
+            self.setgid_dirs.remove(&inode);
+        }
+    }
+
+    /// Set the inode change time of `inode` to now.
+    fn touch_ctime(&mut self, inode: Inode) {
+        self.set_ctime(inode, SystemTime::now());
+    }
+
+    /// Set the inode change time of `inode`, which is tracked by the mount since remotes don't store it.
+    fn set_ctime(&mut self, inode: Inode, ctime: SystemTime) {
+        self.ctimes.insert(inode, ctime);
+    }
+
     /// Get the inode from the [`Inode`] number
     fn get_inode(&mut self, inode: Inode) -> RemoteResult<(File, FileAttr)> {
         let path = self
@@ -141,22 +894,237 @@ where
     /// Look up a name in a directory.
     ///
     /// This function is used to resolve a name of a child given the parent [`Inode`] and the name of the child file.
-    fn lookup_name(&mut self, parent: Inode, name: &OsStr) -> Option<PathBuf> {
-        let parent_path = self.database.get(parent)?;
-        let path = parent_path.join(name);
+    /// The path is normalized with [`normalize_path`].
+    ///
+    /// Fails with `ENOENT` if the parent is unknown, or with `EACCES` if the path escapes the root of the mount.
+    fn lookup_name(&mut self, parent: Inode, name: &OsStr) -> Result<PathBuf, c_int> {
+        self.lookup_entry(parent, name).map(|(_, path)| path)
+    }
 
-        // Get the inode and save it to the database
-        let inode = Self::inode(&path);
-        if !self.database.has(inode) {
-            self.database.put(inode, path.clone());
+    /// Look up a name in a directory, as [`Self::lookup_name`], returning the inode of the entry along with its path.
+    fn lookup_entry(&mut self, parent: Inode, name: &OsStr) -> Result<(Inode, PathBuf), c_int> {
+        if parent == ROOT_INODE && self.single_file().is_some() {
+            debug!("the root is a file with single_file; can't look up {name:?}");
+            return Err(libc::ENOTDIR);
+        }
+        let parent_path = self.database.get(parent).ok_or(libc::ENOENT)?.to_path_buf();
+        let Some(mut path) = normalize_path(&parent_path.join(name)) else {
+            error!(
+                "{name:?} in {} escapes the root of the mount",
+                parent_path.display()
+            );
+            return Err(libc::EACCES);
+        };
+
+        if self.case_insensitive()
+            && !self
+                .remote
+                .exists(&self.remote_path(&path))
+                .unwrap_or_default()
+        {
+            if let Some(matched) = self.lookup_case_insensitive(&parent_path, name) {
+                debug!("case insensitive match for {path:?}: {matched:?}");
+                path = matched;
+            }
         }
 
+        // Get the inode and save it to the database
+        self.database.touch(parent);
+        let inode = self
+            .database
+            .inode_of(&path)
+            .unwrap_or_else(|| Self::inode(&path));
+        self.remember_inode(inode, &path);
+
         info!(
             "lookup_name() called with {:?} {:?} -> {:?}",
             parent, name, path
         );
+        self.trace_path(&path);
+
+        Ok((inode, path))
+    }
+
+    /// Find the entry in `parent` whose name matches `name` ignoring case.
+    ///
+    /// The listing of `parent` is cached for [`CASE_INSENSITIVE_LISTING_TTL`].
+    fn lookup_case_insensitive(&mut self, parent: &Path, name: &OsStr) -> Option<PathBuf> {
+        let name = name.to_str()?.to_lowercase();
+
+        let cached = self
+            .case_insensitive_listings
+            .get(parent)
+            .filter(|(listed_at, _)| listed_at.elapsed() < CASE_INSENSITIVE_LISTING_TTL)
+            .is_some();
+        if !cached {
+            let entries = match self.remote.list_dir(&self.remote_path(parent)) {
+                Ok(entries) => entries
+                    .into_iter()
+                    .map(|entry| self.local_path(&entry.path))
+                    .collect::<Vec<_>>(),
+                Err(err) => {
+                    error!(
+                        "Failed to list directory: {}",
+                        self.path_err_context(&err, parent)
+                    );
+                    return None;
+                }
+            };
+            self.case_insensitive_listings
+                .insert(parent.to_path_buf(), (Instant::now(), entries));
+        }
+
+        let (_, entries) = self.case_insensitive_listings.get(parent)?;
+        entries
+            .iter()
+            .find(|entry| {
+                entry
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .map(|entry_name| entry_name.to_lowercase() == name)
+                    .unwrap_or_default()
+            })
+            .cloned()
+    }
+
+    /// Get the path on the remote for the `path` in the mount, joining it under [`MountOption::RootPrefix`].
+    fn remote_path(&self, path: &Path) -> PathBuf {
+        // the root is the file with [`MountOption::SingleFile`]
+        let path = match self.single_file() {
+            Some(file) if path == Path::new("/") => file,
+            _ => path,
+        };
+        match self.root_prefix() {
+            Some(prefix) => prefix.join(path.strip_prefix("/").unwrap_or(path)),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Get the path in the mount for the `path` on the remote, stripping [`MountOption::RootPrefix`].
+    fn local_path(&self, path: &Path) -> PathBuf {
+        let path = match self.root_prefix() {
+            Some(prefix) => match path.strip_prefix(prefix) {
+                Ok(path) => Path::new("/").join(path),
+                Err(_) => {
+                    warn!("{} is outside of the root prefix", path.display());
+                    path.to_path_buf()
+                }
+            },
+            None => path.to_path_buf(),
+        };
+        match self.single_file() {
+            Some(file) if path == file => PathBuf::from("/"),
+            _ => path,
+        }
+    }
+
+    /// Move the file at `src` to `dest` on the remote.
+    ///
+    /// If the remote can't move the file there, e.g. because `src` and `dest` are backed by different stores, the move
+    /// fails with `EXDEV`, so that userland falls back to copying and removing the file.
+    fn move_file(&mut self, src: &Path, dest: &Path) -> Result<(), c_int> {
+        let result = self
+            .remote
+            .mov(&self.remote_path(src), &self.remote_path(dest));
+        self.record_write(&result);
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+                debug!(
+                    "cannot move {} to {} on the remote: {err}",
+                    src.display(),
+                    dest.display()
+                );
+                Err(libc::EXDEV)
+            }
+            Err(err) => {
+                error!("Failed to move file: {}", self.path_err_context(&err, src));
+                Err(libc::EIO)
+            }
+        }
+    }
+
+    /// Create the file at `path` for `create`, according to the open `flags`.
+    ///
+    /// If the file already exists it is kept as is, unless `O_EXCL` is set, which fails with `EEXIST`, or `O_TRUNC`
+    /// is set, which empties it while keeping its attributes.
+    ///
+    /// The remotes have no conditional create, so checking that the file is missing and creating it are not atomic
+    /// on the remote: the kernel serializes the creations of a name in the mount, but a client of the remote may
+    /// still create the file in between.
+    fn create_for_open(
+        &mut self,
+        path: &Path,
+        metadata: &remotefs::fs::Metadata,
+        flags: OFlag,
+    ) -> Result<(), c_int> {
+        let remote_path = self.remote_path(path);
+        let existing = match self.remote.stat(&remote_path) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                if err.kind != RemoteErrorType::NoSuchFileOrDirectory {
+                    debug!("Failed to stat {}: {err}", path.display());
+                }
+                None
+            }
+        };
+
+        let result = match existing {
+            None => self.create_empty_file(path, metadata),
+            Some(_) if flags.contains(OFlag::O_EXCL) => {
+                debug!("{} already exists", path.display());
+                return Err(libc::EEXIST);
+            }
+            Some(_) if !flags.contains(OFlag::O_TRUNC) => {
+                debug!("{} already exists; opening it as is", path.display());
+                return Ok(());
+            }
+            Some(file) => {
+                debug!("{} already exists; truncating it", path.display());
+                let inode = Self::inode(path);
+                self.invalidate_page_cache(inode);
+                if let Some(buffer) = self.write_buffers.get_mut(&inode) {
+                    if let Err(err) = buffer.truncate(0) {
+                        error!("Failed to truncate write buffer: {err}");
+                        return Err(libc::EIO);
+                    }
+                }
+                let metadata = file.metadata().clone().size(0);
+                self.upload_empty(&remote_path, &metadata)
+            }
+        };
+        self.record_write(&result);
+        result.map_err(|err| {
+            error!(
+                "Failed to create file: {}",
+                self.path_err_context(&err, path)
+            );
+            write_err_to_errno(&err)
+        })
+    }
+
+    /// Check the `access_mask` of `access` for the user on `file`.
+    ///
+    /// Write checks fail with `EROFS` while the mount is read-only, whatever the mode of the file.
+    fn check_access_mask(
+        &mut self,
+        file: &File,
+        uid: u32,
+        gid: u32,
+        access_mask: AccessFlags,
+    ) -> Result<(), c_int> {
+        if access_mask.contains(AccessFlags::W_OK) {
+            self.check_writable()?;
+            if !self.is_writable_path(&self.local_path(file.path())) {
+                return Err(libc::EROFS);
+            }
+        }
+        if !self.check_access(file, uid, gid, access_mask) {
+            error!("No access to file: {}", file.path().display());
+            return Err(libc::EACCES);
+        }
 
-        Some(path)
+        Ok(())
     }
 
     /// Check whether the user has access to a inode.
@@ -169,7 +1137,10 @@ where
         let (parent, _) = match self.get_inode(inode) {
             Ok(res) => res,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, inode)
+                );
                 return false;
             }
         };
@@ -180,15 +1151,16 @@ where
     /// Check whether the user has access to a file.
     fn check_access(&self, file: &File, uid: u32, gid: u32, mut access_mask: AccessFlags) -> bool {
         debug!("Checking access for file: {:?} {:?}; UID: {uid}; GID: {gid} access_mask: {access_mask:?}", file.path(), file.metadata());
-        if access_mask == AccessFlags::F_OK {
+        if access_mask == AccessFlags::F_OK || self.no_access_check() {
             return true;
         }
 
-        let file_mode = file
-            .metadata()
-            .mode
-            .map(u32::from)
-            .unwrap_or_else(|| self.default_mode()) as i32;
+        let file_mode = self.mask_mode(
+            file.metadata()
+                .mode
+                .map(u32::from)
+                .unwrap_or_else(|| self.default_mode()),
+        ) as i32;
 
         debug!("file mode for {}: {file_mode:o}", file.path().display());
 
@@ -208,10 +1180,10 @@ where
 
         let file_uid = self
             .uid()
-            .unwrap_or_else(|| file.metadata().uid.unwrap_or_default());
+            .unwrap_or_else(|| self.local_uid(file.metadata().uid.unwrap_or_default()));
         let file_gid = self
             .gid()
-            .unwrap_or_else(|| file.metadata().gid.unwrap_or_default());
+            .unwrap_or_else(|| self.local_gid(file.metadata().gid.unwrap_or_default()));
 
         if uid == file_uid {
             access_mask -= access_mask & (file_mode >> 6);
@@ -235,177 +1207,1774 @@ where
     /// otherwise it will use a temporary file (*sigh*).
     /// Note that most of remotefs supports streaming, so this should be rare.
     fn read(&mut self, path: &Path, buffer: &mut [u8], offset: u64) -> RemoteResult<usize> {
-        match self.remote.open(path) {
-            Ok(mut reader) => {
-                debug!("Reading file from stream: {:?} at {offset}", path);
-                if offset > 0 {
-                    // read file until offset
-                    let mut offset_buff = vec![0; offset as usize];
-                    reader.read_exact(&mut offset_buff).map_err(|err| {
-                        remotefs::RemoteError::new_ex(
-                            remotefs::RemoteErrorType::IoError,
-                            err.to_string(),
-                        )
-                    })?;
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let chunk_size = self.io_chunk_size();
+        let retries = self.io_retries();
+        let remote_path = self.remote_path(path);
+        let mut stream = match self.read_stream.take() {
+            Some(stream) if stream.is_at(path, offset) => {
+                debug!("Reading file from open stream: {:?} at {offset}", path);
+                stream
+            }
+            stream => {
+                if let Some(stream) = stream {
+                    self.finalize_read_stream(stream);
+                }
+                match self.remote.open(&remote_path) {
+                    Ok(mut reader) => {
+                        debug!("Reading file from stream: {:?} at {offset}", path);
+                        if offset > 0 {
+                            // read file until offset
+                            seek_stream(&mut reader, offset, chunk_size, retries).map_err(
+                                |err| {
+                                    remotefs::RemoteError::new_ex(
+                                        remotefs::RemoteErrorType::IoError,
+                                        err.to_string(),
+                                    )
+                                },
+                            )?;
+                        }
+                        OpenStream::new(path, offset, reader)
+                    }
+                    Err(RemoteError {
+                        kind: RemoteErrorType::UnsupportedFeature,
+                        ..
+                    }) => {
+                        let temp_dir = self.temp_dir().map(Path::to_path_buf);
+                        let bytes_read = read_tempfile(
+                            &mut self.remote,
+                            &remote_path,
+                            buffer,
+                            offset,
+                            retries,
+                            temp_dir.as_deref(),
+                        )?;
+                        self.throttle_read(bytes_read);
+                        return Ok(bytes_read);
+                    }
+                    Err(err) => return Err(err),
                 }
-
-                // read file
-                let bytes_read = reader.read(buffer).map_err(|err| {
-                    remotefs::RemoteError::new_ex(
-                        remotefs::RemoteErrorType::IoError,
-                        err.to_string(),
-                    )
-                })?;
-                debug!("Read {bytes_read} bytes from stream; closing stream");
-
-                // close file
-                self.remote.on_read(reader)?;
-
-                Ok(bytes_read)
             }
-            Err(RemoteError {
-                kind: RemoteErrorType::UnsupportedFeature,
-                ..
-            }) => self.read_tempfile(path, buffer, offset),
-            Err(err) => Err(err),
+        };
+
+        // read file
+        let bytes_read = match read_chunked(
+            &mut Retrying::new(stream.reader(), retries),
+            buffer,
+            chunk_size,
+        ) {
+            Ok(bytes_read) => bytes_read,
+            Err(err) => {
+                self.finalize_read_stream(stream);
+                return Err(remotefs::RemoteError::new_ex(
+                    remotefs::RemoteErrorType::IoError,
+                    err.to_string(),
+                ));
+            }
+        };
+        stream.advance(bytes_read);
+
+        if bytes_read < buffer.len() {
+            debug!("Read {bytes_read} bytes from stream; reached EOF, closing stream");
+            self.remote.on_read(stream.into_reader())?;
+        } else {
+            debug!("Read {bytes_read} bytes from stream; keeping stream open");
+            self.read_stream = Some(stream);
+        }
+        self.throttle_read(bytes_read);
+
+        Ok(bytes_read)
+    }
+
+    /// Close the stream kept open between sequential reads, if any.
+    ///
+    /// Must be called before any other operation on the remote, since some protocols can't handle
+    /// other requests while a transfer is in progress.
+    fn close_read_stream(&mut self) {
+        if let Some(stream) = self.read_stream.take() {
+            self.finalize_read_stream(stream);
+        }
+    }
+
+    /// Get the file `inode` to read at `offset`.
+    ///
+    /// A read continuing the stream kept open is served with the file as it was on the previous read, without a
+    /// round trip to the remote, which may not handle other requests while the transfer is in progress. Otherwise
+    /// the stream is closed before looking up the file.
+    fn file_to_read(&mut self, inode: Inode, offset: u64) -> RemoteResult<File> {
+        let streamed = self.database.get(inode).and_then(|path| {
+            self.read_stream
+                .as_ref()
+                .filter(|stream| stream.is_at(path, offset))
+                .and_then(OpenStream::file)
+                .cloned()
+        });
+        if let Some(file) = streamed {
+            debug!("Reading {inode} from open stream; not checking its attributes");
+            return Ok(file);
+        }
+
+        self.close_read_stream();
+        self.get_inode(inode).map(|(file, _)| file)
+    }
+
+    /// Keep `file` along with the stream it's being read from, if still open, for the next [`Self::file_to_read`]
+    fn keep_stream_file(&mut self, file: File) {
+        if let Some(stream) = self
+            .read_stream
+            .as_mut()
+            .filter(|stream| stream.path() == file.path())
+        {
+            stream.set_file(file);
+        }
+    }
+
+    /// Finalize `stream` on the remote.
+    fn finalize_read_stream(&mut self, stream: OpenStream) {
+        debug!("Closing read stream: {stream:?}");
+        if let Err(err) = self.remote.on_read(stream.into_reader()) {
+            error!("Failed to close read stream: {err}");
         }
     }
 
-    /// Read data from a file using a temporary file.
-    fn read_tempfile(
+    /// Read data from a file, using the page cache if enabled.
+    ///
+    /// The pages which are not in the cache are fetched from the remote, coalescing contiguous missing pages
+    /// into a single read. `size` is the size of the file.
+    fn read_cached(
         &mut self,
+        inode: Inode,
         path: &Path,
+        size: u64,
         buffer: &mut [u8],
         offset: u64,
     ) -> RemoteResult<usize> {
-        let Ok(tempfile) = tempfile::NamedTempFile::new() else {
-            return Err(remotefs::RemoteError::new(
-                remotefs::RemoteErrorType::IoError,
-            ));
-        };
-        let Ok(writer) = fs::OpenOptions::new().write(true).open(tempfile.path()) else {
-            error!("Failed to open temporary file");
-            return Err(remotefs::RemoteError::new(
-                remotefs::RemoteErrorType::IoError,
-            ));
-        };
+        if self.page_cache.is_none() || buffer.is_empty() {
+            return self.read(path, buffer, offset);
+        }
 
-        // transfer to tempfile
-        self.remote.open_file(path, Box::new(writer))?;
+        let page_size = PAGE_SIZE as u64;
+        let first_page = offset / page_size;
+        let last_page = (offset + buffer.len() as u64 - 1) / page_size;
 
-        let Ok(mut reader) = fs::File::open(tempfile.path()) else {
-            error!("Failed to open temporary file");
-            return Err(remotefs::RemoteError::new(
-                remotefs::RemoteErrorType::IoError,
-            ));
-        };
+        // fetch missing pages
+        let mut index = first_page;
+        while index <= last_page {
+            if self.is_page_cached(inode, index) {
+                index += 1;
+                continue;
+            }
+            let run_start = index;
+            while index <= last_page && !self.is_page_cached(inode, index) {
+                index += 1;
+            }
 
-        // skip to offset
-        if offset > 0 {
-            let mut offset_buff = vec![0; offset as usize];
-            if let Err(err) = reader.read_exact(&mut offset_buff) {
-                error!("Failed to read file: {err}");
-                return Err(remotefs::RemoteError::new(
-                    remotefs::RemoteErrorType::IoError,
-                ));
+            let run_offset = run_start * page_size;
+            let run_len = ((index - run_start) * page_size).min(size.saturating_sub(run_offset));
+            debug!("fetching pages {run_start}..{index} of {inode} from remote");
+            let mut data = vec![0; run_len as usize];
+            let bytes_read = self.read(path, &mut data, run_offset)?;
+            if (bytes_read as u64) < run_len {
+                debug!("short read while fetching pages; not caching");
+                return self.read(path, buffer, offset);
+            }
+
+            let Some(cache) = self.page_cache.as_mut() else {
+                break;
+            };
+            for page in run_start..index {
+                let start = ((page - run_start) * page_size).min(run_len) as usize;
+                let end = ((page - run_start + 1) * page_size).min(run_len) as usize;
+                cache.insert(inode, page, data[start..end].to_vec());
             }
         }
 
-        // read file
-        reader.read_exact(buffer).map_err(|err| {
-            remotefs::RemoteError::new_ex(remotefs::RemoteErrorType::IoError, err.to_string())
+        // assemble the buffer from the cache
+        let Some(cache) = self.page_cache.as_mut() else {
+            return self.read(path, buffer, offset);
+        };
+        let mut bytes_read = 0;
+        for index in first_page..=last_page {
+            let Some(page) = cache.get(inode, index) else {
+                break;
+            };
+            let page_offset = if index == first_page {
+                (offset % page_size) as usize
+            } else {
+                0
+            };
+            if page_offset >= page.len() {
+                break;
+            }
+            let len = (page.len() - page_offset).min(buffer.len() - bytes_read);
+            buffer[bytes_read..bytes_read + len]
+                .copy_from_slice(&page[page_offset..page_offset + len]);
+            bytes_read += len;
+            if page.len() < PAGE_SIZE {
+                break;
+            }
+        }
+
+        Ok(bytes_read)
+    }
+
+    /// Check whether the page at `index` for `inode` is in the page cache.
+    fn is_page_cached(&self, inode: Inode, index: u64) -> bool {
+        self.page_cache
+            .as_ref()
+            .map(|cache| cache.contains(inode, index))
+            .unwrap_or_default()
+    }
+
+    /// Remove all the cached pages for `inode`.
+    fn invalidate_page_cache(&mut self, inode: Inode) {
+        if let Some(cache) = self.page_cache.as_mut() {
+            debug!("invalidating page cache for {inode}");
+            cache.invalidate(inode);
+        }
+    }
+
+    /// Write data to the write buffer of `inode`.
+    ///
+    /// If the file has no write buffer yet, it is seeded with the current content of the file on the remote.
+    /// The data is uploaded on [`Self::flush_write_buffer`].
+    fn write_buffered(
+        &mut self,
+        inode: Inode,
+        file: &File,
+        data: &[u8],
+        offset: u64,
+    ) -> RemoteResult<u32> {
+        if !self.write_buffers.contains_key(&inode) {
+            let buffer = self.seed_write_buffer(file)?;
+            self.write_buffers.insert(inode, buffer);
+            // the content of the file is the one written through the mount from now on
+            if let Some(monitor) = self.monitor.as_ref() {
+                monitor.unwatch_file(inode);
+            }
+        }
+        let buffer = self.write_buffers.get_mut(&inode).expect("write buffer");
+        buffer.write(offset, data).map_err(|err| {
+            error!("Failed to write to write buffer: {err}");
+            RemoteError::new_ex(RemoteErrorType::IoError, err.to_string())
         })?;
 
-        if let Err(err) = tempfile.close() {
-            error!("Failed to close temporary file: {err}");
+        Ok(data.len() as u32)
+    }
+
+    /// Create a write buffer for `file`, seeded with its current content on the remote.
+    ///
+    /// With [`MountOption::WriteMemLimit`], the content beyond the limit is kept in a temporary file in
+    /// [`MountOption::TempDir`].
+    fn seed_write_buffer(&mut self, file: &File) -> RemoteResult<WriteBuffer> {
+        let mut buffer = WriteBuffer::new(Vec::new());
+        if let Some(limit) = self.write_mem_limit() {
+            buffer = buffer.spill_over(limit, self.temp_dir().map(Path::to_path_buf));
+        }
+
+        let size = file.metadata().size;
+        let mut chunk = vec![0; self.io_chunk_size().min(size as usize)];
+        let mut offset = 0;
+        while offset < size {
+            let read = self.read(file.path(), &mut chunk, offset)?;
+            if read == 0 {
+                break;
+            }
+            buffer.write(offset, &chunk[..read]).map_err(|err| {
+                error!("Failed to write to write buffer: {err}");
+                RemoteError::new_ex(RemoteErrorType::IoError, err.to_string())
+            })?;
+            offset += read as u64;
         }
+        buffer.mark_clean();
 
-        Ok(buffer.len())
+        Ok(buffer)
     }
 
-    /// Write data to a file.
-    fn write(&mut self, file: &File, data: &[u8], offset: u64) -> RemoteResult<u32> {
-        // write data
-        let mut reader = Cursor::new(data);
-        let mut writer = match self.remote.create(file.path(), file.metadata()) {
-            Ok(writer) => writer,
+    /// Write data to `inode` for the `write` handler.
+    ///
+    /// The data is buffered; with [`MountOption::SyncWrites`] it is uploaded right away, so that the upload
+    /// error is reported to the writer.
+    fn write_data(
+        &mut self,
+        inode: Inode,
+        file: &File,
+        data: &[u8],
+        offset: u64,
+    ) -> RemoteResult<u32> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if self.append_writes()
+            && !self.append_unsupported
+            && offset == file.metadata().size
+            && !self
+                .write_buffers
+                .get(&inode)
+                .is_some_and(WriteBuffer::is_dirty)
+        {
+            match self.append(file, data) {
+                Ok(()) => {
+                    // the content seeded into a clean buffer is stale
+                    self.write_buffers.remove(&inode);
+                    return Ok(data.len() as u32);
+                }
+                Err(RemoteError {
+                    kind: RemoteErrorType::UnsupportedFeature,
+                    ..
+                }) => {
+                    debug!("the remote doesn't support append; falling back to buffered writes");
+                    self.append_unsupported = true;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let bytes_written = self.write_buffered(inode, file, data, offset)?;
+        if self.sync_writes() {
+            debug!("uploading write to {} synchronously", file.path().display());
+            self.flush_write_buffer(inode)?;
+        } else if self.write_coalesce_window().is_some()
+            && self
+                .write_buffers
+                .get(&inode)
+                .is_some_and(|buffer| buffer.pending() >= WRITE_COALESCE_SIZE)
+        {
+            debug!(
+                "uploading {WRITE_COALESCE_SIZE} bytes or more of coalesced writes to {}",
+                file.path().display()
+            );
+            self.flush_write_buffer(inode)?;
+        }
+        self.flush_idle_writes();
+
+        Ok(bytes_written)
+    }
+
+    /// Upload the write buffers which haven't changed for [`MountOption::WriteCoalesceWindow`].
+    ///
+    /// Failed uploads are retried on the next check, and reported on flush. Returns the uploaded inodes.
+    fn flush_idle_writes(&mut self) -> Vec<Inode> {
+        let Some(window) = self.write_coalesce_window() else {
+            return Vec::new();
+        };
+        let idle = self
+            .write_buffers
+            .iter()
+            .filter(|(_, buffer)| buffer.is_dirty() && buffer.idle_for() >= window)
+            .map(|(inode, _)| *inode)
+            .collect::<Vec<_>>();
+
+        let mut uploaded = Vec::with_capacity(idle.len());
+        for inode in idle {
+            debug!("uploading coalesced writes to {inode}");
+            match self.flush_write_buffer(inode) {
+                Ok(()) => uploaded.push(inode),
+                Err(err) => error!(
+                    "Failed to upload coalesced writes: {}",
+                    self.err_context(&err, inode)
+                ),
+            }
+        }
+
+        uploaded
+    }
+
+    /// Upload an empty file at `remote_path` with `metadata`.
+    ///
+    /// Like [`Driver::write`], if the remote doesn't support `create_file`, the file is created by opening a `create`
+    /// stream and closing it right away.
+    fn upload_empty(
+        &mut self,
+        remote_path: &Path,
+        metadata: &remotefs::fs::Metadata,
+    ) -> RemoteResult<()> {
+        let reader = Cursor::new(Vec::new());
+        match self
+            .remote
+            .create_file(remote_path, metadata, Box::new(reader))
+        {
             Err(RemoteError {
                 kind: RemoteErrorType::UnsupportedFeature,
                 ..
-            }) if offset > 0 => {
-                error!("remote file system doesn't support stream, so it is not possible to write at offset");
-                return Err(RemoteError::new_ex(
-                    RemoteErrorType::UnsupportedFeature,
-                    "remote file system doesn't support stream, so it is not possible to write at offset".to_string(),
-                ));
+            }) => {
+                debug!(
+                    "create_file is not supported; creating {} with a stream",
+                    remote_path.display()
+                );
+                let writer = self.remote.create(remote_path, metadata)?;
+                self.remote.on_written(writer)
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// Create an empty file at `path` with `metadata`.
+    ///
+    /// Some backends ignore the mode and the owner passed on creation; if so, they're set with `setstat` right after,
+    /// so that the attributes of the new file are stable from the start.
+    fn create_empty_file(
+        &mut self,
+        path: &Path,
+        metadata: &remotefs::fs::Metadata,
+    ) -> RemoteResult<()> {
+        let remote_path = self.remote_path(path);
+        self.upload_empty(&remote_path, metadata)?;
+
+        let created = match self.remote.stat(&remote_path) {
+            Ok(file) => file,
+            Err(err) => {
+                debug!("Failed to stat created file {}: {err}", path.display());
+                return Ok(());
+            }
+        };
+        let mut wanted = created.metadata().clone();
+        let mut dropped = false;
+        if metadata.mode.is_some()
+            && created.metadata().mode.map(u32::from) != metadata.mode.map(u32::from)
+        {
+            wanted.mode = metadata.mode;
+            dropped = true;
+        }
+        // the owner is overridden anyway if set with the mount options
+        if self.uid().is_none() && metadata.uid.is_some() && created.metadata().uid != metadata.uid
+        {
+            wanted.uid = metadata.uid;
+            dropped = true;
+        }
+        if self.gid().is_none() && metadata.gid.is_some() && created.metadata().gid != metadata.gid
+        {
+            wanted.gid = metadata.gid;
+            dropped = true;
+        }
+        if !dropped {
+            return Ok(());
+        }
+
+        debug!(
+            "the remote ignored the metadata of {} on creation; setting it",
+            path.display()
+        );
+        if let Err(err) = self.remote.setstat(&remote_path, wanted) {
+            warn!("Failed to set the metadata of {}: {err}", path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Upload the pending content of the write buffer of `inode` to the remote, if any.
+    fn flush_write_buffer(&mut self, inode: Inode) -> RemoteResult<()> {
+        let pending = self
+            .write_buffers
+            .get(&inode)
+            .map(WriteBuffer::is_dirty)
+            .unwrap_or_default();
+        if !pending {
+            return Ok(());
+        }
+
+        let (file, _) = self.get_inode(inode)?;
+        // take the buffer out while uploading, to avoid copying its content
+        let Some(mut buffer) = self.write_buffers.remove(&inode) else {
+            return Ok(());
+        };
+        debug!(
+            "uploading {} bytes of pending writes to {}",
+            buffer.size(),
+            file.path().display()
+        );
+        self.invalidate_page_cache(inode);
+        let result = self.write(&file, &buffer);
+        self.record_write(&result);
+        if result.is_ok() {
+            buffer.mark_clean();
+        }
+        self.write_buffers.insert(inode, buffer);
+
+        result.map(|_| ())
+    }
+
+    /// Upload the pending writes of all the files, so that they're not lost on unmount
+    fn flush_all_write_buffers(&mut self) {
+        let dirty = self
+            .write_buffers
+            .iter()
+            .filter(|(_, buffer)| buffer.is_dirty())
+            .map(|(inode, _)| *inode)
+            .collect::<Vec<_>>();
+        for inode in dirty {
+            if let Err(err) = self.flush_write_buffer(inode) {
+                error!("Pending writes are lost: {}", self.err_context(&err, inode));
+            }
+        }
+    }
+
+    /// Force the pending writes of `inode` to the remote.
+    ///
+    /// Unless `datasync` is set, the file mode and ownership are also written back after the upload,
+    /// since some remotes reset them when the file is re-created.
+    fn sync_file(&mut self, inode: Inode, datasync: bool) -> RemoteResult<()> {
+        let pending = self
+            .write_buffers
+            .get(&inode)
+            .map(WriteBuffer::is_dirty)
+            .unwrap_or_default();
+        if !pending {
+            return Ok(());
+        }
+
+        let (file, _) = self.get_inode(inode)?;
+        self.flush_write_buffer(inode)?;
+        if datasync {
+            return Ok(());
+        }
+
+        let remote_path = self.remote_path(file.path());
+        let mut metadata = self.remote.stat(&remote_path)?.metadata;
+        metadata.mode = file.metadata().mode.or(metadata.mode);
+        metadata.uid = file.metadata().uid.or(metadata.uid);
+        metadata.gid = file.metadata().gid.or(metadata.gid);
+        debug!(
+            "syncing metadata of {}: {metadata:?}",
+            file.path().display()
+        );
+        let result = self.remote.setstat(&remote_path, metadata);
+        self.record_write(&result);
+
+        result
+    }
+
+    /// Read the target of the symlink `file` at `inode`.
+    ///
+    /// The target is read up to EOF, since some remotes under-report the size of the symlinks, often as 0; the
+    /// trailing NULs are trimmed. With [`MountOption::CacheSymlinks`] the target is served from the cache once read.
+    fn read_link_target(&mut self, inode: Inode, file: &File) -> RemoteResult<Vec<u8>> {
+        if let Some(target) = self
+            .symlink_cache
+            .as_mut()
+            .and_then(|cache| cache.get(inode))
+        {
+            debug!("serving symlink target of {inode} from cache");
+            return Ok(target.to_vec());
+        }
+
+        let chunk = (file.metadata().size as usize).max(libc::PATH_MAX as usize);
+        let mut target = Vec::new();
+        loop {
+            let offset = target.len();
+            target.resize(offset + chunk, 0);
+            let bytes_read = self.read(file.path(), &mut target[offset..], offset as u64)?;
+            target.truncate(offset + bytes_read);
+            if bytes_read < chunk {
+                break;
             }
+        }
+        while target.last() == Some(&0) {
+            target.pop();
+        }
+        if let Some(cache) = self.symlink_cache.as_mut() {
+            cache.insert(inode, target.clone());
+        }
+
+        Ok(target)
+    }
+
+    /// Remove the cached target of the symlink at `inode`, since it was replaced or removed.
+    fn invalidate_symlink(&mut self, inode: Inode) {
+        if let Some(cache) = self.symlink_cache.as_mut() {
+            cache.invalidate(inode);
+        }
+    }
+
+    /// Upload the pending writes of `inode` on release.
+    ///
+    /// With [`MountOption::FsyncOnClose`] the file is synced as on `fsync`, and the size of the remote file is
+    /// checked against the written one, so that a truncated upload is reported.
+    fn release_writes(&mut self, inode: Inode) -> RemoteResult<()> {
+        if !self.fsync_on_close() {
+            return self.flush_write_buffer(inode);
+        }
+        let Some(size) = self
+            .write_buffers
+            .get(&inode)
+            .filter(|buffer| buffer.is_dirty())
+            .map(WriteBuffer::size)
+        else {
+            return Ok(());
+        };
+
+        self.sync_file(inode, false)?;
+        let (file, _) = self.get_inode(inode)?;
+        let uploaded = self
+            .remote
+            .stat(&self.remote_path(file.path()))?
+            .metadata
+            .size;
+        if uploaded != size {
+            error!(
+                "{} has {uploaded} bytes on the remote, but {size} were written",
+                file.path().display()
+            );
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::IoError,
+                format!("uploaded {uploaded} of {size} bytes"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Upload the content of `buffer` as the content of `file`.
+    fn write(&mut self, file: &File, buffer: &WriteBuffer) -> RemoteResult<u64> {
+        self.close_read_stream();
+        // write data
+        let mut writer = match self
+            .remote
+            .create(&self.remote_path(file.path()), file.metadata())
+        {
+            Ok(writer) => writer,
             Err(RemoteError {
                 kind: RemoteErrorType::UnsupportedFeature,
                 ..
             }) => {
-                return self.write_wno_stream(file, data);
+                return self.write_wno_stream(file, buffer);
             }
             Err(err) => {
-                error!("Failed to write file: {err}");
+                error!(
+                    "Failed to write file: {}",
+                    ErrorContext::new(&err).path(file.path())
+                );
                 return Err(err);
             }
         };
-        if offset > 0 {
-            // try to seek
-            if let Err(err) = writer.seek(std::io::SeekFrom::Start(offset)) {
-                error!("Failed to seek file: {err}. Not that not all the remote filesystems support seeking");
-                return Err(RemoteError::new_ex(
-                    RemoteErrorType::IoError,
-                    err.to_string(),
-                ));
-            }
-        }
         // write
-        let bytes_written = match std::io::copy(&mut reader, &mut writer) {
-            Ok(bytes) => bytes as u32,
-            Err(err) => {
-                error!("Failed to write file: {err}");
-                return Err(RemoteError::new_ex(
-                    RemoteErrorType::IoError,
-                    err.to_string(),
-                ));
-            }
+        let chunk_size = self.io_chunk_size();
+        let mut retrying = Retrying::new(&mut writer, self.io_retries());
+        let result = match buffer.data() {
+            Some(data) => data.chunks(chunk_size).try_for_each(|chunk| {
+                if let Some(limit) = self.write_limit.as_ref() {
+                    limit.take(chunk.len());
+                }
+                retrying.write_all(chunk)
+            }),
+            None => buffer.reader().and_then(|reader| {
+                copy_chunked(
+                    &mut self.throttled_upload(reader),
+                    &mut retrying,
+                    chunk_size,
+                )
+            }),
         };
+        if let Err(err) = result {
+            error!("Failed to write file: {err}");
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::IoError,
+                err.to_string(),
+            ));
+        }
         // on write
         self.remote
             .on_written(writer)
             .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))?;
 
-        Ok(bytes_written)
+        Ok(buffer.size())
+    }
+
+    /// Append `data` to the end of `file` on the remote, with [`MountOption::AppendWrites`].
+    ///
+    /// Fails with [`RemoteErrorType::UnsupportedFeature`] if the remote can't append.
+    fn append(&mut self, file: &File, data: &[u8]) -> RemoteResult<()> {
+        self.close_read_stream();
+        let remote_path = self.remote_path(file.path());
+        debug!(
+            "appending {} bytes to {}",
+            data.len(),
+            file.path().display()
+        );
+        let result = match self.remote.append(&remote_path, file.metadata()) {
+            Ok(mut writer) => {
+                let chunk_size = self.io_chunk_size();
+                let mut retrying = Retrying::new(&mut writer, self.io_retries());
+                data.chunks(chunk_size)
+                    .try_for_each(|chunk| {
+                        if let Some(limit) = self.write_limit.as_ref() {
+                            limit.take(chunk.len());
+                        }
+                        retrying.write_all(chunk)
+                    })
+                    .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))
+                    .and_then(|_| self.remote.on_written(writer))
+            }
+            Err(RemoteError {
+                kind: RemoteErrorType::UnsupportedFeature,
+                ..
+            }) => {
+                let reader = self.throttled_upload(Box::new(Cursor::new(data.to_vec())));
+                self.remote
+                    .append_file(&remote_path, file.metadata(), reader)
+                    .map(|_| ())
+            }
+            Err(err) => Err(err),
+        };
+        if !matches!(
+            result,
+            Err(RemoteError {
+                kind: RemoteErrorType::UnsupportedFeature,
+                ..
+            })
+        ) {
+            self.record_write(&result);
+        }
+
+        result
     }
 
-    /// Write data to a file without using a stream.
-    fn write_wno_stream(&mut self, file: &File, data: &[u8]) -> RemoteResult<u32> {
+    /// Upload the content of `buffer` as the content of `file` without using a stream.
+    fn write_wno_stream(&mut self, file: &File, buffer: &WriteBuffer) -> RemoteResult<u64> {
         debug!(
             "Writing file without stream: {:?} {} bytes",
             file.path(),
-            data.len()
+            buffer.size()
         );
-        let reader = Cursor::new(data.to_vec());
+        let reader = buffer
+            .reader()
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))?;
+        let reader = self.throttled_upload(Box::new(Retrying::new(reader, self.io_retries())));
         self.remote
-            .create_file(file.path(), file.metadata(), Box::new(reader))
-            .map(|len| len as u32)
+            .create_file(&self.remote_path(file.path()), file.metadata(), reader)
+    }
+
+    /// Wait for `bytes` read from the remote to fit in the [`MountOption::ReadRateLimit`], if set.
+    fn throttle_read(&self, bytes: usize) {
+        if let Some(limit) = self.read_limit.as_ref() {
+            limit.take(bytes);
+        }
+    }
+
+    /// Pace `reader`, uploaded to the remote, with the [`MountOption::WriteRateLimit`], if set.
+    fn throttled_upload(&self, reader: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        match self.write_limit.clone() {
+            Some(limit) => Box::new(Throttled::new(reader, limit)),
+            None => reader,
+        }
+    }
+
+    /// Start timing the operation `op`, if metrics or debug logging are enabled.
+    ///
+    /// The latency is logged at debug level, so that slow operations show up in the logs next to their `info` line.
+    fn op_timer(&self, op: &'static str) -> Option<OpTimer> {
+        let timer = match self.metrics.as_ref() {
+            Some(metrics) => metrics.timer(op),
+            None if self.op_trace.is_some()
+                || self.health.is_some()
+                || log_enabled!(log::Level::Debug) =>
+            {
+                OpTimer::new(op)
+            }
+            None => return None,
+        };
+
+        Some(
+            timer
+                .traced(self.op_trace.clone())
+                .checked(self.health.clone()),
+        )
+    }
+
+    /// Record `errno` as the result of the operation being handled in the [`MountOption::OpTrace`] and the
+    /// [`MountOption::HealthAddr`] endpoint, returning it.
+    fn failed(&self, errno: c_int) -> c_int {
+        if let Some(trace) = self.op_trace.as_ref() {
+            trace.set_error(errno);
+        }
+        if let Some(health) = self.health.as_ref() {
+            health.set_error(errno);
+        }
+
+        errno
+    }
+
+    /// Get the [`ErrorContext`] of `err` raised by an operation on `inode`, with the path of the inode if known.
+    fn err_context<'a>(&'a self, err: &'a RemoteError, inode: Inode) -> ErrorContext<'a> {
+        let context = ErrorContext::new(err).inode(Some(inode));
+        match self.database.get(inode) {
+            Some(path) => context.path(path),
+            None => context,
+        }
+    }
+
+    /// Get the [`ErrorContext`] of `err` raised by an operation on `path`, with the inode of the path if known.
+    fn path_err_context<'a>(&'a self, err: &'a RemoteError, path: &'a Path) -> ErrorContext<'a> {
+        ErrorContext::new(err)
+            .path(path)
+            .inode(self.database.inode_of(path))
+    }
+
+    /// Log that `what` failed on `path` with `err`, returning the errno to reply with per [`remote_err_to_errno`].
+    fn remote_failed(&self, what: &str, path: &Path, err: &RemoteError) -> c_int {
+        error!("Failed to {what}: {}", self.path_err_context(err, path));
+        remote_err_to_errno(err)
+    }
+
+    /// Log that `what` failed on `inode` with `err`, returning the errno to reply with per [`write_err_to_errno`].
+    fn write_failed(&self, what: &str, inode: Inode, err: &RemoteError) -> c_int {
+        error!("Failed to {what}: {}", self.err_context(err, inode));
+        write_err_to_errno(err)
+    }
+
+    /// Record `path` as the path of the operation being handled in the [`MountOption::OpTrace`].
+    fn trace_path(&self, path: &Path) {
+        if let Some(trace) = self.op_trace.as_ref() {
+            trace.set_path(path);
+        }
+    }
+
+    /// Get the specified metrics address from the mount options.
+    fn metrics_addr(&self) -> Option<std::net::SocketAddr> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::MetricsAddr(addr) => Some(*addr),
+            _ => None,
+        })
+    }
+
+    /// Get the specified health endpoint address from the mount options.
+    fn health_addr(&self) -> Option<std::net::SocketAddr> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::HealthAddr(addr) => Some(*addr),
+            _ => None,
+        })
+    }
+
+    /// Whether directories must be listed on open, according to [`MountOption::PrefetchDirs`].
+    fn prefetch_dirs(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::PrefetchDirs))
+    }
+
+    /// List the directory at `path` with inode `inode` for the directory file handle `fh` of `pid`, so that the first
+    /// `readdir` doesn't wait for the remote.
+    ///
+    /// If listing fails, nothing is stored and `readdir` lists the directory again.
+    fn prefetch_dir(&mut self, pid: u32, fh: u64, inode: Inode, path: &Path) {
+        match self.read_dir_listing(inode, path) {
+            Ok(entries) => self
+                .file_handlers
+                .set_prefetched_dir_entries(pid, fh, entries),
+            Err(err) => debug!("Failed to prefetch directory {}: {err}", path.display()),
+        }
+    }
+
+    /// Get the listing of the directory at `path` with inode `inode` served by `readdir`.
+    ///
+    /// The listing starts with the `.` and `..` entries, followed by the entries on the remote.
+    fn read_dir_listing(&mut self, inode: Inode, path: &Path) -> RemoteResult<Vec<DirEntry>> {
+        let parent = path.parent().unwrap_or(path);
+        let mut listing = vec![
+            DirEntry {
+                inode,
+                kind: FileType::Directory,
+                name: ".".into(),
+            },
+            DirEntry {
+                inode: Self::inode(parent),
+                kind: FileType::Directory,
+                name: "..".into(),
+            },
+        ];
+        listing.extend(self.read_dir_entries(path)?);
+
+        Ok(listing)
+    }
+
+    /// List the entries of the directory at `path` for `readdir`.
+    ///
+    /// If the name of an entry can't be derived from its path, it is synthesized from the last path component,
+    /// and the entry is dropped only if that is not possible either.
+    fn read_dir_entries(&mut self, path: &Path) -> RemoteResult<Vec<DirEntry>> {
+        let entries = {
+            let _timer = self.op_timer("list_dir");
+            self.remote.list_dir(&self.remote_path(path))
+        }?;
+
+        let mut warned = false;
+        let mut dir_entries = Vec::with_capacity(entries.len());
+        for mut entry in entries {
+            entry.path = self.local_path(&entry.path);
+            let name = match entry.path().file_name() {
+                Some(name) => name,
+                None => {
+                    let name = entry_name_from_bytes(entry.path());
+                    let message = match name {
+                        Some(name) => format!(
+                            "could not get file name of {}; using {name:?}",
+                            entry.path().display()
+                        ),
+                        None => format!(
+                            "could not get file name of {}; entry dropped",
+                            entry.path().display()
+                        ),
+                    };
+                    if warned {
+                        debug!("{message}");
+                    } else {
+                        warn!("{message}");
+                        warned = true;
+                    }
+                    match name {
+                        Some(name) => name,
+                        None => continue,
+                    }
+                }
+            };
+
+            if self.is_excluded(entry.path()) {
+                debug!("{} is excluded from the listing", entry.path().display());
+                continue;
+            }
+            if self.hide_dotfiles() && name.as_bytes().starts_with(b".") {
+                debug!("{} is hidden from the listing", entry.path().display());
+                continue;
+            }
+
+            let name = name.to_os_string();
+            let inode = Self::inode(entry.path());
+            let kind = if self.follow_symlinks() && entry.metadata().symlink.is_some() {
+                let path = entry.path().to_path_buf();
+                self.dereference(&path, entry).metadata().file_type
+            } else {
+                entry.metadata().file_type
+            };
+            dir_entries.push(DirEntry {
+                inode,
+                kind: convert_remote_filetype(kind),
+                name,
+            });
+        }
+
+        Ok(dir_entries)
+    }
+
+    /// Resolve the symbolic link at `path` to the file it points to, following chained links.
+    ///
+    /// At most [`MountOption::MaxSymlinkDepth`] links are followed; if the limit is exceeded `ELOOP` is returned.
+    /// If `path` is not a symbolic link, the file at `path` is returned.
+    fn resolve_symlink(&mut self, path: &Path) -> Result<File, c_int> {
+        let max_depth = self.max_symlink_depth();
+        let mut file = self.remote.stat(&self.remote_path(path)).map_err(|err| {
+            error!("Failed to stat: {}", self.path_err_context(&err, path));
+            libc::ENOENT
+        })?;
+
+        let mut depth = 0;
+        while let Some(target) = file.metadata().symlink.clone() {
+            if depth >= max_depth {
+                error!(
+                    "Too many levels of symbolic links resolving {}",
+                    path.display()
+                );
+                return Err(libc::ELOOP);
+            }
+            depth += 1;
+
+            let target = match file.path().parent() {
+                Some(parent) if target.is_relative() => parent.join(target),
+                _ => target,
+            };
+            debug!("{} -> {}", file.path().display(), target.display());
+            file = self.remote.stat(&target).map_err(|err| {
+                error!(
+                    "Failed to stat symlink target: {}",
+                    ErrorContext::new(&err).path(&target)
+                );
+                libc::ENOENT
+            })?;
+        }
+        file.path = self.local_path(&file.path);
+
+        Ok(file)
+    }
+
+    /// Whether the symbolic links are presented as their target with [`MountOption::FollowSymlinks`].
+    fn follow_symlinks(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::FollowSymlinks))
+    }
+
+    /// Whether the writes at the end of a file are appended, with [`MountOption::AppendWrites`].
+    fn append_writes(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::AppendWrites))
+    }
+
+    /// Whether every write is uploaded synchronously with [`MountOption::SyncWrites`].
+    fn sync_writes(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::SyncWrites))
+    }
+
+    /// Get the maximum symlink depth from the mount options.
+    /// If not set, the default is 40.
+    fn max_symlink_depth(&self) -> usize {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::MaxSymlinkDepth(depth) => Some(*depth),
+                _ => None,
+            })
+            .unwrap_or(40)
+    }
+
+    /// Whether the permissions are left to the remote, according to [`MountOption::NoAccessCheck`].
+    fn no_access_check(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::NoAccessCheck))
+    }
+
+    /// Whether names must be looked up case-insensitively.
+    fn case_insensitive(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::CaseInsensitive))
+    }
+
+    /// Whether the file at `path` in the mount matches any [`MountOption::Exclude`] pattern.
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.options
+            .iter()
+            .filter_map(|opt| match opt {
+                MountOption::Exclude(patterns) => Some(patterns),
+                _ => None,
+            })
+            .any(|patterns| matches_patterns(patterns, path))
+    }
+
+    /// Whether excluded files must be inaccessible, according to [`MountOption::HideStrict`].
+    fn hide_strict(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::HideStrict))
+    }
+
+    /// Whether dotfiles must be hidden from the listings, according to [`MountOption::HideDotfiles`].
+    fn hide_dotfiles(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::HideDotfiles))
+    }
+
+    /// Swap in the remote set with [`crate::Refresher::refresh_credentials`], if any.
+    ///
+    /// The new remote is connected before replacing the current one, which is then disconnected; if it fails to
+    /// connect, the current remote is kept.
+    fn swap_pending_remote(&mut self) {
+        let Some(mut remote) = self.pending_remote.take() else {
+            return;
+        };
+        info!("Replacing the connection to the remote filesystem");
+        if !remote.is_connected() {
+            if let Err(err) = remote.connect() {
+                error!(
+                    "Failed to connect to remote filesystem; keeping the current connection: {err}"
+                );
+                return;
+            }
+        }
+
+        // the open stream belongs to the current connection
+        self.close_read_stream();
+        let mut previous = std::mem::replace(&mut self.remote, remote);
+        if let Err(err) = previous.disconnect() {
+            debug!("Failed to disconnect from the replaced remote filesystem: {err}");
+        }
+        info!("Connection to the remote filesystem replaced");
+    }
+
+    /// Check whether the mount accepts changes, failing with `EROFS` if it is mounted with [`MountOption::RO`] or
+    /// once [`MountOption::WriteFailureThreshold`] has been reached.
+    ///
+    /// While the mount is read-only because of the write failures, the driver periodically tries to reconnect to the
+    /// remote; a successful reconnect makes the mount writable again.
+    fn check_writable(&mut self) -> Result<(), c_int> {
+        if self.read_only() {
+            return Err(libc::EROFS);
+        }
+        let Some(breaker) = self.write_breaker.as_mut() else {
+            return Ok(());
+        };
+        if !breaker.is_tripped() {
+            return Ok(());
+        }
+        if breaker.reconnect_due() {
+            info!("Reconnecting to the remote to leave read-only mode");
+            if let Err(err) = self.remote.disconnect() {
+                debug!("Failed to disconnect from remote filesystem: {err}");
+            }
+            match self.remote.connect() {
+                Ok(_) => {
+                    warn!("Reconnected to the remote filesystem; the mount is writable again");
+                    breaker.reset();
+                    return Ok(());
+                }
+                Err(err) => error!("Failed to reconnect to remote filesystem: {err}"),
+            }
+        }
+
+        Err(libc::EROFS)
+    }
+
+    /// Whether `path` is under one of the [`MountOption::WritablePrefix`]es, or any path if none is set.
+    fn is_writable_path(&self, path: &Path) -> bool {
+        let mut prefixes = self
+            .options
+            .iter()
+            .filter_map(|opt| match opt {
+                MountOption::WritablePrefix(prefixes) => Some(prefixes),
+                _ => None,
+            })
+            .flatten()
+            .peekable();
+
+        prefixes.peek().is_none()
+            || prefixes.any(|prefix| path.starts_with(Path::new("/").join(prefix)))
+    }
+
+    /// Fail with `EROFS` unless the entry `name` of `ino`, or `ino` itself if `name` is not set, is writable according
+    /// to [`MountOption::WritablePrefix`].
+    ///
+    /// Unknown inodes are let through, so that the operation fails as it would without the option.
+    fn check_writable_path(&self, ino: u64, name: Option<&OsStr>) -> Result<(), c_int> {
+        let Some(path) = self.database.get(ino) else {
+            return Ok(());
+        };
+        let path = match name {
+            Some(name) => path.join(name),
+            None => path.to_path_buf(),
+        };
+        if self.is_writable_path(&path) {
+            Ok(())
+        } else {
+            debug!("{} is not under a writable prefix", path.display());
+            Err(libc::EROFS)
+        }
+    }
+
+    /// Wrap `reply` to record the operation `op` on the entry `name` of `ino`, or on `ino` itself if `name` is not
+    /// set, in the [`MountOption::AuditLog`].
+    fn audited<R>(
+        &self,
+        op: &'static str,
+        req: &Request,
+        ino: u64,
+        name: Option<&OsStr>,
+        reply: R,
+    ) -> Audited<R> {
+        let event = self.audit_log.as_ref().map(|log| {
+            let path = self.database.get(ino).map(|path| {
+                name.map(|name| path.join(name))
+                    .unwrap_or_else(|| path.to_path_buf())
+            });
+            log.event(op, path, req.uid(), req.gid())
+        });
+
+        Audited::new(reply, event)
+    }
+
+    /// Wrap `reply` like [`Driver::audited`], for an operation which is recorded only with [`MountOption::AuditReads`].
+    fn audited_read<R>(
+        &self,
+        op: &'static str,
+        req: &Request,
+        ino: u64,
+        name: Option<&OsStr>,
+        reply: R,
+    ) -> Audited<R> {
+        if self.audit_log.as_ref().is_some_and(AuditLog::reads) {
+            self.audited(op, req, ino, name, reply)
+        } else {
+            Audited::new(reply, None)
+        }
+    }
+
+    /// Begin an operation changing the filesystem, tracked by [`Drain`] until the returned [`InFlight`] is dropped.
+    ///
+    /// Fails with `EROFS` if the mount is read-only because of write failures, or with `ESHUTDOWN` if the filesystem
+    /// is being unmounted.
+    fn begin_change(&mut self) -> Result<InFlight, c_int> {
+        self.check_writable()?;
+        self.drain.begin().ok_or_else(|| {
+            debug!("Rejecting change: the filesystem is being unmounted");
+            libc::ESHUTDOWN
+        })
+    }
+
+    /// Record the outcome of a change on the remote for [`MountOption::WriteFailureThreshold`].
+    ///
+    /// Errors caused by the request itself, such as a missing file, are not counted as failures.
+    fn record_write<R>(&mut self, result: &RemoteResult<R>) {
+        let Some(breaker) = self.write_breaker.as_mut() else {
+            return;
+        };
+        match result {
+            Ok(_) => breaker.success(),
+            Err(RemoteError {
+                kind:
+                    RemoteErrorType::NoSuchFileOrDirectory
+                    | RemoteErrorType::DirectoryAlreadyExists
+                    | RemoteErrorType::DirectoryNotEmpty,
+                ..
+            }) => {}
+            Err(_) => {
+                if breaker.failure() {
+                    error!(
+                        "Too many consecutive writes failed on the remote; the mount is now READ-ONLY until the remote is reconnected"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Drop the cached data made stale by the changes on the remote, as detected by the [`Monitor`].
+    fn apply_remote_changes(&mut self) {
+        let stale = self
+            .monitor
+            .as_ref()
+            .map(Monitor::take_stale)
+            .unwrap_or_default();
+        for stale in stale {
+            match stale {
+                Stale::File(inode) => self.invalidate_page_cache(inode),
+                Stale::Entry(
+                    Change::Created(path) | Change::Deleted(path) | Change::Modified(path),
+                ) => {
+                    self.invalidate_page_cache(Self::inode(&path));
+                    if let Some(parent) = path.parent() {
+                        self.case_insensitive_listings.remove(parent);
+                    }
+                    self.forget_missing(&path);
+                }
+            }
+        }
+    }
+
+    /// Get the events of `events` which are ready on the file with inode `ino`.
+    ///
+    /// Changes on the remote can't be waited for, so the files are always reported as ready for reading and writing:
+    /// readers then get the data or EOF straight away.
+    fn poll_events(&mut self, ino: u64, events: u32) -> Result<u32, c_int> {
+        const READY: u32 =
+            (libc::POLLIN | libc::POLLRDNORM | libc::POLLOUT | libc::POLLWRNORM) as u32;
+
+        self.get_inode(ino).map_err(|err| {
+            debug!(
+                "Failed to get file attributes: {}",
+                self.err_context(&err, ino)
+            );
+            libc::ENOENT
+        })?;
+
+        Ok(events & READY)
+    }
+
+    /// Get the value of the extended attribute `name` of `ino`.
+    ///
+    /// Remote filesystems don't store extended attributes, so no attribute exists: this fails with `ENODATA`, as
+    /// POSIX expects for a missing attribute, rather than reporting extended attributes as unsupported.
+    fn get_xattr(&mut self, ino: u64, name: &OsStr) -> Result<Vec<u8>, c_int> {
+        self.get_inode(ino).map_err(|err| {
+            debug!(
+                "Failed to get file attributes: {}",
+                self.err_context(&err, ino)
+            );
+            libc::ENOENT
+        })?;
+        debug!("no extended attribute {name:?} on {ino}");
+
+        Err(libc::ENODATA)
+    }
+
+    /// Get the names of the extended attributes of `ino`, each terminated by a nul byte; it's always empty.
+    fn list_xattr(&mut self, ino: u64) -> Result<Vec<u8>, c_int> {
+        self.get_inode(ino).map_err(|err| {
+            debug!(
+                "Failed to get file attributes: {}",
+                self.err_context(&err, ino)
+            );
+            libc::ENOENT
+        })?;
+
+        Ok(Vec::new())
+    }
+
+    /// Granularity of the times stored by the remote, set with [`MountOption::TimeGranularity`].
+    fn time_granularity(&self) -> Option<Duration> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::TimeGranularity(granularity) => Some(*granularity),
+            _ => None,
+        })
+    }
+
+    /// How long the kernel caches the attributes of a file, according to [`MountOption::AttrTimeout`].
+    fn attr_timeout(&self) -> Duration {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::AttrTimeout(timeout) => Some(*timeout),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_ATTR_TIMEOUT)
+    }
+
+    /// How long the kernel caches a directory entry, according to [`MountOption::EntryTimeout`].
+    fn entry_timeout(&self) -> Duration {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::EntryTimeout(timeout) => Some(*timeout),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_ENTRY_TIMEOUT)
+    }
+
+    /// How long a missing path is remembered, according to [`MountOption::NegativeTimeout`].
+    fn negative_timeout(&self) -> Duration {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::NegativeTimeout(timeout) => Some(*timeout),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_NEGATIVE_TIMEOUT)
+    }
+
+    /// How long the buffered writes of a file are held after its last write, according to
+    /// [`MountOption::WriteCoalesceWindow`].
+    fn write_coalesce_window(&self) -> Option<Duration> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::WriteCoalesceWindow(window) => Some(*window),
+            _ => None,
+        })
+    }
+
+    /// Maximum length of a file name, according to [`MountOption::MaxNameLen`].
+    fn max_name_len(&self) -> u32 {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::MaxNameLen(len) => Some(*len),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_MAX_NAME_LEN)
+    }
+
+    /// Total capacity of the filesystem in bytes, according to [`MountOption::Capacity`].
+    fn capacity(&self) -> u64 {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::Capacity(capacity) => Some(*capacity),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_CAPACITY)
+    }
+
+    /// Check `name` is not longer than [`MountOption::MaxNameLen`], failing with `ENAMETOOLONG` otherwise.
+    fn check_name_len(&self, name: &OsStr) -> Result<(), c_int> {
+        if name.as_bytes().len() > self.max_name_len() as usize {
+            debug!("name {name:?} is longer than {} bytes", self.max_name_len());
+            return Err(libc::ENAMETOOLONG);
+        }
+
+        Ok(())
+    }
+
+    /// Whether files can be executed from the mount, according to [`MountOption::NoExec`].
+    fn exec_allowed(&self) -> bool {
+        !self
+            .options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::NoExec))
+    }
+
+    /// Get the access mask to check and whether the file is opened for reading and writing, from the `flags`
+    /// passed to `open`.
+    fn open_access(&self, flags: i32) -> Result<(AccessFlags, bool, bool), c_int> {
+        // FMODE_EXEC is not a known open flag, so it must be checked before truncating the flags
+        let exec = flags & FMODE_EXEC != 0;
+        let flags = OFlag::from_bits_truncate(flags);
+        match flags & OFlag::O_ACCMODE {
+            OFlag::O_RDONLY => {
+                // Behavior is undefined, but most filesystems return EACCES
+                if flags.intersects(OFlag::O_TRUNC) {
+                    error!("EACCESS due to O_TRUNC flag");
+                    return Err(libc::EACCES);
+                }
+                if exec {
+                    // Open is from internal exec syscall
+                    if !self.exec_allowed() {
+                        error!("EACCES due to exec on a noexec mount");
+                        return Err(libc::EACCES);
+                    }
+                    Ok((AccessFlags::X_OK, true, false))
+                } else {
+                    Ok((AccessFlags::R_OK, true, false))
+                }
+            }
+            OFlag::O_WRONLY => Ok((AccessFlags::W_OK, false, true)),
+            OFlag::O_RDWR => Ok((AccessFlags::R_OK | AccessFlags::W_OK, true, true)),
+            // Exactly one access mode flag must be specified
+            _ => {
+                error!("Invalid access mode flags: {flags:?}");
+                Err(libc::EINVAL)
+            }
+        }
+    }
+
+    /// Get the flags to reply with when a file is opened.
+    fn open_flags(&self) -> u32 {
+        if self
+            .options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::DirectIo))
+        {
+            fuser::consts::FOPEN_DIRECT_IO
+        } else {
+            0
+        }
+    }
+
+    /// Start checking the remote for changes in background, on a connection created with `connect`.
+    ///
+    /// Does nothing unless [`MountOption::RevalidateInterval`] or [`MountOption::WatchInterval`] is provided; see
+    /// [`Monitor`].
+    pub(crate) fn spawn_monitor<F>(&mut self, connect: F)
+    where
+        F: Fn() -> T,
+        T: Send + 'static,
+    {
+        let Some(monitor) = self.monitor.as_mut() else {
+            return;
+        };
+        if let Err(err) = monitor.spawn(connect(), self.notifier.clone(), Self::inode) {
+            error!("Failed to spawn monitor: {err}");
+        }
+    }
+
+    /// Spawn the workers set with [`MountOption::Workers`], creating the connections of their pool with `connect`.
+    pub(crate) fn spawn_workers<F>(&mut self, connect: F)
+    where
+        F: Fn() -> T,
+        T: Send + 'static,
+    {
+        let Some(size) = self.options.iter().find_map(|opt| match opt {
+            MountOption::Workers(size) => Some(*size),
+            _ => None,
+        }) else {
+            return;
+        };
+        let pool_size = self
+            .options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::PoolSize(size) => Some(*size),
+                _ => None,
+            })
+            .unwrap_or(size)
+            .max(1);
+
+        let pool = Arc::new(RemotePool::new((0..pool_size).map(|_| connect()).collect()));
+        match WorkerPool::new(size, pool) {
+            Ok(workers) => {
+                info!(
+                    "Serving reads on {} workers with {pool_size} connections",
+                    workers.size()
+                );
+                self.workers = Some(workers);
+            }
+            Err(err) => error!("Failed to spawn workers: {err}"),
+        }
+    }
+
+    /// Get the maximum size of the kernel read requests from the mount options.
+    fn max_read(&self) -> Option<u32> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::MaxRead(size) => Some(*size),
+            _ => None,
+        })
+    }
+
+    /// Get the size of the kernel readahead from the mount options.
+    fn read_ahead(&self) -> Option<u32> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::ReadAhead(size) => Some(*size),
+            _ => None,
+        })
+    }
+
+    /// Get the maximum size of the kernel write requests from the mount options.
+    fn max_write(&self) -> Option<u32> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::MaxWrite(size) => Some(*size),
+            _ => None,
+        })
+    }
+
+    /// Apply the transfer sizes from the mount options to the kernel configuration.
+    fn configure_kernel(&self, config: &mut KernelConfig) {
+        if let Some(size) = self.max_write() {
+            let size = negotiate_kernel_value("max_write", size, |v| config.set_max_write(v));
+            info!("Negotiated max_write: {size} bytes");
+        }
+        let read_ahead = self
+            .read_ahead()
+            .or(self.max_read())
+            .unwrap_or(DEFAULT_READ_AHEAD);
+        let read_ahead =
+            negotiate_kernel_value("max_readahead", read_ahead, |v| config.set_max_readahead(v));
+        info!("Negotiated max_readahead: {read_ahead} bytes");
+        let max_background = negotiate_kernel_value("max_background", self.max_background(), |v| {
+            config.set_max_background(v)
+        });
+        // the kernel caps the congestion threshold to the maximum background requests
+        let congestion_threshold = self
+            .congestion_threshold()
+            .map(|threshold| {
+                negotiate_kernel_value("congestion_threshold", threshold, |v| {
+                    config.set_congestion_threshold(v)
+                })
+                .min(max_background)
+            })
+            .unwrap_or((u32::from(max_background) * 3 / 4) as u16);
+        info!("Negotiated max_background: {max_background}, congestion_threshold: {congestion_threshold}");
+    }
+
+    /// Get the maximum amount of pending background requests from the mount options.
+    /// If not set, the default is [`DEFAULT_MAX_BACKGROUND`].
+    fn max_background(&self) -> u16 {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::MaxBackground(value) => Some(*value),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_MAX_BACKGROUND)
+    }
+
+    /// Get the congestion threshold of the background requests from the mount options.
+    fn congestion_threshold(&self) -> Option<u16> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::CongestionThreshold(value) => Some(*value),
+            _ => None,
+        })
+    }
+
+    /// Get the amount of ranges read concurrently by [`MountOption::ParallelReadStreams`]; `1` if not set.
+    fn parallel_read_streams(&self) -> usize {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::ParallelReadStreams(streams) => Some(*streams),
+                _ => None,
+            })
+            .unwrap_or(1)
+    }
+
+    /// Get the size of the chunks used to transfer data from and to the remote.
+    /// If not set, the default is [`PAGE_SIZE`].
+    fn io_chunk_size(&self) -> usize {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::IoChunkSize(size) => Some((*size).max(1)),
+                _ => None,
+            })
+            .unwrap_or(PAGE_SIZE)
+    }
+
+    /// How many times an interrupted stream operation is retried, according to [`MountOption::IoRetries`].
+    fn io_retries(&self) -> usize {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::IoRetries(retries) => Some(*retries),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_IO_RETRIES)
+    }
+
+    /// Get the path of the file mounted as the root with [`MountOption::SingleFile`].
+    fn single_file(&self) -> Option<&Path> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::SingleFile(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Whether created entries always inherit the group of their directory, with [`MountOption::GidFromParent`].
+    fn gid_from_parent(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::GidFromParent))
+    }
+
+    /// Get the root prefix from the mount options.
+    fn root_prefix(&self) -> Option<&Path> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::RootPrefix(prefix) => Some(prefix.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Get the maximum size of the write buffers kept in memory from the mount options.
+    fn write_mem_limit(&self) -> Option<usize> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::WriteMemLimit(limit) => Some(*limit),
+            _ => None,
+        })
+    }
+
+    /// Get the directory where the temporary files are created from the mount options.
+    fn temp_dir(&self) -> Option<&Path> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::TempDir(dir) => Some(dir.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Check that temporary files can be created in the directory set with [`MountOption::TempDir`], if any.
+    fn check_temp_dir(&self) -> std::io::Result<()> {
+        match self.temp_dir() {
+            Some(dir) => create_tempfile(Some(dir))?.close(),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the specified uid from the mount options.
+    fn uid(&self) -> Option<u32> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::Uid(uid) => Some(*uid),
+            _ => None,
+        })
+    }
+
+    /// Get the specified gid from the mount options.
+    fn gid(&self) -> Option<u32> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::Gid(gid) => Some(*gid),
+            _ => None,
+        })
+    }
+
+    /// Map the remote `uid` to the local one with [`MountOption::AllSquash`], [`MountOption::RootSquash`] and
+    /// [`MountOption::IdMap`], in this order of precedence.
+    fn local_uid(&self, uid: u32) -> u32 {
+        if let Some((squashed, _)) = self.all_squash() {
+            return squashed;
+        }
+        match self.root_squash() {
+            Some((squashed, _)) if uid == ROOT_UID => squashed,
+            _ => self.id_map.as_ref().map_or(uid, |map| map.local_uid(uid)),
+        }
+    }
+
+    /// Map the remote `gid` to the local one with [`MountOption::AllSquash`], [`MountOption::RootSquash`] and
+    /// [`MountOption::IdMap`], in this order of precedence.
+    fn local_gid(&self, gid: u32) -> u32 {
+        if let Some((_, squashed)) = self.all_squash() {
+            return squashed;
+        }
+        match self.root_squash() {
+            Some((_, squashed)) if gid == ROOT_GID => squashed,
+            _ => self.id_map.as_ref().map_or(gid, |map| map.local_gid(gid)),
+        }
+    }
+
+    /// Get the owner all the files are shown as, according to [`MountOption::AllSquash`].
+    fn all_squash(&self) -> Option<(u32, u32)> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::AllSquash { uid, gid } => Some((*uid, *gid)),
+            _ => None,
+        })
+    }
+
+    /// Get the owner the files of root are shown as, according to [`MountOption::RootSquash`].
+    fn root_squash(&self) -> Option<(u32, u32)> {
+        self.options.iter().find_map(|opt| match opt {
+            MountOption::RootSquash { uid, gid } => Some((*uid, *gid)),
+            _ => None,
+        })
+    }
+
+    /// Force the permission bits of [`MountOption::AttrMask`] on `mode`.
+    fn mask_mode(&self, mode: u32) -> u32 {
+        self.options
+            .iter()
+            .filter_map(|opt| match opt {
+                MountOption::AttrMask { or_bits, and_bits } => Some((*or_bits, *and_bits)),
+                _ => None,
+            })
+            .fold(mode, |mode, (or_bits, and_bits)| {
+                ((mode & and_bits) | or_bits) & 0o7777
+            })
+    }
+
+    /// Map the local `uid` to the remote one with [`MountOption::IdMap`].
+    fn remote_uid(&self, uid: u32) -> u32 {
+        self.id_map.as_ref().map_or(uid, |map| map.remote_uid(uid))
+    }
+
+    /// Map the local `gid` to the remote one with [`MountOption::IdMap`].
+    fn remote_gid(&self, gid: u32) -> u32 {
+        self.id_map.as_ref().map_or(gid, |map| map.remote_gid(gid))
+    }
+
+    /// Whether permission checks are delegated to the kernel with [`MountOption::DefaultPermissions`].
+    ///
+    /// When set, the driver doesn't check access on its own in `lookup`, `open` and `opendir`.
+    fn default_permissions(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::DefaultPermissions))
+    }
+
+    /// Whether the files are synced on release, with [`MountOption::FsyncOnClose`].
+    fn fsync_on_close(&self) -> bool {
+        self.options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::FsyncOnClose(enabled) => Some(*enabled),
+                _ => None,
+            })
+            .unwrap_or_default()
     }
 
-    /// Get the specified uid from the mount options.
-    fn uid(&self) -> Option<u32> {
-        self.options.iter().find_map(|opt| match opt {
-            MountOption::Uid(uid) => Some(*uid),
-            _ => None,
-        })
+    /// Whether the filesystem is mounted read-only with [`MountOption::RO`].
+    fn read_only(&self) -> bool {
+        self.options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::RO))
     }
 
-    /// Get the specified gid from the mount options.
-    fn gid(&self) -> Option<u32> {
-        self.options.iter().find_map(|opt| match opt {
-            MountOption::Gid(gid) => Some(*gid),
-            _ => None,
-        })
+    /// Apply the umask to the `mode` of a created file, keeping the file type bits.
+    ///
+    /// [`MountOption::Umask`] takes precedence over the `umask` of the creating process.
+    fn apply_umask(&self, mode: u32, umask: u32) -> u32 {
+        let umask = self
+            .options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::Umask(umask) => Some(*umask),
+                _ => None,
+            })
+            .unwrap_or(umask);
+
+        mode & !(umask & 0o7777)
     }
 
     /// Get the specified default mode from the mount options.
@@ -427,14 +2996,55 @@ where
 {
     /// Initialize filesystem.
     /// Called before any other filesystem method.
-    fn init(&mut self, _req: &Request, _config: &mut KernelConfig) -> Result<(), c_int> {
+    fn init(&mut self, _req: &Request, config: &mut KernelConfig) -> Result<(), c_int> {
         info!("Initializing filesystem");
+        self.configure_kernel(config);
+        if let Err(err) = self.check_temp_dir() {
+            error!("Temporary directory is not writable: {err}");
+            return Err(libc::EACCES);
+        }
         if let Err(err) = self.remote.connect() {
             error!("Failed to connect to remote filesystem: {err}");
             return Err(libc::EIO);
         }
         info!("Connected to remote filesystem");
 
+        if let Some(trace) = self.op_trace.clone() {
+            if let Err(err) = trace::dump_on_sigusr1(trace) {
+                error!("Failed to install the SIGUSR1 handler for the operation trace: {err}");
+            }
+        }
+        if let Some(health) = self.health.as_ref() {
+            health.set_alive(true);
+        }
+        let metrics_addr = self.metrics_addr();
+        if let (Some(metrics), Some(addr)) = (self.metrics.clone(), metrics_addr) {
+            let endpoints = Endpoints {
+                metrics: Some(metrics),
+                trace: self.op_trace.clone(),
+                health: self
+                    .health
+                    .clone()
+                    .filter(|_| self.health_addr() == Some(addr)),
+            };
+            match metrics::serve(endpoints, addr) {
+                Ok(addr) => info!("Serving metrics at http://{addr}/metrics"),
+                Err(err) => error!("Failed to start metrics server at {addr}: {err}"),
+            }
+        }
+        if let (Some(health), Some(addr)) = (self.health.clone(), self.health_addr()) {
+            if metrics_addr != Some(addr) {
+                let endpoints = Endpoints {
+                    health: Some(health),
+                    ..Default::default()
+                };
+                match metrics::serve(endpoints, addr) {
+                    Ok(addr) => info!("Serving health at http://{addr}/health"),
+                    Err(err) => error!("Failed to start health server at {addr}: {err}"),
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -442,6 +3052,11 @@ where
     /// Called on filesystem exit.
     fn destroy(&mut self) {
         info!("Destroying filesystem");
+        if let Some(health) = self.health.as_ref() {
+            health.set_alive(false);
+        }
+        self.close_read_stream();
+        self.flush_all_write_buffers();
         if let Err(err) = self.remote.disconnect() {
             error!("Failed to disconnect from remote filesystem: {err}");
         } else {
@@ -451,32 +3066,51 @@ where
 
     /// Look up a directory entry by name and get its attributes.
     fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let _timer = self.op_timer("lookup");
+        self.swap_pending_remote();
+        self.apply_remote_changes();
+        self.close_read_stream();
         info!("lookup() called with {:?} {:?}", parent, name);
         let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+            Ok(path) => path,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
 
+        if self.hide_strict() && self.is_excluded(&path) {
+            debug!("{} is excluded", path.display());
+            reply.error(self.failed(libc::ENOENT));
+            return;
+        }
+
         let (file, attrs) = match self.get_inode_from_path(path.as_path()) {
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.path_err_context(&err, &path)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
             Ok(res) => res,
         };
 
-        if !self.check_access(&file, req.uid(), req.gid(), AccessFlags::F_OK) {
+        if !self.default_permissions()
+            && !self.check_access(&file, req.uid(), req.gid(), AccessFlags::F_OK)
+        {
             error!("No access to file: {path:?}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        reply.entry(&Duration::new(0, 0), &attrs, 0)
+        reply.entry(
+            &self.entry_timeout(),
+            &attrs,
+            self.database.generation(attrs.ino),
+        )
     }
 
     /// Forget about an inode.
@@ -487,23 +3121,42 @@ where
     /// have a limited lifetime. On unmount it is not guaranteed, that all referenced
     /// inodes will receive a forget message.
     fn forget(&mut self, _req: &Request, ino: u64, _nlookup: u64) {
+        let _timer = self.op_timer("forget");
+        self.swap_pending_remote();
         info!("forget() called with {ino}");
-        self.database.forget(ino);
+        self.forget_inodes([ino]);
+    }
+
+    /// Forget about several inodes at once.
+    /// The kernel sends this instead of many forget requests when it evicts a lot of entries.
+    fn batch_forget(&mut self, _req: &Request, nodes: &[fuse_forget_one]) {
+        let _timer = self.op_timer("batch_forget");
+        self.swap_pending_remote();
+        info!("batch_forget() called with {} inodes", nodes.len());
+        self.forget_inodes(nodes.iter().map(|node| node.nodeid));
     }
 
     /// Get file attributes.
     fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        let _timer = self.op_timer("getattr");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("getattr() called with {ino}");
+        self.apply_remote_changes();
+        self.flush_idle_writes();
         let attrs = match self.get_inode(ino) {
             Err(err) => {
-                error!("Failed to get file attributes for {ino}: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
             Ok((_, attrs)) => attrs,
         };
 
-        reply.attr(&Duration::new(0, 0), &attrs);
+        reply.attr(&self.attr_timeout(), &attrs);
     }
 
     /// Set file attributes.
@@ -525,22 +3178,40 @@ where
         _flags: Option<u32>,
         reply: ReplyAttr,
     ) {
+        let _timer = self.op_timer("setattr");
+        self.swap_pending_remote();
+        let reply = self.audited("setattr", req, ino, None, reply);
+        self.close_read_stream();
         info!(
             "setattr() called with mode: {:?}, uid: {:?}, gid: {:?}, size: {:?}, atime: {:?}, mtime: {:?}, ctime: {:?}",
             mode, uid, gid, size, atime, mtime, ctime
         );
-        let (mut file, _) = match self.get_inode(ino) {
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(ino, None) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        let (mut file, current) = match self.get_inode(ino) {
             Ok(attrs) => attrs,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
 
         if !self.check_access(&file, req.uid(), req.gid(), AccessFlags::W_OK) {
             error!("No access to file: {}", file.path().display());
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
@@ -548,57 +3219,79 @@ where
             file.metadata.mode = Some(mode.into());
         }
         if let Some(uid) = uid {
-            file.metadata.uid = Some(uid);
+            file.metadata.uid = Some(self.remote_uid(uid));
         }
         if let Some(gid) = gid {
-            file.metadata.gid = Some(gid);
+            file.metadata.gid = Some(self.remote_gid(gid));
         }
         if let Some(size) = size {
             file.metadata.size = size;
         }
-        if let Some(atime) = atime {
-            file.metadata.accessed = Some(time_or_now(atime));
-        }
-        if let Some(mtime) = mtime {
-            file.metadata.modified = Some(time_or_now(mtime));
-        }
-        if let Some(ctime) = ctime {
-            file.metadata.created = Some(ctime);
+        set_times(&mut file.metadata, &current, atime, mtime);
+        self.truncate_times(&mut file.metadata);
+
+        // truncate pending writes
+        if let (Some(size), Some(buffer)) = (size, self.write_buffers.get_mut(&ino)) {
+            if let Err(err) = buffer.truncate(size) {
+                error!("Failed to truncate write buffer: {err}");
+                reply.error(self.failed(libc::EIO));
+                return;
+            }
         }
 
         // set attributes
-        match self.remote.setstat(file.path(), file.metadata().clone()) {
+        self.invalidate_page_cache(ino);
+        let result = self
+            .remote
+            .setstat(&self.remote_path(file.path()), file.metadata().clone());
+        self.record_write(&result);
+        match result {
             Ok(_) => {
-                let attrs = convert_file::<T>(&file);
-                reply.attr(&Duration::new(0, 0), &attrs);
+                self.set_ctime(ino, ctime.unwrap_or_else(SystemTime::now));
+                if let Some(mode) = mode.filter(|_| file.is_dir()) {
+                    self.set_setgid(ino, mode & S_ISGID != 0);
+                }
+                let attrs = self.file_attr(&file);
+                reply.attr(&self.attr_timeout(), &attrs);
             }
             Err(err) => {
-                error!("Failed to set file attributes: {err}");
-                reply.error(libc::EIO);
+                error!(
+                    "Failed to set file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::EIO));
             }
         }
     }
 
     /// Read symbolic link.
     fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        let _timer = self.op_timer("readlink");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("readlink() called with {:?}", ino);
         let (file, _) = match self.get_inode(ino) {
             Ok(attrs) => attrs,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
 
-        let mut buffer = vec![0; file.metadata().size as usize];
-        if let Err(err) = self.read(file.path(), &mut buffer, 0) {
-            error!("Failed to read file: {err}");
-            reply.error(libc::EIO);
-            return;
-        }
+        let target = match self.read_link_target(ino, &file) {
+            Ok(target) => target,
+            Err(err) => {
+                error!("Failed to read link: {}", self.err_context(&err, ino));
+                reply.error(self.failed(libc::EIO));
+                return;
+            }
+        };
 
-        reply.data(&buffer);
+        reply.data(&target);
     }
 
     /// Create file node.
@@ -610,27 +3303,46 @@ where
         parent: u64,
         name: &OsStr,
         mode: u32,
-        _umask: u32,
+        umask: u32,
         _rdev: u32,
         reply: ReplyEntry,
     ) {
+        let _timer = self.op_timer("mknod");
+        self.swap_pending_remote();
+        let reply = self.audited("mknod", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!("mknod() called with {:?} {:?} {:o}", parent, name, mode);
+        if let Err(errno) = self.check_name_len(name) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(parent, Some(name)) {
+            reply.error(self.failed(errno));
+            return;
+        }
 
-        let mode = SFlag::from_bits_retain(mode as mode_t);
+        let mode = SFlag::from_bits_retain(self.apply_umask(mode, umask) as mode_t);
         let file_type = mode & SFlag::S_IFMT;
 
         if file_type != SFlag::S_IFREG && file_type != SFlag::S_IFLNK && file_type != SFlag::S_IFDIR
         {
             warn!("mknod() implementation is incomplete. Only supports regular files, symlinks, and directories. Got {:o}", mode);
-            reply.error(libc::ENOSYS);
+            reply.error(self.failed(libc::ENOSYS));
             return;
         }
 
         let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+            Ok(path) => path,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
@@ -638,47 +3350,55 @@ where
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
         // Check file type
         let res = match as_file_kind(mode) {
-            Some(FileType::Directory) => self
-                .remote
-                .create_dir(&path, UnixPex::from(mode.bits() as u32)),
+            Some(FileType::Directory) => {
+                self.make_dir(parent, &path, UnixPex::from(mode.bits() as u32))
+            }
             Some(FileType::RegularFile) => {
+                let gid = self
+                    .inherited_gid(parent)
+                    .unwrap_or_else(|| self.remote_gid(req.gid()));
                 let metadata = remotefs::fs::Metadata {
                     mode: Some(UnixPex::from(mode.bits() as u32)),
-                    gid: Some(req.gid()),
-                    uid: Some(req.uid()),
+                    gid: Some(gid),
+                    uid: Some(self.remote_uid(req.uid())),
                     ..Default::default()
                 };
-                let reader = Cursor::new(Vec::new());
-                self.remote
-                    .create_file(&path, &metadata, Box::new(reader))
-                    .map(|_| ())
+                // mknod never reuses an existing file
+                self.create_for_open(&path, &metadata, OFlag::O_EXCL)
             }
             Some(_) | None => {
                 warn!("mknod() implementation is incomplete. Only supports regular files and directories. Got {:o}", mode);
-                reply.error(libc::ENOSYS);
+                reply.error(self.failed(libc::ENOSYS));
                 return;
             }
         };
 
-        if let Err(err) = res {
-            error!("Failed to create file: {err}");
-            reply.error(libc::EIO);
+        if let Err(errno) = res {
+            reply.error(self.failed(errno));
             return;
         }
+        self.forget_missing(&path);
 
         // Get the inode
         match self.get_inode_from_path(path.as_path()) {
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.path_err_context(&err, &path)
+                );
+                reply.error(self.failed(libc::ENOENT));
             }
-            Ok((_, attrs)) => reply.entry(&Duration::new(0, 0), &attrs, 0),
+            Ok((_, attrs)) => reply.entry(
+                &self.entry_timeout(),
+                &attrs,
+                self.database.generation(attrs.ino),
+            ),
         }
     }
 
@@ -689,15 +3409,34 @@ where
         parent: u64,
         name: &OsStr,
         mode: u32,
-        _umask: u32,
+        umask: u32,
         reply: ReplyEntry,
     ) {
+        let _timer = self.op_timer("mkdir");
+        self.swap_pending_remote();
+        let reply = self.audited("mkdir", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!("mkdir() called with {:?} {:?} {:o}", parent, name, mode);
+        if let Err(errno) = self.check_name_len(name) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(parent, Some(name)) {
+            reply.error(self.failed(errno));
+            return;
+        }
         let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+            Ok(path) => path,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
@@ -705,35 +3444,57 @@ where
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        let mode = UnixPex::from(mode);
-        if let Err(err) = self.remote.create_dir(&path, mode) {
-            error!("Failed to create directory: {err}");
-            reply.error(libc::EIO);
+        let mode = UnixPex::from(self.apply_umask(mode, umask));
+        if let Err(errno) = self.make_dir(parent, &path, mode) {
+            reply.error(self.failed(errno));
             return;
         }
+        self.forget_missing(&path);
 
         // Get the inode
         match self.get_inode_from_path(path.as_path()) {
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.path_err_context(&err, &path)
+                );
+                reply.error(self.failed(libc::ENOENT));
             }
-            Ok((_, attrs)) => reply.entry(&Duration::new(0, 0), &attrs, 0),
+            Ok((_, attrs)) => reply.entry(
+                &self.entry_timeout(),
+                &attrs,
+                self.database.generation(attrs.ino),
+            ),
         }
     }
 
     /// Remove a file
     fn unlink(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let _timer = self.op_timer("unlink");
+        self.swap_pending_remote();
+        let reply = self.audited("unlink", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!("unlink() called with {:?} {:?}", parent, name);
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(parent, Some(name)) {
+            reply.error(self.failed(errno));
+            return;
+        }
         let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+            Ok(path) => path,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
@@ -741,13 +3502,21 @@ where
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        if let Err(err) = self.remote.remove_file(&path) {
-            error!("Failed to remove file: {err}");
-            reply.error(libc::EIO);
+        self.invalidate_page_cache(Self::inode(&path));
+        self.invalidate_symlink(Self::inode(&path));
+        self.write_buffers.remove(&Self::inode(&path));
+        let result = self.remote.remove_file(&self.remote_path(&path));
+        self.record_write(&result);
+        if let Err(err) = result {
+            error!(
+                "Failed to remove file: {}",
+                self.path_err_context(&err, &path)
+            );
+            reply.error(self.failed(libc::EIO));
             return;
         }
 
@@ -756,12 +3525,27 @@ where
 
     /// Remove a directory
     fn rmdir(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let _timer = self.op_timer("rmdir");
+        self.swap_pending_remote();
+        let reply = self.audited("rmdir", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!("rmdir() called with {:?} {:?}", parent, name);
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(parent, Some(name)) {
+            reply.error(self.failed(errno));
+            return;
+        }
         let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+            Ok(path) => path,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
@@ -769,15 +3553,21 @@ where
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        if let Err(err) = self.remote.remove_dir(&path) {
-            error!("Failed to remove directory: {err}");
-            reply.error(libc::EIO);
+        let result = self.remote.remove_dir(&self.remote_path(&path));
+        self.record_write(&result);
+        if let Err(err) = result {
+            error!(
+                "Failed to remove directory: {}",
+                self.path_err_context(&err, &path)
+            );
+            reply.error(self.failed(libc::EIO));
             return;
         }
+        self.set_setgid(Self::inode(&path), false);
 
         reply.ok();
     }
@@ -791,12 +3581,31 @@ where
         link: &Path,
         reply: ReplyEntry,
     ) {
+        let _timer = self.op_timer("symlink");
+        self.swap_pending_remote();
+        let reply = self.audited("symlink", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!("symlink() called with {:?} {:?} {:?}", parent, name, link);
+        if let Err(errno) = self.check_name_len(name) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(parent, Some(name)) {
+            reply.error(self.failed(errno));
+            return;
+        }
         let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+            Ok(path) => path,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
@@ -804,23 +3613,37 @@ where
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        if let Err(err) = self.remote.symlink(&path, link) {
-            error!("Failed to create symlink: {err}");
-            reply.error(libc::EIO);
+        let result = self.remote.symlink(&self.remote_path(&path), link);
+        self.record_write(&result);
+        if let Err(err) = result {
+            error!(
+                "Failed to create symlink: {}",
+                self.path_err_context(&err, &path)
+            );
+            reply.error(self.failed(libc::EIO));
             return;
         }
+        self.forget_missing(&path);
+        self.invalidate_symlink(Self::inode(&path));
 
         // Get the inode
         match self.get_inode_from_path(path.as_path()) {
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.path_err_context(&err, &path)
+                );
+                reply.error(self.failed(libc::ENOENT));
             }
-            Ok((_, attrs)) => reply.entry(&Duration::new(0, 0), &attrs, 0),
+            Ok((_, attrs)) => reply.entry(
+                &self.entry_timeout(),
+                &attrs,
+                self.database.generation(attrs.ino),
+            ),
         }
     }
 
@@ -835,23 +3658,46 @@ where
         _flags: u32,
         reply: ReplyEmpty,
     ) {
+        let _timer = self.op_timer("rename");
+        self.swap_pending_remote();
+        let mut reply = self.audited("rename", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!(
             "rename() called with {:?} {:?} {:?} {:?}",
             parent, name, newparent, newname
         );
+        // only the new name is checked, so that the entries with a longer name can be renamed to a shorter one
+        if let Err(errno) = self.check_name_len(newname) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self
+            .check_writable_path(parent, Some(name))
+            .and_then(|_| self.check_writable_path(newparent, Some(newname)))
+        {
+            reply.error(self.failed(errno));
+            return;
+        }
 
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        let src = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+        let (src_inode, src) = match self.lookup_entry(parent, name) {
+            Ok(entry) => entry,
+            Err(errno) => {
                 error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
@@ -859,27 +3705,40 @@ where
         // Check access for new parent
         if !self.check_inode_access(newparent, req, AccessFlags::W_OK) {
             error!("No access to new parent: {newparent}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
-        let dest = match self.lookup_name(newparent, newname) {
-            Some(path) => path,
-            None => {
+        let (dest_inode, dest) = match self.lookup_entry(newparent, newname) {
+            Ok(entry) => entry,
+            Err(errno) => {
                 error!("Failed to lookup file: {newname:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
+        reply.target(&dest);
 
-        if let Err(err) = self.remote.mov(&src, &dest) {
-            error!("Failed to move file: {err}");
-            reply.error(libc::EIO);
+        // pending writes must reach the remote before the file is moved
+        if let Err(err) = self.flush_write_buffer(src_inode) {
+            let errno = self.write_failed("upload pending writes", src_inode, &err);
+            reply.error(self.failed(errno));
             return;
         }
+        self.write_buffers.remove(&src_inode);
+        self.write_buffers.remove(&dest_inode);
+
+        self.invalidate_page_cache(src_inode);
+        self.invalidate_page_cache(dest_inode);
+        self.invalidate_symlink(src_inode);
+        self.invalidate_symlink(dest_inode);
+        if let Err(errno) = self.move_file(&src, &dest) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        self.forget_missing(&dest);
 
-        // Update the database
-        self.database.put(Self::inode(&dest), dest);
+        self.moved_inode(src_inode, dest_inode, dest);
 
         reply.ok();
     }
@@ -893,9 +3752,12 @@ where
         _newname: &OsStr,
         reply: ReplyEntry,
     ) {
+        let _timer = self.op_timer("link");
+        self.swap_pending_remote();
+        self.close_read_stream();
         debug!("link() called");
         // not implemented
-        reply.error(libc::ENOSYS);
+        reply.error(self.failed(libc::ENOSYS));
     }
 
     /// Open a file.
@@ -907,51 +3769,65 @@ where
     /// filesystem may set, to change the way the file is opened. See fuse_file_info
     /// structure in <fuse_common.h> for more details.
     fn open(&mut self, req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+        let _timer = self.op_timer("open");
+        self.swap_pending_remote();
+        let reply = self.audited_read("open", req, ino, None, reply);
+        self.close_read_stream();
         info!("open() called for {ino}");
-        let flags = OFlag::from_bits_truncate(flags);
-        let (access_mask, read, write) = match flags & OFlag::O_ACCMODE {
-            OFlag::O_RDONLY => {
-                // Behavior is undefined, but most filesystems return EACCES
-                if flags.intersects(OFlag::O_TRUNC) {
-                    error!("EACCESS due to O_TRUNC flag");
-                    reply.error(libc::EACCES);
+        let (access_mask, read, write) = match self.open_access(flags) {
+            Ok(access) => access,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+
+        let _in_flight = if write {
+            match self
+                .check_writable_path(ino, None)
+                .and_then(|_| self.begin_change())
+            {
+                Ok(in_flight) => Some(in_flight),
+                Err(errno) => {
+                    reply.error(self.failed(errno));
                     return;
                 }
-                if flags.intersects(OFlag::from_bits_retain(FMODE_EXEC)) {
-                    // Open is from internal exec syscall
-                    (AccessFlags::X_OK, true, false)
-                } else {
-                    (AccessFlags::R_OK, true, false)
-                }
-            }
-            OFlag::O_WRONLY => (AccessFlags::W_OK, false, true),
-            OFlag::O_RDWR => (AccessFlags::R_OK | AccessFlags::W_OK, true, true),
-            // Exactly one access mode flag must be specified
-            _ => {
-                error!("Invalid access mode flags: {flags:?}");
-                reply.error(libc::EINVAL);
-                return;
             }
+        } else {
+            None
         };
 
         let (file, _) = match self.get_inode(ino) {
             Ok(res) => res,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
 
-        if !self.check_access(&file, req.uid(), req.gid(), access_mask) {
+        if !self.default_permissions()
+            && !self.check_access(&file, req.uid(), req.gid(), access_mask)
+        {
             error!("No access to file: {}", file.path().display());
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
 
+        if let Some(monitor) = self.monitor.as_ref() {
+            monitor.watch_file(
+                ino,
+                self.remote_path(file.path()),
+                FileVersion::from(file.metadata()),
+            );
+        }
+
         // Set file handle and reply
         let fh = self.file_handlers.open(req.pid(), ino, read, write);
-        reply.opened(fh, 0);
+        reply.opened(fh, self.open_flags());
     }
 
     /// Read data.
@@ -972,6 +3848,9 @@ where
         _lock_owner: Option<u64>,
         reply: ReplyData,
     ) {
+        let _timer = self.op_timer("read");
+        self.swap_pending_remote();
+        self.apply_remote_changes();
         info!("read() called for {ino} {size} bytes at {offset}");
         // check access
         if !self
@@ -981,21 +3860,29 @@ where
             .unwrap_or_default()
         {
             error!("No read permission for fh {fh} and pid {}", req.pid());
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
         // check offset
         if offset < 0 {
             error!("Invalid offset {offset}");
-            reply.error(libc::EINVAL);
+            reply.error(self.failed(libc::EINVAL));
+            return;
+        }
+        // nothing to read, without a round trip to the remote
+        if size == 0 {
+            reply.data(&[]);
             return;
         }
 
-        let (file, _) = match self.get_inode(ino) {
-            Ok(attrs) => attrs,
+        let file = match self.file_to_read(ino, offset as u64) {
+            Ok(file) => file,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
@@ -1003,13 +3890,102 @@ where
         let read_size = (size as u64).min(file.metadata().size.saturating_sub(offset as u64));
         debug!("Reading {read_size} bytes from at {offset}");
         let mut buffer = vec![0; read_size as usize];
-        if let Err(err) = self.read(file.path(), &mut buffer, offset as u64) {
-            error!("Failed to read file: {err}");
-            reply.error(libc::EIO);
+        if let Some(write_buffer) = self.write_buffers.get(&ino) {
+            debug!("Reading from write buffer of {ino}");
+            match write_buffer.read(offset as u64, &mut buffer) {
+                Ok(bytes_read) => reply.data(&buffer[..bytes_read]),
+                Err(err) => {
+                    error!("Failed to read from write buffer: {err}");
+                    reply.error(self.failed(libc::EIO));
+                }
+            }
+            return;
+        }
+        if let (Some(workers), None) = (self.workers.as_ref(), self.page_cache.as_ref()) {
+            let path = self.remote_path(file.path());
+            let chunk_size = self.io_chunk_size();
+            let retries = self.io_retries();
+            let temp_dir = self.temp_dir().map(Path::to_path_buf);
+            let read_limit = self.read_limit.clone();
+            let streams = self.parallel_read_streams();
+            if ParallelRead::ranges(buffer.len(), streams) > 1
+                && !self
+                    .streams_unsupported
+                    .load(std::sync::atomic::Ordering::Relaxed)
+            {
+                let read = ParallelRead {
+                    path,
+                    offset: offset as u64,
+                    len: buffer.len(),
+                    chunk_size,
+                    retries,
+                    temp_dir,
+                    unsupported: self.streams_unsupported.clone(),
+                };
+                read.execute(
+                    workers,
+                    streams,
+                    Box::new(move |result| match result {
+                        Ok(data) => {
+                            if let Some(limit) = read_limit {
+                                limit.take(data.len());
+                            }
+                            reply.data(&data)
+                        }
+                        Err(err) => {
+                            error!("Failed to read file: {err}");
+                            reply.error(libc::EIO);
+                        }
+                    }),
+                );
+                return;
+            }
+            let job: workers::Job<T> = Box::new(move |remote| {
+                match read_remote(
+                    remote,
+                    &path,
+                    &mut buffer,
+                    offset as u64,
+                    chunk_size,
+                    retries,
+                    temp_dir.as_deref(),
+                ) {
+                    Ok(bytes_read) => {
+                        if let Some(limit) = read_limit {
+                            limit.take(bytes_read);
+                        }
+                        reply.data(&buffer[..bytes_read])
+                    }
+                    Err(err) => {
+                        error!("Failed to read file: {err}");
+                        reply.error(libc::EIO);
+                    }
+                }
+            });
+            if let Err(job) = workers.execute(job) {
+                error!("Workers have stopped; reading on the session thread");
+                job(&mut self.remote);
+            }
             return;
         }
+        let bytes_read = match self.read_cached(
+            ino,
+            file.path(),
+            file.metadata().size,
+            &mut buffer,
+            offset as u64,
+        ) {
+            Ok(bytes_read) => bytes_read,
+            Err(err) => {
+                error!("Failed to read file: {}", self.err_context(&err, ino));
+                reply.error(self.failed(libc::EIO));
+                return;
+            }
+        };
+        self.keep_stream_file(file);
 
-        reply.data(&buffer);
+        // reply with the bytes actually read, so that the kernel doesn't fill the tail with zeroes
+        reply.data(&buffer[..bytes_read]);
     }
 
     /// Write data.
@@ -1030,7 +4006,22 @@ where
         _lock_owner: Option<u64>,
         reply: ReplyWrite,
     ) {
+        let _timer = self.op_timer("write");
+        self.swap_pending_remote();
+        let reply = self.audited("write", req, ino, None, reply);
+        self.close_read_stream();
         info!("write() called for {ino} {} bytes at {offset}", data.len());
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(ino, None) {
+            reply.error(self.failed(errno));
+            return;
+        }
         // check access
         if !self
             .file_handlers
@@ -1039,35 +4030,45 @@ where
             .unwrap_or_default()
         {
             debug!("No write permission for fh {fh}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
             return;
         }
         // check offset
         if offset < 0 {
             debug!("Invalid offset {offset}");
-            reply.error(libc::EINVAL);
+            reply.error(self.failed(libc::EINVAL));
+            return;
+        }
+        // nothing to write, without a round trip to the remote
+        if data.is_empty() {
+            reply.written(0);
             return;
         }
 
         let (file, _) = match self.get_inode(ino) {
             Ok(attrs) => attrs,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
 
         // write data
-        let bytes_written = match self.write(&file, data, offset as u64) {
+        self.invalidate_page_cache(ino);
+        let bytes_written = match self.write_data(ino, &file, data, offset as u64) {
             Ok(bytes) => bytes,
             Err(err) => {
-                error!("Failed to write file: {err}");
-                reply.error(libc::EIO);
+                let errno = self.write_failed("write file", ino, &err);
+                reply.error(self.failed(errno));
                 return;
             }
         };
 
+        self.touch_ctime(ino);
         reply.written(bytes_written);
     }
 
@@ -1082,16 +4083,26 @@ where
     /// filesystem wants to return write errors. If the filesystem supports file locking
     /// operations (setlk, getlk) it should remove all locks belonging to 'lock_owner'.
     fn flush(&mut self, req: &Request, ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        let _timer = self.op_timer("flush");
+        self.swap_pending_remote();
+        self.close_read_stream();
+        let _in_flight = self.drain.track();
         info!("flush() called for {ino}");
 
         // get fh
         if self.file_handlers.get(req.pid(), fh).is_none() {
             error!("no file handler found for {fh} and pid {}", req.pid());
-            reply.error(libc::ENOENT);
+            reply.error(self.failed(libc::ENOENT));
+            return;
+        }
+
+        // upload pending writes, so that close() can report the error
+        if let Err(err) = self.flush_write_buffer(ino) {
+            let errno = self.write_failed("upload pending writes", ino, &err);
+            reply.error(self.failed(errno));
             return;
         }
 
-        // nop and ok
         reply.ok();
     }
 
@@ -1106,30 +4117,69 @@ where
     fn release(
         &mut self,
         req: &Request,
-        _ino: u64,
+        ino: u64,
         fh: u64,
         _flags: i32,
         _lock_owner: Option<u64>,
         _flush: bool,
         reply: ReplyEmpty,
     ) {
+        let _timer = self.op_timer("release");
+        self.swap_pending_remote();
+        self.close_read_stream();
+        let _in_flight = self.drain.track();
         // get fh
         if self.file_handlers.get(req.pid(), fh).is_none() {
             error!("no file handler found for {fh} and pid {}", req.pid());
-            reply.error(libc::ENOENT);
+            reply.error(self.failed(libc::ENOENT));
             return;
         }
 
+        // upload pending writes; the error won't reach close() unless synced on close
+        let result = self.release_writes(ino);
+
         // remove fh and ok
         self.file_handlers.close(req.pid(), fh);
-        reply.ok();
+        if !self.file_handlers.is_open(ino) {
+            if let Some(monitor) = self.monitor.as_ref() {
+                monitor.unwatch_file(ino);
+            }
+            // no handle owns the buffer anymore: reusing it on the next open would overwrite the remote changes
+            if let Err(err) = &result {
+                error!("pending writes are lost: {}", self.err_context(err, ino));
+            }
+            self.write_buffers.remove(&ino);
+        }
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(self.failed(write_err_to_errno(&err))),
+        }
     }
 
     /// Synchronize file contents.
     /// If the datasync parameter is non-zero, then only the user data should be flushed,
     /// not the meta data.
-    fn fsync(&mut self, _req: &Request, _ino: u64, _fh: u64, _datasync: bool, reply: ReplyEmpty) {
-        reply.ok();
+    fn fsync(&mut self, req: &Request, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        let _timer = self.op_timer("fsync");
+        self.swap_pending_remote();
+        self.close_read_stream();
+        let _in_flight = self.drain.track();
+        info!("fsync() called for {ino}; datasync: {datasync}");
+        // get fh
+        if self.file_handlers.get(req.pid(), fh).is_none() {
+            error!("no file handler found for {fh} and pid {}", req.pid());
+            reply.error(self.failed(libc::ENOENT));
+            return;
+        }
+
+        match self.sync_file(ino, datasync) {
+            Ok(()) => reply.ok(),
+            Err(err) => {
+                let errno = self.write_failed("sync", ino, &err);
+                reply.error(self.failed(errno));
+            }
+        }
     }
 
     /// Open a directory.
@@ -1140,6 +4190,10 @@ where
     /// directory stream operations in case the contents of the directory can change
     /// between opendir and releasedir.
     fn opendir(&mut self, req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+        let _timer = self.op_timer("opendir");
+        self.swap_pending_remote();
+        let reply = self.audited_read("opendir", req, ino, None, reply);
+        self.close_read_stream();
         info!("opendir() called on {:?}", ino);
         let flags = OFlag::from_bits_truncate(flags);
         let (access_mask, read, write) = match flags & OFlag::O_ACCMODE {
@@ -1147,7 +4201,7 @@ where
                 // Behavior is undefined, but most filesystems return EACCES
                 if flags.intersects(OFlag::O_TRUNC) {
                     error!("EACCES due to O_TRUNC flag");
-                    reply.error(libc::EACCES);
+                    reply.error(self.failed(libc::EACCES));
                     return;
                 }
                 (AccessFlags::R_OK, true, false)
@@ -1157,7 +4211,7 @@ where
             // Exactly one access mode flag must be specified
             _ => {
                 error!("Invalid flags: {flags:?}");
-                reply.error(libc::EINVAL);
+                reply.error(self.failed(libc::EINVAL));
                 return;
             }
         };
@@ -1165,18 +4219,37 @@ where
         let (file, _) = match self.get_inode(ino) {
             Ok(attrs) => attrs,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
+        if !file.is_dir() {
+            error!("{} is not a directory", file.path().display());
+            reply.error(self.failed(libc::ENOTDIR));
+            return;
+        }
 
-        if self.check_access(&file, req.uid(), req.gid(), access_mask) {
+        if self.default_permissions() || self.check_access(&file, req.uid(), req.gid(), access_mask)
+        {
+            if let Some(monitor) = self.monitor.as_ref() {
+                monitor.watch_dir(
+                    ino,
+                    file.path().to_path_buf(),
+                    self.remote_path(file.path()),
+                );
+            }
             let fh = self.file_handlers.open(req.pid(), ino, read, write);
+            if self.prefetch_dirs() {
+                self.prefetch_dir(req.pid(), fh, ino, file.path());
+            }
             reply.opened(fh, 0);
         } else {
             error!("No access to file: {ino}");
-            reply.error(libc::EACCES);
+            reply.error(self.failed(libc::EACCES));
         }
     }
 
@@ -1193,59 +4266,70 @@ where
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
+        let _timer = self.op_timer("readdir");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("readdir() called on {:?}", ino);
         // check fh with read permissions
         match self.file_handlers.get(req.pid(), fh) {
             Some(handler) if !handler.read => {
                 error!("No read permission for fh {fh} and pid {}", req.pid());
-                reply.error(libc::EACCES);
+                reply.error(self.failed(libc::EACCES));
                 return;
             }
             None => {
                 error!("no file handler found for {fh} and pid {}", req.pid());
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
             _ => {}
         }
 
-        // get directory
-        let file = match self.get_inode(ino) {
-            Ok((file, _)) => file,
-            Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-        debug!("Reading directory {ino}: {}", file.path().display());
-
-        // list directory
-        let entries = match self.remote.list_dir(file.path()) {
-            Ok(entries) => entries,
-            Err(err) => {
-                error!("Failed to list directory: {err}");
-                reply.error(libc::EIO);
+        // list the directory once when the listing starts (or is rewound) and serve the next offsets from the snapshot;
+        // the listing prefetched by opendir is served as is
+        let prefetched = self.file_handlers.take_prefetched(req.pid(), fh);
+        if (offset == 0 && !prefetched) || self.file_handlers.dir_entries(req.pid(), fh).is_none() {
+            // get directory
+            let file = match self.get_inode(ino) {
+                Ok((file, _)) => file,
+                Err(err) => {
+                    error!(
+                        "Failed to get file attributes: {}",
+                        self.err_context(&err, ino)
+                    );
+                    self.file_handlers.clear_dir_entries(req.pid(), fh);
+                    reply.error(self.failed(libc::ENOENT));
+                    return;
+                }
+            };
+            if !file.is_dir() {
+                error!("{} is not a directory", file.path().display());
+                self.file_handlers.clear_dir_entries(req.pid(), fh);
+                reply.error(self.failed(libc::ENOTDIR));
                 return;
             }
-        };
-
-        for (index, entry) in entries.into_iter().skip(offset as usize).enumerate() {
-            let inode = Self::inode(entry.path());
-            debug!("Reading entry {inode} {index} {}", entry.path().display());
-            let name = match entry.path().file_name() {
-                Some(name) => OsStr::from_bytes(name.as_bytes()),
-                None => {
-                    error!("Failed to get file name {:?}", entry.path().display());
-                    continue;
+            debug!("Reading directory {ino}: {}", file.path().display());
+
+            // list directory
+            let entries = match self.read_dir_listing(ino, file.path()) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    error!("Failed to list directory: {err}");
+                    self.file_handlers.clear_dir_entries(req.pid(), fh);
+                    reply.error(self.failed(libc::EIO));
+                    return;
                 }
             };
-            let buffer_full = reply.add(
-                inode,
-                offset + index as i64 + 1,
-                convert_remote_filetype(entry.metadata().file_type),
-                name,
-            );
+            self.file_handlers.set_dir_entries(req.pid(), fh, entries);
+        }
+
+        let entries = self
+            .file_handlers
+            .dir_entries(req.pid(), fh)
+            .unwrap_or_default();
+        for (index, entry) in entries.iter().enumerate().skip(offset as usize) {
+            debug!("Reading entry {} {index} {:?}", entry.inode, entry.name);
+            let buffer_full = reply.add(entry.inode, index as i64 + 1, entry.kind, &entry.name);
 
             if buffer_full {
                 debug!("buffer is full");
@@ -1261,13 +4345,16 @@ where
     /// contain the value set by the opendir method, or will be undefined if the
     /// opendir method didn't set any value.
     fn releasedir(&mut self, req: &Request, _ino: u64, fh: u64, _flags: i32, reply: ReplyEmpty) {
+        let _timer = self.op_timer("releasedir");
+        self.swap_pending_remote();
+        self.close_read_stream();
         // get fh
         if self.file_handlers.get(req.pid(), fh).is_none() {
             error!(
                 "Failed to get file handler for {fh} and process {}",
                 req.pid()
             );
-            reply.error(libc::ENOENT);
+            reply.error(self.failed(libc::ENOENT));
             return;
         }
 
@@ -1280,22 +4367,55 @@ where
     /// If the datasync parameter is set, then only the directory contents should
     /// be flushed, not the meta data. fh will contain the value set by the opendir
     /// method, or will be undefined if the opendir method didn't set any value.
-    fn fsyncdir(&mut self, req: &Request, ino: u64, fh: u64, _datasync: bool, reply: ReplyEmpty) {
-        info!("fsyncdir() called for {ino}");
+    fn fsyncdir(&mut self, req: &Request, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        let _timer = self.op_timer("fsyncdir");
+        self.swap_pending_remote();
+        self.close_read_stream();
+        let _in_flight = self.drain.track();
+        info!("fsyncdir() called for {ino}; datasync: {datasync}");
         // get fh
         if self.file_handlers.get(req.pid(), fh).is_none() {
             error!(
                 "Failed to get file handler for {fh} and process {}",
                 req.pid()
             );
-            reply.error(libc::ENOENT);
+            reply.error(self.failed(libc::ENOENT));
+            return;
+        }
+
+        // sync the pending writes of the files in the directory
+        let Some(dir) = self.database.get(ino).map(Path::to_path_buf) else {
+            error!("Failed to get path for {ino}");
+            reply.error(self.failed(libc::ENOENT));
             return;
+        };
+        let children = self
+            .write_buffers
+            .keys()
+            .copied()
+            .filter(|inode| {
+                self.database
+                    .get(*inode)
+                    .map(|path| path.parent() == Some(dir.as_path()))
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        for child in children {
+            if let Err(err) = self.sync_file(child, datasync) {
+                let errno = self.write_failed("sync", child, &err);
+                reply.error(self.failed(errno));
+                return;
+            }
         }
+
         reply.ok();
     }
 
     /// Get file system statistics.
     fn statfs(&mut self, _req: &Request, ino: u64, reply: ReplyStatfs) {
+        let _timer = self.op_timer("statfs");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("statfs() called for {ino}");
 
         // get statfs
@@ -1305,8 +4425,8 @@ where
         }
 
         let path = match self.get_inode(ino) {
-            Ok((file, _)) => file.path().to_path_buf(),
-            Err(_) => PathBuf::from("/"),
+            Ok((file, _)) => self.remote_path(file.path()),
+            Err(_) => self.remote_path(Path::new("/")),
         };
         debug!("Getting filesystem statistics for {path:?}");
 
@@ -1329,19 +4449,20 @@ where
         let mut stats = FsStats { files: 0, size: 0 };
         if let Err(err) = iter_dir(&mut self.remote, &path, &mut stats) {
             error!("Failed to get filesystem statistics: {err}");
-            reply.error(libc::EIO);
+            reply.error(self.failed(libc::EIO));
             return;
         }
 
+        let (blocks, free) = block_usage(self.capacity(), stats.size);
         reply.statfs(
-            stats.size / BLOCK_SIZE as u64,
-            u64::MAX - stats.size / BLOCK_SIZE as u64,
-            u64::MAX - stats.size / BLOCK_SIZE as u64,
-            stats.files,
-            0,
+            blocks,
+            free,
+            free,
+            stats.files + FREE_FILES,
+            FREE_FILES,
+            BLOCK_SIZE as u32,
+            self.max_name_len(),
             BLOCK_SIZE as u32,
-            255,
-            0,
         );
     }
 
@@ -1356,36 +4477,86 @@ where
         _position: u32,
         reply: ReplyEmpty,
     ) {
+        let _timer = self.op_timer("setxattr");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("setxattr() called on {:?} {:?} {:?}", ino, name, value);
         // not supported
-        reply.error(libc::ENOSYS);
+        reply.error(self.failed(libc::ENOSYS));
     }
 
     /// Get an extended attribute.
     /// If `size` is 0, the size of the value should be sent with `reply.size()`.
     /// If `size` is not 0, and the value fits, send it with `reply.data()`, or
-    /// `reply.error(ERANGE)` if it doesn't.
-    fn getxattr(&mut self, _req: &Request, ino: u64, name: &OsStr, _size: u32, reply: ReplyXattr) {
+    /// `reply.error(self.failed(ERANGE))` if it doesn't.
+    fn getxattr(&mut self, _req: &Request, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        let _timer = self.op_timer("getxattr");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("getxattr() called on {:?} {:?}", ino, name);
-        // not supported
-        reply.error(libc::ENOSYS);
+        match self.get_xattr(ino, name) {
+            Ok(value) => reply_xattr(reply, size, &value),
+            Err(errno) => reply.error(self.failed(errno)),
+        }
     }
 
     /// List extended attribute names.
     /// If `size` is 0, the size of the value should be sent with `reply.size()`.
     /// If `size` is not 0, and the value fits, send it with `reply.data()`, or
-    /// `reply.error(ERANGE)` if it doesn't.
+    /// `reply.error(self.failed(ERANGE))` if it doesn't.
     fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: ReplyXattr) {
+        let _timer = self.op_timer("listxattr");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("listxattr() called on {:?} {:?}", ino, size);
-        // not supported
-        reply.error(libc::ENOSYS);
+        match self.list_xattr(ino) {
+            Ok(names) => reply_xattr(reply, size, &names),
+            Err(errno) => reply.error(self.failed(errno)),
+        }
     }
 
     /// Remove an extended attribute.
     fn removexattr(&mut self, _req: &Request, ino: u64, name: &OsStr, reply: ReplyEmpty) {
+        let _timer = self.op_timer("removexattr");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("removexattr() called on {:?} {:?}", ino, name);
+        match self.get_xattr(ino, name) {
+            Ok(_) => reply.ok(),
+            Err(errno) => reply.error(self.failed(errno)),
+        }
+    }
+
+    /// Map a block index within the file to a block index within the device.
+    /// This is only meaningful for block device backed filesystems, so it's not supported.
+    fn bmap(&mut self, _req: &Request, ino: u64, blocksize: u32, idx: u64, reply: ReplyBmap) {
+        let _timer = self.op_timer("bmap");
+        self.swap_pending_remote();
+        self.close_read_stream();
+        info!("bmap() called on {ino} with block size {blocksize} at {idx}");
         // not supported
-        reply.error(libc::ENOSYS);
+        reply.error(self.failed(libc::ENOSYS));
+    }
+
+    /// Poll for IO readiness events.
+    /// The files are always reported as ready, since change notification isn't supported: `ph` is never notified.
+    fn poll(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        _ph: PollHandle,
+        events: u32,
+        flags: u32,
+        reply: ReplyPoll,
+    ) {
+        let _timer = self.op_timer("poll");
+        self.swap_pending_remote();
+        info!("poll() called on {ino} ({fh}) for events {events:#x}; flags: {flags:#x}");
+        match self.poll_events(ino, events) {
+            Ok(revents) => reply.poll(revents),
+            Err(errno) => reply.error(self.failed(errno)),
+        }
     }
 
     /// Check file access permissions.
@@ -1393,26 +4564,30 @@ where
     /// mount option is given, this method is not called. This method is not called
     /// under Linux kernel versions 2.4.x
     fn access(&mut self, req: &Request, ino: u64, mask: i32, reply: ReplyEmpty) {
+        let _timer = self.op_timer("access");
+        self.swap_pending_remote();
+        self.close_read_stream();
         info!("access() called on {:?} {:o}", ino, mask);
         let file = match self.get_inode(ino) {
             Ok((file, _)) => file,
             Err(err) => {
-                error!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                error!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, ino)
+                );
+                reply.error(self.failed(libc::ENOENT));
                 return;
             }
         };
 
-        if self.check_access(
+        match self.check_access_mask(
             &file,
             req.uid(),
             req.gid(),
             AccessFlags::from_bits_truncate(mask),
         ) {
-            reply.ok();
-        } else {
-            error!("No access to file: {}", file.path().display());
-            reply.error(libc::EACCES);
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(self.failed(errno)),
         }
     }
 
@@ -1432,11 +4607,30 @@ where
         parent: u64,
         name: &OsStr,
         mode: u32,
-        _umask: u32,
+        umask: u32,
         flags: i32,
         reply: ReplyCreate,
     ) {
+        let _timer = self.op_timer("create");
+        self.swap_pending_remote();
+        let reply = self.audited("create", req, parent, Some(name), reply);
+        self.close_read_stream();
         info!("create() called with {:?} {:?} {:o}", parent, name, mode);
+        if let Err(errno) = self.check_name_len(name) {
+            reply.error(self.failed(errno));
+            return;
+        }
+        let _in_flight = match self.begin_change() {
+            Ok(in_flight) => in_flight,
+            Err(errno) => {
+                reply.error(self.failed(errno));
+                return;
+            }
+        };
+        if let Err(errno) = self.check_writable_path(parent, Some(name)) {
+            reply.error(self.failed(errno));
+            return;
+        }
 
         let flags = OFlag::from_bits_truncate(flags);
         let (read, write) = match flags & OFlag::O_ACCMODE {
@@ -1446,44 +4640,53 @@ where
             // Exactly one access mode flag must be specified
             _ => {
                 error!("Invalid access mode flag: {flags:?}");
-                reply.error(libc::EINVAL);
+                reply.error(self.failed(libc::EINVAL));
                 return;
             }
         };
 
-        let path = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
+        let (inode, path) = match self.lookup_entry(parent, name) {
+            Ok(entry) => entry,
+            Err(errno) => {
                 error!("Failed to lookup name {name:?}");
-                reply.error(libc::ENOENT);
+                reply.error(self.failed(errno));
                 return;
             }
         };
 
+        let gid = self
+            .inherited_gid(parent)
+            .unwrap_or_else(|| self.remote_gid(req.gid()));
         let metadata = remotefs::fs::Metadata {
-            mode: Some(mode.into()),
-            gid: Some(req.gid()),
-            uid: Some(req.uid()),
+            mode: Some(self.apply_umask(mode, umask).into()),
+            gid: Some(gid),
+            uid: Some(self.remote_uid(req.uid())),
             ..Default::default()
         };
-        let reader = Cursor::new(Vec::new());
-        if let Err(err) = self.remote.create_file(&path, &metadata, Box::new(reader)) {
-            error!("Failed to create file: {err}");
-            reply.error(libc::EIO);
+        if let Err(errno) = self.create_for_open(&path, &metadata, flags) {
+            reply.error(self.failed(errno));
             return;
         }
-
-        let inode = Self::inode(&path);
+        self.forget_missing(&path);
 
         // return created
         match self.get_inode(inode) {
             Err(err) => {
-                debug!("Failed to get file attributes: {err}");
-                reply.error(libc::ENOENT);
+                debug!(
+                    "Failed to get file attributes: {}",
+                    self.err_context(&err, inode)
+                );
+                reply.error(self.failed(libc::ENOENT));
             }
             Ok((_, attrs)) => {
                 let fh = self.file_handlers.open(req.pid(), inode, read, write);
-                reply.created(&Duration::new(0, 0), &attrs, 0, fh, 0);
+                reply.created(
+                    &self.entry_timeout(),
+                    &attrs,
+                    self.database.generation(attrs.ino),
+                    fh,
+                    self.open_flags(),
+                );
             }
         }
     }
@@ -0,0 +1,329 @@
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fuser::{FileAttr, ReplyAttr, ReplyCreate, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite};
+use libc::c_int;
+
+/// A sink writing a record for each audited operation as a line of JSON to a file.
+///
+/// Records are sent to a background writer, so that auditing never blocks the FUSE thread on the disk.
+#[derive(Debug)]
+pub struct AuditLog {
+    sender: Option<Sender<String>>,
+    writer: Option<JoinHandle<()>>,
+    /// Whether read-only operations are audited too
+    reads: bool,
+}
+
+impl AuditLog {
+    /// Open the audit log at `path`, appending to it if it exists.
+    ///
+    /// If `reads` is set, read-only operations, such as opening a file for reading, are audited too.
+    pub fn open(path: &Path, reads: bool) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel();
+        let writer = std::thread::Builder::new()
+            .name("remotefs-fuse-audit".to_string())
+            .spawn(move || run_writer(BufWriter::new(file), &receiver))?;
+
+        Ok(Self {
+            sender: Some(sender),
+            writer: Some(writer),
+            reads,
+        })
+    }
+
+    /// Whether read-only operations are audited
+    pub fn reads(&self) -> bool {
+        self.reads
+    }
+
+    /// Start auditing the operation `op` on `path`, issued by `uid` and `gid`.
+    ///
+    /// The record is written once the result of the operation is set on the returned [`AuditEvent`].
+    pub fn event(&self, op: &'static str, path: Option<PathBuf>, uid: u32, gid: u32) -> AuditEvent {
+        AuditEvent {
+            sender: self.sender.clone(),
+            time: SystemTime::now(),
+            op,
+            path,
+            target: None,
+            uid,
+            gid,
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        // closing the channel stops the writer once the queued records are written
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                error!("the audit log writer panicked");
+            }
+        }
+    }
+}
+
+/// Write the records received on `receiver` to `out`, flushing whenever there are no more queued records
+fn run_writer(mut out: BufWriter<std::fs::File>, receiver: &Receiver<String>) {
+    while let Ok(record) = receiver.recv() {
+        let mut result = writeln!(out, "{record}");
+        while let Ok(record) = receiver.try_recv() {
+            result = result.and_then(|_| writeln!(out, "{record}"));
+        }
+        if let Err(err) = result.and_then(|_| out.flush()) {
+            error!("Failed to write audit log: {err}");
+        }
+    }
+}
+
+/// An audited operation, recorded once its result is known
+#[derive(Debug)]
+pub struct AuditEvent {
+    sender: Option<Sender<String>>,
+    time: SystemTime,
+    op: &'static str,
+    path: Option<PathBuf>,
+    /// Destination of the operation, such as the new path on rename
+    target: Option<PathBuf>,
+    uid: u32,
+    gid: u32,
+}
+
+impl AuditEvent {
+    /// Record the operation with `result`
+    pub fn finish(self, result: Result<(), c_int>) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        let _ = sender.send(self.to_json(result));
+    }
+
+    /// Render the record of the operation with `result` as a line of JSON
+    fn to_json(&self, result: Result<(), c_int>) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"time\":\"{}\",\"op\":\"{}\"",
+            format_timestamp(self.time),
+            self.op
+        );
+        if let Some(path) = &self.path {
+            let _ = write!(out, ",\"path\":\"{}\"", escape(&path.to_string_lossy()));
+        }
+        if let Some(target) = &self.target {
+            let _ = write!(out, ",\"target\":\"{}\"", escape(&target.to_string_lossy()));
+        }
+        let _ = write!(out, ",\"uid\":{},\"gid\":{}", self.uid, self.gid);
+        match result {
+            Ok(()) => out.push_str(",\"result\":\"ok\"}"),
+            Err(errno) => {
+                let _ = write!(out, ",\"result\":\"error\",\"errno\":{errno}}}");
+            }
+        }
+
+        out
+    }
+}
+
+/// Escape `s` to be written in a JSON string
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Format `time` as an RFC 3339 timestamp in UTC, with milliseconds
+pub(super) fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // civil date from the days since the epoch; see <http://howardhinnant.github.io/date_algorithms.html>
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// A reply which can report an error
+pub trait ReplyError {
+    fn error(self, err: c_int);
+}
+
+macro_rules! impl_reply_error {
+    ($($reply:ty),*) => {
+        $(impl ReplyError for $reply {
+            fn error(self, err: c_int) {
+                <$reply>::error(self, err)
+            }
+        })*
+    };
+}
+
+impl_reply_error!(
+    ReplyAttr,
+    ReplyCreate,
+    ReplyEmpty,
+    ReplyEntry,
+    ReplyOpen,
+    ReplyWrite
+);
+
+/// A reply to an audited operation: the operation is recorded when replied to.
+pub struct Audited<R> {
+    reply: R,
+    event: Option<AuditEvent>,
+}
+
+impl<R> Audited<R> {
+    /// Wrap `reply`, recording `event` on reply if set
+    pub fn new(reply: R, event: Option<AuditEvent>) -> Self {
+        Self { reply, event }
+    }
+
+    /// Set the destination of the operation
+    pub fn target(&mut self, target: &Path) {
+        if let Some(event) = self.event.as_mut() {
+            event.target = Some(target.to_path_buf());
+        }
+    }
+
+    /// Record the operation with `result` and get the inner reply
+    fn finish(self, result: Result<(), c_int>) -> R {
+        if let Some(event) = self.event {
+            event.finish(result);
+        }
+
+        self.reply
+    }
+}
+
+impl<R> Audited<R>
+where
+    R: ReplyError,
+{
+    /// Reply with an error
+    pub fn error(self, err: c_int) {
+        self.finish(Err(err)).error(err);
+    }
+}
+
+impl Audited<ReplyEmpty> {
+    /// Reply to a request with nothing
+    pub fn ok(self) {
+        self.finish(Ok(())).ok();
+    }
+}
+
+impl Audited<ReplyEntry> {
+    /// Reply to a request with the given entry
+    pub fn entry(self, ttl: &Duration, attr: &FileAttr, generation: u64) {
+        self.finish(Ok(())).entry(ttl, attr, generation);
+    }
+}
+
+impl Audited<ReplyAttr> {
+    /// Reply to a request with the given attribute
+    pub fn attr(self, ttl: &Duration, attr: &FileAttr) {
+        self.finish(Ok(())).attr(ttl, attr);
+    }
+}
+
+impl Audited<ReplyWrite> {
+    /// Reply to a request with the amount of bytes written
+    pub fn written(self, size: u32) {
+        self.finish(Ok(())).written(size);
+    }
+}
+
+impl Audited<ReplyOpen> {
+    /// Reply to a request with the given open result
+    pub fn opened(self, fh: u64, flags: u32) {
+        self.finish(Ok(())).opened(fh, flags);
+    }
+}
+
+impl Audited<ReplyCreate> {
+    /// Reply to a request with the given entry and open result
+    pub fn created(self, ttl: &Duration, attr: &FileAttr, generation: u64, fh: u64, flags: u32) {
+        self.finish(Ok(()))
+            .created(ttl, attr, generation, fh, flags);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_millis(1_709_217_296_250)),
+            "2024-02-29T14:34:56.250Z"
+        );
+    }
+
+    #[test]
+    fn test_should_write_records_as_ndjson() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let log = AuditLog::open(&path, false).expect("failed to open audit log");
+        assert!(!log.reads());
+
+        let mut event = log.event("rename", Some(PathBuf::from("/a \"b\".txt")), 1000, 100);
+        event.target = Some(PathBuf::from("/c.txt"));
+        event.time = UNIX_EPOCH;
+        event.finish(Ok(()));
+        let mut event = log.event("unlink", Some(PathBuf::from("/d.txt")), 0, 0);
+        event.time = UNIX_EPOCH;
+        event.finish(Err(libc::EACCES));
+        drop(log);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            vec![
+                r#"{"time":"1970-01-01T00:00:00.000Z","op":"rename","path":"/a \"b\".txt","target":"/c.txt","uid":1000,"gid":100,"result":"ok"}"#,
+                r#"{"time":"1970-01-01T00:00:00.000Z","op":"unlink","path":"/d.txt","uid":0,"gid":0,"result":"error","errno":13}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tracks the operations changing the filesystem, so that the unmount can wait for them to complete.
+///
+/// Once draining has started, no new change is accepted, while the operations uploading the pending writes, such
+/// as `flush` and `release`, are still served.
+#[derive(Debug, Default)]
+pub struct Drain {
+    draining: AtomicBool,
+    in_flight: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl Drain {
+    /// Stop accepting new changes
+    pub fn start(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether draining has started
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Begin a new change, unless draining has started
+    pub fn begin(self: &Arc<Self>) -> Option<InFlight> {
+        if self.is_draining() {
+            return None;
+        }
+
+        Some(self.track())
+    }
+
+    /// Track an operation which must complete before unmounting, even if draining has started
+    pub fn track(self: &Arc<Self>) -> InFlight {
+        *self.in_flight.lock().expect("drain lock poisoned") += 1;
+
+        InFlight(self.clone())
+    }
+
+    /// Wait up to `timeout` for the tracked operations to complete, returning whether they did
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut in_flight = self.in_flight.lock().expect("drain lock poisoned");
+        while *in_flight > 0 {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return false;
+            };
+            in_flight = self
+                .idle
+                .wait_timeout(in_flight, remaining)
+                .expect("drain lock poisoned")
+                .0;
+        }
+
+        true
+    }
+}
+
+/// An operation tracked by [`Drain`], completed when dropped
+#[derive(Debug)]
+pub struct InFlight(Arc<Drain>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut in_flight = self.0.in_flight.lock().expect("drain lock poisoned");
+        *in_flight = in_flight.saturating_sub(1);
+        if *in_flight == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_wait_for_in_flight_operations() {
+        let drain = Arc::new(Drain::default());
+        assert!(drain.wait(Duration::ZERO));
+
+        // a slow write
+        let write = drain.begin().expect("not draining");
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            drop(write);
+        });
+
+        drain.start();
+        assert!(drain.begin().is_none());
+        assert!(drain.wait(Duration::from_secs(5)));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_should_time_out_waiting_for_operations() {
+        let drain = Arc::new(Drain::default());
+        let _flush = drain.track();
+        drain.start();
+
+        assert!(!drain.wait(Duration::from_millis(10)));
+    }
+}
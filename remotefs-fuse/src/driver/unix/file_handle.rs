@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+
+use fuser::FileType;
 
 use super::inode::Inode;
 
@@ -37,6 +40,70 @@ impl FileHandlersDb {
             .and_then(|handlers| handlers.get(fh))
     }
 
+    /// Check whether any process has a file handle open for `inode`.
+    pub fn is_open(&self, inode: Inode) -> bool {
+        self.handlers.values().any(|handlers| {
+            handlers
+                .handles
+                .values()
+                .any(|handle| handle.inode == inode)
+        })
+    }
+
+    /// Store the directory listing for a directory file handle.
+    ///
+    /// The listing is kept until the file handle is closed.
+    pub fn set_dir_entries(&mut self, pid: Pid, fh: u64, entries: Vec<DirEntry>) {
+        if let Some(handlers) = self.handlers.get_mut(&pid) {
+            handlers.dir_entries.insert(fh, entries);
+        }
+    }
+
+    /// Store the directory listing prefetched by `opendir` for a directory file handle.
+    ///
+    /// The first `readdir` from offset 0 serves it instead of listing the directory again.
+    pub fn set_prefetched_dir_entries(&mut self, pid: Pid, fh: u64, entries: Vec<DirEntry>) {
+        if let Some(handlers) = self.handlers.get_mut(&pid) {
+            handlers.dir_entries.insert(fh, entries);
+            handlers.prefetched.insert(fh);
+        }
+    }
+
+    /// Get whether the directory listing of a directory file handle has been prefetched and not read yet.
+    ///
+    /// Once taken, the listing is no longer considered prefetched.
+    pub fn take_prefetched(&mut self, pid: Pid, fh: u64) -> bool {
+        self.handlers
+            .get_mut(&pid)
+            .map(|handlers| handlers.prefetched.remove(&fh))
+            .unwrap_or_default()
+    }
+
+    /// Get the directory listing stored for a directory file handle.
+    pub fn dir_entries(&self, pid: Pid, fh: u64) -> Option<&[DirEntry]> {
+        self.handlers
+            .get(&pid)
+            .and_then(|handlers| handlers.dir_entries.get(&fh))
+            .map(Vec::as_slice)
+    }
+
+    /// Drop the directory listing stored for a directory file handle, if any.
+    pub fn clear_dir_entries(&mut self, pid: Pid, fh: u64) {
+        if let Some(handlers) = self.handlers.get_mut(&pid) {
+            handlers.dir_entries.remove(&fh);
+            handlers.prefetched.remove(&fh);
+        }
+    }
+
+    /// Amount of directory listings stored for all the processes
+    #[cfg(test)]
+    pub fn dir_listings(&self) -> usize {
+        self.handlers
+            .values()
+            .map(|handlers| handlers.dir_entries.len())
+            .sum()
+    }
+
     /// Close a file handle.
     pub fn close(&mut self, pid: Pid, fh: u64) {
         if let Some(handlers) = self.handlers.get_mut(&pid) {
@@ -63,6 +130,10 @@ impl FileHandlersDb {
 #[derive(Debug, Default)]
 struct ProcessFileHandlers {
     handles: HashMap<Fh, FileHandle>,
+    /// Directory listings taken by `opendir` or `readdir` for open directories
+    dir_entries: HashMap<Fh, Vec<DirEntry>>,
+    /// Directory listings prefetched by `opendir` which haven't been read yet
+    prefetched: HashSet<Fh>,
     /// Next file handle number
     next: u64,
 }
@@ -78,6 +149,17 @@ pub struct FileHandle {
     pub write: bool,
 }
 
+/// An entry of a directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Inode of the entry
+    pub inode: Inode,
+    /// Type of the entry
+    pub kind: FileType,
+    /// Name of the entry
+    pub name: OsString,
+}
+
 impl ProcessFileHandlers {
     /// Open a new [`FileHandle`] into the database.
     ///
@@ -101,6 +183,8 @@ impl ProcessFileHandlers {
     /// The file handle number will be reused next.
     fn close(&mut self, fh: u64) {
         self.handles.remove(&fh);
+        self.dir_entries.remove(&fh);
+        self.prefetched.remove(&fh);
         self.next = fh;
     }
 }
@@ -218,4 +302,54 @@ mod test {
         let fh5 = db.open(5, true, false);
         assert_eq!(fh5, 3);
     }
+
+    #[test]
+    fn test_should_drop_dir_entries_on_close() {
+        let mut db = FileHandlersDb::default();
+
+        let fh = db.open(1, 1, true, false);
+        let entries = vec![DirEntry {
+            inode: 2,
+            kind: FileType::RegularFile,
+            name: OsString::from("foo"),
+        }];
+        db.set_dir_entries(1, fh, entries.clone());
+        assert_eq!(db.dir_entries(1, fh), Some(entries.as_slice()));
+        assert_eq!(db.dir_entries(2, fh), None);
+
+        // keep the pid alive, so the process handlers are not dropped
+        db.open(1, 3, true, false);
+        db.close(1, fh);
+        assert_eq!(db.dir_entries(1, fh), None);
+    }
+
+    #[test]
+    fn test_should_serve_prefetched_dir_entries_once() {
+        let mut db = FileHandlersDb::default();
+
+        let fh = db.open(1, 1, true, false);
+        db.set_prefetched_dir_entries(1, fh, vec![]);
+        assert!(db.take_prefetched(1, fh));
+        assert!(!db.take_prefetched(1, fh));
+        assert_eq!(db.dir_entries(1, fh), Some([].as_slice()));
+
+        db.clear_dir_entries(1, fh);
+        assert_eq!(db.dir_entries(1, fh), None);
+        assert_eq!(db.dir_listings(), 0);
+    }
+
+    #[test]
+    fn test_should_tell_whether_inode_is_open() {
+        let mut db = FileHandlersDb::default();
+
+        let fh = db.open(1, 10, true, false);
+        db.open(2, 10, true, true);
+        assert!(db.is_open(10));
+        assert!(!db.is_open(11));
+
+        db.close(1, fh);
+        assert!(db.is_open(10));
+        db.close(2, 0);
+        assert!(!db.is_open(10));
+    }
 }
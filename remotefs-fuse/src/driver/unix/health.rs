@@ -0,0 +1,140 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use libc::c_int;
+
+use super::audit::format_timestamp;
+
+/// Liveness of the mount, served at the `/health` path for [`crate::MountOption::HealthAddr`].
+///
+/// The mount is healthy while the session is alive and the last operation handled didn't fail on the remote.
+#[derive(Debug, Default)]
+pub struct Health {
+    /// Whether the driver is connected to the remote and the session is running
+    alive: AtomicBool,
+    state: Mutex<HealthState>,
+}
+
+/// Results of the operations handled
+#[derive(Debug, Default)]
+struct HealthState {
+    /// Error the operation being handled failed with, recorded once its timer is dropped
+    current: Option<c_int>,
+    /// Time and result of the last operation
+    last_op: Option<(SystemTime, Result<(), c_int>)>,
+    /// Time and errno of the last operation failed on the remote
+    last_error: Option<(SystemTime, c_int)>,
+}
+
+impl Health {
+    /// Set whether the session is alive
+    pub fn set_alive(&self, alive: bool) {
+        self.alive.store(alive, Ordering::Relaxed);
+    }
+
+    /// Set the error the operation being handled failed with
+    pub fn set_error(&self, errno: c_int) {
+        if let Ok(mut state) = self.state.lock() {
+            state.current = Some(errno);
+        }
+    }
+
+    /// Record the result of the operation being handled.
+    ///
+    /// Only `EIO` is a failure of the remote; other errors, such as `ENOENT`, are replies of a working remote.
+    pub fn record(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let now = SystemTime::now();
+        let result = match state.current.take() {
+            Some(libc::EIO) => {
+                state.last_error = Some((now, libc::EIO));
+                Err(libc::EIO)
+            }
+            _ => Ok(()),
+        };
+        state.last_op = Some((now, result));
+    }
+
+    /// Whether the mount is healthy, along with the JSON summary of its state
+    pub fn status(&self) -> (bool, String) {
+        let alive = self.alive.load(Ordering::Relaxed);
+        let (last_op, last_error) = self
+            .state
+            .lock()
+            .map(|state| (state.last_op, state.last_error))
+            .unwrap_or_default();
+        let healthy = alive && !matches!(last_op, Some((_, Err(_))));
+
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"status\":\"{}\",\"alive\":{alive}",
+            if healthy { "ok" } else { "unavailable" }
+        );
+        match last_op {
+            Some((time, result)) => {
+                let _ = write!(
+                    out,
+                    ",\"last_op\":{{\"time\":\"{}\",\"result\":\"{}\"}}",
+                    format_timestamp(time),
+                    if result.is_ok() { "ok" } else { "error" }
+                );
+            }
+            None => out.push_str(",\"last_op\":null"),
+        }
+        match last_error {
+            Some((time, errno)) => {
+                let _ = write!(
+                    out,
+                    ",\"last_error\":{{\"time\":\"{}\",\"errno\":{errno}}}}}",
+                    format_timestamp(time)
+                );
+            }
+            None => out.push_str(",\"last_error\":null}"),
+        }
+
+        (healthy, out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_report_health() {
+        let health = Health::default();
+        let (healthy, body) = health.status();
+        assert!(!healthy);
+        assert_eq!(
+            body,
+            r#"{"status":"unavailable","alive":false,"last_op":null,"last_error":null}"#
+        );
+
+        health.set_alive(true);
+        health.set_error(libc::ENOENT);
+        health.record();
+        let (healthy, body) = health.status();
+        assert!(healthy);
+        assert!(body.contains(r#""result":"ok"}"#));
+
+        health.set_error(libc::EIO);
+        health.record();
+        let (healthy, body) = health.status();
+        assert!(!healthy);
+        assert!(body.starts_with(r#"{"status":"unavailable","alive":true"#));
+        assert!(body.contains(r#""errno":5}"#));
+
+        health.record();
+        let (healthy, body) = health.status();
+        assert!(healthy);
+        assert!(body.contains(r#""last_error":{"#));
+    }
+}
@@ -0,0 +1,96 @@
+/// Maps the uids and gids of the remote to local ones, as set with [`crate::MountOption::IdMap`].
+#[derive(Debug, Clone, Default)]
+pub struct IdMap {
+    /// Pairs of remote and local uids
+    uids: Vec<(u32, u32)>,
+    /// Pairs of remote and local gids
+    gids: Vec<(u32, u32)>,
+    /// Local uid of the remote uids not in the map
+    default_uid: Option<u32>,
+    /// Local gid of the remote gids not in the map
+    default_gid: Option<u32>,
+}
+
+impl IdMap {
+    /// Create a new [`IdMap`] from the pairs of remote and local ids
+    pub fn new(
+        uids: Vec<(u32, u32)>,
+        gids: Vec<(u32, u32)>,
+        default_uid: Option<u32>,
+        default_gid: Option<u32>,
+    ) -> Self {
+        Self {
+            uids,
+            gids,
+            default_uid,
+            default_gid,
+        }
+    }
+
+    /// Map the remote `uid` to the local one
+    pub fn local_uid(&self, uid: u32) -> u32 {
+        to_local(&self.uids, self.default_uid, uid)
+    }
+
+    /// Map the remote `gid` to the local one
+    pub fn local_gid(&self, gid: u32) -> u32 {
+        to_local(&self.gids, self.default_gid, gid)
+    }
+
+    /// Map the local `uid` to the remote one; the unmapped ids are passed through
+    pub fn remote_uid(&self, uid: u32) -> u32 {
+        to_remote(&self.uids, uid)
+    }
+
+    /// Map the local `gid` to the remote one; the unmapped ids are passed through
+    pub fn remote_gid(&self, gid: u32) -> u32 {
+        to_remote(&self.gids, gid)
+    }
+}
+
+/// Map the remote `id` with `map`, falling back to `default` or to the id itself if unmapped
+fn to_local(map: &[(u32, u32)], default: Option<u32>, id: u32) -> u32 {
+    map.iter()
+        .find(|(remote, _)| *remote == id)
+        .map(|(_, local)| *local)
+        .or(default)
+        .unwrap_or(id)
+}
+
+/// Map the local `id` back with `map`, passing it through if unmapped
+fn to_remote(map: &[(u32, u32)], id: u32) -> u32 {
+    map.iter()
+        .find(|(_, local)| *local == id)
+        .map(|(remote, _)| *remote)
+        .unwrap_or(id)
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_map_ids_both_ways() {
+        let map = IdMap::new(
+            vec![(1002, 1000), (0, 2000)],
+            vec![(100, 1000)],
+            None,
+            Some(65534),
+        );
+
+        assert_eq!(map.local_uid(1002), 1000);
+        assert_eq!(map.local_uid(0), 2000);
+        assert_eq!(map.local_uid(42), 42);
+        assert_eq!(map.local_gid(100), 1000);
+        assert_eq!(map.local_gid(42), 65534);
+
+        assert_eq!(map.remote_uid(1000), 1002);
+        assert_eq!(map.remote_uid(2000), 0);
+        assert_eq!(map.remote_uid(42), 42);
+        assert_eq!(map.remote_gid(1000), 100);
+        assert_eq!(map.remote_gid(65534), 65534);
+    }
+}
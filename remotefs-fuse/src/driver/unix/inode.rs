@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
 pub type Inode = u64;
@@ -9,10 +9,25 @@ pub const ROOT_INODE: Inode = 1;
 
 /// A database to map inodes to files
 ///
-/// The database is saved to a file when the instance is dropped
+/// The database is saved to a file when the instance is dropped.
+/// If a capacity is set, the least recently resolved inodes are evicted once it is exceeded.
 #[derive(Debug, Clone)]
 pub struct InodeDb {
     database: Database,
+    /// Inode of each path put into the database
+    inodes: HashMap<PathBuf, Inode>,
+    /// Other paths which were put with the same inode as the current one, such as on hash collisions
+    aliases: HashMap<Inode, Vec<PathBuf>>,
+    /// Generation of the inodes which have been reassigned to another path, telling the kernel their new files apart
+    generations: HashMap<Inode, u64>,
+    /// Maximum amount of inodes kept in the database
+    capacity: Option<usize>,
+    /// Last usage of each inode
+    last_used: HashMap<Inode, u64>,
+    /// Inodes ordered by last usage
+    lru: BTreeMap<u64, Inode>,
+    /// Monotonic usage counter
+    tick: u64,
 }
 
 impl InodeDb {
@@ -22,6 +37,13 @@ impl InodeDb {
     pub fn load() -> Self {
         let mut db = Self {
             database: Database::new(),
+            inodes: HashMap::new(),
+            aliases: HashMap::new(),
+            generations: HashMap::new(),
+            capacity: None,
+            last_used: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
         };
 
         db.put(ROOT_INODE, PathBuf::from("/"));
@@ -29,15 +51,128 @@ impl InodeDb {
         db
     }
 
+    /// Keep up to `capacity` inodes in the database, evicted by [`InodeDb::evict`]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
     /// Check if the database contains an inode
     pub fn has(&self, inode: Inode) -> bool {
         self.database.contains_key(&inode)
     }
 
-    /// Put a new inode into the database
+    /// Put a new inode into the database.
+    ///
+    /// If the inode was assigned to another path, its generation is incremented; the previous path is kept as an
+    /// alias of the inode, returned by [`InodeDb::paths_for`].
+    /// If `path` had another inode, that inode is forgotten.
     pub fn put(&mut self, inode: Inode, path: PathBuf) {
         debug!("inode {inode} -> {}", path.display());
-        self.database.insert(inode, path);
+        match self.inodes.get(&path) {
+            Some(previous) if *previous != inode => {
+                warn!("{} moved from inode {previous} to {inode}", path.display());
+                self.forget(*previous);
+            }
+            _ => {}
+        }
+        if self
+            .database
+            .get(&inode)
+            .is_some_and(|previous| previous != &path)
+        {
+            let generation = self.generations.entry(inode).or_default();
+            *generation += 1;
+            debug!("inode {inode} reassigned; generation {generation}");
+        }
+
+        let aliases = self.aliases.entry(inode).or_default();
+        aliases.retain(|alias| alias != &path);
+        if let Some(previous) = self.database.insert(inode, path.clone()) {
+            if previous != path {
+                aliases.push(previous);
+            }
+        }
+        if aliases.is_empty() {
+            self.aliases.remove(&inode);
+        }
+        self.inodes.insert(path, inode);
+        self.touch(inode);
+    }
+
+    /// Move `inode` to `path`, which is put with `new_inode`.
+    ///
+    /// The kernel keeps referring to the moved inode, such as for the files opened before the move, so it resolves
+    /// to `path` with the same generation until forgotten; looking up `path` gives `new_inode`.
+    pub fn moved(&mut self, inode: Inode, new_inode: Inode, path: PathBuf) {
+        if let Some(previous) = self.database.get(&inode) {
+            if self.inodes.get(previous) == Some(&inode) {
+                self.inodes.remove(previous);
+            }
+        }
+        let moved = inode != new_inode && self.has(inode);
+        self.put(new_inode, path.clone());
+        if moved {
+            debug!("inode {inode} moved to {}", path.display());
+            self.database.insert(inode, path);
+            self.touch(inode);
+        }
+    }
+
+    /// Get the inode of a path
+    pub fn inode_of(&self, path: &Path) -> Option<Inode> {
+        self.inodes.get(path).copied()
+    }
+
+    /// Get all the paths put with an inode, starting from the current one.
+    ///
+    /// More than one path means the paths collided on the inode.
+    pub fn paths_for(&self, inode: Inode) -> Vec<&Path> {
+        self.database
+            .get(&inode)
+            .into_iter()
+            .chain(self.aliases.get(&inode).into_iter().flatten())
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    /// Mark an inode as recently resolved
+    pub fn touch(&mut self, inode: Inode) {
+        if !self.database.contains_key(&inode) {
+            return;
+        }
+        self.tick += 1;
+        if let Some(last_used) = self.last_used.insert(inode, self.tick) {
+            self.lru.remove(&last_used);
+        }
+        self.lru.insert(self.tick, inode);
+    }
+
+    /// Evict the least recently resolved inodes until the capacity is no longer exceeded.
+    ///
+    /// The root inode and the inodes for which `is_open` holds are never evicted, so the database may stay over
+    /// capacity. Unlike [`InodeDb::forget`], eviction keeps the generation of the inodes, since the kernel may still
+    /// refer to them: resolved again from the same path, they are the same objects.
+    pub fn evict(&mut self, is_open: impl Fn(Inode) -> bool) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        let excess = self.database.len().saturating_sub(capacity);
+        if excess == 0 {
+            return;
+        }
+
+        let evicted: Vec<Inode> = self
+            .lru
+            .values()
+            .copied()
+            .filter(|inode| *inode != ROOT_INODE && !is_open(*inode))
+            .take(excess)
+            .collect();
+        for inode in evicted {
+            debug!("evicting inode {inode}");
+            self.remove(inode);
+        }
     }
 
     /// Forget an inode
@@ -47,7 +182,28 @@ impl InodeDb {
             return;
         }
 
-        self.database.remove(&inode);
+        // the kernel no longer refers to the inode, so the next file with it starts over
+        self.generations.remove(&inode);
+        self.remove(inode);
+    }
+
+    /// Remove an inode and its paths from the database, keeping its generation
+    fn remove(&mut self, inode: Inode) {
+        // the paths may resolve to other inodes, such as the destination of a move
+        let paths = self.database.remove(&inode).into_iter();
+        for path in paths.chain(self.aliases.remove(&inode).unwrap_or_default()) {
+            if self.inodes.get(&path) == Some(&inode) {
+                self.inodes.remove(&path);
+            }
+        }
+        if let Some(last_used) = self.last_used.remove(&inode) {
+            self.lru.remove(&last_used);
+        }
+    }
+
+    /// Get the generation of an inode, passed to the kernel along with the inode number
+    pub fn generation(&self, inode: Inode) -> u64 {
+        self.generations.get(&inode).copied().unwrap_or_default()
     }
 
     /// Get a path from an inode
@@ -79,6 +235,125 @@ mod test {
         assert_eq!(db.has(3), false);
     }
 
+    #[test]
+    fn test_should_bump_generation_on_reassign() {
+        let mut db = InodeDb::load();
+
+        db.put(3, PathBuf::from("/test"));
+        assert_eq!(db.generation(3), 0);
+        db.put(3, PathBuf::from("/test"));
+        assert_eq!(db.generation(3), 0);
+
+        db.put(3, PathBuf::from("/other"));
+        assert_eq!(db.generation(3), 1);
+
+        db.put(3, PathBuf::from("/another"));
+        assert_eq!(db.generation(3), 2);
+
+        // forgotten inodes don't keep their generation
+        db.forget(3);
+        assert_eq!(db.generations.contains_key(&3), false);
+        db.put(3, PathBuf::from("/test"));
+        assert_eq!(db.generation(3), 0);
+    }
+
+    #[test]
+    fn test_should_evict_least_recently_resolved() {
+        let mut db = InodeDb::load().with_capacity(3);
+
+        db.put(2, PathBuf::from("/open"));
+        db.put(3, PathBuf::from("/a"));
+        db.put(4, PathBuf::from("/b"));
+        db.touch(3);
+        db.evict(|inode| inode == 2);
+
+        // root and the open inode are never evicted; /b was resolved before /a
+        assert_eq!(db.has(ROOT_INODE), true);
+        assert_eq!(db.has(2), true);
+        assert_eq!(db.has(3), true);
+        assert_eq!(db.has(4), false);
+
+        // over capacity with the inodes which can't be evicted
+        db.put(5, PathBuf::from("/c"));
+        db.evict(|_| true);
+        assert_eq!(db.has(5), true);
+        assert_eq!(db.lru.len(), 4);
+
+        // evicted inodes resolved again from the same path are the same objects for the kernel
+        db.put(4, PathBuf::from("/b"));
+        assert_eq!(db.generation(4), 0);
+        assert_eq!(db.inode_of(Path::new("/b")), Some(4));
+    }
+
+    #[test]
+    fn test_should_keep_generation_on_evict() {
+        let mut db = InodeDb::load().with_capacity(1);
+
+        db.put(3, PathBuf::from("/test"));
+        db.evict(|_| false);
+        assert_eq!(db.has(3), false);
+        assert_eq!(db.generations.contains_key(&3), false);
+
+        db.put(3, PathBuf::from("/test"));
+        assert_eq!(db.generation(3), 0);
+        assert_eq!(db.get(3), Some(Path::new("/test")));
+    }
+
+    #[test]
+    fn test_should_keep_moved_inode() {
+        let mut db = InodeDb::load();
+
+        db.put(3, PathBuf::from("/a"));
+        db.moved(3, 4, PathBuf::from("/b"));
+        assert_eq!(db.get(3), Some(Path::new("/b")));
+        assert_eq!(db.generation(3), 0);
+        assert_eq!(db.inode_of(Path::new("/a")), None);
+        assert_eq!(db.inode_of(Path::new("/b")), Some(4));
+
+        // forgetting the moved inode keeps the destination
+        db.forget(3);
+        assert_eq!(db.has(3), false);
+        assert_eq!(db.inode_of(Path::new("/b")), Some(4));
+        assert_eq!(db.get(4), Some(Path::new("/b")));
+    }
+
+    #[test]
+    fn test_should_keep_reverse_index_consistent() {
+        let mut db = InodeDb::load();
+        assert_eq!(db.inode_of(Path::new("/")), Some(ROOT_INODE));
+
+        db.put(3, PathBuf::from("/test"));
+        assert_eq!(db.inode_of(Path::new("/test")), Some(3));
+        assert_eq!(db.paths_for(3), vec![Path::new("/test")]);
+
+        // collision
+        db.put(3, PathBuf::from("/other"));
+        assert_eq!(db.get(3), Some(Path::new("/other")));
+        assert_eq!(db.inode_of(Path::new("/test")), Some(3));
+        assert_eq!(
+            db.paths_for(3),
+            vec![Path::new("/other"), Path::new("/test")]
+        );
+        db.put(3, PathBuf::from("/test"));
+        assert_eq!(
+            db.paths_for(3),
+            vec![Path::new("/test"), Path::new("/other")]
+        );
+
+        // a path moved to another inode
+        db.put(4, PathBuf::from("/moved"));
+        db.put(5, PathBuf::from("/moved"));
+        assert_eq!(db.has(4), false);
+        assert_eq!(db.inode_of(Path::new("/moved")), Some(5));
+
+        db.forget(3);
+        assert_eq!(db.inode_of(Path::new("/test")), None);
+        assert_eq!(db.inode_of(Path::new("/other")), None);
+        assert!(db.paths_for(3).is_empty());
+        assert_eq!(db.aliases.len(), 0);
+        assert_eq!(db.inodes.len(), 2);
+    }
+
     #[test]
     fn test_should_not_forget_root() {
         let mut db = InodeDb::load();
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead as _, BufReader, Write as _};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::health::Health;
+use super::trace::OpTrace;
+
+/// Upper bounds (in seconds) of the latency histogram buckets
+const BUCKETS: [f64; 11] = [
+    0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+];
+
+/// Metrics collected for the FUSE operations handled by the driver.
+///
+/// For each operation a counter and a latency histogram are kept.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    ops: Mutex<BTreeMap<&'static str, OpMetrics>>,
+}
+
+/// Metrics for a single operation
+#[derive(Debug, Default, Clone)]
+struct OpMetrics {
+    /// Number of times the operation has been called
+    count: u64,
+    /// Sum of the latencies in seconds
+    sum: f64,
+    /// Number of observations for each bucket in [`BUCKETS`] (not cumulative)
+    buckets: [u64; BUCKETS.len()],
+}
+
+impl Metrics {
+    /// Start timing `op`. The latency is recorded when the returned [`OpTimer`] is dropped.
+    pub fn timer(self: &Arc<Self>, op: &'static str) -> OpTimer {
+        OpTimer {
+            metrics: Some(self.clone()),
+            trace: None,
+            health: None,
+            op,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Record an observation for `op`
+    pub fn record(&self, op: &'static str, elapsed: Duration) {
+        let elapsed = elapsed.as_secs_f64();
+        let mut ops = self.ops.lock().expect("metrics lock poisoned");
+        let metrics = ops.entry(op).or_default();
+        metrics.count += 1;
+        metrics.sum += elapsed;
+        if let Some(bucket) = BUCKETS.iter().position(|bound| elapsed <= *bound) {
+            metrics.buckets[bucket] += 1;
+        }
+    }
+
+    /// Render the metrics using the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let ops = self.ops.lock().expect("metrics lock poisoned").clone();
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP remotefs_fuse_operations_total Number of FUSE operations handled."
+        );
+        let _ = writeln!(out, "# TYPE remotefs_fuse_operations_total counter");
+        for (op, metrics) in ops.iter() {
+            let _ = writeln!(
+                out,
+                "remotefs_fuse_operations_total{{op=\"{op}\"}} {}",
+                metrics.count
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP remotefs_fuse_operation_duration_seconds Latency of FUSE operations."
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE remotefs_fuse_operation_duration_seconds histogram"
+        );
+        for (op, metrics) in ops.iter() {
+            let mut cumulative = 0;
+            for (bound, observations) in BUCKETS.iter().zip(metrics.buckets.iter()) {
+                cumulative += observations;
+                let _ = writeln!(
+                    out,
+                    "remotefs_fuse_operation_duration_seconds_bucket{{op=\"{op}\",le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "remotefs_fuse_operation_duration_seconds_bucket{{op=\"{op}\",le=\"+Inf\"}} {}",
+                metrics.count
+            );
+            let _ = writeln!(
+                out,
+                "remotefs_fuse_operation_duration_seconds_sum{{op=\"{op}\"}} {}",
+                metrics.sum
+            );
+            let _ = writeln!(
+                out,
+                "remotefs_fuse_operation_duration_seconds_count{{op=\"{op}\"}} {}",
+                metrics.count
+            );
+        }
+
+        out
+    }
+}
+
+/// Guard which logs the latency of an operation at debug level, and records it into [`Metrics`], [`OpTrace`] and
+/// [`Health`] if set, when dropped.
+pub struct OpTimer {
+    metrics: Option<Arc<Metrics>>,
+    trace: Option<Arc<OpTrace>>,
+    health: Option<Arc<Health>>,
+    op: &'static str,
+    started_at: Instant,
+}
+
+impl OpTimer {
+    /// Start timing `op`, without recording it into [`Metrics`]
+    pub fn new(op: &'static str) -> Self {
+        Self {
+            metrics: None,
+            trace: None,
+            health: None,
+            op,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Record the operation into `trace` too
+    pub fn traced(mut self, trace: Option<Arc<OpTrace>>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Record the result of the operation into `health` too
+    pub fn checked(mut self, health: Option<Arc<Health>>) -> Self {
+        self.health = health;
+        self
+    }
+}
+
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started_at.elapsed();
+        debug!("{}() took {elapsed:.2?}", self.op);
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.record(self.op, elapsed);
+        }
+        if let Some(trace) = self.trace.as_ref() {
+            trace.record(self.op, elapsed);
+        }
+        if let Some(health) = self.health.as_ref() {
+            health.record();
+        }
+    }
+}
+
+/// The endpoints served over HTTP by [`serve`]; the paths of the unset ones reply `404 Not Found`.
+#[derive(Debug, Default, Clone)]
+pub struct Endpoints {
+    /// Served at `/metrics`
+    pub metrics: Option<Arc<Metrics>>,
+    /// Served at `/trace`
+    pub trace: Option<Arc<OpTrace>>,
+    /// Served at `/health`
+    pub health: Option<Arc<Health>>,
+}
+
+/// Serve `endpoints` over HTTP at `addr` on a background thread.
+///
+/// Returns the address the server is actually bound to.
+pub fn serve(endpoints: Endpoints, addr: SocketAddr) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+
+    std::thread::Builder::new()
+        .name("remotefs-fuse-metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = handle_connection(&endpoints, stream) {
+                            debug!("metrics connection error: {err}");
+                        }
+                    }
+                    Err(err) => error!("failed to accept metrics connection: {err}"),
+                }
+            }
+        })?;
+
+    Ok(local_addr)
+}
+
+/// Handle a single HTTP request on the metrics endpoint
+fn handle_connection(endpoints: &Endpoints, mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // consume headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) if endpoints.metrics.is_some() => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            endpoints
+                .metrics
+                .as_deref()
+                .map(Metrics::render)
+                .unwrap_or_default(),
+        ),
+        (Some("GET"), Some("/trace")) if endpoints.trace.is_some() => (
+            "200 OK",
+            "text/plain",
+            endpoints
+                .trace
+                .as_deref()
+                .map(OpTrace::dump)
+                .unwrap_or_default(),
+        ),
+        (Some("GET"), Some("/health")) if endpoints.health.is_some() => {
+            let (healthy, body) = endpoints
+                .health
+                .as_deref()
+                .map(Health::status)
+                .unwrap_or_default();
+            let status = if healthy {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            (status, "application/json", body)
+        }
+        (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Read as _;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_record_op_metrics() {
+        let metrics = Metrics::default();
+        metrics.record("read", Duration::from_millis(2));
+        metrics.record("read", Duration::from_secs(10));
+        metrics.record("lookup", Duration::from_micros(100));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("remotefs_fuse_operations_total{op=\"read\"} 2"));
+        assert!(rendered.contains("remotefs_fuse_operations_total{op=\"lookup\"} 1"));
+        assert!(rendered.contains(
+            "remotefs_fuse_operation_duration_seconds_bucket{op=\"read\",le=\"0.001\"} 0"
+        ));
+        assert!(rendered.contains(
+            "remotefs_fuse_operation_duration_seconds_bucket{op=\"read\",le=\"0.005\"} 1"
+        ));
+        assert!(rendered
+            .contains("remotefs_fuse_operation_duration_seconds_bucket{op=\"read\",le=\"5\"} 1"));
+        assert!(rendered.contains(
+            "remotefs_fuse_operation_duration_seconds_bucket{op=\"read\",le=\"+Inf\"} 2"
+        ));
+        assert!(rendered.contains("remotefs_fuse_operation_duration_seconds_count{op=\"read\"} 2"));
+    }
+
+    #[test]
+    fn test_should_record_on_timer_drop() {
+        let metrics = Arc::new(Metrics::default());
+        drop(metrics.timer("write"));
+
+        assert_eq!(metrics.ops.lock().unwrap().get("write").unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_should_serve_metrics() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.record("getattr", Duration::from_millis(1));
+        let addr = serve(
+            Endpoints {
+                metrics: Some(metrics),
+                ..Default::default()
+            },
+            "127.0.0.1:0".parse().unwrap(),
+        )
+        .expect("failed to serve");
+
+        let mut stream = TcpStream::connect(addr).expect("failed to connect");
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("remotefs_fuse_operations_total{op=\"getattr\"} 1"));
+    }
+
+    #[test]
+    fn test_should_serve_trace() {
+        let trace = Arc::new(OpTrace::new(8));
+        drop(OpTimer::new("statfs").traced(Some(trace.clone())));
+        let addr = serve(
+            Endpoints {
+                metrics: Some(Arc::new(Metrics::default())),
+                trace: Some(trace),
+                ..Default::default()
+            },
+            "127.0.0.1:0".parse().unwrap(),
+        )
+        .expect("failed to serve");
+
+        let mut stream = TcpStream::connect(addr).expect("failed to connect");
+        stream
+            .write_all(b"GET /trace HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(" statfs ok "));
+    }
+
+    #[test]
+    fn test_should_serve_health() {
+        let health = Arc::new(Health::default());
+        let addr = serve(
+            Endpoints {
+                health: Some(health.clone()),
+                ..Default::default()
+            },
+            "127.0.0.1:0".parse().unwrap(),
+        )
+        .expect("failed to serve");
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).expect("failed to connect");
+            write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        assert!(get("/health").starts_with("HTTP/1.1 503 Service Unavailable"));
+        health.set_alive(true);
+        let response = get("/health");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: application/json"));
+        assert!(response.ends_with(r#""last_error":null}"#));
+        assert!(get("/metrics").starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
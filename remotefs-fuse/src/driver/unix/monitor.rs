@@ -0,0 +1,401 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use remotefs::{RemoteFs, RemoteResult};
+
+use super::inode::Inode;
+use super::revalidate::{FileVersion, Revalidator};
+use super::watcher::{Change, DirWatcher, Snapshot};
+
+/// Cached data made stale by a change on the remote, detected by the [`Monitor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stale {
+    /// The open file has changed
+    File(Inode),
+    /// An entry of a watched directory has been created, deleted or modified
+    Entry(Change),
+}
+
+/// Checks the open files and the opened directories for changes made on the remote by other clients.
+///
+/// The checks run on a background thread with its own connection to the remote, so that they don't depend on the
+/// requests served by the mount. The kernel is notified of the changes from that thread, since the session thread
+/// can't send notifications while serving a request; the changes are queued for the driver, which drops its own
+/// cached data before serving the next operation.
+pub struct Monitor {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// State shared between the driver and the thread of the [`Monitor`]
+struct Shared {
+    /// Set only if the open files are checked
+    revalidator: Option<Mutex<Revalidator>>,
+    /// Set only if the opened directories are listed
+    dir_watcher: Option<Mutex<DirWatcher>>,
+    /// Patterns of the paths excluded from the mount, which are not reported
+    exclude: Vec<glob::Pattern>,
+    /// Changes detected on the remote, whose cached data hasn't been dropped by the driver yet
+    stale: Mutex<Vec<Stale>>,
+    /// Set once the monitor is dropped, to stop its thread
+    stopped: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl Monitor {
+    /// Create a new [`Monitor`] checking the open files with `revalidator` and the opened directories with
+    /// `dir_watcher`, skipping the entries matching the `exclude` patterns.
+    ///
+    /// The checks only run in background once the thread is started with [`Monitor::spawn`].
+    pub fn new(
+        revalidator: Option<Revalidator>,
+        dir_watcher: Option<DirWatcher>,
+        exclude: Vec<glob::Pattern>,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                revalidator: revalidator.map(Mutex::new),
+                dir_watcher: dir_watcher.map(Mutex::new),
+                exclude,
+                stale: Mutex::default(),
+                stopped: Mutex::new(false),
+                wakeup: Condvar::new(),
+            }),
+            thread: None,
+        }
+    }
+
+    /// Start checking the remote on a background thread, using the connection `remote`.
+    ///
+    /// The kernel is notified of the changes with the notifier, once the session has set it; `inode` gets the inode
+    /// of a path in the mount.
+    pub fn spawn<T>(
+        &mut self,
+        remote: T,
+        notifier: Arc<OnceLock<fuser::Notifier>>,
+        inode: fn(&Path) -> Inode,
+    ) -> std::io::Result<()>
+    where
+        T: RemoteFs + Send + 'static,
+    {
+        let shared = self.shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("remotefs-fuse-monitor".to_string())
+            .spawn(move || run(&shared, remote, &notifier, inode))?;
+        self.thread = Some(thread);
+
+        Ok(())
+    }
+
+    /// Start watching the open file `inode`, at `path` on the remote, if not watched yet
+    pub fn watch_file(&self, inode: Inode, path: PathBuf, version: FileVersion) {
+        if let Some(Ok(mut revalidator)) = self.shared.revalidator.as_ref().map(Mutex::lock) {
+            revalidator.watch(inode, path, version);
+        }
+    }
+
+    /// Stop watching the file `inode`
+    pub fn unwatch_file(&self, inode: Inode) {
+        if let Some(Ok(mut revalidator)) = self.shared.revalidator.as_ref().map(Mutex::lock) {
+            revalidator.unwatch(inode);
+        }
+    }
+
+    /// Start watching the directory `inode`, at `path` in the mount and at `remote` on the remote, if not watched yet
+    pub fn watch_dir(&self, inode: Inode, path: PathBuf, remote: PathBuf) {
+        if let Some(Ok(mut watcher)) = self.shared.dir_watcher.as_ref().map(Mutex::lock) {
+            watcher.watch(inode, path, remote);
+        }
+    }
+
+    /// Stop watching the directory `inode`
+    pub fn unwatch_dir(&self, inode: Inode) {
+        if let Some(Ok(mut watcher)) = self.shared.dir_watcher.as_ref().map(Mutex::lock) {
+            watcher.unwatch(inode);
+        }
+    }
+
+    /// Check the watched open files for changes on `remote`.
+    ///
+    /// The changed inodes are queued for [`Monitor::take_stale`] and returned.
+    #[cfg(test)]
+    pub fn check_files<T: RemoteFs>(&self, remote: &mut T) -> Vec<Inode> {
+        self.shared.check_files(remote)
+    }
+
+    /// List the watched directories on `remote` for entries created, deleted or modified.
+    ///
+    /// The changes are queued for [`Monitor::take_stale`] and returned.
+    #[cfg(test)]
+    pub fn check_dirs<T: RemoteFs>(&self, remote: &mut T) -> Vec<Change> {
+        self.shared.check_dirs(remote)
+    }
+
+    /// Take the changes detected on the remote since the last call, whose cached data must be dropped
+    pub fn take_stale(&self) -> Vec<Stale> {
+        self.shared
+            .stale
+            .lock()
+            .map(|mut stale| std::mem::take(&mut *stale))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        if let Ok(mut stopped) = self.shared.stopped.lock() {
+            *stopped = true;
+        }
+        self.shared.wakeup.notify_all();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("the monitor thread panicked");
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// Wait for `timeout`, returning whether the monitor is still running
+    fn wait(&self, timeout: Duration) -> bool {
+        let Ok(stopped) = self.stopped.lock() else {
+            return false;
+        };
+        match self
+            .wakeup
+            .wait_timeout_while(stopped, timeout, |stopped| !*stopped)
+        {
+            Ok((stopped, _)) => !*stopped,
+            Err(_) => false,
+        }
+    }
+
+    /// How often the remote is checked: the shortest of the intervals of the checks
+    fn interval(&self) -> Duration {
+        let files = self
+            .revalidator
+            .as_ref()
+            .and_then(|revalidator| revalidator.lock().ok())
+            .map(|revalidator| revalidator.interval());
+        let dirs = self
+            .dir_watcher
+            .as_ref()
+            .and_then(|watcher| watcher.lock().ok())
+            .map(|watcher| watcher.interval());
+
+        files
+            .into_iter()
+            .chain(dirs)
+            .min()
+            .unwrap_or(Duration::from_secs(1))
+    }
+
+    /// Whether the open files are due to be checked
+    fn files_due(&self) -> bool {
+        self.revalidator
+            .as_ref()
+            .and_then(|revalidator| revalidator.lock().ok())
+            .map(|mut revalidator| revalidator.due())
+            .unwrap_or_default()
+    }
+
+    /// Whether the watched directories are due to be listed
+    fn dirs_due(&self) -> bool {
+        self.dir_watcher
+            .as_ref()
+            .and_then(|watcher| watcher.lock().ok())
+            .map(|mut watcher| watcher.due())
+            .unwrap_or_default()
+    }
+
+    /// Queue `stale` for the driver
+    fn push_stale(&self, stale: impl IntoIterator<Item = Stale>) {
+        if let Ok(mut queue) = self.stale.lock() {
+            queue.extend(stale);
+        }
+    }
+
+    fn check_files<T: RemoteFs>(&self, remote: &mut T) -> Vec<Inode> {
+        let Some(revalidator) = self.revalidator.as_ref() else {
+            return Vec::new();
+        };
+        let watched = revalidator
+            .lock()
+            .map(|revalidator| revalidator.watched())
+            .unwrap_or_default();
+
+        let mut changed = Vec::new();
+        for (inode, path) in watched {
+            // the lock is not held while waiting for the remote, to leave the session thread free to open files
+            let version = match remote.stat(&path) {
+                Ok(file) => FileVersion::from(file.metadata()),
+                Err(err) => {
+                    debug!("Failed to revalidate {}: {err}", path.display());
+                    continue;
+                }
+            };
+            let Ok(mut revalidator) = revalidator.lock() else {
+                break;
+            };
+            if revalidator.update(inode, version) {
+                info!(
+                    "{} has changed on the remote; invalidating cached data",
+                    path.display()
+                );
+                changed.push(inode);
+            }
+        }
+        self.push_stale(changed.iter().copied().map(Stale::File));
+
+        changed
+    }
+
+    fn check_dirs<T: RemoteFs>(&self, remote: &mut T) -> Vec<Change> {
+        let Some(watcher) = self.dir_watcher.as_ref() else {
+            return Vec::new();
+        };
+        let Ok((watched, depth)) = watcher
+            .lock()
+            .map(|watcher| (watcher.watched(), watcher.depth()))
+        else {
+            return Vec::new();
+        };
+
+        let mut changes = Vec::new();
+        for (inode, path, remote_path) in watched {
+            let mut snapshot = Snapshot::new();
+            if let Err(err) = self.snapshot_dir(remote, &path, &remote_path, depth, &mut snapshot) {
+                debug!("Failed to list watched directory {}: {err}", path.display());
+                continue;
+            }
+            let Ok(mut watcher) = watcher.lock() else {
+                break;
+            };
+            for change in watcher.update(inode, snapshot) {
+                info!("{change:?} on the remote; invalidating cached data");
+                changes.push(change);
+            }
+        }
+        self.push_stale(changes.iter().cloned().map(Stale::Entry));
+
+        changes
+    }
+
+    /// Collect the entries of the directory at `path` in the mount, and at `remote_path` on `remote`, into
+    /// `snapshot`, descending `depth` levels of subdirectories
+    fn snapshot_dir<T: RemoteFs>(
+        &self,
+        remote: &mut T,
+        path: &Path,
+        remote_path: &Path,
+        depth: u32,
+        snapshot: &mut Snapshot,
+    ) -> RemoteResult<()> {
+        for entry in remote.list_dir(remote_path)? {
+            let path = path.join(entry.name());
+            if super::matches_patterns(&self.exclude, &path) {
+                continue;
+            }
+            snapshot.insert(path.clone(), FileVersion::from(entry.metadata()));
+            if depth > 0 && entry.is_dir() {
+                self.snapshot_dir(remote, &path, entry.path(), depth - 1, snapshot)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the remote every interval with `remote`, until the monitor is dropped
+fn run<T: RemoteFs>(
+    shared: &Shared,
+    mut remote: T,
+    notifier: &OnceLock<fuser::Notifier>,
+    inode: fn(&Path) -> Inode,
+) {
+    let interval = shared.interval();
+    debug!("monitor started");
+    while shared.wait(interval) {
+        let files_due = shared.files_due();
+        let dirs_due = shared.dirs_due();
+        if !files_due && !dirs_due {
+            continue;
+        }
+        if !remote.is_connected() {
+            if let Err(err) = remote.connect() {
+                debug!("Failed to connect the monitor to the remote: {err}");
+                continue;
+            }
+        }
+
+        if files_due {
+            for inode in shared.check_files(&mut remote) {
+                if let Some(notifier) = notifier.get() {
+                    if let Err(err) = notifier.inval_inode(inode, 0, 0) {
+                        error!("Failed to invalidate kernel cache for {inode}: {err}");
+                    }
+                }
+            }
+        }
+        if dirs_due {
+            for change in shared.check_dirs(&mut remote) {
+                if let Some(notifier) = notifier.get() {
+                    notify_change(notifier, &change, inode);
+                }
+            }
+        }
+    }
+    if let Err(err) = remote.disconnect() {
+        debug!("Failed to disconnect the monitor from the remote: {err}");
+    }
+    debug!("monitor stopped");
+}
+
+/// Notify the kernel of `change`, invalidating the attributes and the content of a modified entry, or the lookup of a
+/// created or deleted one
+fn notify_change(notifier: &fuser::Notifier, change: &Change, inode: fn(&Path) -> Inode) {
+    let (Change::Created(path) | Change::Deleted(path) | Change::Modified(path)) = change;
+    let result = match (change, path.parent(), path.file_name()) {
+        (Change::Modified(_), _, _) => notifier.inval_inode(inode(path), 0, 0),
+        (_, Some(parent), Some(name)) => notifier.inval_entry(inode(parent), name),
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        error!(
+            "Failed to invalidate kernel cache for {}: {err}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Instant;
+
+    use remotefs_memory::{node, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_stop_thread_on_drop() {
+        let remote = MemoryFs::new(Tree::new(node!(
+            "/".into(),
+            remotefs_memory::Inode::dir(0, 0, remotefs::fs::UnixPex::from(0o755))
+        )));
+        let mut monitor = Monitor::new(
+            Some(Revalidator::new(Duration::from_secs(3600))),
+            None,
+            Vec::new(),
+        );
+        monitor
+            .spawn(remote, Default::default(), |_| 1)
+            .expect("failed to spawn monitor");
+
+        let started = Instant::now();
+        drop(monitor);
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::inode::Inode;
+use super::BLOCK_SIZE;
+
+/// Size of a cached page. It is always a multiple of the block size.
+pub const PAGE_SIZE: usize = BLOCK_SIZE * 128;
+
+/// Key of a cached page: the inode and the index of the page in the file
+type PageKey = (Inode, u64);
+
+/// A bounded LRU cache of file pages used to serve reads.
+///
+/// Each page contains [`PAGE_SIZE`] bytes of the file, except for the last page of the file
+/// which may be shorter.
+#[derive(Debug)]
+pub struct PageCache {
+    /// Maximum amount of pages stored in the cache
+    capacity: usize,
+    pages: HashMap<PageKey, Page>,
+    /// Pages ordered by last usage
+    lru: BTreeMap<u64, PageKey>,
+    /// Monotonic usage counter
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Page {
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+impl PageCache {
+    /// Create a new [`PageCache`] which can hold up to `size` bytes.
+    ///
+    /// The cache will always be able to hold at least one page.
+    pub fn new(size: usize) -> Self {
+        Self {
+            capacity: (size / PAGE_SIZE).max(1),
+            pages: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Check whether the page at `index` for `inode` is cached
+    pub fn contains(&self, inode: Inode, index: u64) -> bool {
+        self.pages.contains_key(&(inode, index))
+    }
+
+    /// Get the page at `index` for `inode`, marking it as recently used
+    pub fn get(&mut self, inode: Inode, index: u64) -> Option<&[u8]> {
+        self.tick += 1;
+        let tick = self.tick;
+        let page = self.pages.get_mut(&(inode, index))?;
+        self.lru.remove(&page.last_used);
+        self.lru.insert(tick, (inode, index));
+        page.last_used = tick;
+
+        Some(page.data.as_slice())
+    }
+
+    /// Insert the page at `index` for `inode`, evicting the least recently used page if the cache is full
+    pub fn insert(&mut self, inode: Inode, index: u64, data: Vec<u8>) {
+        self.tick += 1;
+        if let Some(page) = self.pages.remove(&(inode, index)) {
+            self.lru.remove(&page.last_used);
+        }
+        while self.pages.len() >= self.capacity {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            debug!("evicting page {} of inode {}", key.1, key.0);
+            self.pages.remove(&key);
+        }
+
+        self.lru.insert(self.tick, (inode, index));
+        self.pages.insert(
+            (inode, index),
+            Page {
+                data,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    /// Remove all the pages of `inode` from the cache
+    pub fn invalidate(&mut self, inode: Inode) {
+        let lru = &mut self.lru;
+        self.pages.retain(|(page_inode, _), page| {
+            if *page_inode == inode {
+                lru.remove(&page.last_used);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Amount of pages currently cached
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_get_cached_pages() {
+        let mut cache = PageCache::new(PAGE_SIZE * 4);
+        cache.insert(1, 0, vec![1; PAGE_SIZE]);
+        cache.insert(1, 1, vec![2; 10]);
+
+        assert!(cache.contains(1, 0));
+        assert_eq!(cache.get(1, 1), Some([2; 10].as_slice()));
+        assert_eq!(cache.get(2, 0), None);
+    }
+
+    #[test]
+    fn test_should_evict_least_recently_used() {
+        let mut cache = PageCache::new(PAGE_SIZE * 2);
+        cache.insert(1, 0, vec![0; 1]);
+        cache.insert(1, 1, vec![1; 1]);
+        // use page 0, so page 1 becomes the least recently used
+        assert!(cache.get(1, 0).is_some());
+        cache.insert(1, 2, vec![2; 1]);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(1, 0));
+        assert!(!cache.contains(1, 1));
+        assert!(cache.contains(1, 2));
+    }
+
+    #[test]
+    fn test_should_invalidate_inode() {
+        let mut cache = PageCache::new(PAGE_SIZE * 8);
+        cache.insert(1, 0, vec![0; 1]);
+        cache.insert(1, 1, vec![1; 1]);
+        cache.insert(2, 0, vec![2; 1]);
+
+        cache.invalidate(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains(2, 0));
+        assert_eq!(cache.lru.len(), 1);
+    }
+
+    #[test]
+    fn test_should_hold_at_least_one_page() {
+        let mut cache = PageCache::new(0);
+        cache.insert(1, 0, vec![0; 1]);
+        assert_eq!(cache.len(), 1);
+    }
+}
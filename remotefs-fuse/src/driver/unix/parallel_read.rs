@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use remotefs::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::workers::{Job, WorkerPool};
+use super::{read_from_stream, read_tempfile};
+
+/// Smallest range read by a stream of a parallel read; smaller windows are split in fewer ranges
+pub const MIN_RANGE_SIZE: usize = 64 * 1024;
+
+/// Called with the data of a parallel read once all its ranges are read
+pub type Done = Box<dyn FnOnce(RemoteResult<Vec<u8>>) + Send>;
+
+/// A read of a window of a file, split in ranges read concurrently, for [`crate::MountOption::ParallelReadStreams`].
+///
+/// Each range is read from its own stream, seeking to its offset; this pays off when the throughput of a single
+/// stream is limited by the latency of the remote rather than by its bandwidth. Remotes whose streams can't seek
+/// would download the file up to each range, so once found the reads aren't split anymore.
+pub struct ParallelRead {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub len: usize,
+    pub chunk_size: usize,
+    /// How many times an interrupted read is retried
+    pub retries: usize,
+    pub temp_dir: Option<PathBuf>,
+    /// Set once the remote streams are found not to support seeking, so that the next reads aren't split anymore
+    pub unsupported: Arc<AtomicBool>,
+}
+
+/// The ranges of a parallel read, reassembled as they complete
+struct Assembly {
+    ranges: Vec<Option<RemoteResult<Vec<u8>>>>,
+    /// Length of each range, but the last one
+    range_len: usize,
+    remaining: usize,
+    done: Option<Done>,
+}
+
+impl ParallelRead {
+    /// Amount of ranges to split the window in, with at most `streams` streams
+    pub fn ranges(len: usize, streams: usize) -> usize {
+        streams.min(len / MIN_RANGE_SIZE).max(1)
+    }
+
+    /// Read the window in `streams` ranges on `workers`, calling `done` with the reassembled data.
+    ///
+    /// The data stops at the end of the first short range, which reached the end of the file.
+    pub fn execute<T: RemoteFs>(self, workers: &WorkerPool<T>, streams: usize, done: Done) {
+        let count = Self::ranges(self.len, streams);
+        let range_len = self.len.div_ceil(count);
+        debug!(
+            "Reading {} bytes of {} at {} in {count} ranges",
+            self.len,
+            self.path.display(),
+            self.offset
+        );
+        let assembly = Arc::new(Mutex::new(Assembly {
+            ranges: (0..count).map(|_| None).collect(),
+            range_len,
+            remaining: count,
+            done: Some(done),
+        }));
+        let read = Arc::new(self);
+
+        for index in 0..count {
+            let start = index * range_len;
+            let len = range_len.min(read.len - start);
+            let (read, range_assembly) = (read.clone(), assembly.clone());
+            let job: Job<T> = Box::new(move |remote| {
+                let result = read.read_range(remote, read.offset + start as u64, len);
+                complete(&range_assembly, index, result);
+            });
+            if workers.execute(job).is_err() {
+                error!("Workers have stopped; failing the parallel read");
+                complete(
+                    &assembly,
+                    index,
+                    Err(RemoteError::new_ex(
+                        RemoteErrorType::IoError,
+                        "the workers have stopped",
+                    )),
+                );
+            }
+        }
+    }
+
+    /// Read `len` bytes at `offset` from a new stream
+    fn read_range<T: RemoteFs>(
+        &self,
+        remote: &mut T,
+        offset: u64,
+        len: usize,
+    ) -> RemoteResult<Vec<u8>> {
+        let mut buffer = vec![0; len];
+        let bytes_read = match remote.open(&self.path) {
+            Ok(reader) => {
+                if !reader.seekable() {
+                    debug!("Remote streams can't seek; reading the next windows serially");
+                    self.unsupported.store(true, Ordering::Relaxed);
+                }
+                read_from_stream(
+                    remote,
+                    reader,
+                    &self.path,
+                    &mut buffer,
+                    offset,
+                    self.chunk_size,
+                    self.retries,
+                )?
+            }
+            Err(RemoteError {
+                kind: RemoteErrorType::UnsupportedFeature,
+                ..
+            }) => {
+                debug!("Remote doesn't support streams; reading the next windows serially");
+                self.unsupported.store(true, Ordering::Relaxed);
+                read_tempfile(
+                    remote,
+                    &self.path,
+                    &mut buffer,
+                    offset,
+                    self.retries,
+                    self.temp_dir.as_deref().map(Path::new),
+                )?
+            }
+            Err(err) => return Err(err),
+        };
+        buffer.truncate(bytes_read);
+
+        Ok(buffer)
+    }
+}
+
+/// Store the `result` of the range `index` of `assembly`, calling its callback once it is the last one
+fn complete(assembly: &Mutex<Assembly>, index: usize, result: RemoteResult<Vec<u8>>) {
+    let Ok(mut assembly) = assembly.lock() else {
+        error!("parallel read lock poisoned");
+        return;
+    };
+    assembly.ranges[index] = Some(result);
+    assembly.remaining -= 1;
+    if assembly.remaining > 0 {
+        return;
+    }
+    let Some(done) = assembly.done.take() else {
+        return;
+    };
+    let ranges = std::mem::take(&mut assembly.ranges);
+    let range_len = assembly.range_len;
+    drop(assembly);
+
+    done(reassemble(ranges, range_len));
+}
+
+/// Concatenate the data of `ranges`, each of `range_len` bytes, stopping at the end of the file
+fn reassemble(
+    ranges: Vec<Option<RemoteResult<Vec<u8>>>>,
+    range_len: usize,
+) -> RemoteResult<Vec<u8>> {
+    let mut data = Vec::with_capacity(range_len * ranges.len());
+    for range in ranges {
+        let range = range.unwrap_or_else(|| {
+            Err(RemoteError::new_ex(
+                RemoteErrorType::IoError,
+                "range was not read",
+            ))
+        })?;
+        data.extend_from_slice(&range);
+        if range.len() < range_len {
+            break;
+        }
+    }
+
+    Ok(data)
+}
@@ -0,0 +1,201 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use remotefs::RemoteFs;
+
+/// A pool of connections to the remote.
+///
+/// A connection is checked out for each in-flight operation and returned to the pool when the [`PooledRemote`]
+/// is dropped. On checkout the connection is checked and reconnected if it has been dropped.
+pub struct RemotePool<T>
+where
+    T: RemoteFs,
+{
+    idle: Mutex<Vec<T>>,
+    returned: Condvar,
+    size: usize,
+}
+
+impl<T> RemotePool<T>
+where
+    T: RemoteFs,
+{
+    /// Create a new [`RemotePool`] with `connections`
+    pub fn new(connections: Vec<T>) -> Self {
+        Self {
+            size: connections.len(),
+            idle: Mutex::new(connections),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Amount of connections in the pool
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Check out a connection, waiting until one is available
+    pub fn checkout(&self) -> PooledRemote<'_, T> {
+        let mut idle = self.idle.lock().expect("pool lock poisoned");
+        loop {
+            if let Some(remote) = idle.pop() {
+                return self.pooled(remote);
+            }
+            idle = self.returned.wait(idle).expect("pool lock poisoned");
+        }
+    }
+
+    /// Check out a connection, waiting up to `timeout` for one to be available.
+    ///
+    /// Returns [`None`] if the pool is still exhausted once `timeout` has elapsed.
+    pub fn checkout_timeout(&self, timeout: Duration) -> Option<PooledRemote<'_, T>> {
+        let deadline = Instant::now() + timeout;
+        let mut idle = self.idle.lock().expect("pool lock poisoned");
+        loop {
+            if let Some(remote) = idle.pop() {
+                return Some(self.pooled(remote));
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            idle = self
+                .returned
+                .wait_timeout(idle, remaining)
+                .expect("pool lock poisoned")
+                .0;
+        }
+    }
+
+    /// Wrap `remote` into a [`PooledRemote`], reconnecting it if needed
+    fn pooled(&self, mut remote: T) -> PooledRemote<'_, T> {
+        if !remote.is_connected() {
+            debug!("pooled connection is not connected; connecting");
+            if let Err(err) = remote.connect() {
+                error!("failed to connect pooled connection: {err}");
+            }
+        }
+
+        PooledRemote {
+            pool: self,
+            remote: Some(remote),
+        }
+    }
+
+    /// Return `remote` to the pool
+    fn checkin(&self, remote: T) {
+        self.idle.lock().expect("pool lock poisoned").push(remote);
+        self.returned.notify_one();
+    }
+}
+
+impl<T> Drop for RemotePool<T>
+where
+    T: RemoteFs,
+{
+    fn drop(&mut self) {
+        let Ok(idle) = self.idle.get_mut() else {
+            return;
+        };
+        for remote in idle.iter_mut() {
+            if remote.is_connected() {
+                if let Err(err) = remote.disconnect() {
+                    error!("failed to disconnect pooled connection: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// A connection checked out from a [`RemotePool`], returned to the pool when dropped.
+pub struct PooledRemote<'a, T>
+where
+    T: RemoteFs,
+{
+    pool: &'a RemotePool<T>,
+    remote: Option<T>,
+}
+
+impl<T> Deref for PooledRemote<'_, T>
+where
+    T: RemoteFs,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.remote.as_ref().expect("connection already returned")
+    }
+}
+
+impl<T> DerefMut for PooledRemote<'_, T>
+where
+    T: RemoteFs,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.remote.as_mut().expect("connection already returned")
+    }
+}
+
+impl<T> Drop for PooledRemote<'_, T>
+where
+    T: RemoteFs,
+{
+    fn drop(&mut self) {
+        if let Some(remote) = self.remote.take() {
+            self.pool.checkin(remote);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn memory_fs() -> MemoryFs {
+        MemoryFs::new(Tree::new(node!(
+            "/".into(),
+            remotefs_memory::Inode::dir(0, 0, remotefs::fs::UnixPex::from(0o755))
+        )))
+    }
+
+    #[test]
+    fn test_should_report_pool_exhaustion() {
+        let pool = RemotePool::new(vec![memory_fs()]);
+        assert_eq!(pool.size(), 1);
+
+        let remote = pool.checkout();
+        assert!(pool.checkout_timeout(Duration::from_millis(10)).is_none());
+
+        drop(remote);
+        assert!(pool.checkout_timeout(Duration::from_millis(10)).is_some());
+    }
+
+    #[test]
+    fn test_should_wait_for_returned_connection() {
+        let pool = std::sync::Arc::new(RemotePool::new(vec![memory_fs()]));
+        let remote = pool.checkout();
+
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || pool.checkout().is_connected())
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        drop(remote);
+
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn test_should_reconnect_on_checkout() {
+        let pool = RemotePool::new(vec![memory_fs()]);
+        {
+            let mut remote = pool.checkout();
+            assert!(remote.is_connected());
+            remote.disconnect().unwrap();
+        }
+
+        assert!(pool.checkout().is_connected());
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::ReadStream;
+use remotefs::File;
+
+/// A stream opened on the remote to read a file, kept open between sequential reads.
+///
+/// Kernel readahead issues consecutive reads on the same file; serving them from the same stream avoids
+/// reopening the file and skipping to the offset on each request.
+pub struct OpenStream {
+    /// Path of the file being read
+    path: PathBuf,
+    /// Offset of the next byte returned by the stream
+    offset: u64,
+    /// The file being read, as it was when the stream last served a read
+    file: Option<File>,
+    reader: ReadStream,
+}
+
+impl OpenStream {
+    /// Create a new [`OpenStream`] for `path`, whose next byte is at `offset`
+    pub fn new(path: &Path, offset: u64, reader: ReadStream) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            offset,
+            file: None,
+            reader,
+        }
+    }
+
+    /// Whether the stream can serve a read of `path` at `offset`
+    pub fn is_at(&self, path: &Path, offset: u64) -> bool {
+        self.path == path && self.offset == offset
+    }
+
+    /// Path of the file being read
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file being read, as it was when the stream last served a read
+    pub fn file(&self) -> Option<&File> {
+        self.file.as_ref()
+    }
+
+    /// Set the file being read, as it was when the stream served the last read
+    pub fn set_file(&mut self, file: File) {
+        self.file = Some(file);
+    }
+
+    /// Advance the stream position by `bytes` bytes
+    pub fn advance(&mut self, bytes: usize) {
+        self.offset += bytes as u64;
+    }
+
+    /// The underlying stream
+    pub fn reader(&mut self) -> &mut ReadStream {
+        &mut self.reader
+    }
+
+    /// Take the underlying stream, to finalize it on the remote
+    pub fn into_reader(self) -> ReadStream {
+        self.reader
+    }
+}
+
+impl std::fmt::Debug for OpenStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenStream")
+            .field("path", &self.path)
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_should_track_stream_position() {
+        let reader: Box<dyn std::io::Read + Send> = Box::new(Cursor::new(vec![0; 8]));
+        let mut stream = OpenStream::new(Path::new("/a.txt"), 4, ReadStream::from(reader));
+        assert!(stream.is_at(Path::new("/a.txt"), 4));
+        assert!(!stream.is_at(Path::new("/b.txt"), 4));
+
+        stream.advance(4);
+        assert!(!stream.is_at(Path::new("/a.txt"), 4));
+        assert!(stream.is_at(Path::new("/a.txt"), 8));
+    }
+}
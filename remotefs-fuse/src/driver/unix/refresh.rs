@@ -0,0 +1,52 @@
+use std::sync::Mutex;
+
+/// A remote waiting to replace the one used by the driver, set with [`crate::Refresher::refresh_credentials`].
+///
+/// The driver swaps it in between two operations, so that an in-flight operation always completes on the
+/// connection it started with.
+#[derive(Debug)]
+pub struct PendingRemote<T> {
+    remote: Mutex<Option<T>>,
+}
+
+impl<T> Default for PendingRemote<T> {
+    fn default() -> Self {
+        Self {
+            remote: Mutex::new(None),
+        }
+    }
+}
+
+impl<T> PendingRemote<T> {
+    /// Set `remote` to be swapped in, replacing the remote previously set if it hasn't been swapped in yet
+    pub fn set(&self, remote: T) {
+        *self.remote.lock().expect("pending remote lock poisoned") = Some(remote);
+    }
+
+    /// Take the remote to swap in, if any
+    pub fn take(&self) -> Option<T> {
+        self.remote
+            .lock()
+            .expect("pending remote lock poisoned")
+            .take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_keep_last_pending_remote() {
+        let pending = PendingRemote::default();
+        assert_eq!(pending.take(), None);
+
+        pending.set(1);
+        pending.set(2);
+        assert_eq!(pending.take(), Some(2));
+        assert_eq!(pending.take(), None);
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
+
+/// How many times an interrupted stream operation is retried, if [`crate::MountOption::IoRetries`] is not set
+pub const DEFAULT_IO_RETRIES: usize = 3;
+
+/// How long to wait before retrying a stream operation which would block, multiplied by the attempt
+const WOULD_BLOCK_BACKOFF: Duration = Duration::from_millis(10);
+
+/// A stream retrying the reads and writes interrupted by a signal or which would block, up to `retries` times in
+/// a row.
+///
+/// Once the retries are exhausted, the error is given up as [`ErrorKind::Other`], so that the callers which retry
+/// the interrupted operations themselves, such as [`Write::write_all`], don't try forever.
+pub struct Retrying<S> {
+    inner: S,
+    retries: usize,
+}
+
+impl<S> Retrying<S> {
+    /// Wrap `inner`, retrying its operations up to `retries` times
+    pub fn new(inner: S, retries: usize) -> Self {
+        Self { inner, retries }
+    }
+
+    /// Run `op` on the inner stream, retrying it while it fails with a transient error
+    fn retry<R>(&mut self, mut op: impl FnMut(&mut S) -> std::io::Result<R>) -> std::io::Result<R> {
+        let mut attempt = 0;
+        loop {
+            match op(&mut self.inner) {
+                Err(err)
+                    if matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) =>
+                {
+                    if attempt == self.retries {
+                        return Err(std::io::Error::other(format!(
+                            "{err}; gave up after {attempt} retries"
+                        )));
+                    }
+                    attempt += 1;
+                    debug!(
+                        "retrying stream operation ({attempt}/{}): {err}",
+                        self.retries
+                    );
+                    if err.kind() == ErrorKind::WouldBlock {
+                        std::thread::sleep(WOULD_BLOCK_BACKOFF * attempt as u32);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<S> Read for Retrying<S>
+where
+    S: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.retry(|inner| inner.read(buf))
+    }
+}
+
+impl<S> Write for Retrying<S>
+where
+    S: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.retry(|inner| inner.write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.retry(|inner| inner.flush())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// A reader failing with `kind` the first `failures` reads
+    struct Flaky {
+        inner: Cursor<Vec<u8>>,
+        kind: ErrorKind,
+        failures: usize,
+    }
+
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(self.kind.into());
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    fn flaky(kind: ErrorKind, failures: usize) -> Flaky {
+        Flaky {
+            inner: Cursor::new(b"hello".to_vec()),
+            kind,
+            failures,
+        }
+    }
+
+    #[test]
+    fn test_should_retry_interrupted_reads() {
+        let mut buf = [0; 5];
+        let mut reader = Retrying::new(flaky(ErrorKind::Interrupted, 1), 3);
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"hello");
+
+        let mut reader = Retrying::new(flaky(ErrorKind::WouldBlock, 2), 3);
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_should_give_up_after_retries() {
+        let mut buf = [0; 5];
+        let mut reader = Retrying::new(flaky(ErrorKind::Interrupted, 3), 2);
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::Other);
+
+        // other errors are not retried
+        let mut reader = Retrying::new(flaky(ErrorKind::BrokenPipe, 1), 2);
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use fuser::FileAttr;
+
+use super::inode::Inode;
+
+/// Version of a file on the remote, used to detect external changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileVersion {
+    mtime: SystemTime,
+    size: u64,
+}
+
+impl From<&FileAttr> for FileVersion {
+    fn from(attrs: &FileAttr) -> Self {
+        Self {
+            mtime: attrs.mtime,
+            size: attrs.size,
+        }
+    }
+}
+
+impl From<&remotefs::fs::Metadata> for FileVersion {
+    fn from(metadata: &remotefs::fs::Metadata) -> Self {
+        Self {
+            mtime: metadata.modified.unwrap_or(SystemTime::UNIX_EPOCH),
+            size: metadata.size,
+        }
+    }
+}
+
+/// Keeps track of the open files, to detect when they're changed on the remote by another client.
+#[derive(Debug)]
+pub struct Revalidator {
+    /// How often the open files are checked
+    interval: Duration,
+    /// Last time the open files were checked
+    last_check: Instant,
+    /// Open files, with their path on the remote and their version, as last seen
+    watched: HashMap<Inode, (PathBuf, FileVersion)>,
+}
+
+impl Revalidator {
+    /// Create a new [`Revalidator`] which checks the open files every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_check: Instant::now(),
+            watched: HashMap::new(),
+        }
+    }
+
+    /// Start watching `inode`, at `path` on the remote, if not watched yet
+    pub fn watch(&mut self, inode: Inode, path: PathBuf, version: FileVersion) {
+        self.watched.entry(inode).or_insert((path, version));
+    }
+
+    /// Stop watching `inode`
+    pub fn unwatch(&mut self, inode: Inode) {
+        self.watched.remove(&inode);
+    }
+
+    /// Inodes currently watched, with their path on the remote
+    pub fn watched(&self) -> Vec<(Inode, PathBuf)> {
+        self.watched
+            .iter()
+            .map(|(inode, (path, _))| (*inode, path.clone()))
+            .collect()
+    }
+
+    /// How often the open files are checked
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Whether the interval has elapsed since the last check. If so, the check is considered started.
+    pub fn due(&mut self) -> bool {
+        if self.last_check.elapsed() < self.interval {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        true
+    }
+
+    /// Update the version of `inode`, returning whether it has changed since it was last seen
+    pub fn update(&mut self, inode: Inode, version: FileVersion) -> bool {
+        match self.watched.get_mut(&inode) {
+            Some((_, seen)) if *seen != version => {
+                *seen = version;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::UNIX_EPOCH;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn version(secs: u64, size: u64) -> FileVersion {
+        FileVersion {
+            mtime: UNIX_EPOCH + Duration::from_secs(secs),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_should_detect_changed_versions() {
+        let mut revalidator = Revalidator::new(Duration::from_secs(1));
+        revalidator.watch(1, PathBuf::from("/a.txt"), version(10, 100));
+        // watching again keeps the first version
+        revalidator.watch(1, PathBuf::from("/a.txt"), version(20, 100));
+        assert_eq!(revalidator.watched(), vec![(1, PathBuf::from("/a.txt"))]);
+
+        assert_eq!(revalidator.update(1, version(10, 100)), false);
+        assert_eq!(revalidator.update(1, version(10, 101)), true);
+        assert_eq!(revalidator.update(1, version(10, 101)), false);
+        // not watched
+        assert_eq!(revalidator.update(2, version(10, 101)), false);
+
+        revalidator.unwatch(1);
+        assert!(revalidator.watched().is_empty());
+    }
+
+    #[test]
+    fn test_should_tell_when_check_is_due() {
+        let mut revalidator = Revalidator::new(Duration::ZERO);
+        assert!(revalidator.due());
+
+        let mut revalidator = Revalidator::new(Duration::from_secs(3600));
+        assert!(!revalidator.due());
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::inode::Inode;
+
+/// Size of the symlink cache when [`crate::MountOption::ReadCacheSize`] is not set
+pub const DEFAULT_SYMLINK_CACHE_SIZE: usize = 1024 * 1024;
+
+/// A bounded LRU cache of symlink targets, by inode.
+///
+/// Targets don't change for the life of a link, so they are only invalidated when the link is replaced or removed.
+#[derive(Debug)]
+pub struct SymlinkCache {
+    /// Maximum amount of bytes of targets stored in the cache
+    capacity: usize,
+    /// Amount of bytes of targets currently stored
+    size: usize,
+    targets: HashMap<Inode, Target>,
+    /// Inodes ordered by last usage
+    lru: BTreeMap<u64, Inode>,
+    /// Monotonic usage counter
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Target {
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+impl SymlinkCache {
+    /// Create a new [`SymlinkCache`] which can hold up to `size` bytes of targets.
+    pub fn new(size: usize) -> Self {
+        Self {
+            capacity: size,
+            size: 0,
+            targets: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Get the target of the link `inode`, marking it as recently used
+    pub fn get(&mut self, inode: Inode) -> Option<&[u8]> {
+        self.tick += 1;
+        let tick = self.tick;
+        let target = self.targets.get_mut(&inode)?;
+        self.lru.remove(&target.last_used);
+        self.lru.insert(tick, inode);
+        target.last_used = tick;
+
+        Some(target.data.as_slice())
+    }
+
+    /// Insert the target of the link `inode`, evicting the least recently used targets to make room for it.
+    ///
+    /// Targets larger than the whole cache are not stored.
+    pub fn insert(&mut self, inode: Inode, data: Vec<u8>) {
+        self.invalidate(inode);
+        if data.len() > self.capacity {
+            return;
+        }
+        while self.size + data.len() > self.capacity {
+            let Some((_, evicted)) = self.lru.pop_first() else {
+                break;
+            };
+            debug!("evicting symlink target of inode {evicted}");
+            if let Some(target) = self.targets.remove(&evicted) {
+                self.size -= target.data.len();
+            }
+        }
+
+        self.tick += 1;
+        self.size += data.len();
+        self.lru.insert(self.tick, inode);
+        self.targets.insert(
+            inode,
+            Target {
+                data,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    /// Remove the target of the link `inode` from the cache
+    pub fn invalidate(&mut self, inode: Inode) {
+        if let Some(target) = self.targets.remove(&inode) {
+            self.lru.remove(&target.last_used);
+            self.size -= target.data.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_get_cached_targets() {
+        let mut cache = SymlinkCache::new(64);
+        cache.insert(1, b"/tmp/a".to_vec());
+
+        assert_eq!(cache.get(1), Some(b"/tmp/a".as_slice()));
+        assert_eq!(cache.get(2), None);
+
+        cache.invalidate(1);
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.size, 0);
+    }
+
+    #[test]
+    fn test_should_evict_least_recently_used_target() {
+        let mut cache = SymlinkCache::new(8);
+        cache.insert(1, vec![1; 4]);
+        cache.insert(2, vec![2; 4]);
+        // use target 1, so target 2 becomes the least recently used
+        assert!(cache.get(1).is_some());
+        cache.insert(3, vec![3; 4]);
+
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(3).is_some());
+
+        // larger than the cache
+        cache.insert(4, vec![4; 9]);
+        assert!(cache.get(4).is_none());
+        assert_eq!(cache.size, 8);
+    }
+}
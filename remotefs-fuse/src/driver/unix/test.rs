@@ -1,16 +1,34 @@
+mod stub;
+
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use nix::fcntl::OFlag;
 use nix::unistd::AccessFlags;
 use pretty_assertions::{assert_eq, assert_ne};
 use remotefs::fs::{Metadata, UnixPex};
 use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs};
 use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
 
-use super::Driver;
+use self::stub::StubFs;
+use super::watcher::Change;
+use super::{block_usage, normalize_path, Driver, WriteBuffer};
 use crate::MountOption;
 
 fn setup_driver() -> Driver<MemoryFs> {
+    setup_driver_with_options(vec![])
+}
+
+fn setup_driver_with_options(options: Vec<MountOption>) -> Driver<MemoryFs> {
+    Driver::new(setup_memory_fs(), driver_options(options))
+}
+
+fn setup_stub_driver() -> Driver<StubFs> {
+    Driver::new(StubFs::new(setup_memory_fs()), driver_options(vec![]))
+}
+
+fn setup_memory_fs() -> MemoryFs {
     let gid = nix::unistd::getgid().as_raw();
     let uid = nix::unistd::getuid().as_raw();
 
@@ -26,15 +44,19 @@ fn setup_driver() -> Driver<MemoryFs> {
     fs.connect().expect("Failed to connect");
     assert!(fs.is_connected());
 
-    Driver::new(
-        fs,
-        vec![
-            MountOption::AllowRoot,
-            MountOption::RW,
-            MountOption::Exec,
-            MountOption::Sync,
-        ],
-    )
+    fs
+}
+
+fn driver_options(options: Vec<MountOption>) -> Vec<MountOption> {
+    let mut driver_options = vec![
+        MountOption::AllowRoot,
+        MountOption::RW,
+        MountOption::Exec,
+        MountOption::Sync,
+    ];
+    driver_options.extend(options);
+
+    driver_options
 }
 
 fn setup_driver_with_mode(mode: u32) -> Driver<MemoryFs> {
@@ -94,7 +116,7 @@ fn setup_driver_with_uid(uid: u32, gid: u32) -> Driver<MemoryFs> {
 /// Make file on the remote fs at `path` with `content`
 ///
 /// If the stems in the path do not exist, they will be created.
-fn make_file_at(driver: &mut Driver<MemoryFs>, path: &Path, content: &[u8]) {
+fn make_file_at<T: RemoteFs>(driver: &mut Driver<T>, path: &Path, content: &[u8]) {
     let parent_dir = path.parent().expect("Path has no parent");
     make_dir_at(driver, parent_dir);
 
@@ -112,7 +134,7 @@ fn make_file_at(driver: &mut Driver<MemoryFs>, path: &Path, content: &[u8]) {
 /// Make directory on the remote fs at `path`
 ///
 /// All the stems in the path will be created if they do not exist.
-fn make_dir_at(driver: &mut Driver<MemoryFs>, path: &Path) {
+fn make_dir_at<T: RemoteFs>(driver: &mut Driver<T>, path: &Path) {
     let mut abs_path = Path::new("/").to_path_buf();
     for stem in path.iter() {
         abs_path.push(stem);
@@ -208,6 +230,66 @@ fn test_should_lookup_name() {
     );
 }
 
+#[test]
+fn test_should_normalize_path() {
+    assert_eq!(
+        normalize_path(Path::new("/home/./user")).unwrap(),
+        Path::new("/home/user")
+    );
+    assert_eq!(
+        normalize_path(Path::new("//home//user/")).unwrap(),
+        Path::new("/home/user")
+    );
+    assert_eq!(
+        normalize_path(Path::new("/home/user/../other")).unwrap(),
+        Path::new("/home/other")
+    );
+    assert_eq!(
+        normalize_path(Path::new("/home/..")).unwrap(),
+        Path::new("/")
+    );
+    assert!(normalize_path(Path::new("/..")).is_none());
+    assert!(normalize_path(Path::new("/home/../../etc")).is_none());
+}
+
+#[test]
+fn test_should_lookup_normalized_name() {
+    let mut driver = setup_driver();
+    let parent_dir = Path::new("/home/user");
+    make_dir_at(&mut driver, parent_dir);
+    let inode = driver.get_inode_from_path(parent_dir).unwrap().1.ino;
+
+    assert_eq!(
+        driver.lookup_name(inode, OsStr::new(".")).unwrap(),
+        parent_dir
+    );
+    assert_eq!(
+        driver.lookup_name(inode, OsStr::new("..")).unwrap(),
+        Path::new("/home")
+    );
+    assert_eq!(
+        driver
+            .lookup_name(super::ROOT_INODE, OsStr::new(".."))
+            .unwrap_err(),
+        libc::EACCES
+    );
+    assert_eq!(
+        driver
+            .get_inode_from_path(Path::new("/home//user/."))
+            .unwrap()
+            .1
+            .ino,
+        inode
+    );
+    assert_eq!(
+        driver
+            .get_inode_from_path(Path::new("/home/user/../../.."))
+            .unwrap_err()
+            .kind,
+        RemoteErrorType::CouldNotOpenFile
+    );
+}
+
 #[test]
 fn test_should_check_access_accessible_for_user() {
     let driver = setup_driver();
@@ -614,3 +696,2236 @@ fn test_should_check_access_write_for_configured_mode() {
         true
     );
 }
+
+#[test]
+fn test_should_serve_second_read_from_page_cache() {
+    let mut driver = setup_driver_with_options(vec![MountOption::ReadCacheSize(1024 * 1024)]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let inode = Driver::<MemoryFs>::inode(file_path);
+
+    let mut buffer = vec![0; 5];
+    let bytes_read = driver
+        .read_cached(inode, file_path, 11, &mut buffer, 6)
+        .expect("failed to read");
+    assert_eq!(bytes_read, 5);
+    assert_eq!(buffer, b"world");
+
+    // change the file on the remote; the second read must be served by the cache
+    make_file_at(&mut driver, file_path, b"HELLO WORLD");
+    let mut buffer = vec![0; 11];
+    let bytes_read = driver
+        .read_cached(inode, file_path, 11, &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(bytes_read, 11);
+    assert_eq!(buffer, b"hello world");
+
+    // after invalidation the new content is read
+    driver.invalidate_page_cache(inode);
+    let bytes_read = driver
+        .read_cached(inode, file_path, 11, &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(bytes_read, 11);
+    assert_eq!(buffer, b"HELLO WORLD");
+}
+
+#[test]
+fn test_should_read_across_cached_pages() {
+    let mut driver = setup_driver_with_options(vec![MountOption::ReadCacheSize(1024 * 1024)]);
+    let file_path = Path::new("/tmp/big.bin");
+    let content = (0..super::PAGE_SIZE * 3)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<u8>>();
+    make_file_at(&mut driver, file_path, &content);
+    let inode = Driver::<MemoryFs>::inode(file_path);
+
+    // read the middle page first, then a window spanning all the pages
+    let mut buffer = vec![0; 100];
+    driver
+        .read_cached(
+            inode,
+            file_path,
+            content.len() as u64,
+            &mut buffer,
+            super::PAGE_SIZE as u64 + 10,
+        )
+        .expect("failed to read");
+    assert_eq!(
+        buffer,
+        &content[super::PAGE_SIZE + 10..super::PAGE_SIZE + 110]
+    );
+
+    let offset = super::PAGE_SIZE - 7;
+    let mut buffer = vec![0; super::PAGE_SIZE * 2];
+    let bytes_read = driver
+        .read_cached(
+            inode,
+            file_path,
+            content.len() as u64,
+            &mut buffer,
+            offset as u64,
+        )
+        .expect("failed to read");
+    assert_eq!(bytes_read, super::PAGE_SIZE * 2);
+    assert_eq!(buffer, &content[offset..offset + super::PAGE_SIZE * 2]);
+}
+
+#[test]
+fn test_should_convert_file_times() {
+    let modified = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let created = UNIX_EPOCH + Duration::from_secs(500_000);
+    let accessed = UNIX_EPOCH + Duration::from_secs(2_000_000);
+    let file = File {
+        path: PathBuf::from("/tmp/test.txt"),
+        metadata: Metadata::default()
+            .modified(modified)
+            .created(created)
+            .accessed(accessed),
+    };
+
+    let attrs = super::convert_file::<MemoryFs>(&file);
+    assert_eq!(attrs.mtime, modified);
+    assert_eq!(attrs.atime, accessed);
+    assert_eq!(attrs.crtime, created);
+    assert_eq!(attrs.ctime, modified);
+}
+
+#[test]
+fn test_should_fallback_to_mtime_for_missing_times() {
+    let modified = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let file = File {
+        path: PathBuf::from("/tmp/test.txt"),
+        metadata: Metadata::default().modified(modified),
+    };
+
+    let attrs = super::convert_file::<MemoryFs>(&file);
+    assert_eq!(attrs.atime, modified);
+    assert_eq!(attrs.crtime, modified);
+    assert_eq!(attrs.ctime, modified);
+
+    // created is used as last resort before the epoch
+    let created = UNIX_EPOCH + Duration::from_secs(500_000);
+    let file = File {
+        path: PathBuf::from("/tmp/test.txt"),
+        metadata: Metadata::default().created(created),
+    };
+    let attrs = super::convert_file::<MemoryFs>(&file);
+    assert_eq!(attrs.mtime, created);
+}
+
+#[test]
+fn test_should_track_ctime() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    assert_eq!(attrs.ctime, attrs.mtime);
+
+    let before = SystemTime::now();
+    driver.touch_ctime(attrs.ino);
+    let (_, attrs) = driver.get_inode(attrs.ino).expect("failed to get inode");
+    assert!(attrs.ctime >= before);
+
+    // an explicit ctime doesn't change the creation time
+    let ctime = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let crtime = attrs.crtime;
+    driver.set_ctime(attrs.ino, ctime);
+    let (_, attrs) = driver.get_inode(attrs.ino).expect("failed to get inode");
+    assert_eq!(attrs.ctime, ctime);
+    assert_eq!(attrs.crtime, crtime);
+}
+
+#[test]
+fn test_should_prune_tracked_attributes_on_forget() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    driver.touch_ctime(attrs.ino);
+    driver.set_setgid(attrs.ino, true);
+    assert!(driver.ctimes.contains_key(&attrs.ino));
+    assert!(driver.setgid_dirs.contains(&attrs.ino));
+
+    driver.forget_inodes([attrs.ino]);
+    assert!(!driver.ctimes.contains_key(&attrs.ino));
+    assert!(!driver.setgid_dirs.contains(&attrs.ino));
+}
+
+#[test]
+fn test_should_delegate_permissions_to_kernel() {
+    let driver = setup_driver();
+    assert_eq!(driver.default_permissions(), false);
+
+    let driver = setup_driver_with_options(vec![MountOption::DefaultPermissions]);
+    assert_eq!(driver.default_permissions(), true);
+}
+
+#[test]
+fn test_should_list_entries_with_trailing_separator() {
+    let mut driver = setup_stub_driver();
+    driver.remote.list_dir = Some(vec![
+        File {
+            path: PathBuf::from("/foo/bar/"),
+            metadata: Metadata::default(),
+        },
+        File {
+            path: PathBuf::from("/foo/.."),
+            metadata: Metadata::default(),
+        },
+    ]);
+
+    let entries = driver
+        .read_dir_entries(Path::new("/foo"))
+        .expect("failed to read dir");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].inode,
+        Driver::<StubFs>::inode(Path::new("/foo/bar"))
+    );
+    assert_eq!(entries[0].name, OsStr::new("bar"));
+}
+
+#[test]
+fn test_should_get_entry_name_from_bytes() {
+    assert_eq!(
+        super::entry_name_from_bytes(Path::new("/foo/bar//")),
+        Some(OsStr::new("bar"))
+    );
+    assert_eq!(super::entry_name_from_bytes(Path::new("/foo/..")), None);
+    assert_eq!(super::entry_name_from_bytes(Path::new("/")), None);
+}
+
+#[test]
+fn test_should_list_dot_entries_first() {
+    let mut driver = setup_driver();
+    let dir_path = Path::new("/dir");
+    make_dir_at(&mut driver, dir_path);
+    make_file_at(&mut driver, &dir_path.join("file.txt"), b"hello");
+    let (_, attrs) = driver
+        .get_inode_from_path(dir_path)
+        .expect("failed to get inode");
+
+    let listing = driver
+        .read_dir_listing(attrs.ino, dir_path)
+        .expect("failed to read dir");
+    let names = listing
+        .iter()
+        .map(|entry| entry.name.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![".", "..", "file.txt"]);
+    assert_eq!(listing[0].inode, attrs.ino);
+    assert_eq!(listing[1].inode, super::ROOT_INODE);
+    assert_eq!(listing[1].kind, fuser::FileType::Directory);
+
+    // the root is the parent of itself
+    let listing = driver
+        .read_dir_listing(super::ROOT_INODE, Path::new("/"))
+        .expect("failed to read dir");
+    assert_eq!(listing[0].inode, super::ROOT_INODE);
+    assert_eq!(listing[1].inode, super::ROOT_INODE);
+}
+
+#[test]
+fn test_should_read_link_target_with_under_reported_size() {
+    let mut driver = setup_driver();
+    let link_path = Path::new("/tmp/link");
+    make_file_at(&mut driver, link_path, b"/tmp/target\0\0");
+    let (mut file, attrs) = driver
+        .get_inode_from_path(link_path)
+        .expect("failed to get inode");
+    file.metadata.size = 0;
+
+    assert_eq!(
+        driver
+            .read_link_target(attrs.ino, &file)
+            .expect("failed to read link"),
+        b"/tmp/target"
+    );
+}
+
+#[test]
+fn test_should_serve_cached_symlink_target() {
+    let mut driver = setup_driver_with_options(vec![MountOption::CacheSymlinks]);
+    let link_path = Path::new("/tmp/link");
+    make_file_at(&mut driver, link_path, b"/tmp/target");
+    let (file, attrs) = driver
+        .get_inode_from_path(link_path)
+        .expect("failed to get inode");
+    assert_eq!(
+        driver.read_link_target(attrs.ino, &file).unwrap(),
+        b"/tmp/target"
+    );
+
+    // the second read doesn't hit the remote
+    driver.remote.remove_file(link_path).unwrap();
+    assert_eq!(
+        driver.read_link_target(attrs.ino, &file).unwrap(),
+        b"/tmp/target"
+    );
+
+    driver.invalidate_symlink(attrs.ino);
+    assert!(driver.read_link_target(attrs.ino, &file).is_err());
+}
+
+#[test]
+fn test_should_mount_single_file() {
+    let image_path = Path::new("/images/disk.img");
+    let mut driver =
+        setup_driver_with_options(vec![MountOption::SingleFile(image_path.to_path_buf())]);
+    make_file_at(&mut driver, image_path, b"hello world");
+
+    let (file, attrs) = driver
+        .get_inode(super::ROOT_INODE)
+        .expect("failed to get root");
+    assert_eq!(attrs.ino, super::ROOT_INODE);
+    assert_eq!(attrs.kind, fuser::FileType::RegularFile);
+    assert_eq!(attrs.size, 11);
+
+    let mut buffer = vec![0; 5];
+    assert_eq!(driver.read(Path::new("/"), &mut buffer, 6).unwrap(), 5);
+    assert_eq!(buffer, b"world");
+
+    driver
+        .write_buffered(super::ROOT_INODE, &file, b"HELLO", 0)
+        .expect("failed to write");
+    driver.flush_write_buffer(super::ROOT_INODE).unwrap();
+    let mut buffer = vec![0; 11];
+    assert_eq!(driver.read(Path::new("/"), &mut buffer, 0).unwrap(), 11);
+    assert_eq!(buffer, b"HELLO world");
+
+    assert_eq!(
+        driver.lookup_name(super::ROOT_INODE, OsStr::new("disk.img")),
+        Err(libc::ENOTDIR)
+    );
+}
+
+#[test]
+fn test_should_inherit_group_of_setgid_dir() {
+    let mut driver = setup_driver();
+    let dir_path = Path::new("/tmp/shared");
+    make_dir_at(&mut driver, dir_path);
+    let metadata = driver.remote.stat(dir_path).unwrap().metadata.gid(100);
+    driver.remote.setstat(dir_path, metadata).unwrap();
+    let (_, tmp) = driver.get_inode_from_path(Path::new("/tmp")).unwrap();
+    let (_, dir) = driver.get_inode_from_path(dir_path).unwrap();
+    assert_eq!(driver.inherited_gid(tmp.ino), None);
+
+    driver.set_setgid(dir.ino, true);
+    assert_eq!(driver.inherited_gid(dir.ino), Some(100));
+    assert_eq!(
+        driver.get_inode(dir.ino).unwrap().1.perm & super::S_ISGID as u16,
+        super::S_ISGID as u16
+    );
+
+    // subdirectories inherit the group and the setgid bit
+    let sub_path = Path::new("/tmp/shared/sub");
+    driver
+        .make_dir(dir.ino, sub_path, UnixPex::from(0o755))
+        .expect("failed to make dir");
+    assert_eq!(
+        driver.remote.stat(sub_path).unwrap().metadata.gid,
+        Some(100)
+    );
+    let (_, sub) = driver.get_inode_from_path(sub_path).unwrap();
+    assert_eq!(sub.perm & super::S_ISGID as u16, super::S_ISGID as u16);
+
+    // with gid_from_parent any directory passes its group
+    let mut driver = setup_driver_with_options(vec![MountOption::GidFromParent]);
+    make_dir_at(&mut driver, dir_path);
+    let metadata = driver.remote.stat(dir_path).unwrap().metadata.gid(100);
+    driver.remote.setstat(dir_path, metadata).unwrap();
+    let (_, dir) = driver.get_inode_from_path(dir_path).unwrap();
+    assert_eq!(driver.inherited_gid(dir.ino), Some(100));
+}
+
+#[test]
+fn test_should_deny_writes_outside_of_writable_prefix() {
+    let mut driver =
+        setup_driver_with_options(vec![MountOption::WritablePrefix(vec![PathBuf::from(
+            "/tmp/uploads",
+        )])]);
+    make_dir_at(&mut driver, Path::new("/tmp/uploads"));
+    make_file_at(&mut driver, Path::new("/tmp/uploads/a.txt"), b"hello");
+    make_file_at(&mut driver, Path::new("/tmp/b.txt"), b"hello");
+    let (_, tmp) = driver.get_inode_from_path(Path::new("/tmp")).unwrap();
+    let (_, uploads) = driver
+        .get_inode_from_path(Path::new("/tmp/uploads"))
+        .unwrap();
+    let (_, allowed) = driver
+        .get_inode_from_path(Path::new("/tmp/uploads/a.txt"))
+        .unwrap();
+    let (_, denied) = driver.get_inode_from_path(Path::new("/tmp/b.txt")).unwrap();
+
+    assert!(driver.check_writable_path(allowed.ino, None).is_ok());
+    assert!(driver
+        .check_writable_path(uploads.ino, Some(OsStr::new("c.txt")))
+        .is_ok());
+    assert!(driver
+        .check_writable_path(tmp.ino, Some(OsStr::new("uploads")))
+        .is_ok());
+    assert_eq!(
+        driver.check_writable_path(denied.ino, None),
+        Err(libc::EROFS)
+    );
+    assert_eq!(
+        driver.check_writable_path(tmp.ino, Some(OsStr::new("uploads-old"))),
+        Err(libc::EROFS)
+    );
+    assert_eq!(
+        driver.check_writable_path(super::ROOT_INODE, None),
+        Err(libc::EROFS)
+    );
+
+    // without the option everything is writable
+    let mut driver = setup_driver();
+    make_file_at(&mut driver, Path::new("/tmp/b.txt"), b"hello");
+    let (_, file) = driver.get_inode_from_path(Path::new("/tmp/b.txt")).unwrap();
+    assert!(driver.check_writable_path(file.ino, None).is_ok());
+}
+
+#[test]
+fn test_should_log_error_context() {
+    let err = RemoteError::new_ex(RemoteErrorType::IoError, "connection reset by peer");
+    assert_eq!(
+        super::ErrorContext::new(&err).to_string(),
+        "IO error (connection reset by peer) [kind: IoError]"
+    );
+
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (_, attrs) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(
+        driver.err_context(&err, attrs.ino).to_string(),
+        format!(
+            "IO error (connection reset by peer) [kind: IoError, path: /tmp/test.txt, inode: {}]",
+            attrs.ino
+        )
+    );
+    assert_eq!(
+        driver.path_err_context(&err, file_path).to_string(),
+        driver.err_context(&err, attrs.ino).to_string()
+    );
+}
+
+#[test]
+fn test_should_update_inode_index_on_rename() {
+    let mut driver = setup_driver();
+    make_dir_at(&mut driver, Path::new("/tmp"));
+    let (_, parent) = driver
+        .get_inode_from_path(Path::new("/tmp"))
+        .expect("failed to get parent");
+    let (src_inode, src) = driver
+        .lookup_entry(parent.ino, OsStr::new("a.txt"))
+        .expect("failed to look up source");
+    assert_eq!(driver.database.inode_of(&src), Some(src_inode));
+    let (dest_inode, dest) = driver
+        .lookup_entry(parent.ino, OsStr::new("b.txt"))
+        .expect("failed to look up destination");
+    assert_eq!(driver.database.inode_of(&dest), Some(dest_inode));
+
+    driver.moved_inode(src_inode, dest_inode, dest.clone());
+    assert_eq!(driver.database.inode_of(&src), None);
+    assert_eq!(driver.database.get(src_inode), Some(dest.as_path()));
+    assert_eq!(driver.database.inode_of(&dest), Some(dest_inode));
+    assert_eq!(driver.database.paths_for(dest_inode), vec![dest.as_path()]);
+}
+
+#[test]
+fn test_should_resolve_open_file_after_rename() {
+    let mut driver = setup_driver();
+    let src = Path::new("/tmp/a.txt");
+    let dest = Path::new("/tmp/b.txt");
+    make_file_at(&mut driver, src, b"hello");
+    let (_, attrs) = driver
+        .get_inode_from_path(src)
+        .expect("failed to get inode");
+    let src_inode = attrs.ino;
+    driver.file_handlers.open(1, src_inode, true, false);
+
+    driver.remote.mov(src, dest).expect("failed to move file");
+    driver.moved_inode(
+        src_inode,
+        Driver::<MemoryFs>::inode(dest),
+        dest.to_path_buf(),
+    );
+
+    let (file, attrs) = driver
+        .get_inode(src_inode)
+        .expect("failed to resolve the moved inode");
+    assert_eq!(file.path(), dest);
+    assert_eq!(attrs.size, 5);
+}
+
+#[test]
+fn test_should_batch_forget_inodes() {
+    let mut driver = setup_driver();
+    let inodes = (0..4)
+        .map(|i| {
+            let path = PathBuf::from(format!("/tmp/{i}.txt"));
+            make_file_at(&mut driver, &path, b"hello");
+            driver.get_inode_from_path(&path).unwrap().1.ino
+        })
+        .collect::<Vec<_>>();
+    assert!(inodes.iter().all(|ino| driver.database.get(*ino).is_some()));
+
+    let nodes = inodes[..3]
+        .iter()
+        .map(|ino| fuser::fuse_forget_one {
+            nodeid: *ino,
+            nlookup: 1,
+        })
+        .collect::<Vec<_>>();
+    driver.forget_inodes(nodes.iter().map(|node| node.nodeid));
+
+    assert!(inodes[..3]
+        .iter()
+        .all(|ino| driver.database.get(*ino).is_none()));
+    assert!(driver.database.get(inodes[3]).is_some());
+}
+
+#[test]
+fn test_should_resolve_symlink() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    driver
+        .remote
+        .symlink(Path::new("/tmp/a"), file_path)
+        .expect("failed to create symlink");
+    driver
+        .remote
+        .symlink(Path::new("/tmp/b"), Path::new("/tmp/a"))
+        .expect("failed to create symlink");
+
+    let file = driver
+        .resolve_symlink(Path::new("/tmp/b"))
+        .expect("failed to resolve symlink");
+    assert_eq!(file.path(), file_path);
+
+    // not a symlink
+    let file = driver
+        .resolve_symlink(file_path)
+        .expect("failed to resolve symlink");
+    assert_eq!(file.path(), file_path);
+}
+
+#[test]
+fn test_should_fail_resolving_symlink_cycle() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let a = Path::new("/tmp/a");
+    let b = Path::new("/tmp/b");
+    driver
+        .remote
+        .symlink(a, file_path)
+        .expect("failed to create symlink");
+    driver
+        .remote
+        .symlink(b, a)
+        .expect("failed to create symlink");
+    // make `a` point to `b`
+    driver
+        .remote
+        .remove_file(a)
+        .expect("failed to remove symlink");
+    driver
+        .remote
+        .symlink(a, b)
+        .expect("failed to create symlink");
+
+    assert_eq!(driver.resolve_symlink(b).unwrap_err(), libc::ELOOP);
+}
+
+#[test]
+fn test_should_fail_resolving_symlink_over_max_depth() {
+    let mut driver = setup_driver_with_options(vec![MountOption::MaxSymlinkDepth(1)]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let a = Path::new("/tmp/a");
+    let b = Path::new("/tmp/b");
+    driver
+        .remote
+        .symlink(a, file_path)
+        .expect("failed to create symlink");
+    driver
+        .remote
+        .symlink(b, a)
+        .expect("failed to create symlink");
+
+    assert!(driver.resolve_symlink(a).is_ok());
+    assert_eq!(driver.resolve_symlink(b).unwrap_err(), libc::ELOOP);
+}
+
+#[test]
+fn test_should_buffer_writes_until_flush() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver
+        .write_buffered(attrs.ino, &file, b"there!", 6)
+        .expect("failed to write");
+
+    // the pending size is reported, while the remote is untouched
+    let (_, attrs) = driver.get_inode(attrs.ino).expect("failed to get inode");
+    assert_eq!(attrs.size, 12);
+    assert_eq!(driver.remote.stat(file_path).unwrap().metadata().size, 11);
+
+    driver
+        .flush_write_buffer(attrs.ino)
+        .expect("failed to flush");
+    let mut buffer = vec![0; 12];
+    driver
+        .read(file_path, &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(buffer, b"hello there!");
+    assert!(!driver.write_buffers[&attrs.ino].is_dirty());
+}
+
+#[test]
+fn test_should_report_flush_upload_error() {
+    let mut driver = setup_stub_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver.remote.fail_upload = Some(RemoteError::new(RemoteErrorType::IoError));
+    driver
+        .write_buffered(attrs.ino, &file, b"!", 11)
+        .expect("failed to write");
+
+    let err = driver.flush_write_buffer(attrs.ino).unwrap_err();
+    assert_eq!(super::remote_err_to_errno(&err), libc::EIO);
+    // the pending writes are kept for the next flush
+    assert!(driver.write_buffers[&attrs.ino].is_dirty());
+
+    driver.remote.fail_upload = None;
+    driver
+        .flush_write_buffer(attrs.ino)
+        .expect("failed to flush");
+    assert_eq!(driver.remote.stat(file_path).unwrap().metadata().size, 12);
+}
+
+#[test]
+fn test_should_sync_pending_writes() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    driver
+        .write_buffered(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+
+    driver.sync_file(attrs.ino, false).expect("failed to sync");
+    assert!(!driver.write_buffers[&attrs.ino].is_dirty());
+    assert_eq!(driver.remote.stat(file_path).unwrap().metadata().size, 11);
+
+    // nothing pending
+    driver.sync_file(attrs.ino, true).expect("failed to sync");
+}
+
+#[test]
+fn test_should_sync_on_release_with_fsync_on_close() {
+    let mut driver = setup_driver_with_options(vec![MountOption::FsyncOnClose(true)]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    driver
+        .write_buffered(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+
+    driver.release_writes(attrs.ino).expect("failed to release");
+    assert!(!driver.write_buffers[&attrs.ino].is_dirty());
+    assert_eq!(driver.remote.stat(file_path).unwrap().metadata().size, 11);
+
+    // nothing pending
+    driver.release_writes(attrs.ino).expect("failed to release");
+}
+
+#[test]
+fn test_should_report_sync_error() {
+    let mut driver = setup_stub_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    driver
+        .write_buffered(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+
+    driver.remote.fail_upload = Some(RemoteError::new(RemoteErrorType::IoError));
+    assert!(driver.sync_file(attrs.ino, true).is_err());
+    assert!(driver.write_buffers[&attrs.ino].is_dirty());
+}
+
+#[test]
+fn test_should_fail_cross_device_move_with_exdev() {
+    let mut driver = setup_stub_driver();
+    let (src, dest) = (Path::new("/upper/test.txt"), Path::new("/lower/test.txt"));
+    make_file_at(&mut driver, src, b"hello");
+    make_dir_at(&mut driver, Path::new("/lower"));
+
+    driver.remote.fail_mov = Some(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+    assert_eq!(driver.move_file(src, dest), Err(libc::EXDEV));
+    assert!(driver.remote.exists(src).unwrap());
+
+    driver.remote.fail_mov = Some(RemoteError::new(RemoteErrorType::ProtocolError));
+    assert_eq!(driver.move_file(src, dest), Err(libc::EIO));
+
+    driver.remote.fail_mov = None;
+    assert_eq!(driver.move_file(src, dest), Ok(()));
+    assert!(driver.remote.exists(dest).unwrap());
+}
+
+#[test]
+fn test_should_lookup_name_case_insensitive() {
+    let mut driver = setup_driver_with_options(vec![MountOption::CaseInsensitive]);
+    let file_path = Path::new("/tmp/Test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (_, parent) = driver
+        .get_inode_from_path(Path::new("/tmp"))
+        .expect("failed to get inode");
+
+    assert_eq!(
+        driver
+            .lookup_name(parent.ino, OsStr::new("TEST.TXT"))
+            .unwrap(),
+        file_path
+    );
+    assert_eq!(
+        driver
+            .lookup_name(parent.ino, OsStr::new("Test.txt"))
+            .unwrap(),
+        file_path
+    );
+    // no match keeps the requested name
+    assert_eq!(
+        driver
+            .lookup_name(parent.ino, OsStr::new("other.txt"))
+            .unwrap(),
+        Path::new("/tmp/other.txt")
+    );
+}
+
+#[test]
+fn test_should_lookup_name_case_sensitive_by_default() {
+    let mut driver = setup_driver();
+    make_file_at(&mut driver, Path::new("/tmp/Test.txt"), b"hello world");
+    let (_, parent) = driver
+        .get_inode_from_path(Path::new("/tmp"))
+        .expect("failed to get inode");
+
+    assert_eq!(
+        driver
+            .lookup_name(parent.ino, OsStr::new("TEST.TXT"))
+            .unwrap(),
+        Path::new("/tmp/TEST.TXT")
+    );
+}
+
+#[test]
+fn test_should_mount_root_prefix() {
+    let mut driver =
+        setup_driver_with_options(vec![MountOption::RootPrefix(PathBuf::from("/sub"))]);
+    make_file_at(&mut driver, Path::new("/sub/file.txt"), b"hello world");
+    make_file_at(&mut driver, Path::new("/other.txt"), b"hello world");
+
+    let entries = driver
+        .read_dir_entries(Path::new("/"))
+        .expect("failed to read dir");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, OsStr::new("file.txt"));
+    assert_eq!(
+        entries[0].inode,
+        Driver::<MemoryFs>::inode(Path::new("/file.txt"))
+    );
+
+    let (file, attrs) = driver
+        .get_inode_from_path(Path::new("/file.txt"))
+        .expect("failed to get inode");
+    assert_eq!(file.path(), Path::new("/file.txt"));
+    assert_eq!(attrs.size, 11);
+    assert!(driver.get_inode_from_path(Path::new("/other.txt")).is_err());
+
+    let mut buffer = vec![0; 5];
+    driver
+        .read(Path::new("/file.txt"), &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(buffer, b"hello");
+}
+
+#[test]
+fn test_should_exclude_entries_from_listing() {
+    let mut driver = setup_driver_with_options(vec![MountOption::Exclude(vec![
+        glob::Pattern::new(".DS_Store").unwrap(),
+        glob::Pattern::new("*.tmp").unwrap(),
+        glob::Pattern::new("/tmp/cache/*").unwrap(),
+    ])]);
+    make_file_at(&mut driver, Path::new("/tmp/.DS_Store"), b"");
+    make_file_at(&mut driver, Path::new("/tmp/file.tmp"), b"");
+    make_file_at(&mut driver, Path::new("/tmp/file.txt"), b"");
+    make_file_at(&mut driver, Path::new("/tmp/cache/file.txt"), b"");
+
+    let entries = driver
+        .read_dir_entries(Path::new("/tmp"))
+        .expect("failed to read dir");
+    let mut names = entries
+        .iter()
+        .map(|entry| entry.name.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["cache", "file.txt"]);
+    assert!(driver
+        .read_dir_entries(Path::new("/tmp/cache"))
+        .expect("failed to read dir")
+        .is_empty());
+}
+
+#[test]
+fn test_should_hide_dotfiles_from_listing() {
+    let mut driver = setup_driver_with_options(vec![MountOption::HideDotfiles]);
+    make_file_at(&mut driver, Path::new("/tmp/.hidden"), b"hello");
+    make_file_at(&mut driver, Path::new("/tmp/file.txt"), b"");
+
+    let (_, attrs) = driver
+        .get_inode_from_path(Path::new("/tmp"))
+        .expect("failed to get inode");
+    let names = driver
+        .read_dir_listing(attrs.ino, Path::new("/tmp"))
+        .expect("failed to read dir")
+        .iter()
+        .map(|entry| entry.name.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![".", "..", "file.txt"]);
+
+    // the dotfile is still accessible by name
+    let path = driver
+        .lookup_name(attrs.ino, OsStr::new(".hidden"))
+        .expect("failed to lookup name");
+    assert_eq!(path, Path::new("/tmp/.hidden"));
+    let mut buffer = vec![0; 5];
+    assert_eq!(driver.read(&path, &mut buffer, 0).unwrap(), 5);
+    assert_eq!(buffer, b"hello");
+}
+
+#[test]
+fn test_should_drop_prefetched_listings_on_release() {
+    let mut driver = setup_driver_with_options(vec![MountOption::PrefetchDirs]);
+    assert!(driver.prefetch_dirs());
+    let mut dirs = Vec::new();
+    for i in 0..100 {
+        let path = PathBuf::from(format!("/tmp/dir{i}"));
+        make_file_at(&mut driver, &path.join("file.txt"), b"");
+        let (_, attrs) = driver.get_inode_from_path(&path).unwrap();
+        dirs.push((attrs.ino, path));
+    }
+
+    let mut handles = Vec::new();
+    for (ino, path) in &dirs {
+        let fh = driver.file_handlers.open(1, *ino, true, false);
+        driver.prefetch_dir(1, fh, *ino, path);
+        let names = driver
+            .file_handlers
+            .dir_entries(1, fh)
+            .expect("listing not prefetched")
+            .iter()
+            .map(|entry| entry.name.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![".", "..", "file.txt"]);
+        handles.push(fh);
+    }
+    assert_eq!(driver.file_handlers.dir_listings(), 100);
+
+    for fh in handles {
+        driver.file_handlers.close(1, fh);
+    }
+    assert_eq!(driver.file_handlers.dir_listings(), 0);
+}
+
+#[test]
+fn test_should_tell_whether_excluded_files_are_hidden() {
+    let exclude = MountOption::Exclude(vec![glob::Pattern::new("*.tmp").unwrap()]);
+    let driver = setup_driver_with_options(vec![exclude.clone()]);
+    assert!(driver.is_excluded(Path::new("/tmp/file.tmp")));
+    assert!(!driver.is_excluded(Path::new("/tmp/file.txt")));
+    // explicit access is still allowed
+    assert!(!driver.hide_strict());
+
+    let driver = setup_driver_with_options(vec![exclude, MountOption::HideStrict]);
+    assert!(driver.hide_strict());
+}
+
+#[test]
+fn test_should_read_in_chunks() {
+    let mut driver = setup_driver_with_options(vec![MountOption::IoChunkSize(3)]);
+    let file_path = Path::new("/tmp/test.txt");
+    let content = (0..100u8).collect::<Vec<_>>();
+    make_file_at(&mut driver, file_path, &content);
+
+    let mut buffer = vec![0; 20];
+    assert_eq!(
+        driver
+            .read(file_path, &mut buffer, 50)
+            .expect("failed to read"),
+        20
+    );
+    assert_eq!(buffer, &content[50..70]);
+
+    // short read at the end of the file
+    assert_eq!(
+        driver
+            .read(file_path, &mut buffer, 90)
+            .expect("failed to read"),
+        10
+    );
+    assert_eq!(&buffer[..10], &content[90..]);
+}
+
+#[test]
+fn test_should_write_in_chunks() {
+    let mut driver = setup_driver_with_options(vec![MountOption::IoChunkSize(3)]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"");
+    let (file, _) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    let content = (0..100u8).collect::<Vec<_>>();
+    assert_eq!(
+        driver
+            .write(&file, &WriteBuffer::new(content.clone()))
+            .expect("failed to write"),
+        100
+    );
+
+    let mut buffer = vec![0; 100];
+    driver
+        .read(file_path, &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(buffer, content);
+}
+
+#[test]
+fn test_should_spill_write_buffer_to_temp_dir() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    for no_streams in [false, true] {
+        let mut driver = Driver::new(
+            StubFs::new(setup_memory_fs()),
+            driver_options(vec![
+                MountOption::WriteMemLimit(8),
+                MountOption::TempDir(temp_dir.path().to_path_buf()),
+                MountOption::IoChunkSize(3),
+            ]),
+        );
+        driver.remote.no_streams = no_streams;
+        let file_path = Path::new("/tmp/test.txt");
+        make_file_at(&mut driver, file_path, b"hello");
+        let (file, attrs) = driver
+            .get_inode_from_path(file_path)
+            .expect("failed to get inode");
+
+        driver
+            .write_buffered(attrs.ino, &file, b" world", 5)
+            .expect("failed to write");
+        let buffer = &driver.write_buffers[&attrs.ino];
+        assert!(buffer.data().is_none());
+        let mut data = vec![0; 6];
+        assert_eq!(buffer.read(3, &mut data).unwrap(), 6);
+        assert_eq!(data, b"lo wor");
+
+        driver
+            .flush_write_buffer(attrs.ino)
+            .expect("failed to flush");
+        let mut content = vec![0; 11];
+        driver.write_buffers.clear();
+        assert_eq!(driver.read(file_path, &mut content, 0).unwrap(), 11);
+        assert_eq!(content, b"hello world");
+    }
+}
+
+#[test]
+fn test_should_set_direct_io_open_flag() {
+    let driver = setup_driver();
+    assert_eq!(driver.open_flags() & fuser::consts::FOPEN_DIRECT_IO, 0);
+
+    let driver = setup_driver_with_options(vec![MountOption::DirectIo]);
+    assert_ne!(driver.open_flags() & fuser::consts::FOPEN_DIRECT_IO, 0);
+}
+
+#[test]
+fn test_should_revalidate_open_files() {
+    let mut driver = setup_driver_with_options(vec![MountOption::RevalidateInterval(
+        Duration::from_secs(3600),
+    )]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    let monitor = driver.monitor.as_ref().expect("no monitor");
+    monitor.watch_file(
+        attrs.ino,
+        file_path.to_path_buf(),
+        super::FileVersion::from(&Metadata::default().modified(attrs.mtime).size(attrs.size)),
+    );
+    driver
+        .page_cache
+        .replace(super::PageCache::new(super::PAGE_SIZE));
+    let mut buffer = vec![0; 5];
+    driver
+        .read_cached(attrs.ino, file_path, attrs.size, &mut buffer, 0)
+        .expect("failed to read");
+    assert!(driver.is_page_cached(attrs.ino, 0));
+
+    let monitor = driver.monitor.as_ref().expect("no monitor");
+    assert!(monitor.check_files(&mut driver.remote).is_empty());
+    driver.apply_remote_changes();
+    assert!(driver.is_page_cached(attrs.ino, 0));
+
+    // another client changes the file
+    make_file_at(&mut driver, file_path, b"hello world, again");
+    let monitor = driver.monitor.as_ref().expect("no monitor");
+    assert_eq!(monitor.check_files(&mut driver.remote), vec![attrs.ino]);
+    // the cached data is dropped by the driver before the next read
+    assert!(driver.is_page_cached(attrs.ino, 0));
+    driver.apply_remote_changes();
+    assert!(!driver.is_page_cached(attrs.ino, 0));
+    let monitor = driver.monitor.as_ref().expect("no monitor");
+    assert!(monitor.check_files(&mut driver.remote).is_empty());
+}
+
+#[test]
+fn test_should_detect_changes_in_watched_dirs() {
+    let mut driver = setup_driver_with_options(vec![
+        MountOption::WatchInterval(Duration::from_secs(1)),
+        MountOption::WatchDepth(1),
+    ]);
+    make_file_at(&mut driver, Path::new("/tmp/a.txt"), b"hello");
+    make_file_at(&mut driver, Path::new("/tmp/nested/b.txt"), b"hello");
+    make_file_at(&mut driver, Path::new("/tmp/nested/deep/c.txt"), b"hello");
+    let (_, attrs) = driver
+        .get_inode_from_path(Path::new("/tmp"))
+        .expect("failed to get inode");
+    let monitor = driver.monitor.as_ref().expect("no monitor");
+    monitor.watch_dir(attrs.ino, PathBuf::from("/tmp"), PathBuf::from("/tmp"));
+    assert!(monitor.check_dirs(&mut driver.remote).is_empty());
+
+    // another client changes the directory
+    make_file_at(&mut driver, Path::new("/tmp/a.txt"), b"hello world");
+    make_file_at(&mut driver, Path::new("/tmp/nested/new.txt"), b"hello");
+    make_file_at(
+        &mut driver,
+        Path::new("/tmp/nested/deep/c.txt"),
+        b"hello world",
+    );
+    driver
+        .remote
+        .remove_file(Path::new("/tmp/nested/b.txt"))
+        .expect("failed to remove file");
+    driver.missing_paths.insert(
+        PathBuf::from("/tmp/nested/new.txt"),
+        std::time::Instant::now(),
+    );
+    let monitor = driver.monitor.as_ref().expect("no monitor");
+    assert_eq!(
+        monitor.check_dirs(&mut driver.remote),
+        vec![
+            Change::Modified(PathBuf::from("/tmp/a.txt")),
+            Change::Deleted(PathBuf::from("/tmp/nested/b.txt")),
+            Change::Created(PathBuf::from("/tmp/nested/new.txt")),
+        ]
+    );
+    assert!(monitor.check_dirs(&mut driver.remote).is_empty());
+
+    // the created entry isn't reported as missing anymore once the changes are applied
+    assert!(driver
+        .missing_paths
+        .contains_key(Path::new("/tmp/nested/new.txt")));
+    driver.apply_remote_changes();
+    assert!(!driver
+        .missing_paths
+        .contains_key(Path::new("/tmp/nested/new.txt")));
+}
+
+#[test]
+fn test_should_clamp_kernel_values() {
+    let max = 128 * 1024;
+    let mut value = 0;
+    let mut set = |v: u32| {
+        if v > max {
+            Err(max)
+        } else {
+            value = v;
+            Ok(0)
+        }
+    };
+    assert_eq!(
+        super::negotiate_kernel_value("max_write", 4096, &mut set),
+        4096
+    );
+    assert_eq!(
+        super::negotiate_kernel_value("max_write", 1024 * 1024, &mut set),
+        max
+    );
+    assert_eq!(value, max);
+}
+
+#[test]
+fn test_should_keep_stream_open_for_sequential_reads() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    let content = (0..100u8).collect::<Vec<_>>();
+    make_file_at(&mut driver, file_path, &content);
+
+    let mut buffer = vec![0; 40];
+    assert_eq!(driver.read(file_path, &mut buffer, 0).unwrap(), 40);
+    assert!(driver.read_stream.as_ref().unwrap().is_at(file_path, 40));
+    assert_eq!(driver.read(file_path, &mut buffer, 40).unwrap(), 40);
+    assert_eq!(buffer, &content[40..80]);
+    assert!(driver.read_stream.as_ref().unwrap().is_at(file_path, 80));
+
+    // non-sequential read reopens the stream
+    assert_eq!(driver.read(file_path, &mut buffer, 10).unwrap(), 40);
+    assert_eq!(buffer, &content[10..50]);
+    assert!(driver.read_stream.as_ref().unwrap().is_at(file_path, 50));
+
+    // reaching EOF closes the stream
+    assert_eq!(driver.read(file_path, &mut buffer, 50).unwrap(), 40);
+    assert_eq!(driver.read(file_path, &mut buffer, 90).unwrap(), 10);
+    assert!(driver.read_stream.is_none());
+
+    assert_eq!(driver.read(file_path, &mut buffer, 0).unwrap(), 40);
+    driver.close_read_stream();
+    assert!(driver.read_stream.is_none());
+}
+
+#[test]
+fn test_should_not_stat_while_stream_is_open() {
+    let mut driver = setup_stub_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    let content = (0..100u8).collect::<Vec<_>>();
+    make_file_at(&mut driver, file_path, &content);
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    driver.remote.single_transfer = true;
+
+    let mut buffer = vec![0; 40];
+    let file = driver
+        .file_to_read(attrs.ino, 0)
+        .expect("failed to get file");
+    assert_eq!(
+        driver
+            .read_cached(attrs.ino, file.path(), file.metadata().size, &mut buffer, 0)
+            .unwrap(),
+        40
+    );
+    driver.keep_stream_file(file);
+    assert_eq!(driver.remote.open_streams, 1);
+
+    // the sequential read is served with the file kept along with the stream
+    let file = driver
+        .file_to_read(attrs.ino, 40)
+        .expect("stat while the stream is open");
+    assert_eq!(file.metadata().size, 100);
+    assert_eq!(
+        driver
+            .read_cached(
+                attrs.ino,
+                file.path(),
+                file.metadata().size,
+                &mut buffer,
+                40
+            )
+            .unwrap(),
+        40
+    );
+    assert_eq!(buffer, &content[40..80]);
+    driver.keep_stream_file(file);
+
+    // a non-sequential read closes the stream before the stat
+    let file = driver
+        .file_to_read(attrs.ino, 10)
+        .expect("failed to get file");
+    assert_eq!(file.metadata().size, 100);
+    assert!(driver.read_stream.is_none());
+    assert_eq!(driver.remote.open_streams, 0);
+}
+
+#[test]
+fn test_should_switch_to_read_only_after_write_failures() {
+    let mut driver = Driver::new(
+        StubFs::new(setup_memory_fs()),
+        driver_options(vec![MountOption::WriteFailureThreshold(2)]),
+    );
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver.remote.fail_upload = Some(RemoteError::new(RemoteErrorType::IoError));
+    driver
+        .write_buffered(attrs.ino, &file, b"!", 11)
+        .expect("failed to write");
+    assert!(driver.flush_write_buffer(attrs.ino).is_err());
+    assert_eq!(driver.check_writable(), Ok(()));
+
+    assert!(driver.flush_write_buffer(attrs.ino).is_err());
+    assert_eq!(driver.check_writable(), Err(libc::EROFS));
+}
+
+#[test]
+fn test_should_drain_changes_on_unmount() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    let write = driver.begin_change().expect("not draining");
+    driver
+        .write_buffered(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+    driver.drain.start();
+    assert!(driver.begin_change().is_err());
+    assert!(!driver.drain.wait(Duration::ZERO));
+    drop(write);
+    assert!(driver.drain.wait(Duration::ZERO));
+
+    // pending writes are uploaded on destroy
+    driver.flush_all_write_buffers();
+    driver.write_buffers.clear();
+    let mut buffer = vec![0; 64];
+    let bytes_read = driver
+        .read(file_path, &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(&buffer[..bytes_read], b"hello world");
+}
+
+#[test]
+fn test_should_report_out_of_space_on_write() {
+    let mut driver = setup_stub_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    driver
+        .write_buffered(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+
+    driver.remote.fail_upload = Some(RemoteError::new_ex(
+        RemoteErrorType::ProtocolError,
+        "552 Disk quota exceeded",
+    ));
+    let err = driver.flush_write_buffer(attrs.ino).unwrap_err();
+    assert_eq!(super::write_err_to_errno(&err), libc::ENOSPC);
+
+    driver.remote.fail_upload = Some(RemoteError::new_ex(
+        RemoteErrorType::IoError,
+        "No space left on device (os error 28)",
+    ));
+    let err = driver.flush_write_buffer(attrs.ino).unwrap_err();
+    assert_eq!(super::write_err_to_errno(&err), libc::ENOSPC);
+
+    // other errors are not affected
+    driver.remote.fail_upload = Some(RemoteError::new_ex(
+        RemoteErrorType::ProtocolError,
+        "connection reset",
+    ));
+    let err = driver.flush_write_buffer(attrs.ino).unwrap_err();
+    assert_eq!(super::write_err_to_errno(&err), libc::EIO);
+}
+
+#[test]
+fn test_should_synthesize_root_attributes() {
+    let mut driver = setup_stub_driver();
+    make_file_at(&mut driver, Path::new("/a.txt"), b"hello");
+    driver.remote.fail_stat = vec![(
+        PathBuf::from("/"),
+        RemoteError::new(RemoteErrorType::StatFailed),
+    )];
+
+    let (_, attrs) = driver
+        .get_inode(super::ROOT_INODE)
+        .expect("failed to get root");
+    assert_eq!(attrs.kind, fuser::FileType::Directory);
+    assert_eq!(attrs.perm, 0o755);
+    assert_eq!(attrs.uid, nix::unistd::getuid().as_raw());
+
+    // the root can still be listed
+    let entries = driver
+        .read_dir_entries(Path::new("/"))
+        .expect("failed to list root");
+    assert!(entries.iter().any(|entry| entry.name == "a.txt"));
+
+    // other stat errors are still reported
+    driver.remote.fail_stat = vec![(
+        PathBuf::from("/a.txt"),
+        RemoteError::new(RemoteErrorType::StatFailed),
+    )];
+    assert!(driver.get_inode_from_path(Path::new("/a.txt")).is_err());
+}
+
+#[test]
+fn test_should_follow_symlinks() {
+    let file_path = Path::new("/tmp/test.txt");
+    let link_path = Path::new("/tmp/link");
+    let setup = |options| {
+        let mut driver = setup_driver_with_options(options);
+        make_file_at(&mut driver, file_path, b"hello world");
+        driver
+            .remote
+            .symlink(link_path, file_path)
+            .expect("failed to create symlink");
+        driver
+    };
+    let link_kind = |driver: &mut Driver<MemoryFs>| {
+        driver
+            .read_dir_entries(Path::new("/tmp"))
+            .expect("failed to list dir")
+            .into_iter()
+            .find(|entry| entry.name == "link")
+            .expect("link not listed")
+            .kind
+    };
+
+    let mut driver = setup(vec![]);
+    let (_, attrs) = driver.get_inode_from_path(link_path).unwrap();
+    assert_eq!(attrs.kind, fuser::FileType::Symlink);
+    assert_eq!(link_kind(&mut driver), fuser::FileType::Symlink);
+
+    let mut driver = setup(vec![MountOption::FollowSymlinks]);
+    let (file, attrs) = driver.get_inode_from_path(link_path).unwrap();
+    assert_eq!(file.path(), link_path);
+    assert_eq!(attrs.ino, Driver::<MemoryFs>::inode(link_path));
+    assert_eq!(attrs.kind, fuser::FileType::RegularFile);
+    assert_eq!(attrs.size, 11);
+    assert_eq!(link_kind(&mut driver), fuser::FileType::RegularFile);
+}
+
+#[test]
+fn test_should_read_tail_at_eof() {
+    for no_streams in [false, true] {
+        let mut driver = setup_stub_driver();
+        driver.remote.no_streams = no_streams;
+        let file_path = Path::new("/tmp/test.txt");
+        make_file_at(&mut driver, file_path, b"hello world");
+
+        // the read straddles the end of the file
+        let mut buffer = vec![0; 8];
+        let bytes_read = driver
+            .read(file_path, &mut buffer, 6)
+            .expect("failed to read");
+        assert_eq!(&buffer[..bytes_read], b"world");
+
+        // past the end of the file
+        let bytes_read = driver
+            .read(file_path, &mut buffer, 20)
+            .expect("failed to read");
+        assert_eq!(bytes_read, 0);
+    }
+}
+
+#[test]
+fn test_should_create_tempfiles_in_temp_dir() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut driver = Driver::new(
+        StubFs::new(setup_memory_fs()),
+        driver_options(vec![MountOption::TempDir(temp_dir.path().to_path_buf())]),
+    );
+    driver.remote.no_streams = true;
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    assert!(driver.check_temp_dir().is_ok());
+
+    let mut buffer = vec![0; 5];
+    assert_eq!(driver.read(file_path, &mut buffer, 6).unwrap(), 5);
+    assert_eq!(buffer, b"world");
+
+    // reading fails if temporary files can't be created in the directory
+    let missing_dir = temp_dir.path().join("missing");
+    driver.options = driver_options(vec![MountOption::TempDir(missing_dir)]);
+    assert!(driver.check_temp_dir().is_err());
+    assert!(driver.read(file_path, &mut buffer, 0).is_err());
+}
+
+#[test]
+fn test_should_preserve_atime_when_setting_mtime() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let atime = UNIX_EPOCH + Duration::from_secs(1_000);
+    let mtime = UNIX_EPOCH + Duration::from_secs(2_000);
+    let (mut file, current) = driver.get_inode_from_path(file_path).unwrap();
+
+    // touch -m on a remote which doesn't report the access time
+    file.metadata.accessed = None;
+    file.metadata.modified = Some(atime);
+    let current = fuser::FileAttr {
+        atime,
+        mtime: atime,
+        ..current
+    };
+    super::set_times(
+        &mut file.metadata,
+        &current,
+        None,
+        Some(fuser::TimeOrNow::SpecificTime(mtime)),
+    );
+    assert_eq!(file.metadata.accessed, Some(atime));
+    assert_eq!(file.metadata.modified, Some(mtime));
+
+    // touch -a
+    super::set_times(
+        &mut file.metadata,
+        &current,
+        Some(fuser::TimeOrNow::Now),
+        None,
+    );
+    assert_ne!(file.metadata.accessed, Some(atime));
+    assert_eq!(file.metadata.modified, Some(mtime));
+
+    // both set to now are the same instant
+    super::set_times(
+        &mut file.metadata,
+        &current,
+        Some(fuser::TimeOrNow::Now),
+        Some(fuser::TimeOrNow::Now),
+    );
+    assert_eq!(file.metadata.accessed, file.metadata.modified);
+}
+
+#[test]
+fn test_should_round_trip_times_under_granularity() {
+    let mut driver =
+        setup_driver_with_options(vec![MountOption::TimeGranularity(Duration::from_secs(1))]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let mtime = UNIX_EPOCH + Duration::from_millis(2_000_750);
+
+    let (mut file, _) = driver.get_inode_from_path(file_path).unwrap();
+    file.metadata.modified = Some(mtime);
+    driver.truncate_times(&mut file.metadata);
+    driver
+        .remote
+        .setstat(file_path, file.metadata().clone())
+        .unwrap();
+    let (mut file, attrs) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(attrs.mtime, UNIX_EPOCH + Duration::from_secs(2_000));
+
+    // setting the time read back doesn't change it
+    file.metadata.modified = Some(attrs.mtime);
+    driver.truncate_times(&mut file.metadata);
+    driver
+        .remote
+        .setstat(file_path, file.metadata().clone())
+        .unwrap();
+    let (_, again) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(again.mtime, attrs.mtime);
+
+    // reported times are truncated even when stored with a finer precision
+    let (mut file, _) = driver.get_inode_from_path(file_path).unwrap();
+    file.metadata.modified = Some(mtime);
+    driver
+        .remote
+        .setstat(file_path, file.metadata().clone())
+        .unwrap();
+    let (_, attrs) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(attrs.mtime, UNIX_EPOCH + Duration::from_secs(2_000));
+}
+
+#[test]
+fn test_should_upload_writes_synchronously() {
+    let mut driver = Driver::new(
+        StubFs::new(setup_memory_fs()),
+        driver_options(vec![MountOption::SyncWrites]),
+    );
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver
+        .write_data(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+    assert!(!driver.write_buffers[&attrs.ino].is_dirty());
+    assert_eq!(driver.remote.stat(file_path).unwrap().metadata().size, 11);
+
+    // the upload error is reported by the write
+    driver.remote.fail_upload = Some(RemoteError::new(RemoteErrorType::IoError));
+    assert!(driver.write_data(attrs.ino, &file, b"!", 11).is_err());
+}
+
+#[test]
+fn test_should_append_writes_at_end_of_file() {
+    let mut driver = Driver::new(
+        StubFs::new(setup_memory_fs()),
+        driver_options(vec![MountOption::AppendWrites]),
+    );
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let uploads = driver.remote.uploads;
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver
+        .write_data(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+    assert_eq!(driver.remote.appends, 1);
+    assert_eq!(driver.remote.uploads, uploads);
+    assert!(!driver.write_buffers.contains_key(&attrs.ino));
+
+    // not at the end of the file
+    let (file, _) = driver.get_inode(attrs.ino).unwrap();
+    driver
+        .write_data(attrs.ino, &file, b"H", 0)
+        .expect("failed to write");
+    driver.flush_write_buffer(attrs.ino).unwrap();
+    assert_eq!(driver.remote.appends, 1);
+
+    // the remote can't append
+    driver.remote.no_append = true;
+    let (file, _) = driver.get_inode(attrs.ino).unwrap();
+    driver
+        .write_data(attrs.ino, &file, b"!", 11)
+        .expect("failed to write");
+    assert!(driver.append_unsupported);
+    driver.flush_write_buffer(attrs.ino).unwrap();
+
+    let mut buffer = vec![0; 12];
+    assert_eq!(driver.read(file_path, &mut buffer, 0).unwrap(), 12);
+    assert_eq!(buffer, b"Hello world!");
+}
+
+#[test]
+fn test_should_not_read_zero_bytes_from_remote() {
+    let mut driver = setup_driver();
+
+    // the file doesn't exist, so any remote call would fail
+    let mut buffer = [];
+    assert_eq!(
+        driver
+            .read(Path::new("/tmp/missing.txt"), &mut buffer, 0)
+            .unwrap(),
+        0
+    );
+    assert!(driver.read_stream.is_none());
+}
+
+#[test]
+fn test_should_not_write_zero_bytes_to_remote() {
+    let mut driver = Driver::new(
+        StubFs::new(setup_memory_fs()),
+        driver_options(vec![MountOption::SyncWrites]),
+    );
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let uploads = driver.remote.uploads;
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver.remote.fail_upload = Some(RemoteError::new(RemoteErrorType::IoError));
+    assert_eq!(driver.write_data(attrs.ino, &file, b"", 5).unwrap(), 0);
+    assert_eq!(driver.remote.uploads, uploads);
+    assert!(!driver.write_buffers.contains_key(&attrs.ino));
+}
+
+#[test]
+fn test_should_defer_writes_by_default() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    driver
+        .write_data(attrs.ino, &file, b" world", 5)
+        .expect("failed to write");
+    assert!(driver.write_buffers[&attrs.ino].is_dirty());
+    assert_eq!(driver.remote.stat(file_path).unwrap().metadata().size, 5);
+}
+
+#[test]
+fn test_should_apply_umask() {
+    let driver = setup_driver();
+    assert_eq!(driver.apply_umask(0o666, 0o022), 0o644);
+    assert_eq!(driver.apply_umask(0o777, 0o022), 0o755);
+    // the file type is kept
+    assert_eq!(
+        driver.apply_umask(libc::S_IFREG | 0o666, 0o027),
+        libc::S_IFREG | 0o640
+    );
+
+    let driver = setup_driver_with_options(vec![MountOption::Umask(0o077)]);
+    assert_eq!(driver.apply_umask(0o666, 0o022), 0o600);
+}
+
+#[test]
+fn test_should_poll_files_as_always_ready() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    let events = (libc::POLLIN | libc::POLLOUT | libc::POLLPRI) as u32;
+    assert_eq!(
+        driver.poll_events(attrs.ino, events),
+        Ok((libc::POLLIN | libc::POLLOUT) as u32)
+    );
+    assert_eq!(driver.poll_events(attrs.ino, 0), Ok(0));
+    assert_eq!(driver.poll_events(u64::MAX, events), Err(libc::ENOENT));
+}
+
+#[test]
+fn test_should_check_name_length() {
+    let driver = setup_driver();
+    assert_eq!(driver.check_name_len(OsStr::new(&"a".repeat(255))), Ok(()));
+    assert_eq!(
+        driver.check_name_len(OsStr::new(&"a".repeat(256))),
+        Err(libc::ENAMETOOLONG)
+    );
+
+    let driver = setup_driver_with_options(vec![MountOption::MaxNameLen(8)]);
+    assert_eq!(driver.max_name_len(), 8);
+    assert_eq!(driver.check_name_len(OsStr::new("12345678")), Ok(()));
+    // length is in bytes
+    assert_eq!(
+        driver.check_name_len(OsStr::new("1234567è")),
+        Err(libc::ENAMETOOLONG)
+    );
+}
+
+#[test]
+fn test_should_look_up_and_remove_existing_long_names() {
+    let mut driver = setup_driver_with_options(vec![MountOption::MaxNameLen(8)]);
+    let file_path = Path::new("/tmp/a-very-long-name.txt");
+    make_file_at(&mut driver, file_path, b"hello world");
+    let name = file_path.file_name().unwrap();
+    // the name can't be created through the mount
+    assert_eq!(driver.check_name_len(name), Err(libc::ENAMETOOLONG));
+
+    let (_, parent) = driver
+        .get_inode_from_path(Path::new("/tmp"))
+        .expect("failed to get parent");
+    let path = driver
+        .lookup_name(parent.ino, name)
+        .expect("failed to look up long name");
+    assert_eq!(path, file_path);
+    assert!(driver.get_inode_from_path(&path).is_ok());
+
+    // it can be removed
+    driver
+        .remote
+        .remove_file(&driver.remote_path(&path))
+        .expect("failed to remove file");
+    assert_eq!(driver.lookup_name(parent.ino, name), Ok(path.clone()));
+    assert!(driver.get_inode_from_path(&path).is_err());
+}
+
+#[test]
+fn test_should_check_exec_access_on_open() {
+    let driver = setup_driver();
+    let exec = libc::O_RDONLY | super::FMODE_EXEC;
+    assert_eq!(
+        driver.open_access(exec),
+        Ok((AccessFlags::X_OK, true, false))
+    );
+    assert_eq!(
+        driver.open_access(libc::O_RDONLY),
+        Ok((AccessFlags::R_OK, true, false))
+    );
+
+    let driver = setup_driver_with_options(vec![MountOption::NoExec]);
+    assert_eq!(driver.open_access(exec), Err(libc::EACCES));
+    assert_eq!(
+        driver.open_access(libc::O_RDONLY),
+        Ok((AccessFlags::R_OK, true, false))
+    );
+}
+
+#[test]
+fn test_should_get_background_requests_limits() {
+    let driver = setup_driver();
+    assert_eq!(driver.max_background(), 64);
+    assert_eq!(driver.congestion_threshold(), None);
+
+    let driver = setup_driver_with_options(vec![
+        MountOption::MaxBackground(128),
+        MountOption::CongestionThreshold(96),
+    ]);
+    assert_eq!(driver.max_background(), 128);
+    assert_eq!(driver.congestion_threshold(), Some(96));
+}
+
+#[test]
+fn test_should_get_cache_timeouts() {
+    let driver = setup_driver();
+    assert_eq!(driver.attr_timeout(), Duration::from_secs(1));
+    assert_eq!(driver.entry_timeout(), Duration::from_secs(1));
+
+    let driver = setup_driver_with_options(vec![
+        MountOption::AttrTimeout(Duration::ZERO),
+        MountOption::EntryTimeout(Duration::from_secs(60)),
+    ]);
+    assert_eq!(driver.attr_timeout(), Duration::ZERO);
+    assert_eq!(driver.entry_timeout(), Duration::from_secs(60));
+}
+
+#[test]
+fn test_should_cache_missing_paths() {
+    let file_path = Path::new("/missing.txt");
+    let mut driver = setup_driver();
+    assert!(driver.get_inode_from_path(file_path).is_err());
+
+    // created behind the mount, so the second lookup is served from the cache
+    make_file_at(&mut driver, file_path, b"hello");
+    assert!(driver.get_inode_from_path(file_path).is_err());
+
+    driver.forget_missing(file_path);
+    assert!(driver.get_inode_from_path(file_path).is_ok());
+}
+
+#[test]
+fn test_should_not_cache_missing_paths_with_zero_negative_timeout() {
+    let file_path = Path::new("/missing.txt");
+    let mut driver = setup_driver_with_options(vec![MountOption::NegativeTimeout(Duration::ZERO)]);
+    assert!(driver.get_inode_from_path(file_path).is_err());
+
+    make_file_at(&mut driver, file_path, b"hello");
+    assert!(driver.get_inode_from_path(file_path).is_ok());
+}
+
+#[test]
+fn test_should_persist_mode_of_created_file() {
+    let mut driver = setup_stub_driver();
+    driver.remote.drop_create_metadata = true;
+    make_dir_at(&mut driver, Path::new("/tmp"));
+
+    let file_path = Path::new("/tmp/test.txt");
+    let metadata = Metadata {
+        mode: Some(UnixPex::from(0o600)),
+        ..Default::default()
+    };
+    driver
+        .create_empty_file(file_path, &metadata)
+        .expect("failed to create file");
+
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    assert_eq!(attrs.perm, 0o600);
+}
+
+#[test]
+fn test_should_create_missing_file_for_open() {
+    let mut driver = setup_driver();
+    make_dir_at(&mut driver, Path::new("/tmp"));
+    let file_path = Path::new("/tmp/test.txt");
+
+    assert!(driver
+        .create_for_open(
+            file_path,
+            &Metadata::default(),
+            OFlag::O_CREAT | OFlag::O_EXCL
+        )
+        .is_ok());
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(file.metadata().size, 0);
+}
+
+#[test]
+fn test_should_create_file_with_stream_without_create_file() {
+    let mut driver = setup_stub_driver();
+    driver.remote.no_create_file = true;
+    make_dir_at(&mut driver, Path::new("/tmp"));
+    let file_path = Path::new("/tmp/test.txt");
+
+    assert!(driver
+        .create_for_open(
+            file_path,
+            &Metadata::default(),
+            OFlag::O_CREAT | OFlag::O_EXCL
+        )
+        .is_ok());
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+    assert!(file.is_file());
+    assert_eq!(file.metadata().size, 0);
+    assert_eq!(driver.remote.uploads, 1);
+
+    // truncating an existing file goes through the stream too
+    driver.remote.no_create_file = false;
+    make_file_at(&mut driver, file_path, b"hello");
+    driver.remote.no_create_file = true;
+    assert!(driver
+        .create_for_open(
+            file_path,
+            &Metadata::default(),
+            OFlag::O_CREAT | OFlag::O_TRUNC
+        )
+        .is_ok());
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(file.metadata().size, 0);
+}
+
+#[test]
+fn test_should_keep_existing_file_content_on_create() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+
+    assert!(driver
+        .create_for_open(file_path, &Metadata::default(), OFlag::O_CREAT)
+        .is_ok());
+    let mut buffer = vec![0; 5];
+    assert_eq!(driver.read(file_path, &mut buffer, 0).unwrap(), 5);
+    assert_eq!(buffer, b"hello");
+}
+
+#[test]
+fn test_should_fail_exclusive_create_of_existing_file() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+
+    assert_eq!(
+        driver
+            .create_for_open(
+                file_path,
+                &Metadata::default(),
+                OFlag::O_CREAT | OFlag::O_EXCL
+            )
+            .unwrap_err(),
+        libc::EEXIST
+    );
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(file.metadata().size, 5);
+}
+
+#[test]
+fn test_should_fail_second_exclusive_create() {
+    let mut driver = setup_driver();
+    make_dir_at(&mut driver, Path::new("/tmp"));
+    let file_path = Path::new("/tmp/test.txt");
+    let metadata = Metadata {
+        mode: Some(UnixPex::from(0o644)),
+        ..Default::default()
+    };
+
+    assert!(driver
+        .create_for_open(file_path, &metadata, OFlag::O_EXCL)
+        .is_ok());
+    assert_eq!(
+        driver
+            .create_for_open(file_path, &metadata, OFlag::O_EXCL)
+            .unwrap_err(),
+        libc::EEXIST
+    );
+}
+
+#[test]
+fn test_should_truncate_existing_file_on_create() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let mut metadata = Metadata::default().size(5);
+    metadata.mode = Some(UnixPex::from(0o640));
+    driver.remote.setstat(file_path, metadata).unwrap();
+
+    let requested = Metadata {
+        mode: Some(UnixPex::from(0o600)),
+        ..Default::default()
+    };
+    assert!(driver
+        .create_for_open(file_path, &requested, OFlag::O_CREAT | OFlag::O_TRUNC)
+        .is_ok());
+    let (file, attrs) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(file.metadata().size, 0);
+    assert_eq!(attrs.perm, 0o640);
+}
+
+#[test]
+fn test_should_coalesce_small_writes() {
+    let mut driver = Driver::new(
+        StubFs::new(setup_memory_fs()),
+        driver_options(vec![MountOption::WriteCoalesceWindow(
+            Duration::from_millis(100),
+        )]),
+    );
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"");
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    let uploads = driver.remote.uploads;
+
+    for offset in 0..100 {
+        driver
+            .write_data(attrs.ino, &file, b"x", offset)
+            .expect("failed to write");
+    }
+    assert!(driver.flush_idle_writes().is_empty());
+    assert_eq!(driver.remote.uploads, uploads);
+
+    std::thread::sleep(Duration::from_millis(150));
+    assert_eq!(driver.flush_idle_writes(), vec![attrs.ino]);
+    assert_eq!(driver.remote.uploads, uploads + 1);
+    assert!(driver.flush_idle_writes().is_empty());
+
+    let mut buffer = vec![0; 128];
+    driver.write_buffers.clear();
+    let bytes_read = driver
+        .read(file_path, &mut buffer, 0)
+        .expect("failed to read");
+    assert_eq!(&buffer[..bytes_read], [b'x'; 100]);
+}
+
+#[test]
+fn test_should_swap_in_refreshed_remote() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+
+    // nothing to swap in
+    driver.swap_pending_remote();
+    assert!(driver.remote.exists(file_path).unwrap());
+
+    let mut remote = setup_memory_fs();
+    remote.disconnect().unwrap();
+    driver.pending_remote.set(remote);
+    driver.swap_pending_remote();
+    assert!(driver.remote.is_connected());
+    assert!(!driver.remote.exists(file_path).unwrap());
+    assert!(driver.pending_remote.take().is_none());
+}
+
+#[test]
+fn test_should_account_statfs_blocks() {
+    let driver = setup_driver_with_options(vec![MountOption::Capacity(1024 * 1024)]);
+    assert_eq!(driver.capacity(), 1024 * 1024);
+    assert_eq!(setup_driver().capacity(), super::DEFAULT_CAPACITY);
+
+    let (blocks, free) = block_usage(driver.capacity(), 1000);
+    assert_eq!(blocks, 2048);
+    assert!(free <= blocks);
+    assert_eq!(2 + free, blocks);
+
+    // more used than the capacity reports a full filesystem
+    let (blocks, free) = block_usage(1024, 4096);
+    assert_eq!(blocks, 8);
+    assert_eq!(free, 0);
+}
+
+#[test]
+fn test_should_map_owner_ids() {
+    let mut driver = setup_driver_with_options(vec![MountOption::IdMap {
+        uid_map: vec![(1002, 4242)],
+        gid_map: vec![(100, 4343)],
+        default_uid: None,
+        default_gid: None,
+    }]);
+    make_dir_at(&mut driver, Path::new("/tmp"));
+
+    // local to remote on create
+    let file_path = Path::new("/tmp/test.txt");
+    let metadata = Metadata {
+        mode: Some(UnixPex::from(0o644)),
+        uid: Some(driver.remote_uid(4242)),
+        gid: Some(driver.remote_gid(4343)),
+        ..Default::default()
+    };
+    driver
+        .create_empty_file(file_path, &metadata)
+        .expect("failed to create file");
+    let remote = driver.remote.stat(file_path).expect("failed to stat");
+    assert_eq!(remote.metadata().uid, Some(1002));
+    assert_eq!(remote.metadata().gid, Some(100));
+
+    // remote to local on stat
+    let (file, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+    assert_eq!(attrs.uid, 4242);
+    assert_eq!(attrs.gid, 4343);
+    assert!(driver.check_access(&file, 4242, 4343, AccessFlags::W_OK));
+}
+
+#[test]
+fn test_should_squash_owners() {
+    let root_squash = setup_driver_with_options(vec![
+        MountOption::RootSquash {
+            uid: 65534,
+            gid: 65534,
+        },
+        MountOption::IdMap {
+            uid_map: vec![(0, 1), (1002, 1000)],
+            gid_map: vec![],
+            default_uid: None,
+            default_gid: None,
+        },
+    ]);
+    assert_eq!(root_squash.local_uid(0), 65534);
+    assert_eq!(root_squash.local_gid(0), 65534);
+    assert_eq!(root_squash.local_uid(1002), 1000);
+    assert_eq!(root_squash.local_gid(100), 100);
+
+    let all_squash = setup_driver_with_options(vec![
+        MountOption::AllSquash {
+            uid: 1000,
+            gid: 100,
+        },
+        MountOption::RootSquash {
+            uid: 65534,
+            gid: 65534,
+        },
+    ]);
+    assert_eq!(all_squash.local_uid(0), 1000);
+    assert_eq!(all_squash.local_uid(1002), 1000);
+    assert_eq!(all_squash.local_gid(0), 100);
+}
+
+#[test]
+fn test_should_mask_modes() {
+    let file_path = Path::new("/tmp/test.txt");
+    let mut metadata = Metadata::default().size(5);
+    metadata.mode = Some(UnixPex::from(0o602));
+
+    // no mask by default
+    let mut driver = setup_driver();
+    make_file_at(&mut driver, file_path, b"hello");
+    driver.remote.setstat(file_path, metadata.clone()).unwrap();
+    let (_, attrs) = driver.get_inode_from_path(file_path).unwrap();
+    assert_eq!(attrs.perm, 0o602);
+    assert_eq!(driver.mask_mode(0o602), 0o602);
+
+    let mut driver = setup_driver_with_options(vec![MountOption::AttrMask {
+        or_bits: 0o040,
+        and_bits: 0o775,
+    }]);
+    make_file_at(&mut driver, file_path, b"hello");
+    driver.remote.setstat(file_path, metadata).unwrap();
+    let (file, attrs) = driver.get_inode_from_path(file_path).unwrap();
+    // group read is granted and other write is stripped
+    assert_eq!(attrs.perm, 0o640);
+    assert_eq!(driver.mask_mode(0o777), 0o775);
+    assert_eq!(driver.mask_mode(0o000), 0o040);
+    // the mode on the remote is untouched
+    assert_eq!(
+        driver
+            .remote
+            .stat(file_path)
+            .unwrap()
+            .metadata()
+            .mode
+            .map(u32::from),
+        Some(0o602)
+    );
+    // the masked mode is the one checked
+    assert!(!driver.check_access(&file, 1, 1, AccessFlags::W_OK));
+}
+
+#[test]
+fn test_should_defer_access_checks_to_remote() {
+    let file_path = Path::new("/tmp/test.txt");
+    let mut metadata = Metadata::default().size(5);
+    metadata.mode = Some(UnixPex::from(0o600));
+
+    let mut driver = setup_driver();
+    make_file_at(&mut driver, file_path, b"hello");
+    driver.remote.setstat(file_path, metadata.clone()).unwrap();
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+    assert!(!driver.check_access(&file, 1, 1, AccessFlags::R_OK));
+
+    let mut driver = setup_driver_with_options(vec![MountOption::NoAccessCheck]);
+    make_file_at(&mut driver, file_path, b"hello");
+    driver.remote.setstat(file_path, metadata).unwrap();
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+    assert!(driver.check_access(
+        &file,
+        1,
+        1,
+        AccessFlags::R_OK | AccessFlags::W_OK | AccessFlags::X_OK
+    ));
+}
+
+#[test]
+fn test_should_report_missing_xattrs() {
+    let mut driver = setup_driver();
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, b"hello");
+    let (_, attrs) = driver
+        .get_inode_from_path(file_path)
+        .expect("failed to get inode");
+
+    assert_eq!(
+        driver.get_xattr(attrs.ino, OsStr::new("system.posix_acl_access")),
+        Err(libc::ENODATA)
+    );
+    assert_eq!(driver.list_xattr(attrs.ino), Ok(Vec::new()));
+    assert_eq!(
+        driver.get_xattr(u64::MAX, OsStr::new("user.foo")),
+        Err(libc::ENOENT)
+    );
+    assert_eq!(driver.list_xattr(u64::MAX), Err(libc::ENOENT));
+}
+
+#[test]
+fn test_should_throttle_reads() {
+    let mut driver = setup_driver_with_options(vec![MountOption::ReadRateLimit(50_000)]);
+    let file_path = Path::new("/tmp/test.txt");
+    make_file_at(&mut driver, file_path, &[0; 100_000]);
+
+    let started_at = std::time::Instant::now();
+    let mut buffer = vec![0; 50_000];
+    assert_eq!(driver.read(file_path, &mut buffer, 0).unwrap(), 50_000);
+    assert_eq!(driver.read(file_path, &mut buffer, 50_000).unwrap(), 50_000);
+
+    // the first second of transfer is a burst, the rest is paced
+    assert!(started_at.elapsed() >= Duration::from_millis(950));
+}
+
+#[test]
+fn test_should_deny_write_access_on_read_only_mount() {
+    let file_path = Path::new("/tmp/test.txt");
+    let mut metadata = Metadata::default().size(5);
+    metadata.mode = Some(UnixPex::from(0o777));
+
+    let mut driver = setup_driver_with_options(vec![MountOption::RO]);
+    make_file_at(&mut driver, file_path, b"hello");
+    driver.remote.setstat(file_path, metadata).unwrap();
+    let (file, _) = driver.get_inode_from_path(file_path).unwrap();
+
+    assert_eq!(
+        driver.check_access_mask(&file, 1, 1, AccessFlags::W_OK),
+        Err(libc::EROFS)
+    );
+    assert_eq!(
+        driver.check_access_mask(&file, 1, 1, AccessFlags::R_OK | AccessFlags::X_OK),
+        Ok(())
+    );
+}
+
+/// Read `content`, written at `path`, in `streams` ranges on four workers, over remotes whose every read takes 50 ms
+fn read_parallel_with_delay(path: &Path, content: &[u8], streams: usize) -> (Vec<u8>, Duration) {
+    use super::parallel_read::ParallelRead;
+    use super::pool::RemotePool;
+
+    let connections = (0..4)
+        .map(|_| {
+            let mut remote = StubFs::new(setup_memory_fs());
+            remote
+                .create_dir(Path::new("/tmp"), UnixPex::from(0o755))
+                .unwrap();
+            remote
+                .create_file(
+                    path,
+                    &Metadata::default().size(content.len() as u64),
+                    Box::new(std::io::Cursor::new(content.to_vec())),
+                )
+                .unwrap();
+            remote.read_delay = Some(Duration::from_millis(50));
+            remote
+        })
+        .collect();
+    let workers = super::WorkerPool::new(4, std::sync::Arc::new(RemotePool::new(connections)))
+        .expect("failed to spawn workers");
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let started_at = std::time::Instant::now();
+    ParallelRead {
+        path: path.to_path_buf(),
+        offset: 0,
+        len: content.len(),
+        chunk_size: 64 * 1024,
+        retries: 0,
+        temp_dir: None,
+        unsupported: Default::default(),
+    }
+    .execute(
+        &workers,
+        streams,
+        Box::new(move |result| tx.send(result.unwrap()).unwrap()),
+    );
+    let data = rx.recv().unwrap();
+
+    (data, started_at.elapsed())
+}
+
+#[test]
+fn test_should_read_ranges_in_parallel() {
+    let file_path = Path::new("/tmp/test.txt");
+    let content = (0..512 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    // 8 chunks of 64 KiB: 8 reads in a row on a single stream, 2 on each of the 4 streams
+    let (serial, serial_elapsed) = read_parallel_with_delay(file_path, &content, 1);
+    let (parallel, parallel_elapsed) = read_parallel_with_delay(file_path, &content, 4);
+
+    assert_eq!(serial, content);
+    assert_eq!(parallel, content);
+    assert!(serial_elapsed >= Duration::from_millis(400));
+    assert!(parallel_elapsed < serial_elapsed / 2);
+}
+
+#[test]
+fn test_should_stop_parallel_read_at_eof() {
+    let file_path = Path::new("/tmp/test.txt");
+    let content = vec![1; 200 * 1024];
+    let mut remote = StubFs::new(setup_memory_fs());
+    remote
+        .create_dir(Path::new("/tmp"), UnixPex::from(0o755))
+        .unwrap();
+    remote
+        .create_file(
+            file_path,
+            &Metadata::default().size(content.len() as u64),
+            Box::new(std::io::Cursor::new(content.clone())),
+        )
+        .unwrap();
+    let workers = super::WorkerPool::new(
+        1,
+        std::sync::Arc::new(super::pool::RemotePool::new(vec![remote])),
+    )
+    .expect("failed to spawn workers");
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    super::parallel_read::ParallelRead {
+        path: file_path.to_path_buf(),
+        offset: 0,
+        len: 256 * 1024,
+        chunk_size: 64 * 1024,
+        retries: 0,
+        temp_dir: None,
+        unsupported: Default::default(),
+    }
+    .execute(
+        &workers,
+        4,
+        Box::new(move |result| tx.send(result.unwrap()).unwrap()),
+    );
+
+    assert_eq!(rx.recv().unwrap(), content);
+}
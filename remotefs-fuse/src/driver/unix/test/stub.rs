@@ -0,0 +1,252 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use remotefs::fs::stream::ReadAndSeek;
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use remotefs_memory::MemoryFs;
+
+/// A [`RemoteFs`] backed by a [`MemoryFs`], whose behaviour can be altered by the tests.
+pub struct StubFs {
+    inner: MemoryFs,
+    /// Entries returned by `list_dir` instead of the actual directory content
+    pub list_dir: Option<Vec<File>>,
+    /// If set, uploads (`create` and `create_file`) fail with this error
+    pub fail_upload: Option<RemoteError>,
+    /// If set, `open` is not supported, so files are transferred with `open_file`
+    pub no_streams: bool,
+    /// Paths whose `stat` fails with this error
+    pub fail_stat: Vec<(PathBuf, RemoteError)>,
+    /// If set, `create_file` is not supported, so files are uploaded with `create`
+    pub no_create_file: bool,
+    /// If set, `create_file` ignores the mode and the owner of the file
+    pub drop_create_metadata: bool,
+    /// Amount of uploads started with `create` and `create_file`
+    pub uploads: usize,
+    /// If set, `mov` fails with this error
+    pub fail_mov: Option<RemoteError>,
+    /// If set, `open` returns seekable streams whose every read takes this long
+    pub read_delay: Option<Duration>,
+    /// If set, `append` is not supported
+    pub no_append: bool,
+    /// Amount of appends started with `append`
+    pub appends: usize,
+    /// If set, like single-connection protocols, `stat` fails while a stream opened with `open` is not finalized
+    pub single_transfer: bool,
+    /// Amount of streams opened with `open` and not finalized yet
+    pub open_streams: usize,
+}
+
+/// A seekable stream whose every read takes `delay`, like a remote limited by its latency
+struct SlowReader {
+    inner: Cursor<Vec<u8>>,
+    delay: Duration,
+}
+
+impl Read for SlowReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        std::thread::sleep(self.delay);
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for SlowReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl ReadAndSeek for SlowReader {}
+
+impl StubFs {
+    pub fn new(inner: MemoryFs) -> Self {
+        Self {
+            inner,
+            list_dir: None,
+            fail_upload: None,
+            no_streams: false,
+            fail_stat: Vec::new(),
+            no_create_file: false,
+            drop_create_metadata: false,
+            uploads: 0,
+            fail_mov: None,
+            read_delay: None,
+            no_append: false,
+            appends: 0,
+            single_transfer: false,
+            open_streams: 0,
+        }
+    }
+}
+
+impl RemoteFs for StubFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        match &self.list_dir {
+            Some(entries) => Ok(entries.clone()),
+            None => self.inner.list_dir(path),
+        }
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        if let Some((_, err)) = self.fail_stat.iter().find(|(p, _)| p == path) {
+            return Err(err.clone());
+        }
+        if self.single_transfer && self.open_streams > 0 {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::ProtocolError,
+                "stat while a transfer is in progress",
+            ));
+        }
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        if let Some(err) = &self.fail_mov {
+            return Err(err.clone());
+        }
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        if self.no_append {
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        self.appends += 1;
+        // re-create the file with its content, since the append streams of the memory fs start at the beginning
+        let mut content = Vec::new();
+        self.inner
+            .open(path)?
+            .read_to_end(&mut content)
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+        let mut stream = self.inner.create(path, metadata)?;
+        std::io::Write::write_all(&mut stream, &content)
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+
+        Ok(stream)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        if let Some(err) = &self.fail_upload {
+            return Err(err.clone());
+        }
+        self.uploads += 1;
+        self.inner.create(path, metadata)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn std::io::Read + Send>,
+    ) -> RemoteResult<u64> {
+        if let Some(err) = &self.fail_upload {
+            return Err(err.clone());
+        }
+        if self.no_create_file {
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        self.uploads += 1;
+        if self.drop_create_metadata {
+            return self.inner.create_file(path, &Metadata::default(), reader);
+        }
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        if self.no_streams {
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        if let Some(delay) = self.read_delay {
+            let mut content = Vec::new();
+            self.inner
+                .open(path)?
+                .read_to_end(&mut content)
+                .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+            let reader: Box<dyn ReadAndSeek> = Box::new(SlowReader {
+                inner: Cursor::new(content),
+                delay,
+            });
+            self.open_streams += 1;
+            return Ok(ReadStream::from(reader));
+        }
+        let stream = self.inner.open(path)?;
+        self.open_streams += 1;
+
+        Ok(stream)
+    }
+
+    fn open_file(
+        &mut self,
+        src: &Path,
+        mut dest: Box<dyn std::io::Write + Send>,
+    ) -> RemoteResult<u64> {
+        let mut stream = self.inner.open(src)?;
+        let size = std::io::copy(&mut stream, &mut dest)
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))?;
+        self.inner.on_read(stream)?;
+
+        Ok(size)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.open_streams = self.open_streams.saturating_sub(1);
+        self.inner.on_read(readable)
+    }
+}
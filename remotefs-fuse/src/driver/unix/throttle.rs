@@ -0,0 +1,106 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A token bucket pacing the transfers to a rate in bytes per second, shared by all the transfers of the mount.
+///
+/// The bucket holds up to a second of transfer, so short bursts are not delayed; once empty, each transfer waits
+/// for the time its bytes take at the configured rate.
+#[derive(Debug)]
+pub struct RateLimit {
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes which can be transferred right away; negative when transfers are waiting for their share
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimit {
+    /// Create a limit of `rate` bytes per second
+    pub fn new(rate: u64) -> Self {
+        let rate = rate.max(1);
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until `bytes` can be transferred at the configured rate
+    pub fn take(&self, bytes: usize) {
+        let wait = {
+            let Ok(mut bucket) = self.bucket.lock() else {
+                return;
+            };
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.updated_at).as_secs_f64() * self.rate as f64;
+            bucket.tokens = (bucket.tokens + refill).min(self.rate as f64) - bytes as f64;
+            bucket.updated_at = now;
+
+            Duration::from_secs_f64((-bucket.tokens).max(0.0) / self.rate as f64)
+        };
+        if !wait.is_zero() {
+            debug!("throttling transfer of {bytes} bytes for {wait:.2?}");
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// A reader whose transfers are paced by a [`RateLimit`]
+pub struct Throttled<S> {
+    inner: S,
+    limit: Arc<RateLimit>,
+}
+
+impl<S> Throttled<S> {
+    /// Pace the transfers of `inner` with `limit`
+    pub fn new(inner: S, limit: Arc<RateLimit>) -> Self {
+        Self { inner, limit }
+    }
+}
+
+impl<S: Read> Read for Throttled<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        self.limit.take(bytes);
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_not_delay_bursts() {
+        let limit = RateLimit::new(1024 * 1024);
+        let started_at = Instant::now();
+        limit.take(512 * 1024);
+
+        assert!(started_at.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_should_pace_transfers() {
+        let limit = Arc::new(RateLimit::new(100_000));
+        let started_at = Instant::now();
+        let mut reader = Throttled::new(Cursor::new(vec![0; 150_000]), limit);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data.len(), 150_000);
+        // the first 100 KB are the burst, the other 50 KB take half a second
+        assert!(started_at.elapsed() >= Duration::from_millis(450));
+    }
+}
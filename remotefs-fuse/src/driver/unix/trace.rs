@@ -0,0 +1,204 @@
+use std::fmt::Write as _;
+use std::fs::File as StdFile;
+use std::io::Read as _;
+use std::os::fd::FromRawFd as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use libc::c_int;
+
+use super::audit::format_timestamp;
+
+/// Write end of the pipe woken up by `SIGUSR1`, or `-1` if no handler is installed
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// A ring buffer of the last operations handled by the driver, for [`crate::MountOption::OpTrace`].
+///
+/// Each record has its own slot, so recording only locks the slot being overwritten and never contends with other
+/// records, unless the trace is being dumped.
+#[derive(Debug)]
+pub struct OpTrace {
+    slots: Box<[Mutex<Option<TraceRecord>>]>,
+    /// Index of the next slot to write, increasing forever
+    next: AtomicUsize,
+    /// Path and result of the operation being handled, recorded once its timer is dropped
+    current: Mutex<Current>,
+}
+
+/// Path and result of the operation being handled
+#[derive(Debug, Default)]
+struct Current {
+    path: Option<PathBuf>,
+    errno: Option<c_int>,
+}
+
+/// A traced operation
+#[derive(Debug, Clone)]
+struct TraceRecord {
+    time: SystemTime,
+    op: &'static str,
+    path: Option<PathBuf>,
+    result: Result<(), c_int>,
+    elapsed: Duration,
+}
+
+impl OpTrace {
+    /// Create a trace keeping the last `size` operations
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: (0..size.max(1)).map(|_| Mutex::new(None)).collect(),
+            next: AtomicUsize::new(0),
+            current: Mutex::default(),
+        }
+    }
+
+    /// Set the path of the operation being handled
+    pub fn set_path(&self, path: &Path) {
+        if let Ok(mut current) = self.current.lock() {
+            current.path = Some(path.to_path_buf());
+        }
+    }
+
+    /// Set the error the operation being handled failed with
+    pub fn set_error(&self, errno: c_int) {
+        if let Ok(mut current) = self.current.lock() {
+            current.errno = Some(errno);
+        }
+    }
+
+    /// Record the operation `op` being handled, which took `elapsed`
+    pub fn record(&self, op: &'static str, elapsed: Duration) {
+        let current = self
+            .current
+            .lock()
+            .map(|mut current| std::mem::take(&mut *current))
+            .unwrap_or_default();
+        let record = TraceRecord {
+            time: SystemTime::now() - elapsed,
+            op,
+            path: current.path,
+            result: current.errno.map_or(Ok(()), Err),
+            elapsed,
+        };
+
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        if let Ok(mut slot) = self.slots[index].lock() {
+            *slot = Some(record);
+        }
+    }
+
+    /// Render the traced operations, from the oldest to the most recent, one per line
+    pub fn dump(&self) -> String {
+        let next = self.next.load(Ordering::Relaxed);
+        let mut out = String::new();
+        for offset in 0..self.slots.len() {
+            let index = (next + offset) % self.slots.len();
+            let Some(record) = self.slots[index].lock().ok().and_then(|slot| slot.clone()) else {
+                continue;
+            };
+            let _ = write!(out, "{} {}", format_timestamp(record.time), record.op);
+            if let Some(path) = &record.path {
+                let _ = write!(out, " {}", path.display());
+            }
+            match record.result {
+                Ok(()) => out.push_str(" ok"),
+                Err(errno) => {
+                    let _ = write!(out, " errno={errno}");
+                }
+            }
+            let _ = writeln!(out, " {:.2?}", record.elapsed);
+        }
+
+        out
+    }
+}
+
+/// Log the dump of `trace` whenever the process receives `SIGUSR1`.
+///
+/// Only one trace can be dumped on signal: installing the handler again replaces the trace dumped.
+pub fn dump_on_sigusr1(trace: Arc<OpTrace>) -> std::io::Result<()> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+    let mut pipe = unsafe { StdFile::from_raw_fd(read_fd) };
+
+    let previous = SIGNAL_PIPE.swap(write_fd, Ordering::SeqCst);
+    if previous >= 0 {
+        // wakes up the previous dumper on a closed pipe, which then stops
+        unsafe { libc::close(previous) };
+    }
+    let handler = on_sigusr1 as extern "C" fn(c_int);
+    if unsafe { libc::signal(libc::SIGUSR1, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    std::thread::Builder::new()
+        .name("remotefs-fuse-trace".to_string())
+        .spawn(move || {
+            let mut byte = [0; 1];
+            while matches!(pipe.read(&mut byte), Ok(1)) {
+                info!("Last operations handled:\n{}", trace.dump());
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Wake up the trace dumper; only async-signal-safe calls are allowed here
+extern "C" fn on_sigusr1(_: c_int) {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        unsafe { libc::write(fd, [1u8].as_ptr().cast(), 1) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Trace lines without their timestamp
+    fn lines(trace: &OpTrace) -> Vec<String> {
+        trace
+            .dump()
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_should_trace_operations() {
+        let trace = OpTrace::new(4);
+        trace.set_path(Path::new("/a.txt"));
+        trace.record("getattr", Duration::from_millis(1));
+        trace.set_path(Path::new("/b.txt"));
+        trace.set_error(libc::ENOENT);
+        trace.record("lookup", Duration::from_millis(2));
+        trace.record("statfs", Duration::from_millis(3));
+
+        assert_eq!(
+            lines(&trace),
+            vec![
+                "getattr /a.txt ok 1.00ms",
+                "lookup /b.txt errno=2 2.00ms",
+                "statfs ok 3.00ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_keep_last_operations() {
+        let trace = OpTrace::new(2);
+        for op in ["open", "read", "release"] {
+            trace.record(op, Duration::ZERO);
+        }
+
+        assert_eq!(lines(&trace), vec!["read ok 0.00ns", "release ok 0.00ns"]);
+    }
+}
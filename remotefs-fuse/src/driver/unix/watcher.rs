@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::inode::Inode;
+use super::revalidate::FileVersion;
+
+/// Entries of a watched directory, and of its subdirectories up to the watched depth, by path
+pub type Snapshot = HashMap<PathBuf, FileVersion>;
+
+/// A change detected in a watched directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The entry at path has been created
+    Created(PathBuf),
+    /// The entry at path has been deleted
+    Deleted(PathBuf),
+    /// The entry at path has been modified
+    Modified(PathBuf),
+}
+
+/// Keeps track of the opened directories, to detect the entries created, deleted or modified on the remote by
+/// another client.
+#[derive(Debug)]
+pub struct DirWatcher {
+    /// How often the directories are listed
+    interval: Duration,
+    /// Levels of subdirectories listed below each watched directory
+    depth: u32,
+    /// Last time the directories were listed
+    last_check: Instant,
+    /// Watched directories and their last snapshot, if listed yet
+    watched: HashMap<Inode, WatchedDir>,
+}
+
+/// A directory watched by the [`DirWatcher`]
+#[derive(Debug)]
+struct WatchedDir {
+    /// Path of the directory in the mount
+    path: PathBuf,
+    /// Path of the directory on the remote
+    remote: PathBuf,
+    /// Last snapshot of the directory, if listed yet
+    snapshot: Option<Snapshot>,
+}
+
+impl DirWatcher {
+    /// Create a new [`DirWatcher`] which lists the watched directories every `interval`, down to `depth` levels of
+    /// subdirectories
+    pub fn new(interval: Duration, depth: u32) -> Self {
+        Self {
+            interval,
+            depth,
+            last_check: Instant::now(),
+            watched: HashMap::new(),
+        }
+    }
+
+    /// Levels of subdirectories listed below each watched directory
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Start watching the directory `inode`, at `path` in the mount and at `remote` on the remote, if not watched yet
+    pub fn watch(&mut self, inode: Inode, path: PathBuf, remote: PathBuf) {
+        self.watched.entry(inode).or_insert(WatchedDir {
+            path,
+            remote,
+            snapshot: None,
+        });
+    }
+
+    /// Stop watching the directory `inode`
+    pub fn unwatch(&mut self, inode: Inode) {
+        self.watched.remove(&inode);
+    }
+
+    /// Directories currently watched, with their path in the mount and on the remote
+    pub fn watched(&self) -> Vec<(Inode, PathBuf, PathBuf)> {
+        self.watched
+            .iter()
+            .map(|(inode, dir)| (*inode, dir.path.clone(), dir.remote.clone()))
+            .collect()
+    }
+
+    /// How often the directories are listed
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Whether the interval has elapsed since the last check. If so, the check is considered started.
+    pub fn due(&mut self) -> bool {
+        if self.last_check.elapsed() < self.interval {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        true
+    }
+
+    /// Update the snapshot of `inode`, returning the changes since the previous one.
+    ///
+    /// The first snapshot of a directory reports no change.
+    pub fn update(&mut self, inode: Inode, snapshot: Snapshot) -> Vec<Change> {
+        let Some(dir) = self.watched.get_mut(&inode) else {
+            return Vec::new();
+        };
+        let changes = dir
+            .snapshot
+            .as_ref()
+            .map(|seen| diff(seen, &snapshot))
+            .unwrap_or_default();
+        dir.snapshot = Some(snapshot);
+
+        changes
+    }
+}
+
+/// Get the changes from `old` to `new`, sorted by path
+fn diff(old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+    let mut changes = new
+        .iter()
+        .filter_map(|(path, version)| match old.get(path) {
+            None => Some(Change::Created(path.clone())),
+            Some(seen) if seen != version => Some(Change::Modified(path.clone())),
+            Some(_) => None,
+        })
+        .chain(
+            old.keys()
+                .filter(|path| !new.contains_key(*path))
+                .map(|path| Change::Deleted(path.clone())),
+        )
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| change_path(a).cmp(change_path(b)));
+
+    changes
+}
+
+/// Path of the entry affected by `change`
+fn change_path(change: &Change) -> &PathBuf {
+    match change {
+        Change::Created(path) | Change::Deleted(path) | Change::Modified(path) => path,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::UNIX_EPOCH;
+
+    use pretty_assertions::assert_eq;
+    use remotefs::fs::Metadata;
+
+    use super::*;
+
+    fn version(secs: u64, size: u64) -> FileVersion {
+        FileVersion::from(
+            &Metadata::default()
+                .modified(UNIX_EPOCH + Duration::from_secs(secs))
+                .size(size),
+        )
+    }
+
+    fn snapshot(entries: &[(&str, FileVersion)]) -> Snapshot {
+        entries
+            .iter()
+            .map(|(path, version)| (PathBuf::from(path), *version))
+            .collect()
+    }
+
+    #[test]
+    fn test_should_diff_directory_snapshots() {
+        let mut watcher = DirWatcher::new(Duration::from_secs(1), 0);
+        watcher.watch(1, PathBuf::from("/"), PathBuf::from("/"));
+        assert_eq!(
+            watcher.watched(),
+            vec![(1, PathBuf::from("/"), PathBuf::from("/"))]
+        );
+
+        let first = snapshot(&[("/a.txt", version(10, 1)), ("/b.txt", version(10, 2))]);
+        assert!(watcher.update(1, first).is_empty());
+
+        let second = snapshot(&[("/a.txt", version(20, 1)), ("/c.txt", version(10, 3))]);
+        assert_eq!(
+            watcher.update(1, second),
+            vec![
+                Change::Modified(PathBuf::from("/a.txt")),
+                Change::Deleted(PathBuf::from("/b.txt")),
+                Change::Created(PathBuf::from("/c.txt")),
+            ]
+        );
+
+        // not watched
+        assert!(watcher.update(2, Snapshot::new()).is_empty());
+        watcher.unwatch(1);
+        assert!(watcher.watched().is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use remotefs::RemoteFs;
+
+use super::pool::RemotePool;
+
+/// How long a worker waits for a connection before warning that the pool is exhausted
+const POOL_EXHAUSTED_WARNING: Duration = Duration::from_secs(5);
+
+/// A job executed by a worker, with a connection to the remote checked out from the pool
+pub type Job<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+/// A bounded pool of worker threads, running the jobs with the connections of a [`RemotePool`].
+///
+/// Jobs are queued up to the amount of workers; once the queue is full, [`WorkerPool::execute`] blocks until
+/// a worker becomes available.
+pub struct WorkerPool<T> {
+    sender: Option<SyncSender<Job<T>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T> WorkerPool<T>
+where
+    T: RemoteFs + Send + 'static,
+{
+    /// Spawn a new [`WorkerPool`] with `size` workers, checking out the connections from `pool`.
+    pub fn new(size: usize, pool: Arc<RemotePool<T>>) -> std::io::Result<Self> {
+        let size = size.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Job<T>>(size);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+        for id in 0..size {
+            let receiver = receiver.clone();
+            let pool = pool.clone();
+            let worker = std::thread::Builder::new()
+                .name(format!("remotefs-fuse-worker-{id}"))
+                .spawn(move || run_worker(id, &pool, &receiver))?;
+            workers.push(worker);
+        }
+
+        Ok(Self {
+            sender: Some(sender),
+            workers,
+        })
+    }
+}
+
+impl<T> WorkerPool<T> {
+    /// Amount of workers in the pool
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queue `job` to be executed by the first available worker.
+    ///
+    /// If the workers have stopped, the job is given back.
+    pub fn execute(&self, job: Job<T>) -> Result<(), Job<T>> {
+        match self.sender.as_ref() {
+            Some(sender) => sender.send(job).map_err(|err| err.0),
+            None => Err(job),
+        }
+    }
+}
+
+impl<T> Drop for WorkerPool<T> {
+    fn drop(&mut self) {
+        // closing the channel stops the workers once the queued jobs are done
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                error!("a worker panicked");
+            }
+        }
+    }
+}
+
+/// Run the jobs received on `receiver` with the connections of `pool`, until the channel is closed
+fn run_worker<T>(id: usize, pool: &RemotePool<T>, receiver: &Mutex<Receiver<Job<T>>>)
+where
+    T: RemoteFs,
+{
+    debug!("worker {id} started");
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => match receiver.recv() {
+                Ok(job) => job,
+                Err(_) => break,
+            },
+            Err(_) => break,
+        };
+
+        let mut remote = match pool.checkout_timeout(POOL_EXHAUSTED_WARNING) {
+            Some(remote) => remote,
+            None => {
+                warn!(
+                    "worker {id} is waiting for a connection; all the {} connections are in use",
+                    pool.size()
+                );
+                pool.checkout()
+            }
+        };
+        job(&mut remote);
+    }
+    debug!("worker {id} stopped");
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn memory_pool(size: usize) -> Arc<RemotePool<MemoryFs>> {
+        let connections = (0..size)
+            .map(|_| {
+                MemoryFs::new(Tree::new(node!(
+                    "/".into(),
+                    remotefs_memory::Inode::dir(0, 0, remotefs::fs::UnixPex::from(0o755))
+                )))
+            })
+            .collect();
+
+        Arc::new(RemotePool::new(connections))
+    }
+
+    #[test]
+    fn test_should_run_jobs_on_workers() {
+        let pool = WorkerPool::new(2, memory_pool(2)).expect("failed to spawn pool");
+        assert_eq!(pool.size(), 2);
+
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..4 {
+            let tx = tx.clone();
+            assert!(pool
+                .execute(Box::new(move |remote: &mut MemoryFs| {
+                    tx.send(remote.is_connected() && remote.exists(Path::new("/")).unwrap())
+                        .unwrap();
+                }))
+                .is_ok());
+        }
+        drop(tx);
+
+        assert_eq!(rx.iter().filter(|ok| *ok).count(), 4);
+    }
+
+    #[test]
+    fn test_should_complete_queued_jobs_on_drop() {
+        let pool = WorkerPool::new(1, memory_pool(1)).expect("failed to spawn pool");
+        let (tx, rx) = mpsc::channel();
+        for i in 0..3 {
+            let tx = tx.clone();
+            assert!(pool
+                .execute(Box::new(move |_: &mut MemoryFs| tx.send(i).unwrap()))
+                .is_ok());
+        }
+        drop(pool);
+        drop(tx);
+
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+}
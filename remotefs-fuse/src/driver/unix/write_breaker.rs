@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+/// How often a tripped breaker tries to reconnect to the remote
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Circuit breaker which switches the mount to read-only after too many consecutive failed writes.
+#[derive(Debug)]
+pub struct WriteBreaker {
+    /// Amount of consecutive failures which trips the breaker
+    threshold: u32,
+    /// Consecutive failures since the last success
+    failures: u32,
+    /// When the breaker was tripped or last tried to reconnect; set only while tripped
+    tripped_at: Option<Instant>,
+}
+
+impl WriteBreaker {
+    /// Create a new [`WriteBreaker`] tripping after `threshold` consecutive failures
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            failures: 0,
+            tripped_at: None,
+        }
+    }
+
+    /// Whether the breaker is tripped, so the mount is read-only
+    pub fn is_tripped(&self) -> bool {
+        self.tripped_at.is_some()
+    }
+
+    /// Record a successful write, clearing the failures
+    pub fn success(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Record a failed write, returning whether the breaker has just been tripped
+    pub fn failure(&mut self) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        if self.is_tripped() || self.failures < self.threshold {
+            return false;
+        }
+        self.tripped_at = Some(Instant::now());
+
+        true
+    }
+
+    /// Whether the breaker is tripped and it's time to try reconnecting. If so, the attempt is considered started.
+    pub fn reconnect_due(&mut self) -> bool {
+        match self.tripped_at {
+            Some(at) if at.elapsed() >= RECONNECT_INTERVAL => {
+                self.tripped_at = Some(Instant::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Reset the breaker after a successful reconnect
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.tripped_at = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_trip_after_threshold() {
+        let mut breaker = WriteBreaker::new(3);
+        assert_eq!(breaker.failure(), false);
+        assert_eq!(breaker.failure(), false);
+        breaker.success();
+        assert_eq!(breaker.failure(), false);
+        assert_eq!(breaker.failure(), false);
+        assert!(!breaker.is_tripped());
+
+        assert_eq!(breaker.failure(), true);
+        assert!(breaker.is_tripped());
+        // already tripped
+        assert_eq!(breaker.failure(), false);
+        assert!(!breaker.reconnect_due());
+
+        breaker.reset();
+        assert!(!breaker.is_tripped());
+    }
+}
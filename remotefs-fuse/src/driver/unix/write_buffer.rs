@@ -0,0 +1,286 @@
+use std::fs;
+use std::io::{Cursor, Read, Seek as _, SeekFrom};
+use std::os::unix::fs::FileExt as _;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Where the content of a [`WriteBuffer`] is kept
+#[derive(Debug)]
+enum Storage {
+    Memory(Vec<u8>),
+    /// A temporary file, holding `size` bytes
+    Disk {
+        file: fs::File,
+        size: u64,
+    },
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::Memory(Vec::new())
+    }
+}
+
+/// Buffer holding the content of a file being written through the mount.
+///
+/// Writes are applied to the buffer, which is seeded with the current content of the file on the remote,
+/// and the whole content is uploaded when the file is flushed.
+///
+/// The content is kept in memory up to the memory limit, if any; beyond that, it is moved to a temporary file, so that
+/// writing large files doesn't exhaust the memory of the host.
+#[derive(Debug, Default)]
+pub struct WriteBuffer {
+    storage: Storage,
+    /// Maximum size of the content kept in memory
+    mem_limit: Option<usize>,
+    /// Directory where the temporary file is created; if not set, the system temporary directory is used
+    temp_dir: Option<PathBuf>,
+    /// Whether the buffer contains data which has not been uploaded yet
+    dirty: bool,
+    /// Amount of bytes written since the last upload
+    pending: u64,
+    /// Time of the last change to the buffer
+    last_write: Option<Instant>,
+}
+
+impl WriteBuffer {
+    /// Create a new [`WriteBuffer`] seeded with the current content of the file
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            storage: Storage::Memory(data),
+            ..Default::default()
+        }
+    }
+
+    /// Move the content to a temporary file in `temp_dir` once it grows beyond `mem_limit` bytes
+    pub fn spill_over(mut self, mem_limit: usize, temp_dir: Option<PathBuf>) -> Self {
+        self.mem_limit = Some(mem_limit);
+        self.temp_dir = temp_dir;
+        self
+    }
+
+    /// Size of the file content
+    pub fn size(&self) -> u64 {
+        match &self.storage {
+            Storage::Memory(data) => data.len() as u64,
+            Storage::Disk { size, .. } => *size,
+        }
+    }
+
+    /// Whether the buffer contains data which has not been uploaded yet
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark the buffer content as uploaded
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+        self.pending = 0;
+    }
+
+    /// Amount of bytes written since the last upload
+    pub fn pending(&self) -> u64 {
+        self.pending
+    }
+
+    /// Time elapsed since the last change to the buffer
+    pub fn idle_for(&self) -> Duration {
+        self.last_write
+            .map(|last_write| last_write.elapsed())
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// The file content, if it is kept in memory
+    pub fn data(&self) -> Option<&[u8]> {
+        match &self.storage {
+            Storage::Memory(data) => Some(data),
+            Storage::Disk { .. } => None,
+        }
+    }
+
+    /// Get a reader of the whole file content, which owns its data.
+    pub fn reader(&self) -> std::io::Result<Box<dyn Read + Send>> {
+        match &self.storage {
+            Storage::Memory(data) => Ok(Box::new(Cursor::new(data.clone()))),
+            Storage::Disk { file, size } => {
+                let mut file = file.try_clone()?;
+                file.seek(SeekFrom::Start(0))?;
+                Ok(Box::new(file.take(*size)))
+            }
+        }
+    }
+
+    /// Write `data` at `offset`. If `offset` is beyond the end of the file, the gap is filled with zeros.
+    pub fn write(&mut self, offset: u64, data: &[u8]) -> std::io::Result<()> {
+        let end = offset + data.len() as u64;
+        self.reserve(end)?;
+        match &mut self.storage {
+            Storage::Memory(content) => {
+                let offset = offset as usize;
+                if content.len() < end as usize {
+                    content.resize(end as usize, 0);
+                }
+                content[offset..end as usize].copy_from_slice(data);
+            }
+            Storage::Disk { file, size } => {
+                file.write_all_at(data, offset)?;
+                *size = (*size).max(end);
+            }
+        }
+        self.touch(data.len() as u64);
+
+        Ok(())
+    }
+
+    /// Read into `buffer` from `offset`, returning the amount of bytes read
+    pub fn read(&self, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let offset = offset.min(self.size());
+        let len = buffer.len().min((self.size() - offset) as usize);
+        match &self.storage {
+            Storage::Memory(data) => {
+                let offset = offset as usize;
+                buffer[..len].copy_from_slice(&data[offset..offset + len]);
+            }
+            Storage::Disk { file, .. } => file.read_exact_at(&mut buffer[..len], offset)?,
+        }
+
+        Ok(len)
+    }
+
+    /// Truncate or extend the file to `size` bytes
+    pub fn truncate(&mut self, size: u64) -> std::io::Result<()> {
+        self.reserve(size)?;
+        match &mut self.storage {
+            Storage::Memory(data) => data.resize(size as usize, 0),
+            Storage::Disk {
+                file,
+                size: file_size,
+            } => {
+                file.set_len(size)?;
+                *file_size = size;
+            }
+        }
+        self.touch(0);
+
+        Ok(())
+    }
+
+    /// Move the content to a temporary file if growing it to `size` bytes exceeds the memory limit
+    fn reserve(&mut self, size: u64) -> std::io::Result<()> {
+        let Storage::Memory(data) = &self.storage else {
+            return Ok(());
+        };
+        match self.mem_limit {
+            Some(limit) if size > limit as u64 => {}
+            _ => return Ok(()),
+        }
+
+        let file = match self.temp_dir.as_deref() {
+            Some(dir) => tempfile::tempfile_in(dir)?,
+            None => tempfile::tempfile()?,
+        };
+        file.write_all_at(data, 0)?;
+        debug!(
+            "write buffer grew beyond {} bytes; moved to a temporary file",
+            self.mem_limit.unwrap_or_default()
+        );
+        self.storage = Storage::Disk {
+            file,
+            size: data.len() as u64,
+        };
+
+        Ok(())
+    }
+
+    /// Mark the buffer as changed, after writing `bytes` bytes
+    fn touch(&mut self, bytes: u64) {
+        self.dirty = true;
+        self.pending += bytes;
+        self.last_write = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_write_to_buffer() {
+        let mut buffer = WriteBuffer::new(b"hello world".to_vec());
+        assert!(!buffer.is_dirty());
+
+        buffer.write(6, b"there!").unwrap();
+        assert!(buffer.is_dirty());
+        assert_eq!(buffer.data().unwrap(), b"hello there!");
+
+        // write beyond the end
+        buffer.write(14, b"x").unwrap();
+        assert_eq!(buffer.data().unwrap(), b"hello there!\0\0x");
+        assert_eq!(buffer.size(), 15);
+
+        assert_eq!(buffer.pending(), 7);
+        assert!(buffer.idle_for() < Duration::from_secs(60));
+
+        buffer.mark_clean();
+        assert!(!buffer.is_dirty());
+        assert_eq!(buffer.pending(), 0);
+    }
+
+    #[test]
+    fn test_should_read_from_buffer() {
+        let buffer = WriteBuffer::new(b"hello world".to_vec());
+
+        let mut data = vec![0; 5];
+        assert_eq!(buffer.read(6, &mut data).unwrap(), 5);
+        assert_eq!(data, b"world");
+
+        assert_eq!(buffer.read(9, &mut data).unwrap(), 2);
+        assert_eq!(&data[..2], b"ld");
+        assert_eq!(buffer.read(20, &mut data).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_should_truncate_buffer() {
+        let mut buffer = WriteBuffer::new(b"hello world".to_vec());
+        buffer.truncate(5).unwrap();
+        assert_eq!(buffer.data().unwrap(), b"hello");
+        assert!(buffer.is_dirty());
+
+        buffer.truncate(7).unwrap();
+        assert_eq!(buffer.data().unwrap(), b"hello\0\0");
+    }
+
+    #[test]
+    fn test_should_spill_buffer_to_disk() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut buffer =
+            WriteBuffer::new(b"hello".to_vec()).spill_over(8, Some(temp_dir.path().to_path_buf()));
+        buffer.write(5, b" wo").unwrap();
+        assert!(buffer.data().is_some());
+
+        // crossing the limit moves the content to disk
+        buffer.write(8, b"rld").unwrap();
+        assert!(buffer.data().is_none());
+        assert_eq!(buffer.size(), 11);
+
+        // reads straddle the content written before and after spilling
+        let mut data = vec![0; 6];
+        assert_eq!(buffer.read(3, &mut data).unwrap(), 6);
+        assert_eq!(data, b"lo wor");
+
+        // write beyond the end
+        buffer.write(13, b"!").unwrap();
+        let mut content = Vec::new();
+        buffer.reader().unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"hello world\0\0!");
+
+        buffer.truncate(5).unwrap();
+        assert_eq!(buffer.size(), 5);
+        let mut content = Vec::new();
+        buffer.reader().unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"hello");
+    }
+}
@@ -11,32 +11,32 @@
 //!
 //! - **Linux**: you need to have `fuse3` installed on your system.
 //!
-//!     Of course, you also need to have the `FUSE` kernel module installed.
-//!     To build `remotefs-fuse` on Linux, you need to have the `libfuse3` development package installed.
+//!   Of course, you also need to have the `FUSE` kernel module installed.
+//!   To build `remotefs-fuse` on Linux, you need to have the `libfuse3` development package installed.
 //!
-//!     In Ubuntu, you can install it with:
+//!   In Ubuntu, you can install it with:
 //!
-//!     ```sh
-//!     sudo apt-get install fuse3 libfuse3-dev
-//!     ```
+//!   ```sh
+//!   sudo apt-get install fuse3 libfuse3-dev
+//!   ```
 //!
-//!     In CentOS, you can install it with:
+//!   In CentOS, you can install it with:
 //!
-//!     ```sh
-//!     sudo yum install fuse-devel
-//!     ```
+//!   ```sh
+//!   sudo yum install fuse-devel
+//!   ```
 //!
 //! - **macOS**: you need to have the `macfuse` service installed on your system.
 //!
-//!     You can install it with:
+//!   You can install it with:
 //!
-//!     ```sh
-//!     brew install macfuse
-//!     ```
+//!   ```sh
+//!   brew install macfuse
+//!   ```
 //!
 //! - **Windows**: you need to have the `dokany` service installed on your system.
-//!    
-//!    You can install it from <https://github.com/dokan-dev/dokany?tab=readme-ov-file#installation>
+//!   
+//!   You can install it from <https://github.com/dokan-dev/dokany?tab=readme-ov-file#installation>
 //!
 //! ## Get started
 //!
@@ -70,7 +70,7 @@
 //!
 //! let remote = MyRemoteFileSystem::new();
 //! let mount_path = std::path::PathBuf::from("/mnt/remote");
-//! let mut mount = Mount::mount(remote, &mount_path, &options).expect("Failed to mount");
+//! let mut mount = Mount::mount_blocking(remote, &mount_path, &options).expect("Failed to mount");
 //! let mut umount = mount.unmounter();
 //!
 //! // setup signal handler
@@ -104,5 +104,12 @@ extern crate log;
 
 mod driver;
 mod mount;
+mod overlay;
 
-pub use self::mount::{Mount, MountOption, Unmount};
+#[cfg(unix)]
+#[allow(deprecated)]
+pub use self::mount::MountHandle;
+#[cfg(unix)]
+pub use self::mount::{BackgroundMount, Refresher};
+pub use self::mount::{Mount, MountError, MountOption, Unmount};
+pub use self::overlay::{Layer, OverlayFs};
@@ -1,19 +1,37 @@
+mod error;
 mod option;
 
 use std::path::Path;
 
 use remotefs::RemoteFs;
 
+pub use self::error::MountError;
 pub use self::option::MountOption;
 use crate::driver::Driver;
 
 /// A struct to mount the filesystem.
+///
+/// On unix, the filesystem is unmounted when dropped, unless it was already unmounted with an [`Unmount`].
 pub struct Mount<T>
 where
     T: RemoteFs + Sync + Send,
 {
     #[cfg(unix)]
     session: fuser::Session<Driver<T>>,
+    #[cfg(unix)]
+    mountpoint: std::path::PathBuf,
+    /// Keeps the poller of the idle writes running while the filesystem is mounted
+    #[cfg(unix)]
+    _poller: Option<std::sync::Arc<()>>,
+    /// Operations changing the filesystem, waited for by the graceful unmount
+    #[cfg(unix)]
+    drain: std::sync::Arc<crate::driver::Drain>,
+    /// Remote swapped in by the [`Refresher`]
+    #[cfg(unix)]
+    pending_remote: std::sync::Arc<crate::driver::PendingRemote<T>>,
+    /// Set once the filesystem has been unmounted, either by an [`Unmount`] or on drop
+    #[cfg(unix)]
+    unmounted: std::sync::Arc<std::sync::atomic::AtomicBool>,
     #[cfg(windows)]
     mountpoint: widestring::U16CString,
     #[cfg(windows)]
@@ -28,13 +46,114 @@ where
     ///
     /// You can specify the mount options using the `options` parameter as an array of [`MountOption`].
     #[allow(clippy::self_named_constructors)]
-    #[cfg(unix)]
+    #[deprecated(
+        since = "0.1.0",
+        note = "use `Mount::mount_blocking` to run the event loop on the current thread, or `Mount::spawn` to run it in background"
+    )]
     pub fn mount(
         remote: T,
         mountpoint: &Path,
         options: &[MountOption],
-    ) -> Result<Self, std::io::Error> {
-        let driver = Driver::new(remote, options.to_vec());
+    ) -> Result<Self, MountError> {
+        Self::mount_blocking(remote, mountpoint, options)
+    }
+
+    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint, returning the foreground session.
+    ///
+    /// The returned [`Mount`] owns the session: its event loop is run on the calling thread with [`Mount::run`], which
+    /// blocks until the filesystem is unmounted, such as with the [`Unmount`] handle from [`Mount::unmounter`]. To run
+    /// the event loop on a background thread instead, see [`Mount::spawn`].
+    ///
+    /// You can specify the mount options using the `options` parameter as an array of [`MountOption`].
+    ///
+    /// If the filesystem can't be mounted, the [`MountError`] tells the likely cause, such as a missing FUSE
+    /// installation or a busy mount point.
+    #[cfg(unix)]
+    pub fn mount_blocking(
+        remote: T,
+        mountpoint: &Path,
+        options: &[MountOption],
+    ) -> Result<Self, MountError> {
+        if options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::Workers(_)))
+        {
+            warn!("MountOption::Workers requires a connector; use Mount::mount_with_connector to enable workers");
+        }
+        if options.iter().any(|opt| {
+            matches!(
+                opt,
+                MountOption::RevalidateInterval(_) | MountOption::WatchInterval(_)
+            )
+        }) {
+            warn!("MountOption::RevalidateInterval and MountOption::WatchInterval require a connector; use Mount::mount_with_connector to check the remote for changes");
+        }
+        Self::mount_driver(Driver::new(remote, options.to_vec()), mountpoint)
+    }
+
+    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint, using `connect` to create
+    /// additional connections to the remote.
+    ///
+    /// The connections are pooled and used by the workers set with [`MountOption::Workers`]; the size of the pool is
+    /// set with [`MountOption::PoolSize`]. With [`MountOption::RevalidateInterval`] and [`MountOption::WatchInterval`],
+    /// another connection checks the open files and the opened directories for changes on the remote. `remote` is used for all the other operations.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn mount_with_connector<F>(
+        remote: T,
+        connect: F,
+        mountpoint: &Path,
+        options: &[MountOption],
+    ) -> Result<Self, MountError>
+    where
+        F: Fn() -> T,
+        T: 'static,
+    {
+        let mut driver = Driver::new(remote, options.to_vec());
+        driver.spawn_monitor(&connect);
+        driver.spawn_workers(connect);
+        Self::mount_driver(driver, mountpoint)
+    }
+
+    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint, running the event loop on a
+    /// background thread.
+    ///
+    /// The filesystem stays mounted until [`BackgroundMount::umount`] is called, it is unmounted from outside, such
+    /// as with `fusermount -u`, or the returned [`BackgroundMount`] is dropped.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn spawn(
+        remote: T,
+        mountpoint: &Path,
+        options: &[MountOption],
+    ) -> Result<BackgroundMount, MountError>
+    where
+        T: 'static,
+    {
+        let mut mount = Self::mount_blocking(remote, mountpoint, options)?;
+        let unmount = mount.unmounter();
+        let notifier = mount.notifier();
+        let event_loop = std::thread::Builder::new()
+            .name("remotefs-fuse-session".to_string())
+            .spawn(move || mount.run())?;
+
+        Ok(BackgroundMount {
+            event_loop: Some(event_loop),
+            unmount,
+            notifier,
+        })
+    }
+
+    /// Mount `driver` to the provided mountpoint
+    #[cfg(unix)]
+    fn mount_driver(driver: Driver<T>, mountpoint: &Path) -> Result<Self, MountError> {
+        let notifier = driver.notifier.clone();
+        let drain = driver.drain.clone();
+        let pending_remote = driver.pending_remote.clone();
+        let poll_interval = driver.options.iter().find_map(|opt| match opt {
+            MountOption::WriteCoalesceWindow(interval) => Some(*interval),
+            _ => None,
+        });
 
         let options = driver
             .options
@@ -42,21 +161,41 @@ where
             .flat_map(|opt| opt.try_into())
             .collect::<Vec<_>>();
 
+        let session = fuser::Session::new(driver, mountpoint, &options)?;
+        let _ = notifier.set(session.notifier());
+
+        let poller = poll_interval.map(|interval| {
+            let alive = std::sync::Arc::new(());
+            spawn_write_poller(
+                mountpoint.to_path_buf(),
+                interval,
+                std::sync::Arc::downgrade(&alive),
+            );
+            alive
+        });
+
         Ok(Self {
-            session: fuser::Session::new(driver, mountpoint, &options)?,
+            session,
+            mountpoint: mountpoint.to_path_buf(),
+            _poller: poller,
+            drain,
+            pending_remote,
+            unmounted: Default::default(),
         })
     }
 
-    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint.
+    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint, returning the foreground session.
+    ///
+    /// The returned [`Mount`] owns the session: its event loop is run on the calling thread with [`Mount::run`], which
+    /// blocks until the filesystem is unmounted, such as with the [`Unmount`] handle from [`Mount::unmounter`].
     ///
     /// You can specify the mount options using the `options` parameter as an array of [`MountOption`].
     #[cfg(windows)]
-    #[allow(clippy::self_named_constructors)]
-    pub fn mount(
+    pub fn mount_blocking(
         remote: T,
         mountpoint: &Path,
         options: &[MountOption],
-    ) -> Result<Self, std::io::Error> {
+    ) -> Result<Self, MountError> {
         use widestring::U16CString;
 
         let driver = Driver::new(remote, options.to_vec());
@@ -73,9 +212,36 @@ where
     /// Run the filesystem event loop.
     ///
     /// This function will block the current thread.
+    ///
+    /// On unix, if the event loop ends because the FUSE session died, such as when the connection to the kernel is
+    /// aborted, the mount point is detached, so that it isn't left as a stale mount failing with `ENOTCONN`, and an
+    /// error is returned.
     pub fn run(&mut self) -> Result<(), std::io::Error> {
         #[cfg(unix)]
-        self.session.run()?;
+        {
+            let result = self.session.run();
+            match &result {
+                Ok(()) => info!("FUSE session at {} ended", self.mountpoint.display()),
+                Err(err) => error!(
+                    "FUSE session at {} failed: {err}",
+                    self.mountpoint.display()
+                ),
+            }
+            if is_stale_mount(&self.mountpoint) {
+                error!(
+                    "the FUSE connection to {} is broken (transport endpoint is not connected); unmounting it",
+                    self.mountpoint.display()
+                );
+                self.session.unmount();
+                self.unmounted
+                    .store(true, std::sync::atomic::Ordering::SeqCst);
+                detach_mount(&self.mountpoint);
+                return Err(result
+                    .err()
+                    .unwrap_or_else(|| std::io::Error::from_raw_os_error(libc::ENOTCONN)));
+            }
+            result?;
+        }
 
         #[cfg(windows)]
         {
@@ -91,23 +257,358 @@ where
         Ok(())
     }
 
+    /// Get the [`fuser::Notifier`] of the session, to control the kernel caches of the mounted filesystem.
+    ///
+    /// Since [`Mount::run`] blocks the current thread, to send notifications while the event loop is running get the
+    /// notifier before calling it and move it to another thread. Notifications must never be sent from the thread
+    /// running the event loop, since the kernel may need to call into the filesystem to process them.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn notifier(&self) -> fuser::Notifier {
+        self.session.notifier()
+    }
+
+    /// Invalidate the kernel cache for the inode `ino`, from `offset` for `len` bytes.
+    ///
+    /// A negative `offset` invalidates only the attributes; a `len` of 0 invalidates up to the end of the file.
+    /// See [`Mount::notifier`] for the threading requirements.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn invalidate_inode(&self, ino: u64, offset: i64, len: i64) -> Result<(), std::io::Error> {
+        self.session.notifier().inval_inode(ino, offset, len)
+    }
+
+    /// Invalidate the kernel cache for the entry `name` in the directory with inode `parent`.
+    ///
+    /// See [`Mount::notifier`] for the threading requirements.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn invalidate_entry(
+        &self,
+        parent: u64,
+        name: &std::ffi::OsStr,
+    ) -> Result<(), std::io::Error> {
+        self.session.notifier().inval_entry(parent, name)
+    }
+
+    /// Get a handle to replace the remote of the mounted filesystem, to rotate its credentials.
+    ///
+    /// See [`Refresher::refresh_credentials`].
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn refresher(&self) -> Refresher<T> {
+        Refresher {
+            pending: self.pending_remote.clone(),
+        }
+    }
+
     /// Get a handle to unmount the filesystem.
     ///
-    /// To umount see [`Unmount::unmount`].
+    /// To umount see [`Unmount::unmount`] and [`Unmount::unmount_graceful`].
     pub fn unmounter(&mut self) -> Unmount {
         Unmount {
             #[cfg(unix)]
             umount: self.session.unmount_callable(),
+            #[cfg(unix)]
+            drain: self.drain.clone(),
+            #[cfg(unix)]
+            unmounted: self.unmounted.clone(),
             #[cfg(windows)]
             mountpoint: self.mountpoint.clone(),
         }
     }
 }
 
+#[cfg(unix)]
+impl<T> Drop for Mount<T>
+where
+    T: RemoteFs + Sync + Send,
+{
+    fn drop(&mut self) {
+        // the session doesn't unmount on its own while an unmount handle is alive, so the mount would be left behind
+        if self
+            .unmounted
+            .swap(true, std::sync::atomic::Ordering::SeqCst)
+        {
+            return;
+        }
+        info!("Unmounting {} on drop", self.mountpoint.display());
+        self.session.unmount();
+        if is_mounted(&self.mountpoint) {
+            error!(
+                "Failed to unmount {} on drop; unmount it with `fusermount -u`",
+                self.mountpoint.display()
+            );
+        }
+    }
+}
+
+/// A filesystem mounted with its event loop running on a background thread, as returned by [`Mount::spawn`].
+///
+/// The filesystem is unmounted when the handle is dropped.
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub struct BackgroundMount {
+    /// Thread running the event loop; taken once joined
+    event_loop: Option<std::thread::JoinHandle<Result<(), std::io::Error>>>,
+    unmount: Unmount,
+    notifier: fuser::Notifier,
+}
+
+/// Former name of [`BackgroundMount`].
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+#[deprecated(since = "0.1.0", note = "renamed to `BackgroundMount`")]
+pub type MountHandle = BackgroundMount;
+
+#[cfg(unix)]
+impl BackgroundMount {
+    /// Get the [`fuser::Notifier`] of the session, to control the kernel caches of the mounted filesystem.
+    pub fn notifier(&self) -> fuser::Notifier {
+        self.notifier.clone()
+    }
+
+    /// Unmount the filesystem and wait for the event loop to terminate.
+    pub fn umount(mut self) -> Result<(), std::io::Error> {
+        self.unmount.unmount()?;
+        self.wait()
+    }
+
+    /// Unmount the filesystem once the pending changes are complete, and wait for the event loop to terminate.
+    ///
+    /// See [`Unmount::unmount_graceful`] for how the pending changes are drained.
+    pub fn umount_graceful(mut self, timeout: std::time::Duration) -> Result<(), std::io::Error> {
+        self.unmount.unmount_graceful(timeout)?;
+        self.wait()
+    }
+
+    /// Wait for the event loop to terminate, without unmounting the filesystem.
+    ///
+    /// This blocks until the filesystem is unmounted from outside, such as with `fusermount -u`, returning the
+    /// result of the event loop; see [`Mount::run`].
+    pub fn join(mut self) -> Result<(), std::io::Error> {
+        self.wait()
+    }
+
+    /// Join the thread running the event loop
+    fn wait(&mut self) -> Result<(), std::io::Error> {
+        match self.event_loop.take().map(|event_loop| event_loop.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(std::io::Error::other("the FUSE event loop panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for BackgroundMount {
+    fn drop(&mut self) {
+        if self.event_loop.is_none() {
+            return;
+        }
+        if let Err(err) = self.unmount.unmount() {
+            error!("Failed to unmount filesystem: {err}");
+        }
+        if let Err(err) = self.wait() {
+            error!("FUSE event loop failed: {err}");
+        }
+    }
+}
+
+/// Periodically stat the mountpoint while `alive` can be upgraded.
+///
+/// Each stat reaches the driver as a `getattr` call, giving it a chance to upload the idle writes, even when no other
+/// operation is issued on the mount.
+#[cfg(unix)]
+fn spawn_write_poller(
+    mountpoint: std::path::PathBuf,
+    interval: std::time::Duration,
+    alive: std::sync::Weak<()>,
+) {
+    let result = std::thread::Builder::new()
+        .name("remotefs-fuse-poller".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(interval);
+            if alive.upgrade().is_none() {
+                break;
+            }
+            if let Err(err) = std::fs::metadata(&mountpoint) {
+                debug!("Failed to stat mountpoint for the idle writes: {err}");
+            }
+        });
+    if let Err(err) = result {
+        error!("Failed to start the poller of the idle writes: {err}");
+    }
+}
+
+/// How long a stat of the mount point is waited for when checking whether the mount is stale
+#[cfg(unix)]
+const STALE_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Check whether `mountpoint` is still mounted while its FUSE session is dead.
+///
+/// The mount is stale if a stat of the mount point fails with `ENOTCONN`, or doesn't complete at all since nobody
+/// serves the connection anymore.
+#[cfg(unix)]
+fn is_stale_mount(mountpoint: &Path) -> bool {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let path = mountpoint.to_path_buf();
+    let probe = std::thread::Builder::new()
+        .name("remotefs-fuse-stale-probe".to_string())
+        .spawn(move || {
+            let _ = sender.send(std::fs::metadata(path));
+        });
+    if let Err(err) = probe {
+        error!("Failed to check whether the mount is stale: {err}");
+        return false;
+    }
+
+    match receiver.recv_timeout(STALE_CHECK_TIMEOUT) {
+        Ok(Ok(_)) => false,
+        Ok(Err(err)) => err.raw_os_error() == Some(libc::ENOTCONN),
+        Err(_) => true,
+    }
+}
+
+/// Check whether `mountpoint` is listed among the mounted filesystems.
+///
+/// The mount point is not resolved, since accessing a dead mount may block. On systems without `/proc/self/mounts`
+/// the mount is assumed to be gone.
+#[cfg(unix)]
+fn is_mounted(mountpoint: &Path) -> bool {
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+    let mountpoint = match std::env::current_dir() {
+        Ok(cwd) if mountpoint.is_relative() => cwd.join(mountpoint),
+        _ => mountpoint.to_path_buf(),
+    };
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .any(|target| Path::new(&unescape_mount_path(target)) == mountpoint)
+}
+
+/// Decode the octal escapes, such as `\040` for a space, of a path in `/proc/self/mounts`
+#[cfg(unix)]
+fn unescape_mount_path(path: &str) -> std::ffi::OsString {
+    use std::os::unix::ffi::OsStringExt as _;
+
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes
+            .get(index + 1..index + 4)
+            .filter(|_| bytes[index] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escape {
+            Some(byte) => {
+                out.push(byte);
+                index += 4;
+            }
+            None => {
+                out.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    std::ffi::OsString::from_vec(out)
+}
+
+/// Lazily unmount `mountpoint`, falling back to `fusermount` if not permitted.
+#[cfg(unix)]
+fn detach_mount(mountpoint: &Path) {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let Ok(path) = std::ffi::CString::new(mountpoint.as_os_str().as_bytes()) else {
+        error!("Invalid mount point {}", mountpoint.display());
+        return;
+    };
+    #[cfg(target_os = "linux")]
+    let result = unsafe { libc::umount2(path.as_ptr(), libc::MNT_DETACH) };
+    #[cfg(not(target_os = "linux"))]
+    let result = unsafe { libc::unmount(path.as_ptr(), libc::MNT_FORCE) };
+    if result == 0 {
+        return;
+    }
+    debug!(
+        "Failed to detach {}: {}; trying fusermount",
+        mountpoint.display(),
+        std::io::Error::last_os_error()
+    );
+
+    for bin in ["fusermount3", "fusermount"] {
+        match std::process::Command::new(bin)
+            .args(["-u", "-z", "--"])
+            .arg(mountpoint)
+            .status()
+        {
+            Ok(status) if status.success() => return,
+            Ok(status) => debug!("{bin} exited with {status}"),
+            Err(err) => debug!("Failed to run {bin}: {err}"),
+        }
+    }
+    error!(
+        "Failed to unmount {}; unmount it with `fusermount -u`",
+        mountpoint.display()
+    );
+}
+
+/// Stop accepting changes on the filesystem and wait up to `timeout` for the in-flight ones to complete
+#[cfg(unix)]
+fn drain_changes(drain: &crate::driver::Drain, timeout: std::time::Duration) {
+    info!("Draining in-flight operations before unmounting");
+    drain.start();
+    if !drain.wait(timeout) {
+        warn!("Some operations are still in flight after {timeout:?}; unmounting anyway");
+    }
+}
+
+/// A thread-safe handle to replace the remote of a mounted filesystem, as returned by [`Mount::refresher`].
+///
+/// Long-lived mounts backed by temporary credentials, such as STS tokens, can use it from a supervising thread to
+/// rotate the credentials without unmounting.
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub struct Refresher<T> {
+    pending: std::sync::Arc<crate::driver::PendingRemote<T>>,
+}
+
+#[cfg(unix)]
+impl<T> Clone for Refresher<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pending: self.pending.clone(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl<T> Refresher<T> {
+    /// Replace the remote of the filesystem with `remote`, created with the new credentials.
+    ///
+    /// The remote is swapped in before the next operation on the filesystem: the in-flight operation completes on
+    /// the current connection, which is then disconnected. `remote` is connected first; if it fails to connect, the
+    /// current connection is kept. If called again before the swap, only the last remote is used.
+    ///
+    /// The connections of the workers set with [`MountOption::Workers`] are not replaced.
+    pub fn refresh_credentials(&self, remote: T) {
+        debug!("Queuing a new remote to replace the current connection");
+        self.pending.set(remote);
+    }
+}
+
 /// A thread-safe handle to unmount the filesystem.
 pub struct Unmount {
     #[cfg(unix)]
     umount: fuser::SessionUnmounter,
+    #[cfg(unix)]
+    drain: std::sync::Arc<crate::driver::Drain>,
+    #[cfg(unix)]
+    unmounted: std::sync::Arc<std::sync::atomic::AtomicBool>,
     #[cfg(windows)]
     mountpoint: widestring::U16CString,
 }
@@ -116,7 +617,11 @@ impl Unmount {
     /// Unmount the filesystem.
     pub fn unmount(&mut self) -> Result<(), std::io::Error> {
         #[cfg(unix)]
-        self.umount.unmount()?;
+        {
+            self.umount.unmount()?;
+            self.unmounted
+                .store(true, std::sync::atomic::Ordering::SeqCst);
+        }
 
         #[cfg(windows)]
         if !dokan::unmount(&self.mountpoint) {
@@ -128,4 +633,39 @@ impl Unmount {
 
         Ok(())
     }
+
+    /// Unmount the filesystem once the pending changes are complete.
+    ///
+    /// New operations changing the filesystem are rejected with `ESHUTDOWN`, while the in-flight writes and flushes
+    /// are given up to `timeout` to complete; then the filesystem is unmounted, uploading the remaining buffered
+    /// writes. On Windows the filesystem is unmounted straight away.
+    pub fn unmount_graceful(&mut self, timeout: std::time::Duration) -> Result<(), std::io::Error> {
+        #[cfg(unix)]
+        drain_changes(&self.drain, timeout);
+        #[cfg(windows)]
+        let _ = timeout;
+
+        self.unmount()
+    }
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_unescape_mount_path() {
+        assert_eq!(
+            unescape_mount_path(r"/mnt/my\040remote\134dir"),
+            std::ffi::OsString::from(r"/mnt/my remote\dir")
+        );
+        assert_eq!(
+            unescape_mount_path(r"/mnt/remote\"),
+            std::ffi::OsString::from(r"/mnt/remote\")
+        );
+    }
 }
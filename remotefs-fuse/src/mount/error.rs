@@ -0,0 +1,120 @@
+use std::fmt;
+use std::io;
+
+/// An error mounting the filesystem, telling apart the usual causes so that they can be reported with a remedy.
+///
+/// The underlying [`io::Error`] is kept as the source of each variant.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MountError {
+    /// The user is not allowed to use the FUSE device or to mount on the mount point
+    PermissionDenied(io::Error),
+    /// FUSE is not available: the FUSE device, the kernel module or the mount helper (`fusermount`) is missing
+    MissingHelper(io::Error),
+    /// The mount point is busy, such as when something is already mounted on it, or is a stale mount
+    Busy(io::Error),
+    /// The kernel or the mount helper rejected the mount options or the mount point
+    OptionRejected(io::Error),
+    /// Any other error
+    Io(io::Error),
+}
+
+impl MountError {
+    /// Get the underlying [`io::Error`]
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            Self::PermissionDenied(err)
+            | Self::MissingHelper(err)
+            | Self::Busy(err)
+            | Self::OptionRejected(err)
+            | Self::Io(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for MountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PermissionDenied(err) => write!(f, "permission denied: {err}"),
+            Self::MissingHelper(err) => write!(f, "FUSE is not available: {err}"),
+            Self::Busy(err) => write!(f, "mount point is busy: {err}"),
+            Self::OptionRejected(err) => write!(f, "mount rejected: {err}"),
+            Self::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for MountError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+impl From<io::Error> for MountError {
+    fn from(err: io::Error) -> Self {
+        #[cfg(unix)]
+        match err.raw_os_error() {
+            Some(libc::EBUSY | libc::ENOTCONN) => return Self::Busy(err),
+            Some(libc::ENODEV | libc::ENXIO) => return Self::MissingHelper(err),
+            _ => {}
+        }
+
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(err),
+            io::ErrorKind::NotFound => Self::MissingHelper(err),
+            io::ErrorKind::InvalidInput => Self::OptionRejected(err),
+            _ => Self::Io(err),
+        }
+    }
+}
+
+impl From<MountError> for io::Error {
+    fn from(err: MountError) -> Self {
+        match err {
+            MountError::PermissionDenied(err)
+            | MountError::MissingHelper(err)
+            | MountError::Busy(err)
+            | MountError::OptionRejected(err)
+            | MountError::Io(err) => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_classify_mount_errors() {
+        assert!(matches!(
+            MountError::from(io::Error::from(io::ErrorKind::PermissionDenied)),
+            MountError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            MountError::from(io::Error::from(io::ErrorKind::NotFound)),
+            MountError::MissingHelper(_)
+        ));
+        assert!(matches!(
+            MountError::from(io::Error::from(io::ErrorKind::InvalidInput)),
+            MountError::OptionRejected(_)
+        ));
+        #[cfg(unix)]
+        assert!(matches!(
+            MountError::from(io::Error::from_raw_os_error(libc::EBUSY)),
+            MountError::Busy(_)
+        ));
+        assert!(matches!(
+            MountError::from(io::Error::other("boom")),
+            MountError::Io(_)
+        ));
+    }
+
+    #[test]
+    fn test_should_convert_mount_error_into_io_error() {
+        let err = io::Error::from(MountError::from(io::Error::from(
+            io::ErrorKind::PermissionDenied,
+        )));
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
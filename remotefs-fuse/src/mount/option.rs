@@ -27,6 +27,387 @@ pub enum MountOption {
     /// Set the default file mode in case the filesystem doesn't provide one
     /// If not set, the default is 0755
     DefaultMode(u32),
+    #[cfg(unix)]
+    /// Serve Prometheus metrics (operation counters and latency histograms) over HTTP at the given address.
+    ///
+    /// The metrics are exposed at the `/metrics` path. If not set, no metrics are collected.
+    MetricsAddr(std::net::SocketAddr),
+    #[cfg(unix)]
+    /// Size in bytes of the in-memory cache used to serve reads.
+    ///
+    /// File data is cached in fixed-size pages, so that small or overlapping reads don't need to fetch the data from the
+    /// remote each time. The cache is invalidated when a file is written, its attributes are changed or it is removed.
+    /// If not set, reads are not cached.
+    ReadCacheSize(usize),
+    #[cfg(unix)]
+    /// Maximum number of symbolic links followed by the driver when it resolves a link target.
+    ///
+    /// When the limit is exceeded, the operation fails with `ELOOP`. If not set, the default is 40.
+    MaxSymlinkDepth(usize),
+    #[cfg(unix)]
+    /// Look up names case-insensitively.
+    ///
+    /// When the exact path of a looked up name doesn't exist on the remote, the parent directory is listed and an entry
+    /// matching the name with a different case is used instead. The directory listing is cached for a short time, to avoid
+    /// listing the parent for each failed lookup.
+    ///
+    /// Inodes are derived from the path on the remote, so the same file may be reachable with different names, which all
+    /// resolve to the same inode.
+    CaseInsensitive,
+    #[cfg(unix)]
+    /// Mount the subtree of the remote at the given path instead of its root.
+    ///
+    /// Every path is joined under the prefix before it is passed to the remote, while the mounted filesystem stays rooted
+    /// at `/`.
+    RootPrefix(std::path::PathBuf),
+    #[cfg(unix)]
+    /// Hide the entries whose name (or path in the mount) matches any of the patterns from the directory listings.
+    ///
+    /// This is purely presentational: excluded files can still be accessed by their path, unless
+    /// [`MountOption::HideStrict`] is set too. The patterns are separated by commas, e.g. `exclude=.DS_Store,*.tmp`.
+    Exclude(Vec<glob::Pattern>),
+    #[cfg(unix)]
+    /// Make the files excluded with [`MountOption::Exclude`] inaccessible: looking them up fails with `ENOENT`.
+    HideStrict,
+    #[cfg(unix)]
+    /// Size in bytes of the chunks used to transfer data from and to the remote.
+    ///
+    /// Large reads and writes are processed in chunks of this size, so that the memory used by a single operation doesn't
+    /// depend on the size requested by the kernel. If not set, the default is 64 KiB.
+    IoChunkSize(usize),
+    #[cfg(unix)]
+    /// Open files in direct I/O mode, bypassing the kernel page cache.
+    ///
+    /// In this mode the values returned by `read` and `write` directly reflect the data transferred from and to the remote,
+    /// so a short read is reported as such instead of being substituted with zeroes. Useful for streaming backends, where
+    /// the page cache may cause stale reads or wasteful double-buffering.
+    DirectIo,
+    #[cfg(unix)]
+    /// Check the open files for changes on the remote every interval.
+    ///
+    /// When a file is changed by another client, the cached data for it is invalidated, both in the mount and in the kernel
+    /// page cache. The value is a duration, such as `30s`, `500ms` or `1m`; a plain number is read as seconds.
+    /// If not set, open files are not checked.
+    ///
+    /// The files are checked in background on a dedicated connection, so this requires a connector; see
+    /// [`crate::Mount::mount_with_connector`].
+    RevalidateInterval(std::time::Duration),
+    #[cfg(unix)]
+    /// Maximum size in bytes of the data read ahead by the kernel in a single request.
+    ///
+    /// Larger values reduce the amount of requests on high-latency remotes. The value is clamped to the maximum permitted
+    /// by the kernel.
+    MaxRead(u32),
+    #[cfg(unix)]
+    /// Maximum size in bytes of the data written by the kernel in a single request.
+    ///
+    /// Larger values reduce the amount of requests on high-latency remotes. The value is clamped to the maximum permitted
+    /// by the kernel.
+    MaxWrite(u32),
+    #[cfg(unix)]
+    /// Maximum size in bytes of the kernel readahead.
+    ///
+    /// Sequential reads are served from a single stream on the remote, so a large readahead turns into large forward
+    /// reads, which hides the remote latency. Takes precedence over [`MountOption::MaxRead`].
+    /// If not set, the default is 512 KiB.
+    ReadAhead(u32),
+    #[cfg(unix)]
+    /// Serve reads on a pool of this many worker threads, so that a slow read doesn't block the other operations.
+    ///
+    /// The workers use a pool of connections to the remote (see [`MountOption::PoolSize`]), created with the connector
+    /// passed to [`crate::Mount::mount_with_connector`]; when mounting with [`crate::Mount::mount_blocking`] this option is
+    /// ignored. Since the connections are independent, the workers can be used with every backend whose connections
+    /// see the same filesystem, such as SFTP, SCP, FTP, SMB, WebDAV, S3 and Kube. It must not be used with
+    /// backends whose state is local to the instance, such as the memory filesystem.
+    Workers(usize),
+    #[cfg(unix)]
+    /// Amount of connections to the remote shared by the workers set with [`MountOption::Workers`].
+    ///
+    /// Each in-flight read checks out a connection from the pool and returns it once done; if all the connections
+    /// are in use, the read waits for one to be returned. If not set, there is a connection for each worker.
+    PoolSize(usize),
+    #[cfg(unix)]
+    /// Switch the mount to read-only after this many consecutive failed writes or metadata changes on the remote.
+    ///
+    /// Once tripped, the operations changing the filesystem fail with `EROFS` instead of hitting the remote again, until
+    /// the driver reconnects successfully to the remote. This stops the retry storms caused by a remote which rejects
+    /// every write, e.g. because the quota is exceeded or the credentials have expired. If not set, the mount never
+    /// switches to read-only.
+    WriteFailureThreshold(u32),
+    #[cfg(unix)]
+    /// Follow the symbolic links on the remote, presenting the type, size and attributes of their target instead of a link.
+    ///
+    /// The links are resolved with the limit set by [`MountOption::MaxSymlinkDepth`]; links which can't be resolved, because
+    /// they're dangling or too deeply chained, are presented as links. The types of the directory entries returned by
+    /// `readdir` are dereferenced too, at the cost of a `stat` on the remote for each link in the directory.
+    FollowSymlinks,
+    #[cfg(unix)]
+    /// Upload every write to the remote before replying to it, instead of deferring the upload to `flush` and `release`.
+    ///
+    /// Errors of the upload are reported by the `write` call itself, so no written data is silently lost on a flaky link.
+    /// This is very slow: since the remotes can't write at an offset, the whole pending content of the file is uploaded on
+    /// each write, so writing a file in `n` chunks uploads it `n` times. Use it only for durability-critical mounts of small
+    /// files. If not set, writes are buffered and uploaded on `flush`, `fsync` and `release`.
+    SyncWrites,
+    #[cfg(unix)]
+    /// Umask applied to the mode of the created files and directories, instead of the umask of the creating process.
+    ///
+    /// Useful when the kernel doesn't provide the process umask, or to enforce the same permissions for every file created
+    /// in the mount. The value is an octal number, such as `022`. If not set, the umask of the creating process is applied.
+    Umask(u32),
+    #[cfg(unix)]
+    /// List the opened directories every interval, to notify the changes made on the remote by another client.
+    ///
+    /// When an entry is created, deleted or modified on the remote, its cached data is invalidated in the kernel, so that
+    /// file watchers and tools like `tail -f` see the change. The value is a duration, such as `30s`, `500ms` or `1m`; a
+    /// plain number is read as seconds. If not set, directories are not watched. See also [`MountOption::WatchDepth`].
+    ///
+    /// The directories are listed in background on a dedicated connection, so this requires a connector; see
+    /// [`crate::Mount::mount_with_connector`].
+    WatchInterval(std::time::Duration),
+    #[cfg(unix)]
+    /// Levels of subdirectories listed below each directory watched with [`MountOption::WatchInterval`].
+    ///
+    /// Each level requires an additional listing of every subdirectory on each check. Defaults to 0, only watching the
+    /// entries of the opened directories.
+    WatchDepth(u32),
+    #[cfg(unix)]
+    /// Maximum length in bytes of a file name in the mount.
+    ///
+    /// Creating an entry, or renaming one, with a longer name fails with `ENAMETOOLONG` before reaching the remote; the
+    /// existing entries with a longer name can still be looked up, renamed and removed. The value is reported by
+    /// `statfs`. Defaults to 255.
+    MaxNameLen(u32),
+    #[cfg(unix)]
+    /// How long the kernel caches the attributes of a file, such as its size and modification time, returned by `getattr`
+    /// and `setattr`.
+    ///
+    /// A longer timeout saves round trips to the remote, at the cost of noticing later the changes made by other clients.
+    /// The value is a duration, such as `30s`, `500ms` or `1m`; a plain number is read as seconds. Defaults to `1s`.
+    AttrTimeout(std::time::Duration),
+    #[cfg(unix)]
+    /// How long the kernel caches the name to inode mapping of a directory entry, returned by `lookup`, `create`, `mkdir`,
+    /// `mknod` and `symlink`.
+    ///
+    /// Unlike [`MountOption::AttrTimeout`], this only tells how long a name keeps resolving to the same file: for mostly
+    /// static trees a longer entry timeout avoids a `lookup` on the remote for each path resolution, even while the attributes
+    /// are refreshed more often. Note that the attributes returned along with the entry are cached for the same duration.
+    /// The value is a duration, such as `30s`, `500ms` or `1m`; a plain number is read as seconds. Defaults to `1s`.
+    EntryTimeout(std::time::Duration),
+    #[cfg(unix)]
+    /// How long the mount remembers that a path doesn't exist on the remote, answering the lookups of it without a `stat`.
+    ///
+    /// Shells and build tools probe many nonexistent paths, such as when searching the `PATH`; each miss is otherwise a
+    /// round trip to the remote. A path created through the mount is forgotten at once, but one created by other clients
+    /// of the remote is noticed only once the timeout expires. A timeout of `0` disables the cache.
+    /// The value is a duration, such as `30s`, `500ms` or `1m`; a plain number is read as seconds. Defaults to `1s`.
+    NegativeTimeout(std::time::Duration),
+    #[cfg(unix)]
+    /// Write a record for each operation changing the filesystem to the file at path, as a line of JSON.
+    ///
+    /// Each record has the time of the operation, the operation name, the path (and the destination on rename), the uid and
+    /// gid of the requesting process and the result, with the errno on failure. The records are written in the background, so
+    /// auditing doesn't slow down the operations. See also [`MountOption::AuditReads`].
+    AuditLog(std::path::PathBuf),
+    #[cfg(unix)]
+    /// Also record the files and directories opened for reading in the [`MountOption::AuditLog`].
+    AuditReads,
+    #[cfg(unix)]
+    /// Upload the buffered writes of a file once it hasn't been written for this duration, without waiting for it to be closed.
+    ///
+    /// Rapid small writes are coalesced into a single upload; the writes are also uploaded once more than 8 MiB have been
+    /// written since the last upload. Flushing, closing or syncing the file still uploads its content right away. The value
+    /// is a duration, such as `30s`, `500ms` or `1m`; a plain number is read as seconds. If not set, the writes are uploaded
+    /// only when the file is flushed.
+    WriteCoalesceWindow(std::time::Duration),
+    #[cfg(unix)]
+    /// Total capacity of the filesystem in bytes, reported by `statfs`.
+    ///
+    /// Remote filesystems don't report their size, so `df` shows this capacity, with the size of the files on the remote as
+    /// used and the rest as free. If not set, defaults to 1 PiB.
+    Capacity(u64),
+    #[cfg(unix)]
+    /// Remap the uids and gids of the remote to local ones, like `newuidmap` does for user namespaces.
+    ///
+    /// Each pair maps a remote id to a local id: the owners of the files on the remote are shown as the local ids,
+    /// while the owners set on the mount, such as with `chown` or when creating a file, are mapped back to the remote
+    /// ids. The ids not in the maps are passed through, unless `default_uid` or `default_gid` is set, which is the
+    /// local id the unmapped remote ids are shown as.
+    ///
+    /// As a string, the maps are comma-separated `u<remote>:<local>` and `g<remote>:<local>` entries, with `*` as the
+    /// remote id for the default, e.g. `id_map=u1002:1000,g100:1000,u*:65534`.
+    IdMap {
+        uid_map: Vec<(u32, u32)>,
+        gid_map: Vec<(u32, u32)>,
+        default_uid: Option<u32>,
+        default_gid: Option<u32>,
+    },
+    #[cfg(unix)]
+    /// Show all the files as owned by `uid` and `gid`, like the `all_squash` NFS export option.
+    ///
+    /// Only the owners shown on the mount change, not the ones on the remote. It takes precedence over
+    /// [`MountOption::RootSquash`] and [`MountOption::IdMap`], while [`MountOption::Uid`] and
+    /// [`MountOption::Gid`] still take precedence over it when checking the permissions.
+    ///
+    /// As a string: `all_squash=<uid>:<gid>`.
+    AllSquash { uid: u32, gid: u32 },
+    #[cfg(unix)]
+    /// Show the files owned by root on the remote as owned by `uid`, and the ones of the root group as owned by `gid`,
+    /// like the `root_squash` NFS export option.
+    ///
+    /// Only the owners shown on the mount change, not the ones on the remote. It takes precedence over
+    /// [`MountOption::IdMap`] for the root ids, while [`MountOption::Uid`] and [`MountOption::Gid`] still take
+    /// precedence over it when checking the permissions.
+    ///
+    /// As a string: `root_squash=<uid>:<gid>`.
+    RootSquash { uid: u32, gid: u32 },
+    #[cfg(unix)]
+    /// Hide the entries whose name starts with a `.` from the directory listings.
+    ///
+    /// Like [`MountOption::Exclude`] this is purely presentational: dotfiles can still be looked up and opened by their path.
+    HideDotfiles,
+    #[cfg(unix)]
+    /// Directory where the temporary files are created, such as the files used to read from remotes which don't support
+    /// streams.
+    ///
+    /// If not set, the system temporary directory is used. The directory must be writable, otherwise the mount fails.
+    TempDir(std::path::PathBuf),
+    #[cfg(unix)]
+    /// Maximum size in bytes of the content of a file being written which is kept in memory.
+    ///
+    /// Beyond this size, the pending content is moved to a temporary file in [`MountOption::TempDir`], so that writing large
+    /// files doesn't exhaust the memory of the host. If not set, the whole content is kept in memory.
+    WriteMemLimit(usize),
+    #[cfg(unix)]
+    /// Force permission bits on the mode of all the entries: the mode shown is `(mode & and_bits) | or_bits`.
+    ///
+    /// Only the modes shown on the mount and checked by the driver change, not the ones on the remote. As a string, the
+    /// bits are in octal and either can be omitted, e.g. `attr_mask=or:040,and:0775` to grant read to the group and
+    /// strip write from the others.
+    AttrMask { or_bits: u32, and_bits: u32 },
+    #[cfg(unix)]
+    /// List the directories on `opendir`, so that the first `readdir` is served without waiting for the remote.
+    ///
+    /// The listing is dropped when the directory is released.
+    PrefetchDirs,
+    #[cfg(unix)]
+    /// Don't check the permissions of the files in the driver: every operation is passed to the remote, which is the sole
+    /// authority and fails it with its own error.
+    ///
+    /// This is meant for the remotes with their own authorization, such as Kube RBAC or S3 IAM, whose rules the file modes
+    /// shown on the mount don't reflect. The mount relies entirely on the remote to enforce security: any user who can access
+    /// the mount can attempt any operation with the credentials of the remote.
+    NoAccessCheck,
+    #[cfg(unix)]
+    /// Maximum amount of pending background requests the kernel sends to the driver, such as readaheads and asynchronous
+    /// writes.
+    ///
+    /// Since the latency of the remote dominates, more requests in flight hide it better on concurrent workloads.
+    /// If not set, the default is 64.
+    MaxBackground(u16),
+    #[cfg(unix)]
+    /// Amount of pending background requests at which the kernel considers the request queue of the driver congested.
+    ///
+    /// It is capped to [`MountOption::MaxBackground`]. If not set, the default is 3/4 of the maximum background requests.
+    CongestionThreshold(u16),
+    #[cfg(unix)]
+    /// Keep a trace of the last N operations handled (operation, path, result and duration) in memory.
+    ///
+    /// The trace is written to the log on `SIGUSR1`, and served at the `/trace` path along with the metrics if
+    /// [`MountOption::MetricsAddr`] is set. Reads served by the workers are traced as successful when queued.
+    OpTrace(usize),
+    #[cfg(unix)]
+    /// Limit the rate of the reads from the remote, in bytes per second, across all the files of the mount.
+    ///
+    /// Short bursts of up to a second of transfer are not delayed. If not set, reads are not throttled.
+    ReadRateLimit(u64),
+    #[cfg(unix)]
+    /// Limit the rate of the uploads to the remote, in bytes per second, across all the files of the mount.
+    ///
+    /// Short bursts of up to a second of transfer are not delayed. If not set, uploads are not throttled.
+    WriteRateLimit(u64),
+    #[cfg(unix)]
+    /// Split the reads of at least 128 KiB in up to this many ranges, read concurrently by the workers.
+    ///
+    /// Requires [`MountOption::Workers`]. Each range is read from its own stream, seeking to the offset of the range,
+    /// so this is worth it when a single stream is limited by the latency of the remote. Backends whose streams can't
+    /// seek fall back to serial reads. If not set, reads are not split.
+    ParallelReadStreams(usize),
+    #[cfg(unix)]
+    /// Whether `release` makes the written data durable before closing the file (default: `false`).
+    ///
+    /// Writes are buffered by the driver and uploaded on `flush` and `release`; an upload failing on `release` is
+    /// only logged, since `close()` has already returned, and the pending writes are lost once the file is no
+    /// longer open. When enabled, `release` syncs the file as `fsync` does, confirms the size of the uploaded
+    /// file, and replies with the real error, at the cost of an extra roundtrip on every close of a written file.
+    FsyncOnClose(bool),
+    #[cfg(unix)]
+    /// Cache the targets of the symlinks read with `readlink`.
+    ///
+    /// Targets don't change for the life of a link, so they are only invalidated when the link is replaced by `symlink`
+    /// or `rename`, or removed by `unlink`; this saves a remote read each time a path goes through a link. The cache
+    /// holds up to [`MountOption::ReadCacheSize`] bytes of targets, or 1 MiB if not set.
+    CacheSymlinks,
+    #[cfg(unix)]
+    /// Maximum amount of inodes kept by the driver (default: unbounded).
+    ///
+    /// The kernel forgets the inodes it looked up only opportunistically, so a long-lived mount traversing many paths
+    /// keeps growing; once the capacity is exceeded, the least recently resolved inodes are evicted, except those of
+    /// the open files. The kernel may still refer to an evicted inode, whose operations then fail with `ENOENT` until
+    /// it is looked up again, so the capacity should exceed the working set of the mount by far.
+    InodeCacheCap(usize),
+    #[cfg(unix)]
+    /// Granularity of the times stored by the remote, such as `1s` for a remote keeping only whole seconds.
+    ///
+    /// The access, modification and creation times are truncated to the granularity both when reported and when set,
+    /// so that a time set through the mount reads back the same, and tools comparing the times, such as `make` and
+    /// `rsync`, don't see a file change when the remote drops the sub-second part. The value is a duration, such as
+    /// `1s` or `10ms`; a plain number is read as seconds. If not set, the times are reported as stored by the remote.
+    TimeGranularity(std::time::Duration),
+    #[cfg(unix)]
+    /// Upload the writes at the end of a file with the append operation of the remote, instead of re-uploading the file.
+    ///
+    /// Writes are otherwise buffered with the whole content of the file, which is downloaded first and uploaded again on
+    /// `flush`, so appending to a large file such as a log transfers all of it each time. With this option a write at
+    /// the end of a file with no pending writes is appended at once, at the cost of a request to the remote for each
+    /// write. Remotes which can't append fall back to the buffered writes.
+    AppendWrites,
+    #[cfg(unix)]
+    /// How many times in a row a read or write of a remote stream interrupted by a signal, or which would block, is
+    /// retried before failing with `EIO` (default: `3`).
+    ///
+    /// This only covers the low-level interruptions of the streams; the operations failing on the remote are not
+    /// retried. A value of `0` reports the first interruption.
+    IoRetries(usize),
+    #[cfg(unix)]
+    /// Mount the single file of the remote at the given path, instead of a directory tree.
+    ///
+    /// The mount point itself is the file, which can be read and written like any other, e.g. to attach a disk image
+    /// of the remote as a loop device; since the root isn't a directory, listing it or looking up a name in it fails
+    /// with `ENOTDIR`. The mount point must be a regular file too. The path is joined under
+    /// [`MountOption::RootPrefix`] if set.
+    SingleFile(std::path::PathBuf),
+    #[cfg(unix)]
+    /// Give the entries created in any directory the group of the directory, as if every directory had the setgid bit.
+    ///
+    /// Entries created in a directory with the setgid bit always inherit its group, and new subdirectories inherit the
+    /// bit too; since the remotes don't store the setgid bit, it is only known for the directories set so by `chmod`
+    /// through the mount. If not set, entries created elsewhere belong to the group of the creating process.
+    GidFromParent,
+    #[cfg(unix)]
+    /// Serve a liveness endpoint over HTTP at the given address, at the `/health` path.
+    ///
+    /// It replies `200 OK` while the session is alive and the last operation didn't fail on the remote, and
+    /// `503 Service Unavailable` otherwise, with a JSON body telling the time of the last operation and the last error.
+    /// If the address is the same as [`MountOption::MetricsAddr`], the endpoint is served by the metrics server.
+    HealthAddr(std::net::SocketAddr),
+    #[cfg(unix)]
+    /// Allow the changes only under the given paths of the mount: the operations changing an entry outside of all of
+    /// them fail with `EROFS`, while reads are allowed everywhere.
+    ///
+    /// The paths are separated by commas, e.g. `writable_prefix=/uploads,/tmp`; a prefix itself is writable, so it can
+    /// be created and removed too.
+    WritablePrefix(Vec<std::path::PathBuf>),
     /* fuser */
     /// Set the name of the source in mtab
     #[cfg(unix)]
@@ -56,6 +437,9 @@ pub enum MountOption {
     #[cfg_attr(docsrs, doc(cfg(unix)))]
     AutoUnmount,
     /// Enable permission checking in the kernel
+    ///
+    /// When set, the kernel checks the file modes before calling into the driver, so the driver
+    /// won't perform its own access checks and `access()` won't be called.
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
     DefaultPermissions,
@@ -112,6 +496,14 @@ pub enum MountOption {
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
     Async,
+    /// Allow mounting over a directory which is not empty.
+    ///
+    /// The files in the directory are hidden by the mounted filesystem until it is unmounted: they can't be
+    /// accessed, and the processes which already have them open keep on using the hidden files. libfuse 3 always
+    /// allows mounting on a non-empty directory; this flag is only needed with libfuse 2.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    NonEmpty,
 
     // dokany
     /// Only use a single thread to process events. This is highly not recommended as can easily create a bottleneck.
@@ -167,6 +559,7 @@ impl TryFrom<&MountOption> for fuser::MountOption {
             MountOption::DirSync => fuser::MountOption::DirSync,
             MountOption::Sync => fuser::MountOption::Sync,
             MountOption::Async => fuser::MountOption::Async,
+            MountOption::NonEmpty => fuser::MountOption::CUSTOM("nonempty".to_string()),
             _ => return Err("Unsupported mount option"),
         })
     }
@@ -234,136 +627,915 @@ impl FromStr for MountOption {
             #[cfg(unix)]
             ("default_mode", None) => Err("default_mode requires a value".to_string()),
             #[cfg(unix)]
-            ("fsname", Some(value)) => Ok(MountOption::FSName(value.to_string())),
+            ("metrics_addr", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid metrics_addr value: {}", e))?;
+                Ok(MountOption::MetricsAddr(value))
+            }
             #[cfg(unix)]
-            ("fsname", None) => Err("fsname requires a value".to_string()),
+            ("metrics_addr", None) => Err("metrics_addr requires a value".to_string()),
             #[cfg(unix)]
-            ("subtype", Some(value)) => Ok(MountOption::Subtype(value.to_string())),
+            ("read_cache_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid read_cache_size value: {}", e))?;
+                Ok(MountOption::ReadCacheSize(value))
+            }
             #[cfg(unix)]
-            ("subtype", None) => Err("subtype requires a value".to_string()),
+            ("read_cache_size", None) => Err("read_cache_size requires a value".to_string()),
             #[cfg(unix)]
-            ("custom", Some(value)) => Ok(MountOption::Custom(value.to_string())),
+            ("max_symlink_depth", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_symlink_depth value: {}", e))?;
+                Ok(MountOption::MaxSymlinkDepth(value))
+            }
             #[cfg(unix)]
-            ("custom", None) => Err("custom requires a value".to_string()),
+            ("max_symlink_depth", None) => Err("max_symlink_depth requires a value".to_string()),
             #[cfg(unix)]
-            ("allow_other", None) => Ok(MountOption::AllowOther),
+            ("case_insensitive", None) => Ok(MountOption::CaseInsensitive),
             #[cfg(unix)]
-            ("allow_root", None) => Ok(MountOption::AllowRoot),
+            ("root_prefix", Some(value)) => {
+                Ok(MountOption::RootPrefix(std::path::PathBuf::from(value)))
+            }
             #[cfg(unix)]
-            ("auto_unmount", None) => Ok(MountOption::AutoUnmount),
+            ("root_prefix", None) => Err("root_prefix requires a value".to_string()),
             #[cfg(unix)]
-            ("default_permissions", None) => Ok(MountOption::DefaultPermissions),
+            ("exclude", Some(value)) => {
+                let patterns = value
+                    .split(',')
+                    .map(glob::Pattern::new)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("Invalid exclude value: {}", e))?;
+                Ok(MountOption::Exclude(patterns))
+            }
             #[cfg(unix)]
-            ("dev", None) => Ok(MountOption::Dev),
+            ("exclude", None) => Err("exclude requires a value".to_string()),
             #[cfg(unix)]
-            ("nodev", None) => Ok(MountOption::NoDev),
+            ("hide_strict", None) => Ok(MountOption::HideStrict),
             #[cfg(unix)]
-            ("suid", None) => Ok(MountOption::Suid),
+            ("io_chunk_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid io_chunk_size value: {}", e))?;
+                Ok(MountOption::IoChunkSize(value))
+            }
             #[cfg(unix)]
-            ("nosuid", None) => Ok(MountOption::NoSuid),
+            ("io_chunk_size", None) => Err("io_chunk_size requires a value".to_string()),
             #[cfg(unix)]
-            ("ro", None) => Ok(MountOption::RO),
+            ("direct_io", None) => Ok(MountOption::DirectIo),
             #[cfg(unix)]
-            ("rw", None) => Ok(MountOption::RW),
+            ("revalidate_interval", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid revalidate_interval value: {}", e))?;
+                Ok(MountOption::RevalidateInterval(value))
+            }
             #[cfg(unix)]
-            ("exec", None) => Ok(MountOption::Exec),
+            ("revalidate_interval", None) => {
+                Err("revalidate_interval requires a value".to_string())
+            }
             #[cfg(unix)]
-            ("noexec", None) => Ok(MountOption::NoExec),
+            ("max_read", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_read value: {}", e))?;
+                Ok(MountOption::MaxRead(value))
+            }
             #[cfg(unix)]
-            ("atime", None) => Ok(MountOption::Atime),
+            ("max_read", None) => Err("max_read requires a value".to_string()),
             #[cfg(unix)]
-            ("noatime", None) => Ok(MountOption::NoAtime),
+            ("max_write", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_write value: {}", e))?;
+                Ok(MountOption::MaxWrite(value))
+            }
             #[cfg(unix)]
-            ("dirsync", None) => Ok(MountOption::DirSync),
+            ("max_write", None) => Err("max_write requires a value".to_string()),
             #[cfg(unix)]
-            ("sync", None) => Ok(MountOption::Sync),
+            ("read_ahead", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid read_ahead value: {}", e))?;
+                Ok(MountOption::ReadAhead(value))
+            }
             #[cfg(unix)]
-            ("async", None) => Ok(MountOption::Async),
-            #[cfg(windows)]
-            ("single_thread", None) => Ok(MountOption::SingleThread),
-            #[cfg(windows)]
-            ("flags", Some(value)) => {
+            ("read_ahead", None) => Err("read_ahead requires a value".to_string()),
+            #[cfg(unix)]
+            ("workers", Some(value)) => {
                 let value = value
                     .parse()
-                    .map_err(|e| format!("Invalid flags value: {}", e))?;
-                Ok(MountOption::Flags(value))
+                    .map_err(|e| format!("Invalid workers value: {}", e))?;
+                Ok(MountOption::Workers(value))
             }
-            #[cfg(windows)]
-            ("flags", None) => Err("flags requires a value".to_string()),
-            #[cfg(windows)]
-            ("timeout", Some(value)) => {
-                let value = std::time::Duration::from_millis(
-                    value
-                        .parse()
-                        .map_err(|e| format!("Invalid timeout value: {}", e))?,
-                );
-                Ok(MountOption::Timeout(value))
+            #[cfg(unix)]
+            ("workers", None) => Err("workers requires a value".to_string()),
+            #[cfg(unix)]
+            ("pool_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid pool_size value: {}", e))?;
+                Ok(MountOption::PoolSize(value))
             }
-            #[cfg(windows)]
-            ("timeout", None) => Err("timeout requires a value".to_string()),
-            #[cfg(windows)]
-            ("allocation_unit_size", Some(value)) => {
+            #[cfg(unix)]
+            ("pool_size", None) => Err("pool_size requires a value".to_string()),
+            #[cfg(unix)]
+            ("write_failure_threshold", Some(value)) => {
                 let value = value
                     .parse()
-                    .map_err(|e| format!("Invalid allocation_unit_size value: {}", e))?;
-                Ok(MountOption::AllocationUnitSize(value))
+                    .map_err(|e| format!("Invalid write_failure_threshold value: {}", e))?;
+                Ok(MountOption::WriteFailureThreshold(value))
             }
-            #[cfg(windows)]
-            ("allocation_unit_size", None) => {
-                Err("allocation_unit_size requires a value".to_string())
+            #[cfg(unix)]
+            ("write_failure_threshold", None) => {
+                Err("write_failure_threshold requires a value".to_string())
             }
-            #[cfg(windows)]
-            ("sector_size", Some(value)) => {
+            #[cfg(unix)]
+            ("follow_symlinks", None) => Ok(MountOption::FollowSymlinks),
+            #[cfg(unix)]
+            ("sync_writes", None) => Ok(MountOption::SyncWrites),
+            #[cfg(unix)]
+            ("umask", Some(value)) => {
+                let value = u32::from_str_radix(value, 8)
+                    .map_err(|e| format!("Invalid umask value: {}", e))?;
+                Ok(MountOption::Umask(value))
+            }
+            #[cfg(unix)]
+            ("umask", None) => Err("umask requires a value".to_string()),
+            #[cfg(unix)]
+            ("watch_interval", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid watch_interval value: {}", e))?;
+                Ok(MountOption::WatchInterval(value))
+            }
+            #[cfg(unix)]
+            ("watch_interval", None) => Err("watch_interval requires a value".to_string()),
+            #[cfg(unix)]
+            ("watch_depth", Some(value)) => {
                 let value = value
                     .parse()
-                    .map_err(|e| format!("Invalid sector_size value: {}", e))?;
-                Ok(MountOption::SectorSize(value))
+                    .map_err(|e| format!("Invalid watch_depth value: {}", e))?;
+                Ok(MountOption::WatchDepth(value))
             }
-            #[cfg(windows)]
-            ("sector_size", None) => Err("sector_size requires a value".to_string()),
-            _ => Err(format!("Unknown mount option: {}", s)),
-        }
-    }
-}
-
-#[cfg(test)]
-mod test {
-
-    use pretty_assertions::assert_eq;
-
-    use super::*;
-
-    #[test]
-    fn test_should_convert_str_to_option() {
-        #[cfg(unix)]
-        assert_eq!(
-            MountOption::from_str("uid=1000").unwrap(),
-            MountOption::Uid(1000)
-        );
-        #[cfg(unix)]
-        assert_eq!(
-            MountOption::from_str("gid=1000").unwrap(),
-            MountOption::Gid(1000)
-        );
-        #[cfg(unix)]
-        assert_eq!(
-            MountOption::from_str("default_mode=0755").unwrap(),
-            MountOption::DefaultMode(0o755)
-        );
-        #[cfg(unix)]
-        assert_eq!(
-            MountOption::from_str("fsname=foo").unwrap(),
-            MountOption::FSName("foo".to_string())
-        );
-        #[cfg(unix)]
-        assert_eq!(
-            MountOption::from_str("subtype=foo").unwrap(),
-            MountOption::Subtype("foo".to_string())
-        );
-        #[cfg(unix)]
-        assert_eq!(
-            MountOption::from_str("custom=foo").unwrap(),
-            MountOption::Custom("foo".to_string())
+            #[cfg(unix)]
+            ("watch_depth", None) => Err("watch_depth requires a value".to_string()),
+            #[cfg(unix)]
+            ("max_name_len", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_name_len value: {}", e))?;
+                Ok(MountOption::MaxNameLen(value))
+            }
+            #[cfg(unix)]
+            ("max_name_len", None) => Err("max_name_len requires a value".to_string()),
+            #[cfg(unix)]
+            ("attr_timeout", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid attr_timeout value: {}", e))?;
+                Ok(MountOption::AttrTimeout(value))
+            }
+            #[cfg(unix)]
+            ("attr_timeout", None) => Err("attr_timeout requires a value".to_string()),
+            #[cfg(unix)]
+            ("entry_timeout", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid entry_timeout value: {}", e))?;
+                Ok(MountOption::EntryTimeout(value))
+            }
+            #[cfg(unix)]
+            ("entry_timeout", None) => Err("entry_timeout requires a value".to_string()),
+            #[cfg(unix)]
+            ("negative_timeout", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid negative_timeout value: {}", e))?;
+                Ok(MountOption::NegativeTimeout(value))
+            }
+            #[cfg(unix)]
+            ("negative_timeout", None) => Err("negative_timeout requires a value".to_string()),
+            #[cfg(unix)]
+            ("audit_log", Some(value)) => {
+                Ok(MountOption::AuditLog(std::path::PathBuf::from(value)))
+            }
+            #[cfg(unix)]
+            ("audit_log", None) => Err("audit_log requires a value".to_string()),
+            #[cfg(unix)]
+            ("audit_reads", None) => Ok(MountOption::AuditReads),
+            #[cfg(unix)]
+            ("write_coalesce_window", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid write_coalesce_window value: {}", e))?;
+                Ok(MountOption::WriteCoalesceWindow(value))
+            }
+            #[cfg(unix)]
+            ("write_coalesce_window", None) => {
+                Err("write_coalesce_window requires a value".to_string())
+            }
+            #[cfg(unix)]
+            ("capacity", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid capacity value: {}", e))?;
+                Ok(MountOption::Capacity(value))
+            }
+            #[cfg(unix)]
+            ("capacity", None) => Err("capacity requires a value".to_string()),
+            #[cfg(unix)]
+            ("id_map", Some(value)) => {
+                parse_id_map(value).map_err(|e| format!("Invalid id_map value: {}", e))
+            }
+            #[cfg(unix)]
+            ("id_map", None) => Err("id_map requires a value".to_string()),
+            #[cfg(unix)]
+            ("all_squash", Some(value)) => {
+                let (uid, gid) =
+                    parse_owner(value).map_err(|e| format!("Invalid all_squash value: {}", e))?;
+                Ok(MountOption::AllSquash { uid, gid })
+            }
+            #[cfg(unix)]
+            ("all_squash", None) => Err("all_squash requires a value".to_string()),
+            #[cfg(unix)]
+            ("root_squash", Some(value)) => {
+                let (uid, gid) =
+                    parse_owner(value).map_err(|e| format!("Invalid root_squash value: {}", e))?;
+                Ok(MountOption::RootSquash { uid, gid })
+            }
+            #[cfg(unix)]
+            ("root_squash", None) => Err("root_squash requires a value".to_string()),
+            #[cfg(unix)]
+            ("hide_dotfiles", None) => Ok(MountOption::HideDotfiles),
+            #[cfg(unix)]
+            ("temp_dir", Some(value)) => Ok(MountOption::TempDir(std::path::PathBuf::from(value))),
+            #[cfg(unix)]
+            ("temp_dir", None) => Err("temp_dir requires a value".to_string()),
+            #[cfg(unix)]
+            ("write_mem_limit", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid write_mem_limit value: {}", e))?;
+                Ok(MountOption::WriteMemLimit(value))
+            }
+            #[cfg(unix)]
+            ("write_mem_limit", None) => Err("write_mem_limit requires a value".to_string()),
+            #[cfg(unix)]
+            ("attr_mask", Some(value)) => {
+                parse_attr_mask(value).map_err(|e| format!("Invalid attr_mask value: {}", e))
+            }
+            #[cfg(unix)]
+            ("attr_mask", None) => Err("attr_mask requires a value".to_string()),
+            #[cfg(unix)]
+            ("prefetch_dirs", None) => Ok(MountOption::PrefetchDirs),
+            #[cfg(unix)]
+            ("no_access_check", None) => Ok(MountOption::NoAccessCheck),
+            #[cfg(unix)]
+            ("max_background", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_background value: {}", e))?;
+                Ok(MountOption::MaxBackground(value))
+            }
+            #[cfg(unix)]
+            ("max_background", None) => Err("max_background requires a value".to_string()),
+            #[cfg(unix)]
+            ("congestion_threshold", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid congestion_threshold value: {}", e))?;
+                Ok(MountOption::CongestionThreshold(value))
+            }
+            #[cfg(unix)]
+            ("congestion_threshold", None) => {
+                Err("congestion_threshold requires a value".to_string())
+            }
+            #[cfg(unix)]
+            ("op_trace", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid op_trace value: {}", e))?;
+                Ok(MountOption::OpTrace(value))
+            }
+            #[cfg(unix)]
+            ("op_trace", None) => Err("op_trace requires a value".to_string()),
+            #[cfg(unix)]
+            ("read_rate_limit", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid read_rate_limit value: {}", e))?;
+                Ok(MountOption::ReadRateLimit(value))
+            }
+            #[cfg(unix)]
+            ("read_rate_limit", None) => Err("read_rate_limit requires a value".to_string()),
+            #[cfg(unix)]
+            ("write_rate_limit", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid write_rate_limit value: {}", e))?;
+                Ok(MountOption::WriteRateLimit(value))
+            }
+            #[cfg(unix)]
+            ("write_rate_limit", None) => Err("write_rate_limit requires a value".to_string()),
+            #[cfg(unix)]
+            ("parallel_read_streams", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid parallel_read_streams value: {}", e))?;
+                Ok(MountOption::ParallelReadStreams(value))
+            }
+            #[cfg(unix)]
+            ("parallel_read_streams", None) => {
+                Err("parallel_read_streams requires a value".to_string())
+            }
+            #[cfg(unix)]
+            ("fsync_on_close", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid fsync_on_close value: {}", e))?;
+                Ok(MountOption::FsyncOnClose(value))
+            }
+            #[cfg(unix)]
+            ("fsync_on_close", None) => Err("fsync_on_close requires a value".to_string()),
+            #[cfg(unix)]
+            ("cache_symlinks", None) => Ok(MountOption::CacheSymlinks),
+            #[cfg(unix)]
+            ("inode_cache_cap", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid inode_cache_cap value: {}", e))?;
+                Ok(MountOption::InodeCacheCap(value))
+            }
+            #[cfg(unix)]
+            ("inode_cache_cap", None) => Err("inode_cache_cap requires a value".to_string()),
+            #[cfg(unix)]
+            ("time_granularity", Some(value)) => {
+                let value = parse_duration(value)
+                    .map_err(|e| format!("Invalid time_granularity value: {}", e))?;
+                Ok(MountOption::TimeGranularity(value))
+            }
+            #[cfg(unix)]
+            ("time_granularity", None) => Err("time_granularity requires a value".to_string()),
+            #[cfg(unix)]
+            ("append_writes", None) => Ok(MountOption::AppendWrites),
+            #[cfg(unix)]
+            ("io_retries", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid io_retries value: {}", e))?;
+                Ok(MountOption::IoRetries(value))
+            }
+            #[cfg(unix)]
+            ("io_retries", None) => Err("io_retries requires a value".to_string()),
+            #[cfg(unix)]
+            ("single_file", Some(value)) => {
+                Ok(MountOption::SingleFile(std::path::PathBuf::from(value)))
+            }
+            #[cfg(unix)]
+            ("single_file", None) => Err("single_file requires a value".to_string()),
+            #[cfg(unix)]
+            ("gid_from_parent", None) => Ok(MountOption::GidFromParent),
+            #[cfg(unix)]
+            ("health_addr", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid health_addr value: {}", e))?;
+                Ok(MountOption::HealthAddr(value))
+            }
+            #[cfg(unix)]
+            ("health_addr", None) => Err("health_addr requires a value".to_string()),
+            #[cfg(unix)]
+            ("writable_prefix", Some(value)) => Ok(MountOption::WritablePrefix(
+                value.split(',').map(std::path::PathBuf::from).collect(),
+            )),
+            #[cfg(unix)]
+            ("writable_prefix", None) => Err("writable_prefix requires a value".to_string()),
+            #[cfg(unix)]
+            ("fsname", Some(value)) => Ok(MountOption::FSName(value.to_string())),
+            #[cfg(unix)]
+            ("fsname", None) => Err("fsname requires a value".to_string()),
+            #[cfg(unix)]
+            ("subtype", Some(value)) => Ok(MountOption::Subtype(value.to_string())),
+            #[cfg(unix)]
+            ("subtype", None) => Err("subtype requires a value".to_string()),
+            #[cfg(unix)]
+            ("custom", Some(value)) => Ok(MountOption::Custom(value.to_string())),
+            #[cfg(unix)]
+            ("custom", None) => Err("custom requires a value".to_string()),
+            #[cfg(unix)]
+            ("allow_other", None) => Ok(MountOption::AllowOther),
+            #[cfg(unix)]
+            ("allow_root", None) => Ok(MountOption::AllowRoot),
+            #[cfg(unix)]
+            ("auto_unmount", None) => Ok(MountOption::AutoUnmount),
+            #[cfg(unix)]
+            ("default_permissions", None) => Ok(MountOption::DefaultPermissions),
+            #[cfg(unix)]
+            ("dev", None) => Ok(MountOption::Dev),
+            #[cfg(unix)]
+            ("nodev", None) => Ok(MountOption::NoDev),
+            #[cfg(unix)]
+            ("suid", None) => Ok(MountOption::Suid),
+            #[cfg(unix)]
+            ("nosuid", None) => Ok(MountOption::NoSuid),
+            #[cfg(unix)]
+            ("ro", None) => Ok(MountOption::RO),
+            #[cfg(unix)]
+            ("rw", None) => Ok(MountOption::RW),
+            #[cfg(unix)]
+            ("exec", None) => Ok(MountOption::Exec),
+            #[cfg(unix)]
+            ("noexec", None) => Ok(MountOption::NoExec),
+            #[cfg(unix)]
+            ("atime", None) => Ok(MountOption::Atime),
+            #[cfg(unix)]
+            ("noatime", None) => Ok(MountOption::NoAtime),
+            #[cfg(unix)]
+            ("dirsync", None) => Ok(MountOption::DirSync),
+            #[cfg(unix)]
+            ("sync", None) => Ok(MountOption::Sync),
+            #[cfg(unix)]
+            ("async", None) => Ok(MountOption::Async),
+            #[cfg(unix)]
+            ("nonempty", None) => Ok(MountOption::NonEmpty),
+            #[cfg(windows)]
+            ("single_thread", None) => Ok(MountOption::SingleThread),
+            #[cfg(windows)]
+            ("flags", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid flags value: {}", e))?;
+                Ok(MountOption::Flags(value))
+            }
+            #[cfg(windows)]
+            ("flags", None) => Err("flags requires a value".to_string()),
+            #[cfg(windows)]
+            ("timeout", Some(value)) => {
+                let value = std::time::Duration::from_millis(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid timeout value: {}", e))?,
+                );
+                Ok(MountOption::Timeout(value))
+            }
+            #[cfg(windows)]
+            ("timeout", None) => Err("timeout requires a value".to_string()),
+            #[cfg(windows)]
+            ("allocation_unit_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid allocation_unit_size value: {}", e))?;
+                Ok(MountOption::AllocationUnitSize(value))
+            }
+            #[cfg(windows)]
+            ("allocation_unit_size", None) => {
+                Err("allocation_unit_size requires a value".to_string())
+            }
+            #[cfg(windows)]
+            ("sector_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid sector_size value: {}", e))?;
+                Ok(MountOption::SectorSize(value))
+            }
+            #[cfg(windows)]
+            ("sector_size", None) => Err("sector_size requires a value".to_string()),
+            _ => Err(format!("Unknown mount option: {}", s)),
+        }
+    }
+}
+
+/// Parse a duration such as `500ms`, `30s`, `5m` or `1h`. A plain number is read as seconds.
+#[cfg(unix)]
+fn parse_duration(value: &str) -> Result<std::time::Duration, String> {
+    let (number, unit) = match value.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number = number.trim().parse::<f64>().map_err(|e| e.to_string())?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(format!("{value} is not a valid duration"));
+    }
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        unit => return Err(format!("unknown duration unit: {unit}")),
+    };
+
+    Ok(std::time::Duration::from_secs_f64(seconds))
+}
+
+/// Parse an owner as `<uid>:<gid>`
+#[cfg(unix)]
+fn parse_owner(value: &str) -> Result<(u32, u32), String> {
+    let (uid, gid) = value
+        .split_once(':')
+        .ok_or_else(|| format!("{value} is not a <uid>:<gid> pair"))?;
+
+    Ok((
+        uid.parse().map_err(|e| format!("{uid}: {e}"))?,
+        gid.parse().map_err(|e| format!("{gid}: {e}"))?,
+    ))
+}
+
+/// Parse the comma-separated `or:<bits>` and `and:<bits>` entries of a [`MountOption::AttrMask`], in octal.
+#[cfg(unix)]
+fn parse_attr_mask(value: &str) -> Result<MountOption, String> {
+    let mut or_bits = 0;
+    let mut and_bits = 0o7777;
+
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (kind, bits) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("{entry} is not a <or|and>:<bits> pair"))?;
+        let bits = u32::from_str_radix(bits, 8).map_err(|e| format!("{entry}: {e}"))?;
+        match kind {
+            "or" => or_bits = bits,
+            "and" => and_bits = bits,
+            _ => return Err(format!("{entry} must start with or or and")),
+        }
+    }
+
+    Ok(MountOption::AttrMask { or_bits, and_bits })
+}
+
+/// Parse the comma-separated `u<remote>:<local>` and `g<remote>:<local>` entries of a [`MountOption::IdMap`].
+#[cfg(unix)]
+fn parse_id_map(value: &str) -> Result<MountOption, String> {
+    let mut uid_map = Vec::new();
+    let mut gid_map = Vec::new();
+    let mut default_uid = None;
+    let mut default_gid = None;
+
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (kind, ids) =
+            entry.split_at(entry.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(0));
+        let (remote, local) = ids
+            .split_once(':')
+            .ok_or_else(|| format!("{entry} is not a <remote>:<local> pair"))?;
+        let local = local.parse::<u32>().map_err(|e| format!("{entry}: {e}"))?;
+        let remote = match remote {
+            "*" => None,
+            remote => Some(remote.parse::<u32>().map_err(|e| format!("{entry}: {e}"))?),
+        };
+        match (kind, remote) {
+            ("u", Some(remote)) => uid_map.push((remote, local)),
+            ("u", None) => default_uid = Some(local),
+            ("g", Some(remote)) => gid_map.push((remote, local)),
+            ("g", None) => default_gid = Some(local),
+            _ => return Err(format!("{entry} must start with u or g")),
+        }
+    }
+
+    Ok(MountOption::IdMap {
+        uid_map,
+        gid_map,
+        default_uid,
+        default_gid,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_convert_str_to_option() {
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("uid=1000").unwrap(),
+            MountOption::Uid(1000)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("gid=1000").unwrap(),
+            MountOption::Gid(1000)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("default_mode=0755").unwrap(),
+            MountOption::DefaultMode(0o755)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("metrics_addr=127.0.0.1:9100").unwrap(),
+            MountOption::MetricsAddr("127.0.0.1:9100".parse().unwrap())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("read_cache_size=1048576").unwrap(),
+            MountOption::ReadCacheSize(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_symlink_depth=8").unwrap(),
+            MountOption::MaxSymlinkDepth(8)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("case_insensitive").unwrap(),
+            MountOption::CaseInsensitive
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("root_prefix=/data/exports").unwrap(),
+            MountOption::RootPrefix(std::path::PathBuf::from("/data/exports"))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("exclude=.DS_Store,*.tmp").unwrap(),
+            MountOption::Exclude(vec![
+                glob::Pattern::new(".DS_Store").unwrap(),
+                glob::Pattern::new("*.tmp").unwrap()
+            ])
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("hide_strict").unwrap(),
+            MountOption::HideStrict
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("io_chunk_size=4096").unwrap(),
+            MountOption::IoChunkSize(4096)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("direct_io").unwrap(),
+            MountOption::DirectIo
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("revalidate_interval=30s").unwrap(),
+            MountOption::RevalidateInterval(std::time::Duration::from_secs(30))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_read=1048576").unwrap(),
+            MountOption::MaxRead(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_write=1048576").unwrap(),
+            MountOption::MaxWrite(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("read_ahead=1048576").unwrap(),
+            MountOption::ReadAhead(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("workers=4").unwrap(),
+            MountOption::Workers(4)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("pool_size=2").unwrap(),
+            MountOption::PoolSize(2)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("write_failure_threshold=5").unwrap(),
+            MountOption::WriteFailureThreshold(5)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("follow_symlinks").unwrap(),
+            MountOption::FollowSymlinks
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("sync_writes").unwrap(),
+            MountOption::SyncWrites
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("umask=022").unwrap(),
+            MountOption::Umask(0o22)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("watch_interval=10s").unwrap(),
+            MountOption::WatchInterval(std::time::Duration::from_secs(10))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("watch_depth=2").unwrap(),
+            MountOption::WatchDepth(2)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_name_len=143").unwrap(),
+            MountOption::MaxNameLen(143)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("attr_timeout=5s").unwrap(),
+            MountOption::AttrTimeout(std::time::Duration::from_secs(5))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("entry_timeout=1m").unwrap(),
+            MountOption::EntryTimeout(std::time::Duration::from_secs(60))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("negative_timeout=500ms").unwrap(),
+            MountOption::NegativeTimeout(std::time::Duration::from_millis(500))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("audit_log=/var/log/remotefs.ndjson").unwrap(),
+            MountOption::AuditLog(std::path::PathBuf::from("/var/log/remotefs.ndjson"))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("audit_reads").unwrap(),
+            MountOption::AuditReads
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("write_coalesce_window=500ms").unwrap(),
+            MountOption::WriteCoalesceWindow(std::time::Duration::from_millis(500))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("capacity=1073741824").unwrap(),
+            MountOption::Capacity(1073741824)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("id_map=u1002:1000,g100:1000,u*:65534").unwrap(),
+            MountOption::IdMap {
+                uid_map: vec![(1002, 1000)],
+                gid_map: vec![(100, 1000)],
+                default_uid: Some(65534),
+                default_gid: None,
+            }
+        );
+        #[cfg(unix)]
+        assert!(MountOption::from_str("id_map=x1:2").is_err());
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("all_squash=1000:100").unwrap(),
+            MountOption::AllSquash {
+                uid: 1000,
+                gid: 100
+            }
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("root_squash=65534:65534").unwrap(),
+            MountOption::RootSquash {
+                uid: 65534,
+                gid: 65534
+            }
+        );
+        #[cfg(unix)]
+        assert!(MountOption::from_str("root_squash=65534").is_err());
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("hide_dotfiles").unwrap(),
+            MountOption::HideDotfiles
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("temp_dir=/var/tmp").unwrap(),
+            MountOption::TempDir(std::path::PathBuf::from("/var/tmp"))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("write_mem_limit=67108864").unwrap(),
+            MountOption::WriteMemLimit(67108864)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("attr_mask=or:040,and:0775").unwrap(),
+            MountOption::AttrMask {
+                or_bits: 0o040,
+                and_bits: 0o775
+            }
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("attr_mask=and:0755").unwrap(),
+            MountOption::AttrMask {
+                or_bits: 0,
+                and_bits: 0o755
+            }
+        );
+        #[cfg(unix)]
+        assert!(MountOption::from_str("attr_mask=xor:0755").is_err());
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("prefetch_dirs").unwrap(),
+            MountOption::PrefetchDirs
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("no_access_check").unwrap(),
+            MountOption::NoAccessCheck
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_background=128").unwrap(),
+            MountOption::MaxBackground(128)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("congestion_threshold=96").unwrap(),
+            MountOption::CongestionThreshold(96)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("op_trace=256").unwrap(),
+            MountOption::OpTrace(256)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("read_rate_limit=1048576").unwrap(),
+            MountOption::ReadRateLimit(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("write_rate_limit=1048576").unwrap(),
+            MountOption::WriteRateLimit(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("parallel_read_streams=4").unwrap(),
+            MountOption::ParallelReadStreams(4)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("fsync_on_close=true").unwrap(),
+            MountOption::FsyncOnClose(true)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("cache_symlinks").unwrap(),
+            MountOption::CacheSymlinks
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("inode_cache_cap=100000").unwrap(),
+            MountOption::InodeCacheCap(100000)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("time_granularity=1s").unwrap(),
+            MountOption::TimeGranularity(std::time::Duration::from_secs(1))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("append_writes").unwrap(),
+            MountOption::AppendWrites
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("io_retries=5").unwrap(),
+            MountOption::IoRetries(5)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("single_file=/images/disk.img").unwrap(),
+            MountOption::SingleFile(std::path::PathBuf::from("/images/disk.img"))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("gid_from_parent").unwrap(),
+            MountOption::GidFromParent
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("health_addr=127.0.0.1:9101").unwrap(),
+            MountOption::HealthAddr("127.0.0.1:9101".parse().unwrap())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("writable_prefix=/uploads,/tmp").unwrap(),
+            MountOption::WritablePrefix(vec![
+                std::path::PathBuf::from("/uploads"),
+                std::path::PathBuf::from("/tmp")
+            ])
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("fsname=foo").unwrap(),
+            MountOption::FSName("foo".to_string())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("subtype=foo").unwrap(),
+            MountOption::Subtype("foo".to_string())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("custom=foo").unwrap(),
+            MountOption::Custom("foo".to_string())
         );
         #[cfg(unix)]
         assert_eq!(
@@ -423,6 +1595,11 @@ mod test {
         assert_eq!(MountOption::from_str("sync").unwrap(), MountOption::Sync);
         #[cfg(unix)]
         assert_eq!(MountOption::from_str("async").unwrap(), MountOption::Async);
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("nonempty").unwrap(),
+            MountOption::NonEmpty
+        );
         #[cfg(windows)]
         assert_eq!(
             MountOption::from_str("single_thread").unwrap(),
@@ -449,4 +1626,28 @@ mod test {
             MountOption::SectorSize(512)
         );
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_convert_default_permissions_to_fuser() {
+        assert_eq!(
+            fuser::MountOption::try_from(&MountOption::DefaultPermissions).unwrap(),
+            fuser::MountOption::DefaultPermissions
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_parse_duration() {
+        use std::time::Duration;
+
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("foo").is_err());
+    }
 }
@@ -0,0 +1,527 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::{Seek as _, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A remote which can be layered in an [`OverlayFs`]
+pub type Layer = Box<dyn RemoteFs + Send + Sync>;
+
+/// The layer an entry of the [`OverlayFs`] comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Upper,
+    Lower,
+}
+
+/// A [`RemoteFs`] presenting two remotes as a single union filesystem.
+///
+/// The `upper` remote is writable and takes precedence; the entries missing from it are read from the `lower`
+/// remote, which is never changed:
+///
+/// - changing an entry of the lower remote copies it up first, along with its parent directories;
+/// - removing an entry of the lower remote records a whiteout, which hides it and its children from then on.
+///
+/// Whiteouts are kept in memory, so the entries removed from the lower remote show up again once the overlay is
+/// dropped. Directories coming from the lower remote can't be moved: [`RemoteFs::mov`] fails with
+/// [`RemoteErrorType::UnsupportedFeature`], so that the mount reports `EXDEV` and userland copies them instead.
+///
+/// Wrap the remotes before creating the mount:
+///
+/// ```rust,ignore
+/// let remote = OverlayFs::new(Box::new(upper), Box::new(lower));
+/// let mut mount = Mount::mount(remote, &mount_path, &options)?;
+/// ```
+pub struct OverlayFs {
+    upper: Layer,
+    lower: Layer,
+    /// Paths of the lower remote which have been removed
+    whiteouts: HashSet<PathBuf>,
+    /// Working directory
+    wd: PathBuf,
+    /// Layers of the read streams opened and not finalized yet, in the order they were opened
+    open_streams: VecDeque<Side>,
+}
+
+impl OverlayFs {
+    /// Create an overlay of `upper` over `lower`
+    pub fn new(upper: Layer, lower: Layer) -> Self {
+        Self {
+            upper,
+            lower,
+            whiteouts: HashSet::new(),
+            wd: PathBuf::from("/"),
+            open_streams: VecDeque::new(),
+        }
+    }
+
+    /// Get `path` as an absolute path, relative to the working directory
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        self.wd.join(path)
+    }
+
+    /// Whether the entry at `path` of the lower remote is visible, that is neither it nor its parents are whiteouts
+    fn lower_visible(&self, path: &Path) -> bool {
+        !path
+            .ancestors()
+            .any(|ancestor| self.whiteouts.contains(ancestor))
+    }
+
+    /// Stat `path` on the layer it comes from
+    fn stat_side(&mut self, path: &Path) -> RemoteResult<(Side, File)> {
+        match self.upper.stat(path) {
+            Ok(file) => return Ok((Side::Upper, file)),
+            Err(err) if err.kind != RemoteErrorType::NoSuchFileOrDirectory => return Err(err),
+            Err(_) => {}
+        }
+        if !self.lower_visible(path) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        self.lower.stat(path).map(|file| (Side::Lower, file))
+    }
+
+    /// Whether `path` exists on the lower remote and is visible
+    fn in_lower(&mut self, path: &Path) -> RemoteResult<bool> {
+        if !self.lower_visible(path) {
+            return Ok(false);
+        }
+
+        self.lower.exists(path)
+    }
+
+    /// Create the missing parent directories of `path` on the upper remote, with the mode of the lower ones.
+    fn ensure_parent(&mut self, path: &Path) -> RemoteResult<()> {
+        match path.parent() {
+            Some(parent) if parent != Path::new("/") && !parent.as_os_str().is_empty() => {
+                self.copy_up(parent)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Copy the entry at `path` up from the lower remote to the upper one, unless the upper remote already has it.
+    ///
+    /// Directories are created empty, since their entries keep on being read from the lower remote.
+    fn copy_up(&mut self, path: &Path) -> RemoteResult<()> {
+        let file = match self.stat_side(path)? {
+            (Side::Upper, _) => return Ok(()),
+            (Side::Lower, file) => file,
+        };
+        debug!("copying up {}", path.display());
+        self.ensure_parent(path)?;
+        self.copy_from_lower(&file, path)
+    }
+
+    /// Copy `file` of the lower remote to `dest` on the upper remote
+    fn copy_from_lower(&mut self, file: &File, dest: &Path) -> RemoteResult<()> {
+        if file.is_dir() {
+            let mode = file.metadata().mode.unwrap_or(UnixPex::from(0o755));
+            self.upper.create_dir(dest, mode)?;
+            return self.upper.setstat(dest, file.metadata().clone());
+        }
+        if let Some(target) = file.metadata().symlink.as_deref() {
+            return self.upper.symlink(dest, target);
+        }
+
+        let io_err = |err: std::io::Error| RemoteError::new_ex(RemoteErrorType::IoError, err);
+        let mut content = tempfile::tempfile().map_err(io_err)?;
+        let writer = content.try_clone().map_err(io_err)?;
+        self.lower.open_file(file.path(), Box::new(writer))?;
+        content.seek(SeekFrom::Start(0)).map_err(io_err)?;
+        self.upper
+            .create_file(dest, file.metadata(), Box::new(content))
+            .map(|_| ())
+    }
+}
+
+impl RemoteFs for OverlayFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let welcome = self.upper.connect()?;
+        self.lower.connect()?;
+
+        Ok(welcome)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        let upper = self.upper.disconnect();
+        let lower = self.lower.disconnect();
+
+        upper.and(lower)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.upper.is_connected() && self.lower.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wd.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.absolutize(dir);
+        let (_, file) = self.stat_side(&dir)?;
+        if !file.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wd = dir;
+
+        Ok(self.wd.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = self.absolutize(path);
+        let (side, dir) = self.stat_side(&path)?;
+        if side == Side::Lower {
+            let mut entries = self.lower.list_dir(&path)?;
+            entries.retain(|entry| self.lower_visible(entry.path()));
+            return Ok(entries);
+        }
+
+        let mut entries = self.upper.list_dir(&path)?;
+        if !dir.is_dir() || !self.in_lower(&path)? {
+            return Ok(entries);
+        }
+        let names = entries.iter().map(File::name).collect::<HashSet<_>>();
+        let lower = self
+            .lower
+            .list_dir(&path)?
+            .into_iter()
+            .filter(|entry| !names.contains(&entry.name()) && self.lower_visible(entry.path()))
+            .collect::<Vec<_>>();
+        entries.extend(lower);
+
+        Ok(entries)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.absolutize(path);
+        self.stat_side(&path).map(|(_, file)| file)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        self.copy_up(&path)?;
+        self.upper.setstat(&path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        let path = self.absolutize(path);
+        if self.upper.exists(&path)? {
+            return Ok(true);
+        }
+
+        self.in_lower(&path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        let in_upper = self.upper.exists(&path)?;
+        let in_lower = self.in_lower(&path)?;
+        if !in_upper && !in_lower {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+        if in_upper {
+            self.upper.remove_file(&path)?;
+        }
+        if in_lower {
+            debug!("whiting out {}", path.display());
+            self.whiteouts.insert(path);
+        }
+
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        if !self.list_dir(&path)?.is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+        if self.upper.exists(&path)? {
+            self.upper.remove_dir(&path)?;
+        }
+        if self.in_lower(&path)? {
+            debug!("whiting out {}", path.display());
+            self.whiteouts.insert(path);
+        }
+
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        if self.exists(&path)? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        self.ensure_parent(&path)?;
+        self.upper.create_dir(&path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        self.ensure_parent(&path)?;
+        self.upper.symlink(&path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let (src, dest) = (self.absolutize(src), self.absolutize(dest));
+        let (side, file) = self.stat_side(&src)?;
+        self.ensure_parent(&dest)?;
+        match side {
+            Side::Upper => self.upper.copy(&src, &dest),
+            Side::Lower if file.is_dir() => {
+                Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+            }
+            Side::Lower => self.copy_from_lower(&file, &dest),
+        }
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let (src, dest) = (self.absolutize(src), self.absolutize(dest));
+        let (_, file) = self.stat_side(&src)?;
+        let src_in_lower = self.in_lower(&src)?;
+        if file.is_dir() && src_in_lower {
+            debug!(
+                "{} comes from the lower remote; it can't be moved",
+                src.display()
+            );
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        self.copy_up(&src)?;
+        self.ensure_parent(&dest)?;
+        self.upper.mov(&src, &dest)?;
+        if src_in_lower {
+            self.whiteouts.insert(src);
+        }
+        // the moved entry replaces the one of the lower remote
+        if self.in_lower(&dest)? {
+            self.whiteouts.insert(dest);
+        }
+
+        Ok(())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.upper.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.absolutize(path);
+        self.copy_up(&path)?;
+        self.upper.append(&path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.absolutize(path);
+        self.ensure_parent(&path)?;
+        self.upper.create(&path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let path = self.absolutize(path);
+        let side = self.stat_side(&path)?.0;
+        let stream = match side {
+            Side::Upper => self.upper.open(&path)?,
+            Side::Lower => self.lower.open(&path)?,
+        };
+        self.open_streams.push_back(side);
+
+        Ok(stream)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.upper.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        match self.open_streams.pop_front() {
+            Some(Side::Lower) => self.lower.on_read(readable),
+            _ => self.upper.on_read(readable),
+        }
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn std::io::Read + Send>,
+    ) -> RemoteResult<u64> {
+        let path = self.absolutize(path);
+        self.copy_up(&path)?;
+        self.upper.append_file(&path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn std::io::Read + Send>,
+    ) -> RemoteResult<u64> {
+        let path = self.absolutize(path);
+        self.ensure_parent(&path)?;
+        self.upper.create_file(&path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn std::io::Write + Send>) -> RemoteResult<u64> {
+        let src = self.absolutize(src);
+        match self.stat_side(&src)?.0 {
+            Side::Upper => self.upper.open_file(&src, dest),
+            Side::Lower => self.lower.open_file(&src, dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::{Cursor, Read as _};
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn memory_fs() -> MemoryFs {
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        )))
+    }
+
+    /// An overlay of an empty upper remote over a lower remote with `/docs/a.txt` and `/b.txt`
+    fn setup_overlay() -> OverlayFs {
+        let mut lower = memory_fs();
+        lower.connect().unwrap();
+        lower
+            .create_dir(Path::new("/docs"), UnixPex::from(0o750))
+            .unwrap();
+        for (path, content) in [("/docs/a.txt", "hello"), ("/b.txt", "world")] {
+            lower
+                .create_file(
+                    Path::new(path),
+                    &Metadata::default(),
+                    Box::new(Cursor::new(content.as_bytes().to_vec())),
+                )
+                .unwrap();
+        }
+
+        let mut overlay = OverlayFs::new(Box::new(memory_fs()), Box::new(lower));
+        overlay.connect().unwrap();
+        overlay
+    }
+
+    fn read(fs: &mut dyn RemoteFs, path: &str) -> String {
+        let mut stream = fs.open(Path::new(path)).unwrap();
+        let mut content = String::new();
+        stream.read_to_string(&mut content).unwrap();
+        fs.on_read(stream).unwrap();
+        content
+    }
+
+    fn names(fs: &mut dyn RemoteFs, path: &str) -> Vec<String> {
+        let mut names = fs
+            .list_dir(Path::new(path))
+            .unwrap()
+            .iter()
+            .map(File::name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_should_read_through_to_lower() {
+        let mut overlay = setup_overlay();
+        assert_eq!(read(&mut overlay, "/docs/a.txt"), "hello");
+        assert!(overlay.exists(Path::new("/b.txt")).unwrap());
+        assert!(overlay.stat(Path::new("/docs")).unwrap().is_dir());
+
+        // entries of both layers are listed, the upper ones taking precedence
+        overlay
+            .create_file(
+                Path::new("/b.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"upper".to_vec())),
+            )
+            .unwrap();
+        overlay
+            .create_file(
+                Path::new("/c.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(Vec::new())),
+            )
+            .unwrap();
+        assert_eq!(names(&mut overlay, "/"), vec!["b.txt", "c.txt", "docs"]);
+        assert_eq!(read(&mut overlay, "/b.txt"), "upper");
+        assert_eq!(read(overlay.lower.as_mut(), "/b.txt"), "world");
+    }
+
+    #[test]
+    fn test_should_copy_up_on_write() {
+        let mut overlay = setup_overlay();
+        let mut stream = overlay
+            .append(Path::new("/docs/a.txt"), &Metadata::default())
+            .unwrap();
+        std::io::Write::write_all(&mut stream, b" world").unwrap();
+        overlay.on_written(stream).unwrap();
+
+        // the file and its parent are copied up, the lower remote is left as is
+        let dir = overlay.upper.stat(Path::new("/docs")).unwrap();
+        assert_eq!(dir.metadata().mode, Some(UnixPex::from(0o750)));
+        assert!(overlay.upper.exists(Path::new("/docs/a.txt")).unwrap());
+        assert_eq!(read(overlay.lower.as_mut(), "/docs/a.txt"), "hello");
+
+        let metadata = Metadata {
+            mode: Some(UnixPex::from(0o600)),
+            ..Default::default()
+        };
+        overlay.setstat(Path::new("/b.txt"), metadata).unwrap();
+        assert_eq!(read(overlay.upper.as_mut(), "/b.txt"), "world");
+        assert_eq!(
+            overlay.stat(Path::new("/b.txt")).unwrap().metadata().mode,
+            Some(UnixPex::from(0o600))
+        );
+
+        // directories of the lower remote can't be moved
+        assert_eq!(
+            overlay
+                .mov(Path::new("/docs"), Path::new("/archive"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        overlay
+            .mov(Path::new("/b.txt"), Path::new("/docs/b.txt"))
+            .unwrap();
+        assert!(!overlay.exists(Path::new("/b.txt")).unwrap());
+        assert_eq!(read(&mut overlay, "/docs/b.txt"), "world");
+    }
+
+    #[test]
+    fn test_should_white_out_removed_entries() {
+        let mut overlay = setup_overlay();
+        overlay.remove_file(Path::new("/b.txt")).unwrap();
+        assert!(!overlay.exists(Path::new("/b.txt")).unwrap());
+        assert_eq!(
+            overlay.stat(Path::new("/b.txt")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert_eq!(names(&mut overlay, "/"), vec!["docs"]);
+        assert!(overlay.lower.exists(Path::new("/b.txt")).unwrap());
+
+        // removed directories hide their lower entries, even once created again
+        assert_eq!(
+            overlay.remove_dir(Path::new("/docs")).unwrap_err().kind,
+            RemoteErrorType::DirectoryNotEmpty
+        );
+        overlay.remove_file(Path::new("/docs/a.txt")).unwrap();
+        overlay.remove_dir(Path::new("/docs")).unwrap();
+        assert!(names(&mut overlay, "/").is_empty());
+        overlay
+            .create_dir(Path::new("/docs"), UnixPex::from(0o755))
+            .unwrap();
+        assert!(names(&mut overlay, "/docs").is_empty());
+        assert!(!overlay.exists(Path::new("/docs/a.txt")).unwrap());
+    }
+}
@@ -27,8 +27,8 @@ fn mount(p: &Path) -> (UmountLock, JoinHandle<()>) {
     let umount_t = umount.clone();
 
     let join = std::thread::spawn(move || {
-        let mut mount =
-            Mount::mount(crate::driver::setup_driver(), &mountpoint, &[]).expect("failed to mount");
+        let mut mount = Mount::mount_blocking(crate::driver::setup_driver(), &mountpoint, &[])
+            .expect("failed to mount");
 
         let umount = mount.unmounter();
         *umount_t.lock().unwrap() = Some(umount);
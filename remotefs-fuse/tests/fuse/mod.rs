@@ -1,82 +1,74 @@
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicBool;
-use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
 use std::time::Duration;
 
-use remotefs_fuse::{Mount, MountOption, Unmount};
+use remotefs_fuse::{BackgroundMount, Mount, MountOption};
 use tempfile::TempDir;
 
 use crate::driver::mounted_file_path;
 
-pub type UmountLock = Arc<Mutex<Option<Unmount>>>;
+/// Check whether FUSE can be used on this system: the device must be accessible and `fusermount` installed.
+fn fuse_available() -> bool {
+    let device = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/fuse")
+        .is_ok();
+    let fusermount = std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .any(|dir| dir.join("fusermount3").exists() || dir.join("fusermount").exists())
+        })
+        .unwrap_or_default();
 
-/// Mounts the filesystem in a separate thread.
-///
-/// The filesystem must be unmounted manually and then the thread must be joined.
-fn mount(p: &Path) -> (UmountLock, JoinHandle<()>) {
-    let mountpoint = p.to_path_buf();
-
-    let error_flag = Arc::new(AtomicBool::new(false));
-    let error_flag_t = error_flag.clone();
-
-    let umount = Arc::new(Mutex::new(None));
-    let umount_t = umount.clone();
-
-    let join = std::thread::spawn(move || {
-        let mut mount = Mount::mount(
-            crate::driver::setup_driver(),
-            &mountpoint,
-            &[
-                MountOption::AllowRoot,
-                MountOption::RW,
-                MountOption::Exec,
-                MountOption::Sync,
-            ],
-        )
-        .expect("failed to mount");
-
-        let umount = mount.unmounter();
-        *umount_t.lock().unwrap() = Some(umount);
+    device && fusermount
+}
 
-        mount.run().expect("failed to run filesystem event loop");
+/// Mounts the filesystem in a background thread.
+fn mount(p: &Path, options: &[MountOption]) -> BackgroundMount {
+    let handle = Mount::spawn(crate::driver::setup_driver(), p, options).expect("failed to mount");
 
-        // set the error flag if the filesystem was unmounted
-        error_flag_t.store(true, std::sync::atomic::Ordering::Relaxed);
-    });
-
-    // wait for the filesystem to be mounted
+    // wait for the filesystem to be initialized
     std::thread::sleep(Duration::from_secs(1));
-    if error_flag.load(std::sync::atomic::Ordering::Relaxed) {
-        panic!("Failed to mount filesystem");
-    }
-
-    (umount, join)
-}
 
-fn umount(umount: UmountLock) {
-    umount
-        .lock()
-        .unwrap()
-        .as_mut()
-        .unwrap()
-        .unmount()
-        .expect("Failed to unmount");
+    handle
 }
 
 /// Mounts the filesystem and calls the provided closure with the mountpoint.
+///
+/// The test is skipped if FUSE is not available.
 fn with_mounted_drive<F>(f: F)
+where
+    F: FnOnce(&Path),
+{
+    with_mounted_drive_options(
+        &[
+            MountOption::AllowRoot,
+            MountOption::RW,
+            MountOption::Exec,
+            MountOption::Sync,
+        ],
+        f,
+    )
+}
+
+/// Mounts the filesystem with `options` and calls the provided closure with the mountpoint.
+///
+/// The test is skipped if FUSE is not available.
+fn with_mounted_drive_options<F>(options: &[MountOption], f: F)
 where
     F: FnOnce(&Path),
 {
     let _ = env_logger::try_init();
+    if !fuse_available() {
+        eprintln!("FUSE is not available; skipping test");
+        return;
+    }
     let mnt = TempDir::new().expect("Failed to create tempdir");
     // mount
-    let (umounter, join) = mount(mnt.path());
+    let handle = mount(mnt.path(), options);
     f(mnt.path());
     // unmount
-    umount(umounter);
-    join.join().expect("Failed to join thread");
+    handle.umount().expect("failed to unmount");
 }
 
 #[test]
@@ -154,3 +146,143 @@ fn test_should_make_symlink() {
         assert_eq!(file_content, read_content);
     });
 }
+
+#[test]
+fn test_should_write_and_read_back_file() {
+    with_mounted_drive(|mnt| {
+        let file_path = mnt.join("data.bin");
+        let content = (0..=255u8).cycle().take(1024 * 1024).collect::<Vec<_>>();
+        std::fs::write(&file_path, &content).expect("Failed to write to file");
+
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().len(),
+            content.len() as u64
+        );
+        assert_eq!(std::fs::read(&file_path).unwrap(), content);
+
+        // overwrite with a shorter content
+        std::fs::write(&file_path, b"short").expect("Failed to overwrite file");
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"short");
+    });
+}
+
+#[test]
+fn test_should_append_to_file() {
+    use std::io::Write as _;
+
+    with_mounted_drive(|mnt| {
+        let file_path = mnt.join("log.txt");
+        std::fs::write(&file_path, b"hello").expect("Failed to write to file");
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file_path)
+            .expect("Failed to open file");
+        file.write_all(b", world").expect("Failed to append");
+        drop(file);
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"hello, world");
+    });
+}
+
+#[test]
+fn test_should_rename_file() {
+    with_mounted_drive(|mnt| {
+        let src = mnt.join("src.txt");
+        let dest_dir = mnt.join("dir");
+        let dest = dest_dir.join("dest.txt");
+        std::fs::write(&src, b"renamed").expect("Failed to write to file");
+        std::fs::create_dir(&dest_dir).expect("Failed to create directory");
+
+        std::fs::rename(&src, &dest).expect("Failed to rename file");
+        assert!(!src.exists());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"renamed");
+    });
+}
+
+#[test]
+fn test_should_list_and_delete_files() {
+    with_mounted_drive(|mnt| {
+        let dir = mnt.join("list");
+        std::fs::create_dir(&dir).expect("Failed to create directory");
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(dir.join(name), name).expect("Failed to write to file");
+        }
+
+        let mut names = std::fs::read_dir(&dir)
+            .expect("Failed to read directory")
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["a.txt", "b.txt", "c.txt"]);
+
+        for name in names {
+            std::fs::remove_file(dir.join(name)).expect("Failed to remove file");
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).expect("Failed to remove directory");
+        assert!(!dir.exists());
+    });
+}
+
+/// Write an executable script printing `hello` at `path`
+fn write_script(path: &Path) {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    std::fs::write(path, "#!/bin/sh\necho hello\n").expect("Failed to write script");
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to set permissions");
+}
+
+#[test]
+fn test_should_exec_file() {
+    with_mounted_drive(|mnt| {
+        let script = mnt.join("hello.sh");
+        write_script(&script);
+
+        let output = std::process::Command::new(&script)
+            .output()
+            .expect("Failed to exec script");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello\n");
+    });
+}
+
+#[test]
+fn test_should_not_exec_file_on_noexec_mount() {
+    with_mounted_drive_options(
+        &[
+            MountOption::AllowRoot,
+            MountOption::RW,
+            MountOption::NoExec,
+            MountOption::Sync,
+        ],
+        |mnt| {
+            let script = mnt.join("hello.sh");
+            write_script(&script);
+
+            let err = std::process::Command::new(&script)
+                .output()
+                .expect_err("script should not be executed");
+            assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        },
+    );
+}
+
+#[test]
+fn test_should_unmount_on_drop() {
+    let _ = env_logger::try_init();
+    if !fuse_available() {
+        eprintln!("FUSE is not available; skipping test");
+        return;
+    }
+    let mnt = TempDir::new().expect("Failed to create tempdir");
+    let mut mount = Mount::mount_blocking(crate::driver::setup_driver(), mnt.path(), &[])
+        .expect("failed to mount");
+    // a live unmount handle must not keep the filesystem mounted
+    let _umount = mount.unmounter();
+    drop(mount);
+
+    let mounts = std::fs::read_to_string("/proc/self/mounts").expect("failed to read mounts");
+    assert!(!mounts.contains(&mnt.path().display().to_string()));
+}